@@ -0,0 +1,3 @@
+-- The name of the external (non-RCOS) host of a meeting, such as a guest speaker.
+-- Meetings with an external host may have no RCOS host.
+ALTER TABLE meetings ADD COLUMN external_host_name text;
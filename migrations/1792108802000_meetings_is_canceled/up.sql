@@ -0,0 +1,2 @@
+-- Canceled meetings stay listed (unlike deleted ones), but are marked as canceled.
+ALTER TABLE meetings ADD COLUMN is_canceled boolean NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+-- The start time a meeting was last reminded about. Telescope claims a meeting's
+-- reminder by setting this before sending it, so reminders are not sent again after
+-- a restart or by another instance. A meeting moved to a new start time is
+-- reminded about again.
+ALTER TABLE meetings ADD COLUMN reminder_sent_for timestamptz;
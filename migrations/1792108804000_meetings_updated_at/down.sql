@@ -0,0 +1,3 @@
+ALTER TABLE meetings
+    DROP COLUMN updated_by,
+    DROP COLUMN updated_at;
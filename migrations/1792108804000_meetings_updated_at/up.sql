@@ -0,0 +1,7 @@
+-- When a meeting was last edited, and by who.
+--
+-- Hasura: add an object relationship `editor` on `meetings`, from `updated_by` to
+-- `users.id`.
+ALTER TABLE meetings
+    ADD COLUMN updated_at timestamptz,
+    ADD COLUMN updated_by uuid REFERENCES users (id) ON UPDATE CASCADE ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- When a user was deactivated. Deactivated users keep their records, but are hidden
+-- from listings. Users that are not deactivated have no value.
+ALTER TABLE users ADD COLUMN deactivated_at timestamptz;
@@ -0,0 +1,3 @@
+ALTER TABLE users
+    DROP COLUMN external_links,
+    DROP COLUMN bio;
@@ -0,0 +1,4 @@
+-- A short bio and links (one per line) shown on a user's profile.
+ALTER TABLE users
+    ADD COLUMN bio text,
+    ADD COLUMN external_links text;
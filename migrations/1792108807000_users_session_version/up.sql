@@ -0,0 +1,4 @@
+-- Each user's session version. Identity cookies record the version they were issued
+-- at, and logging out everywhere increments it so that older cookies are rejected.
+-- Users with no value are at version 0.
+ALTER TABLE users ADD COLUMN session_version integer DEFAULT 0;
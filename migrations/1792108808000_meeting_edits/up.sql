@@ -0,0 +1,17 @@
+-- The fields changed by each meeting edit, with their values before and after. The
+-- fields changed by one edit share its editor and time.
+--
+-- Hasura: track the `meeting_edits` table, and add object relationships `editor`
+-- (from `edited_by` to `users.id`) and `meeting` (from `meeting_id` to
+-- `meetings.meeting_id`).
+CREATE TABLE meeting_edits (
+    edit_id serial PRIMARY KEY,
+    meeting_id integer NOT NULL REFERENCES meetings (meeting_id) ON UPDATE CASCADE ON DELETE CASCADE,
+    edited_by uuid NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    edited_at timestamptz NOT NULL DEFAULT now(),
+    field text NOT NULL,
+    old_value text,
+    new_value text
+);
+
+CREATE INDEX meeting_edits_meeting_id_edited_at ON meeting_edits (meeting_id, edited_at DESC);
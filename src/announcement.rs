@@ -0,0 +1,86 @@
+//! Site-wide announcement banner.
+//!
+//! Coordinators can post a short announcement (e.g. "Registration closes Friday") that is
+//! shown at the top of every page until it expires or is removed. The current announcement is
+//! kept in the global app data and saved to a JSON file (`announcement_file` in the config) so
+//! it survives restarts.
+
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
+use std::io::ErrorKind;
+
+/// An announcement shown at the top of every page.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Announcement {
+    /// The text of the announcement.
+    pub message: String,
+    /// When the announcement was posted. This is used to identify the announcement, so that
+    /// dismissing one does not dismiss the next.
+    pub posted_at: DateTime<Utc>,
+    /// When the announcement stops being shown, if ever.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Announcement {
+    /// Create a new announcement posted now.
+    pub fn new(message: impl Into<String>, expires_at: Option<DateTime<Utc>>) -> Self {
+        Announcement {
+            message: message.into(),
+            posted_at: Utc::now(),
+            expires_at,
+        }
+    }
+
+    /// Should this announcement be shown at the given time?
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map(|expiry| now < expiry).unwrap_or(true)
+    }
+
+    /// Load the saved announcement from the announcement file. Return `None` if there is no
+    /// saved announcement or it could not be read.
+    pub fn load() -> Option<Self> {
+        let config = global_config();
+        let path: &str = config.announcement_file.as_str();
+        match std::fs::read(path) {
+            // Parse the saved announcement.
+            Ok(bytes) => serde_json::from_slice(bytes.as_slice())
+                .map_err(|e| error!("Could not parse announcement file {}: {}", path, e))
+                .ok(),
+
+            // No saved announcement.
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+
+            // Could not read the file.
+            Err(e) => {
+                error!("Could not read announcement file {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Save an announcement to the announcement file, or remove the file if there is no
+    /// announcement.
+    pub fn save(announcement: Option<&Self>) -> Result<(), TelescopeError> {
+        let config = global_config();
+        let path: &str = config.announcement_file.as_str();
+        let result = match announcement {
+            Some(announcement) => {
+                let bytes: Vec<u8> = serde_json::to_vec_pretty(announcement).map_err(|e| {
+                    TelescopeError::ise(format!("Could not serialize announcement: {}", e))
+                })?;
+                std::fs::write(path, bytes)
+            }
+
+            // Removing a file that does not exist is fine.
+            None => std::fs::remove_file(path).or_else(|e| match e.kind() {
+                ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        };
+
+        return result.map_err(|e| {
+            TelescopeError::ise(format!("Could not save announcement file {}: {}", path, e))
+        });
+    }
+}
@@ -0,0 +1,288 @@
+//! GitHub App installation tokens.
+//!
+//! User OAuth tokens can only see what the signed in user can see, so queries for
+//! organization data fail for users who are not organization members. If a GitHub App is
+//! configured, those queries use an installation token of the app instead. Installation
+//! tokens last an hour; one is generated from a JWT signed with the app's private key, and
+//! reused until [`TOKEN_REFRESH_MARGIN_SECS`] before it expires.
+
+use crate::api::check_upstream_status;
+use crate::env::{global_config, GithubAppConfig};
+use crate::error::TelescopeError;
+use crate::web::telescope_ua;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use oauth2::AccessToken;
+use reqwest::header::{HeaderValue, ACCEPT, USER_AGENT};
+use reqwest::Client;
+use std::future::Future;
+use std::sync::RwLock;
+
+/// The name of this API in error reporting.
+const API_NAME: &'static str = "GitHub App API";
+
+/// How far back the issued-at time of app JWTs is set, to allow for clock drift between
+/// Telescope and GitHub (as GitHub recommends).
+const JWT_BACKDATE_SECS: i64 = 60;
+
+/// How long app JWTs are valid for. GitHub allows at most ten minutes.
+const JWT_LIFETIME_SECS: i64 = 9 * 60;
+
+/// How long before an installation token expires a new one is generated.
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 5 * 60;
+
+lazy_static! {
+    /// The most recent installation token.
+    static ref INSTALLATION_TOKEN: RwLock<Option<InstallationToken>> = RwLock::new(None);
+}
+
+/// Which token a GitHub query should be sent with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GitHubAuth {
+    /// The signed in user's OAuth token. This is for queries about the user themselves.
+    User,
+    /// The GitHub App's installation token. This is for organization data that should not
+    /// depend on who is signed in.
+    Installation,
+}
+
+/// Claims of the JWT used to authenticate as a GitHub App.
+#[derive(Serialize, Clone, Debug)]
+pub struct AppJwtClaims {
+    /// When the JWT was issued (UNIX time), backdated for clock drift.
+    iat: i64,
+    /// When the JWT expires (UNIX time).
+    exp: i64,
+    /// The GitHub App ID.
+    iss: String,
+}
+
+impl AppJwtClaims {
+    /// Make the claims of an app JWT issued at `now`.
+    pub fn new(app_id: u64, now: DateTime<Utc>) -> Self {
+        AppJwtClaims {
+            iat: (now - Duration::seconds(JWT_BACKDATE_SECS)).timestamp(),
+            exp: (now + Duration::seconds(JWT_LIFETIME_SECS)).timestamp(),
+            iss: app_id.to_string(),
+        }
+    }
+}
+
+/// An installation token and when it expires.
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstallationToken {
+    /// The token.
+    token: String,
+    /// When the token expires.
+    expires_at: DateTime<Utc>,
+}
+
+impl InstallationToken {
+    /// Can this token still be used as of `now`? Tokens are replaced a few minutes before
+    /// they expire so that they do not expire during a request.
+    pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at - Duration::seconds(TOKEN_REFRESH_MARGIN_SECS) > now
+    }
+}
+
+/// Make and sign a JWT to authenticate as the GitHub App, issued at `now`.
+fn app_jwt(app: &GithubAppConfig, now: DateTime<Utc>) -> Result<String, TelescopeError> {
+    let pem: Vec<u8> = std::fs::read(&app.private_key_file).map_err(|e| {
+        TelescopeError::ise(format!(
+            "Could not read GitHub App private key at {}: {}",
+            app.private_key_file, e
+        ))
+    })?;
+    let key: EncodingKey = EncodingKey::from_rsa_pem(pem.as_slice())
+        .map_err(|e| TelescopeError::ise(format!("Malformed GitHub App private key: {}", e)))?;
+
+    return encode(
+        &Header::new(Algorithm::RS256),
+        &AppJwtClaims::new(app.app_id, now),
+        &key,
+    )
+    .map_err(|e| TelescopeError::ise(format!("Could not sign GitHub App JWT: {}", e)));
+}
+
+/// Exchange an app JWT issued at `now` for a new installation token.
+async fn request_installation_token(
+    app: &GithubAppConfig,
+    now: DateTime<Utc>,
+) -> Result<InstallationToken, TelescopeError> {
+    let url: String = format!(
+        "https://api.github.com/app/installations/{}/access_tokens",
+        app.installation_id
+    );
+
+    return Client::new()
+        .post(url.as_str())
+        .bearer_auth(app_jwt(app, now)?)
+        .header(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github.v3+json"),
+        )
+        .header(USER_AGENT, telescope_ua())
+        .send()
+        .await
+        .map_err(TelescopeError::github_api_error)
+        .and_then(|response| check_upstream_status(API_NAME, response))?
+        .error_for_status()
+        .map_err(TelescopeError::github_api_error)?
+        .json::<InstallationToken>()
+        .await
+        .map_err(TelescopeError::github_api_error);
+}
+
+/// Get an installation token of the configured GitHub App, generating a new one if the
+/// cached one is about to expire. Return `None` if no GitHub App is configured.
+pub async fn installation_token() -> Result<Option<AccessToken>, TelescopeError> {
+    let config = global_config();
+    let app: &GithubAppConfig = match config.github_app.as_ref() {
+        Some(app) => app,
+        None => return Ok(None),
+    };
+
+    let now: DateTime<Utc> = Utc::now();
+    let token: InstallationToken = cached_or_generate(&INSTALLATION_TOKEN, now, || {
+        request_installation_token(app, now)
+    })
+    .await?;
+    return Ok(Some(AccessToken::new(token.token)));
+}
+
+/// Get the installation token in `cache` if it is still fresh as of `now`. Otherwise generate
+/// a new one with `generate` and cache it.
+async fn cached_or_generate<F, Fut>(
+    cache: &RwLock<Option<InstallationToken>>,
+    now: DateTime<Utc>,
+    generate: F,
+) -> Result<InstallationToken, TelescopeError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<InstallationToken, TelescopeError>>,
+{
+    // Use the cached token if it is still fresh. The lock is not held across the request
+    // below; if two requests both generate a token, either one is fine to keep.
+    let cached: Option<InstallationToken> = cache
+        .read()
+        .ok()
+        .and_then(|token| token.clone())
+        .filter(|token| token.is_fresh(now));
+    if let Some(token) = cached {
+        return Ok(token);
+    }
+
+    let token: InstallationToken = generate().await?;
+    info!(
+        "Generated a GitHub App installation token (expires {}).",
+        token.expires_at
+    );
+    if let Ok(mut slot) = cache.write() {
+        *slot = Some(token.clone());
+    }
+    return Ok(token);
+}
+
+/// Get the token to send a GitHub query with. Installation queries fall back to the user's
+/// token if no GitHub App is configured, and user queries fall back to the installation token
+/// if no user token is given. Error if neither is available.
+pub async fn token_for(
+    auth: GitHubAuth,
+    user_token: Option<&AccessToken>,
+) -> Result<AccessToken, TelescopeError> {
+    let token: Option<AccessToken> = match auth {
+        GitHubAuth::Installation => match installation_token().await? {
+            Some(token) => Some(token),
+            None => user_token.cloned(),
+        },
+        GitHubAuth::User => match user_token {
+            Some(token) => Some(token.clone()),
+            None => installation_token().await?,
+        },
+    };
+
+    return token.ok_or(TelescopeError::GitHubApiError(
+        "There is no GitHub token to send this query with. Sign in with GitHub or configure \
+        a GitHub App."
+            .into(),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::cell::Cell;
+
+    /// The time tests are run at.
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2022, 9, 6).and_hms(16, 0, 0)
+    }
+
+    /// Make an installation token that expires `minutes` after [`now`].
+    fn token_expiring_in(minutes: i64, token: &str) -> InstallationToken {
+        InstallationToken {
+            token: token.to_string(),
+            expires_at: now() + Duration::minutes(minutes),
+        }
+    }
+
+    /// Get the token [`cached_or_generate`] returns for a cache holding `cached`, and how many
+    /// times it generated a new one.
+    async fn get_with_cached(cached: Option<InstallationToken>) -> (InstallationToken, usize) {
+        let cache: RwLock<Option<InstallationToken>> = RwLock::new(cached);
+        let generated: Cell<usize> = Cell::new(0);
+        let token: InstallationToken = cached_or_generate(&cache, now(), || async {
+            generated.set(generated.get() + 1);
+            Ok(token_expiring_in(60, "new"))
+        })
+        .await
+        .unwrap();
+
+        // The returned token is the one left in the cache.
+        assert_eq!(cache.read().unwrap().as_ref().unwrap().token, token.token);
+        return (token, generated.get());
+    }
+
+    /// App JWTs are backdated a minute, last less than ten minutes, and name the app.
+    #[test]
+    fn jwt_claims() {
+        let claims: AppJwtClaims = AppJwtClaims::new(123456, now());
+        assert_eq!(claims.iat, now().timestamp() - 60);
+        assert_eq!(claims.exp, now().timestamp() + 9 * 60);
+        assert!(claims.exp - claims.iat <= 10 * 60);
+        assert_eq!(claims.iss, "123456");
+    }
+
+    /// A token is generated when there is none cached.
+    #[actix_rt::test]
+    async fn generates_when_empty() {
+        let (token, generated) = get_with_cached(None).await;
+        assert_eq!(token.token, "new");
+        assert_eq!(generated, 1);
+    }
+
+    /// A cached token far from expiring is reused.
+    #[actix_rt::test]
+    async fn reuses_fresh_token() {
+        let (token, generated) = get_with_cached(Some(token_expiring_in(30, "old"))).await;
+        assert_eq!(token.token, "old");
+        assert_eq!(generated, 0);
+    }
+
+    /// A cached token is replaced shortly before it expires.
+    #[actix_rt::test]
+    async fn regenerates_shortly_before_expiry() {
+        let (token, generated) = get_with_cached(Some(token_expiring_in(4, "old"))).await;
+        assert_eq!(token.token, "new");
+        assert_eq!(generated, 1);
+    }
+
+    /// An expired cached token is replaced.
+    #[actix_rt::test]
+    async fn regenerates_expired_token() {
+        let (token, generated) = get_with_cached(Some(token_expiring_in(-1, "old"))).await;
+        assert_eq!(token.token, "new");
+        assert_eq!(generated, 1);
+    }
+}
@@ -1,13 +1,18 @@
 //! GitHub API V4 queries and mutations.
 
-use crate::api::handle_graphql_response;
+use crate::api::github::app::GitHubAuth;
+use crate::api::rcos::response_debug;
+use crate::api::{check_upstream_status, handle_graphql_response};
+use crate::env::global_config;
 use crate::error::TelescopeError;
 use crate::web::telescope_ua;
 use graphql_client::{GraphQLQuery, Response as GraphQLResponse};
 use oauth2::AccessToken;
 use reqwest::header::{HeaderValue, ACCEPT, USER_AGENT};
 use reqwest::Client;
+use std::time::Duration;
 
+pub mod app;
 pub mod users;
 
 /// The GitHub API endpoint
@@ -16,10 +21,21 @@ const GITHUB_API_ENDPOINT: &'static str = "https://api.github.com/graphql";
 /// The name of this API in error reporting.
 const API_NAME: &'static str = "GitHub API V4";
 
-/// Send a GraphQL query to the GitHub API.
+/// Send a GraphQL query to the GitHub API, with the request timeout from the config.
 pub async fn send_query<T: GraphQLQuery>(
     auth_token: &AccessToken,
     variables: T::Variables,
+) -> Result<T::ResponseData, TelescopeError> {
+    let timeout = Duration::from_secs(global_config().upstream_timeout_seconds);
+    return send_query_with_timeout::<T>(auth_token, variables, timeout).await;
+}
+
+/// Send a GraphQL query to the GitHub API, failing with [`TelescopeError::UpstreamTimeout`]
+/// if it takes longer than `timeout`. Use this for queries that are expected to be slow.
+pub async fn send_query_with_timeout<T: GraphQLQuery>(
+    auth_token: &AccessToken,
+    variables: T::Variables,
+    timeout: Duration,
 ) -> Result<T::ResponseData, TelescopeError> {
     // Build GraphQL request
     let query = T::build_query(variables);
@@ -35,32 +51,53 @@ pub async fn send_query<T: GraphQLQuery>(
         // And required headers
         .header(ACCEPT, HeaderValue::from_static("application/json"))
         .header(USER_AGENT, telescope_ua())
+        // Give up if the API hangs.
+        .timeout(timeout)
         // Send and wait for a response
         .send()
         .await
-        // Propagate any errors sending or receiving
-        .map_err(TelescopeError::github_api_error)?
+        // Convert any errors sending or receiving
+        .map_err(TelescopeError::with_timeout(
+            API_NAME,
+            timeout,
+            TelescopeError::github_api_error,
+        ))
+        // Check that the API is not temporarily unavailable and propagate any errors.
+        .and_then(|response| check_upstream_status(API_NAME, response))?
         // Get response as string
         .text()
         // Wait to receive the full response
         .await
         // Convert any errors.
-        .map_err(TelescopeError::github_api_error)
+        .map_err(TelescopeError::with_timeout(
+            API_NAME,
+            timeout,
+            TelescopeError::github_api_error,
+        ))
         // Convert the valid JSON value into the GraphQL response type.
         .and_then(|body| {
             serde_json::from_str::<GraphQLResponse<T::ResponseData>>(body.as_str())
-                // Convert serde error to telescope error
+                // Convert serde error to telescope error. The response body is only logged
+                // (and added to the error) when debugging, and is redacted first.
                 .map_err(|err| {
-                    // Log the error and response body
-                    error!(
-                        "Malformed GitHub API response: {}\nresponse body: {}",
-                        err,
-                        body.as_str()
-                    );
-                    // Convert error.
-                    TelescopeError::GitHubApiError(err.to_string())
+                    TelescopeError::GitHubApiError(response_debug::malformed_body_error(
+                        API_NAME,
+                        &err,
+                        body.as_str(),
+                        response_debug::enabled(),
+                    ))
                 })
         })
         // Convert any errors in the response
         .and_then(|response| handle_graphql_response(API_NAME, response));
 }
+
+/// Send a GraphQL query to the GitHub API with the token [`app::token_for`] picks for it.
+pub async fn send_query_as<T: GraphQLQuery>(
+    auth: GitHubAuth,
+    user_token: Option<&AccessToken>,
+    variables: T::Variables,
+) -> Result<T::ResponseData, TelescopeError> {
+    let token: AccessToken = app::token_for(auth, user_token).await?;
+    return send_query::<T>(&token, variables).await;
+}
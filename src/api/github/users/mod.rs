@@ -1,3 +1,4 @@
 //! GitHub user related authentication and queries.
 
 pub mod authenticated_user;
+pub mod user_by_id;
@@ -0,0 +1,53 @@
+//! Query to get a GitHub user by node ID.
+
+use crate::api::github::app::GitHubAuth;
+use crate::api::github::send_query_as;
+use crate::error::TelescopeError;
+use oauth2::AccessToken;
+
+// Import serializable URL type for query types.
+use url::Url as URI;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/github/schema.json",
+    query_path = "graphql/github/users/user_by_id.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UserById;
+
+use user_by_id::{UserByIdNode, Variables};
+
+/// The login and profile URL of a GitHub user.
+#[derive(Clone, Debug, Serialize)]
+pub struct GitHubProfile {
+    /// The user's GitHub username.
+    pub login: String,
+    /// The user's GitHub profile.
+    pub url: URI,
+}
+
+impl UserById {
+    /// Get the login and profile URL of the GitHub user with a node ID. This is not about the
+    /// signed in user, so it is sent with the GitHub App installation token (or `user_token`
+    /// if no app is configured). Return `None` if there is no user with this ID.
+    pub async fn get(
+        node_id: String,
+        user_token: Option<&AccessToken>,
+    ) -> Result<Option<GitHubProfile>, TelescopeError> {
+        let response = send_query_as::<Self>(
+            GitHubAuth::Installation,
+            user_token,
+            Variables { id: node_id },
+        )
+        .await?;
+
+        return Ok(response.node.and_then(|node| match node {
+            UserByIdNode::User(user) => Some(GitHubProfile {
+                login: user.login,
+                url: user.url,
+            }),
+            _ => None,
+        }));
+    }
+}
@@ -1,12 +1,83 @@
 //! Different API services that Telescope consumes.
 
 use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
 use graphql_client::Response;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::Response as HttpResponse;
+use reqwest::StatusCode;
+use std::time::Duration;
 
 pub mod discord;
 pub mod github;
 pub mod rcos;
 
+/// The header GitHub uses to report how many requests are left in the current rate limit window.
+const RATE_LIMIT_REMAINING: &'static str = "x-ratelimit-remaining";
+
+/// The header GitHub uses to report when the current rate limit window resets (in UTC epoch
+/// seconds).
+const RATE_LIMIT_RESET: &'static str = "x-ratelimit-reset";
+
+/// Get how long an upstream API asked us to wait before trying again. This reads the
+/// `Retry-After` header (either a number of seconds or an HTTP date), and falls back to the
+/// reset time of an exhausted GitHub style rate limit.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(value) = header(RETRY_AFTER.as_str()) {
+        // Retry-After is usually a number of seconds.
+        if let Ok(seconds) = value.trim().parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        // But it may also be an HTTP date.
+        if let Ok(date) = DateTime::parse_from_rfc2822(value.trim()) {
+            return (date.with_timezone(&Utc) - Utc::now()).to_std().ok();
+        }
+    }
+
+    // Only use the reset time if the rate limit has actually been used up.
+    if header(RATE_LIMIT_REMAINING) == Some("0") {
+        let reset: i64 = header(RATE_LIMIT_RESET)?.trim().parse().ok()?;
+        let seconds: i64 = (reset - Utc::now().timestamp()).max(0);
+        return Some(Duration::from_secs(seconds as u64));
+    }
+
+    return None;
+}
+
+/// Check the HTTP status of a response from an upstream API. If it indicates that the API
+/// is temporarily unavailable (usually from a proxy in front of it), return an
+/// [`TelescopeError::UpstreamUnavailable`] error. If it indicates that we are being rate
+/// limited (a 429, or a 403 with a retry time, which is how GitHub reports secondary rate
+/// limits), return a [`TelescopeError::RateLimited`] error. Otherwise pass the response
+/// through.
+fn check_upstream_status(
+    api_name: &'static str,
+    response: HttpResponse,
+) -> Result<HttpResponse, TelescopeError> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::FORBIDDEN {
+        let wait: Option<Duration> = retry_after(response.headers());
+        if status == StatusCode::TOO_MANY_REQUESTS || wait.is_some() {
+            error!(
+                "{} rate limited this request (status {}, retry after {:?})",
+                api_name, status, wait
+            );
+            return Err(TelescopeError::rate_limited(api_name, wait));
+        }
+    }
+
+    if TelescopeError::is_unavailable_status(status) {
+        error!("{} responded with status {}", api_name, status);
+        return Err(TelescopeError::upstream_unavailable(
+            api_name,
+            format!("Responded with status {}.", status),
+        ));
+    }
+    return Ok(response);
+}
+
 /// Handle a response from a GraphQL API. Convert any errors as necessary and
 /// extract the returned data if possible.
 fn handle_graphql_response<T>(
@@ -44,7 +115,10 @@ fn handle_graphql_response<T>(
             data: None,
         } => {
             if errs.is_empty() {
-                panic!("Central GraphQL API returned a response with no errors or data.");
+                Err(TelescopeError::schema_mismatch(
+                    api_name,
+                    "The response has an empty error list and no data.",
+                ))
             } else {
                 Err(TelescopeError::GraphQLError {
                     platform: api_name.to_string(),
@@ -53,10 +127,13 @@ fn handle_graphql_response<T>(
             }
         }
 
-        // Panic on None of either.
+        // A response with neither is not valid GraphQL.
         Response {
             errors: None,
             data: None,
-        } => panic!("Central GraphQL API responded with no errors or data."),
+        } => Err(TelescopeError::schema_mismatch(
+            api_name,
+            "The response has no errors or data.",
+        )),
     }
 }
@@ -0,0 +1,156 @@
+//! Helper for fetching every row of a paginated query.
+//!
+//! Most listing queries are paginated so that pages stay small. Exports need every row, so
+//! this requests pages one after another until the API runs out, up to a safety cap.
+
+use crate::error::TelescopeError;
+use std::future::Future;
+
+/// One page of rows from a paginated query.
+#[derive(Clone, Debug)]
+pub struct Batch<T> {
+    /// The rows on this page.
+    pub rows: Vec<T>,
+    /// Are there more rows after this page?
+    pub has_more: bool,
+}
+
+impl<T> Batch<T> {
+    /// Make a batch from a page of rows, assuming there are more rows if the page is full.
+    pub fn from_page(rows: Vec<T>, page_size: u32) -> Self {
+        let has_more: bool = rows.len() >= page_size as usize;
+        Batch { rows, has_more }
+    }
+}
+
+/// Fetch every page of a paginated query and concatenate the rows. `fetch_page` is called
+/// with zero-indexed page numbers until it returns a batch with no more rows after it (or an
+/// empty batch).
+///
+/// Return an error instead of a partial result if there are more than `max_rows` rows, so
+/// that a runaway query does not exhaust memory or silently drop rows.
+pub async fn fetch_all_pages<T, F, Fut>(
+    max_rows: usize,
+    mut fetch_page: F,
+) -> Result<Vec<T>, TelescopeError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<Batch<T>, TelescopeError>>,
+{
+    let mut all_rows: Vec<T> = Vec::new();
+    let mut page: u32 = 0;
+
+    loop {
+        // Get the next page.
+        let Batch { rows, has_more } = fetch_page(page).await?;
+        let batch_was_empty: bool = rows.is_empty();
+        all_rows.extend(rows);
+
+        // Check the cap.
+        if all_rows.len() > max_rows {
+            return Err(TelescopeError::ise(format!(
+                "Query returned more than the maximum of {} rows.",
+                max_rows
+            )));
+        }
+
+        // Stop when there is nothing left.
+        if !has_more || batch_was_empty {
+            return Ok(all_rows);
+        }
+
+        page += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// The page size used by tests.
+    const PAGE_SIZE: u32 = 10;
+
+    /// Fetch every row of `rows` a page at a time, like an offset-paginated query would.
+    /// Return the rows and the number of pages requested.
+    async fn fetch_rows(rows: &[u32], max_rows: usize) -> (Result<Vec<u32>, TelescopeError>, u32) {
+        let requests: Cell<u32> = Cell::new(0);
+        let result = fetch_all_pages(max_rows, |page_num| {
+            requests.set(requests.get() + 1);
+            let start: usize = (page_num * PAGE_SIZE) as usize;
+            let end: usize = (start + PAGE_SIZE as usize).min(rows.len());
+            let page: Vec<u32> = rows.get(start..end).unwrap_or(&[]).to_vec();
+            async move { Ok(Batch::from_page(page, PAGE_SIZE)) }
+        })
+        .await;
+        return (result, requests.get());
+    }
+
+    /// Every row of two and a half pages comes back exactly once, in order.
+    #[actix_rt::test]
+    async fn partial_last_page() {
+        let rows: Vec<u32> = (0..25).collect();
+        let (result, requests) = fetch_rows(&rows, 100).await;
+        assert_eq!(result.unwrap(), rows);
+        assert_eq!(requests, 3);
+    }
+
+    /// A full last page is followed by one empty page.
+    #[actix_rt::test]
+    async fn full_last_page() {
+        let rows: Vec<u32> = (0..30).collect();
+        let (result, requests) = fetch_rows(&rows, 100).await;
+        assert_eq!(result.unwrap(), rows);
+        assert_eq!(requests, 4);
+    }
+
+    /// No rows is one empty page.
+    #[actix_rt::test]
+    async fn no_rows() {
+        let (result, requests) = fetch_rows(&[], 100).await;
+        assert!(result.unwrap().is_empty());
+        assert_eq!(requests, 1);
+    }
+
+    /// More rows than the cap is an error rather than a partial result.
+    #[actix_rt::test]
+    async fn too_many_rows() {
+        let rows: Vec<u32> = (0..25).collect();
+        let (result, requests) = fetch_rows(&rows, 15).await;
+        assert!(matches!(
+            result,
+            Err(TelescopeError::InternalServerError(_))
+        ));
+        assert_eq!(requests, 2);
+
+        // Exactly the cap is allowed.
+        let (result, _) = fetch_rows(&rows, 25).await;
+        assert_eq!(result.unwrap().len(), 25);
+    }
+
+    /// An error fetching a page is returned and stops paging.
+    #[actix_rt::test]
+    async fn page_error() {
+        let requests: Cell<u32> = Cell::new(0);
+        let result: Result<Vec<u32>, TelescopeError> = fetch_all_pages(100, |page_num| {
+            requests.set(requests.get() + 1);
+            async move {
+                if page_num == 1 {
+                    return Err(TelescopeError::ise("Page failed."));
+                }
+                Ok(Batch::from_page((0..PAGE_SIZE).collect(), PAGE_SIZE))
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(requests.get(), 2);
+    }
+
+    /// Full pages have more rows after them.
+    #[test]
+    fn batch_from_page() {
+        assert!(Batch::from_page(vec![1, 2], 2).has_more);
+        assert!(!Batch::from_page(vec![1], 2).has_more);
+        assert!(!Batch::<u32>::from_page(vec![], 2).has_more);
+    }
+}
@@ -0,0 +1,163 @@
+//! Queries for the weekly coordinator summary email.
+
+use crate::api::rcos::users::UserRole;
+use crate::api::rcos::{prelude::*, send_query};
+use crate::error::TelescopeError;
+use chrono::{DateTime, Duration, Local, Utc};
+
+/// Query for the users who should receive the coordinator summary.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/coordinator_summary.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct CoordinatorSummaryRecipients;
+
+/// Query for the number of new users.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/coordinator_summary.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct NewUserCount;
+
+/// Query for the number of meetings held.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/coordinator_summary.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct MeetingsHeldCount;
+
+/// Query for the number of meeting attendances.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/coordinator_summary.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct AttendanceCount;
+
+/// Query for the number of drafts that still need to be published.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/coordinator_summary.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct DraftBacklogCount;
+
+/// User roles that always receive the coordinator summary.
+const ADMIN_ROLES: [UserRole; 2] = [UserRole::FacultyAdvisor, UserRole::Sysadmin];
+
+/// A user who should receive the coordinator summary.
+#[derive(Clone, Debug, Serialize)]
+pub struct SummaryRecipient {
+    /// The user's first name.
+    pub first_name: String,
+    /// The user's last name.
+    pub last_name: String,
+    /// The user's RCS ID.
+    pub rcs_id: String,
+}
+
+/// The statistics included in the coordinator summary.
+#[derive(Clone, Debug, Serialize)]
+pub struct SummaryStats {
+    /// The start of the period the summary covers.
+    pub since: DateTime<Utc>,
+    /// The end of the period the summary covers.
+    pub until: DateTime<Utc>,
+    /// The number of users who created accounts during the period.
+    pub new_users: i64,
+    /// The number of non-draft meetings held during the period.
+    pub meetings_held: i64,
+    /// The number of attendances recorded at those meetings.
+    pub attendances: i64,
+    /// The number of draft meetings that have not ended yet.
+    pub draft_backlog: i64,
+}
+
+/// Should a user with the given role and coordinator status receive the coordinator summary?
+/// This is anyone who is a coordinator or above.
+pub fn is_summary_recipient(role: UserRole, is_current_coordinator: bool) -> bool {
+    is_current_coordinator || ADMIN_ROLES.contains(&role)
+}
+
+impl CoordinatorSummaryRecipients {
+    /// Get the users who should receive the coordinator summary. Users without an RCS ID
+    /// cannot be emailed and are left out.
+    pub async fn get() -> Result<Vec<SummaryRecipient>, TelescopeError> {
+        // Query the API.
+        let response = send_query::<Self>(coordinator_summary_recipients::Variables {
+            today: Local::today().naive_local(),
+            admin_roles: ADMIN_ROLES.to_vec(),
+        })
+        .await?;
+
+        // Double check roles and extract the RCS IDs.
+        let recipients = response
+            .users
+            .into_iter()
+            .filter(|user| {
+                is_summary_recipient(user.role, !user.current_coordinator_enrollments.is_empty())
+            })
+            .filter_map(|user| {
+                let rcs_id: String = user.rcs_id.first()?.account_id.clone();
+                Some(SummaryRecipient {
+                    first_name: user.first_name,
+                    last_name: user.last_name,
+                    rcs_id,
+                })
+            })
+            .collect();
+
+        return Ok(recipients);
+    }
+}
+
+impl SummaryStats {
+    /// Get the statistics for the week leading up to now. The aggregates are queried
+    /// concurrently.
+    pub async fn for_past_week() -> Result<Self, TelescopeError> {
+        // Get the bounds of the summary period.
+        let until: DateTime<Utc> = Utc::now();
+        let since: DateTime<Utc> = until - Duration::weeks(1);
+
+        // Send all the queries at once.
+        let (new_users, meetings_held, attendances, draft_backlog) = futures::try_join!(
+            send_query::<NewUserCount>(new_user_count::Variables { since }),
+            send_query::<MeetingsHeldCount>(meetings_held_count::Variables { since, now: until }),
+            send_query::<AttendanceCount>(attendance_count::Variables { since, now: until }),
+            send_query::<DraftBacklogCount>(draft_backlog_count::Variables { now: until }),
+        )?;
+
+        return Ok(SummaryStats {
+            since,
+            until,
+            new_users: new_users
+                .users_aggregate
+                .aggregate
+                .map(|a| a.count)
+                .unwrap_or(0),
+            meetings_held: meetings_held
+                .meetings_aggregate
+                .aggregate
+                .map(|a| a.count)
+                .unwrap_or(0),
+            attendances: attendances
+                .meeting_attendances_aggregate
+                .aggregate
+                .map(|a| a.count)
+                .unwrap_or(0),
+            draft_backlog: draft_backlog
+                .meetings_aggregate
+                .aggregate
+                .map(|a| a.count)
+                .unwrap_or(0),
+        });
+    }
+}
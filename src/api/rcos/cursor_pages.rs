@@ -0,0 +1,240 @@
+//! Keyset (cursor) pagination of RCOS API queries.
+//!
+//! Offset pagination (see [`all_pages`](super::all_pages)) can skip or repeat rows if rows are
+//! added or removed between pages. Keyset pagination asks for the rows after the last one seen
+//! instead, ordered by a unique key. A query supports this by having a nullable `$after`
+//! variable compared with `_gt` on its key (Hasura ignores a null comparison, so the first page
+//! starts at the beginning) and ordering by that key.
+//!
+//! Implement [`CursorQuery`] for a type holding the query's other arguments, and use
+//! [`pages`] to get every page as a stream.
+
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+use futures::stream::{self, Stream};
+use graphql_client::GraphQLQuery;
+
+/// A query that can be paged through by cursor.
+pub trait CursorQuery {
+    /// The GraphQL query.
+    type Query: GraphQLQuery;
+    /// A row of the results.
+    type Row;
+    /// The key rows are ordered by.
+    type Cursor: Clone;
+
+    /// The number of rows to ask for at a time.
+    fn page_size(&self) -> u32;
+
+    /// Make the query variables for the page of rows after `after` (or the first page).
+    fn variables(
+        &self,
+        after: Option<Self::Cursor>,
+        limit: u32,
+    ) -> <Self::Query as GraphQLQuery>::Variables;
+
+    /// Get the rows of a page from the query response.
+    fn rows(response: <Self::Query as GraphQLQuery>::ResponseData) -> Vec<Self::Row>;
+
+    /// Get the cursor of a row.
+    fn cursor(row: &Self::Row) -> Self::Cursor;
+}
+
+/// Where a stream of pages is up to.
+enum PageState<C> {
+    /// The next page starts after this cursor (or at the beginning).
+    Next(Option<C>),
+    /// There are no more pages, or a page could not be fetched.
+    Done,
+}
+
+/// Get every page of a cursor query as a stream. Pages are fetched as the stream is polled.
+/// The stream ends after a page with fewer rows than the page size, or after the first
+/// error, which is yielded.
+pub fn pages<Q>(query: Q) -> impl Stream<Item = Result<Vec<Q::Row>, TelescopeError>>
+where
+    Q: CursorQuery,
+{
+    stream::unfold(
+        (query, PageState::Next(None)),
+        |(query, state)| async move {
+            let after: Option<Q::Cursor> = match state {
+                PageState::Next(after) => after,
+                PageState::Done => return None,
+            };
+
+            let limit: u32 = query.page_size();
+            let variables = query.variables(after, limit);
+            match send_query::<Q::Query>(variables).await {
+                Ok(response) => {
+                    let rows: Vec<Q::Row> = Q::rows(response);
+
+                    // A short page is the last one.
+                    let next = match rows.last() {
+                        Some(last) if rows.len() >= limit as usize => {
+                            PageState::Next(Some(Q::cursor(last)))
+                        }
+                        _ => PageState::Done,
+                    };
+
+                    // Do not yield a trailing empty page.
+                    if rows.is_empty() {
+                        return None;
+                    }
+                    return Some((Ok(rows), (query, next)));
+                }
+
+                // Surface the error and stop.
+                Err(err) => return Some((Err(err), (query, PageState::Done))),
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use crate::api::rcos::prelude::uuid;
+    use crate::api::rcos::users::enrollments::enrollments_after::{
+        enrollments_after, EnrollmentsAfter,
+    };
+    use ::uuid::Uuid;
+    use futures::StreamExt;
+    use serde_json::Value;
+
+    /// Enrollments paged through two at a time.
+    struct TestQuery;
+
+    impl CursorQuery for TestQuery {
+        type Query = EnrollmentsAfter;
+        type Row = enrollments_after::EnrollmentsAfterEnrollments;
+        type Cursor = uuid;
+
+        fn page_size(&self) -> u32 {
+            2
+        }
+
+        fn variables(&self, after: Option<uuid>, limit: u32) -> enrollments_after::Variables {
+            enrollments_after::Variables {
+                semester_id: "202209".into(),
+                after,
+                limit: limit as i64,
+            }
+        }
+
+        fn rows(response: enrollments_after::ResponseData) -> Vec<Self::Row> {
+            response.enrollments
+        }
+
+        fn cursor(row: &Self::Row) -> uuid {
+            row.user_id
+        }
+    }
+
+    /// Make user IDs in ascending order.
+    fn user_ids(count: usize) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = (0..count).map(|_| Uuid::new_v4()).collect();
+        ids.sort();
+        return ids;
+    }
+
+    /// A page of enrollments for the given users, as the API would send it.
+    fn page(users: &[Uuid]) -> Value {
+        let enrollments: Vec<Value> = users
+            .iter()
+            .map(|user_id| {
+                json!({
+                    "semester_id": "202209",
+                    "project_id": null,
+                    "is_project_lead": false,
+                    "is_coordinator": false,
+                    "credits": 4,
+                    "is_for_pay": false,
+                    "mid_year_grade": null,
+                    "final_grade": null,
+                    "created_at": "2022-09-01T12:00:00+00:00",
+                    "user_id": user_id
+                })
+            })
+            .collect();
+        json!({ "enrollments": enrollments })
+    }
+
+    /// Get the user IDs on each page of the stream, or the first error.
+    async fn collect_pages(query: TestQuery) -> Result<Vec<Vec<Uuid>>, TelescopeError> {
+        let mut collected: Vec<Vec<Uuid>> = Vec::new();
+        let mut stream = Box::pin(pages(query));
+        while let Some(page) = stream.next().await {
+            collected.push(page?.iter().map(|row| row.user_id).collect());
+        }
+        return Ok(collected);
+    }
+
+    /// Each page starts after the last row of the one before, and a short page is the last.
+    #[actix_rt::test]
+    async fn cursor_advances() {
+        let api = MockRcosApi::start();
+        let ids: Vec<Uuid> = user_ids(5);
+        api.stub_sequence(
+            "EnrollmentsAfter",
+            vec![page(&ids[0..2]), page(&ids[2..4]), page(&ids[4..5])],
+        );
+
+        let pages: Vec<Vec<Uuid>> = collect_pages(TestQuery).await.unwrap();
+        assert_eq!(
+            pages,
+            vec![ids[0..2].to_vec(), ids[2..4].to_vec(), ids[4..5].to_vec()]
+        );
+
+        let afters: Vec<Value> = api
+            .requests("EnrollmentsAfter")
+            .into_iter()
+            .map(|request| request.variables["after"].clone())
+            .collect();
+        assert_eq!(afters, vec![json!(null), json!(ids[1]), json!(ids[3])]);
+        api.assert_variables(
+            "EnrollmentsAfter",
+            json!({ "semester_id": "202209", "after": ids[3], "limit": 2 }),
+        );
+    }
+
+    /// Paging stops at an empty page, which is not yielded.
+    #[actix_rt::test]
+    async fn stops_on_empty_page() {
+        let api = MockRcosApi::start();
+        let ids: Vec<Uuid> = user_ids(2);
+        api.stub_sequence("EnrollmentsAfter", vec![page(&ids), page(&[])]);
+        // Paging past the empty page would get this error.
+        api.stub_error("EnrollmentsAfter", "Paged past the end");
+
+        let pages: Vec<Vec<Uuid>> = collect_pages(TestQuery).await.unwrap();
+        assert_eq!(pages, vec![ids]);
+        api.assert_called("EnrollmentsAfter", 2);
+    }
+
+    /// No rows is no pages.
+    #[actix_rt::test]
+    async fn no_rows() {
+        let api = MockRcosApi::start();
+        api.stub("EnrollmentsAfter", page(&[]));
+
+        assert!(collect_pages(TestQuery).await.unwrap().is_empty());
+        api.assert_called("EnrollmentsAfter", 1);
+    }
+
+    /// An error is yielded, and ends the stream.
+    #[actix_rt::test]
+    async fn error_ends_stream() {
+        let api = MockRcosApi::start();
+        let ids: Vec<Uuid> = user_ids(2);
+        api.stub_sequence("EnrollmentsAfter", vec![page(&ids)]);
+        api.stub_error("EnrollmentsAfter", "Something went wrong");
+
+        let mut stream = Box::pin(pages(TestQuery));
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+        api.assert_called("EnrollmentsAfter", 2);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod create_project_channel;
 pub mod create_project_role;
 pub mod project_info;
+pub mod save_project_channel;
+pub mod save_project_role;
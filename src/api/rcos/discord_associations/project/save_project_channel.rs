@@ -0,0 +1,35 @@
+//! RCOS API mutation to save a discord channel of a project, replacing any previous channel of
+//! the same kind.
+
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+/// Type representing GraphQL mutation to save a channel of a project.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/discord_associations/project/save_project_channel.graphql"
+)]
+pub struct SaveProjectChannel;
+
+impl SaveProjectChannel {
+    /// Save a channel of a project. Return the saved channel ID.
+    pub async fn execute(
+        project_id: i64,
+        channel_id: String,
+        kind: channel_type,
+    ) -> Result<Option<String>, TelescopeError> {
+        send_query::<Self>(save_project_channel::Variables {
+            project_id,
+            channel_id,
+            kind,
+        })
+        .await
+        .map(|response| {
+            response
+                .insert_project_channels_one
+                .map(|obj| obj.channel_id)
+        })
+    }
+}
@@ -0,0 +1,27 @@
+//! RCOS API mutation to save the discord role of a project, replacing any previous one.
+
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+/// Type representing GraphQL mutation to save the role of a project.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/discord_associations/project/save_project_role.graphql"
+)]
+pub struct SaveProjectRole;
+
+impl SaveProjectRole {
+    /// Save the role of a project. Return the saved role ID.
+    pub async fn execute(
+        project_id: i64,
+        role_id: String,
+    ) -> Result<Option<String>, TelescopeError> {
+        send_query::<Self>(save_project_role::Variables {
+            project_id,
+            role_id,
+        })
+        .await
+        .map(|response| response.insert_project_roles_one.map(|obj| obj.role_id))
+    }
+}
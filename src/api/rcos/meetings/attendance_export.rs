@@ -0,0 +1,51 @@
+//! RCOS API query to get every meeting attendance in a semester.
+
+use crate::api::rcos::all_pages::{fetch_all_pages, Batch};
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+/// The number of attendances requested at a time.
+const PER_PAGE: u32 = 500;
+
+/// The most attendances that will be fetched for one semester.
+const MAX_ROWS: usize = 100_000;
+
+/// Type representing GraphQL query to get a page of the attendances in a semester.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/attendance_export.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct SemesterAttendance;
+
+impl SemesterAttendance {
+    /// Get one page of the attendances in a semester.
+    pub async fn get_page(
+        semester_id: String,
+        page_num: u32,
+    ) -> Result<semester_attendance::ResponseData, TelescopeError> {
+        send_query::<Self>(semester_attendance::Variables {
+            semester_id,
+            limit: PER_PAGE as i64,
+            offset: (PER_PAGE * page_num) as i64,
+        })
+        .await
+    }
+
+    /// Get every attendance of every meeting in a semester, ordered by meeting start time.
+    pub async fn get(
+        semester_id: String,
+    ) -> Result<Vec<semester_attendance::SemesterAttendanceMeetingAttendances>, TelescopeError>
+    {
+        fetch_all_pages(MAX_ROWS, |page_num| {
+            let semester_id = semester_id.clone();
+            async move {
+                let response = Self::get_page(semester_id, page_num).await?;
+                Ok(Batch::from_page(response.meeting_attendances, PER_PAGE))
+            }
+        })
+        .await
+    }
+}
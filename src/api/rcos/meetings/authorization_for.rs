@@ -53,6 +53,18 @@ impl UserMeetingAuthorization {
         }
     }
 
+    /// Is the user associated with this authorization a current coordinator, faculty advisor,
+    /// or sysadmin?
+    pub fn is_coordinator_or_admin(&self) -> bool {
+        self.can_view_drafts()
+    }
+
+    /// Is the user associated with this authorization an admin (a faculty advisor or
+    /// sysadmin)?
+    pub fn is_admin(&self) -> bool {
+        self.role.is_admin()
+    }
+
     /// Can the user associated with this authorization view draft meetings?
     pub fn can_view_drafts(&self) -> bool {
         self.is_current_coordinator
@@ -123,6 +135,13 @@ impl UserMeetingAuthorization {
         self.can_view_drafts()
     }
 
+    /// Can the user associated with this authorization create or edit meetings without an
+    /// RCOS host (e.g. guest talks with an external speaker)?
+    /// This is currently just coordinators and faculty advisors.
+    pub fn can_create_hostless_meetings(&self) -> bool {
+        self.can_view_drafts()
+    }
+
     /// Get a list of the types of meetings viewable under this authorization.
     pub fn viewable_types(&self) -> Vec<MeetingType> {
         // Start with a vector of sufficient capacity to hold a full access list.
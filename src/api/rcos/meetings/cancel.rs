@@ -0,0 +1,29 @@
+//! Meeting cancellation mutation.
+
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+/// Type representing GraphQL mutation to cancel or uncancel a meeting.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/cancel.graphql",
+    response_derives = "Debug,Clone,Serialize",
+    variables_derives = "Debug,Clone,Copy"
+)]
+pub struct SetMeetingCanceled;
+
+impl SetMeetingCanceled {
+    /// Set whether a meeting is canceled. Return the meeting's ID if it exists.
+    pub async fn execute(
+        meeting_id: i64,
+        is_canceled: bool,
+    ) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(set_meeting_canceled::Variables {
+            meeting_id,
+            is_canceled,
+        })
+        .await
+        .map(|response| response.update_meetings_by_pk.map(|obj| obj.meeting_id))
+    }
+}
@@ -0,0 +1,242 @@
+//! GraphQL query to find meetings that conflict with a meeting's time, location, or host.
+
+use crate::api::rcos::meetings::meeting_title;
+use crate::api::rcos::{prelude::*, send_query};
+use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/conflicts.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct MeetingConflicts;
+
+use meeting_conflicts::MeetingConflictsMeetings;
+
+/// Make the case-insensitive pattern the RCOS API matches locations against. Any text is
+/// allowed between the words of the location, so this finds every location that could
+/// normalize to the same one (and some others, which are filtered out after the query).
+/// Locations that cannot conflict (remote meetings and blank locations) get an empty pattern,
+/// which only matches blank locations.
+fn location_pattern(location: Option<&str>, is_remote: bool) -> String {
+    if is_remote {
+        return String::new();
+    }
+    return normalized_location(location)
+        .map(|location| location.split(' ').collect::<Vec<_>>().join("%"))
+        .unwrap_or_default();
+}
+
+/// Normalize a location for comparison. Case and spacing are ignored, and blank locations
+/// are `None`.
+fn normalized_location(location: Option<&str>) -> Option<String> {
+    let words: Vec<String> = location?
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    return Some(words.join(" "));
+}
+
+impl MeetingConflictsMeetings {
+    /// Get the title of this meeting (generated if it does not have one).
+    pub fn title(&self) -> String {
+        meeting_title(self.title.as_deref(), self.type_, self.start_date_time)
+    }
+
+    /// Does this meeting conflict with a meeting from `start` to `end` at `location` hosted
+    /// by `host`? Meetings conflict if they overlap in time and have the same host, or are
+    /// both in person at the same location. Remote meetings and meetings without a location
+    /// only conflict on host. Meetings that only touch (one ends as the other starts) do not
+    /// overlap.
+    ///
+    /// The RCOS API query already filters on all of this, except that locations are only
+    /// matched loosely there.
+    fn conflicts_with(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        location: Option<&str>,
+        is_remote: bool,
+        host: Option<uuid>,
+    ) -> bool {
+        if self.start_date_time >= end || self.end_date_time <= start {
+            return false;
+        }
+
+        if host.is_some() && self.host_user_id == host {
+            return true;
+        }
+
+        if is_remote || self.is_remote {
+            return false;
+        }
+
+        return match normalized_location(location) {
+            Some(location) => normalized_location(self.location.as_deref()) == Some(location),
+            None => false,
+        };
+    }
+}
+
+impl MeetingConflicts {
+    /// Get the meetings that overlap the time from `start` to `end` and share the location
+    /// or host of a meeting. Canceled meetings are ignored. `exclude_meeting_id` is the
+    /// meeting being edited, so that it does not conflict with itself. Meeting IDs start at
+    /// 1, so 0 excludes nothing.
+    pub async fn get(
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        location: Option<&str>,
+        is_remote: bool,
+        host: Option<uuid>,
+        exclude_meeting_id: Option<i64>,
+    ) -> Result<Vec<MeetingConflictsMeetings>, TelescopeError> {
+        // Nothing can conflict with a meeting without a host or a location to share.
+        let location_pattern: String = location_pattern(location, is_remote);
+        if host.is_none() && location_pattern.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let candidates: Vec<MeetingConflictsMeetings> =
+            send_query::<Self>(meeting_conflicts::Variables {
+                start,
+                end,
+                exclude_meeting_id: exclude_meeting_id.unwrap_or(0),
+                // The nil UUID is not the ID of any user, so it matches no host.
+                host: host.unwrap_or_else(::uuid::Uuid::nil),
+                location: location_pattern,
+            })
+            .await?
+            .meetings;
+
+        return Ok(candidates
+            .into_iter()
+            .filter(|meeting| meeting.conflicts_with(start, end, location, is_remote, host))
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::meetings::MeetingType;
+    use crate::api::rcos::mock::MockRcosApi;
+    use chrono::{Duration, TimeZone};
+
+    /// The start of the meeting being checked. It is one hour long.
+    fn start() -> DateTime<Utc> {
+        Utc.ymd(2022, 9, 6).and_hms(16, 0, 0)
+    }
+
+    /// The end of the meeting being checked.
+    fn end() -> DateTime<Utc> {
+        start() + Duration::hours(1)
+    }
+
+    /// Make an existing meeting starting `offset_minutes` after the checked meeting and
+    /// lasting an hour.
+    fn existing(
+        offset_minutes: i64,
+        location: Option<&str>,
+        is_remote: bool,
+        host: Option<uuid>,
+    ) -> MeetingConflictsMeetings {
+        let start: DateTime<Utc> = start() + Duration::minutes(offset_minutes);
+        MeetingConflictsMeetings {
+            meeting_id: 12,
+            title: None,
+            type_: MeetingType::SmallGroup,
+            start_date_time: start,
+            end_date_time: start + Duration::hours(1),
+            is_remote,
+            location: location.map(str::to_string),
+            host_user_id: host,
+        }
+    }
+
+    /// Overlapping meetings with the same host conflict.
+    #[test]
+    fn overlapping_same_host_conflicts() {
+        let host: uuid = ::uuid::Uuid::new_v4();
+        let meeting = existing(30, None, true, Some(host));
+        assert!(meeting.conflicts_with(start(), end(), None, true, Some(host)));
+    }
+
+    /// Overlapping in person meetings at the same location conflict, ignoring case and
+    /// spacing.
+    #[test]
+    fn overlapping_same_location_conflicts() {
+        let meeting = existing(-30, Some("DCC  308"), false, None);
+        assert!(meeting.conflicts_with(start(), end(), Some("dcc 308"), false, None));
+        assert!(!meeting.conflicts_with(start(), end(), Some("DCC 3308"), false, None));
+    }
+
+    /// Meetings that end as the other starts do not conflict.
+    #[test]
+    fn adjacent_meetings_do_not_conflict() {
+        let host: uuid = ::uuid::Uuid::new_v4();
+        let before = existing(-60, Some("DCC 308"), false, Some(host));
+        let after = existing(60, Some("DCC 308"), false, Some(host));
+        for meeting in [before, after].iter() {
+            assert!(!meeting.conflicts_with(start(), end(), Some("DCC 308"), false, Some(host)));
+        }
+    }
+
+    /// Remote meetings only conflict on host, not on location.
+    #[test]
+    fn remote_meetings_do_not_conflict_on_location() {
+        let remote = existing(0, Some("DCC 308"), true, None);
+        assert!(!remote.conflicts_with(start(), end(), Some("DCC 308"), false, None));
+
+        let in_person = existing(0, Some("DCC 308"), false, None);
+        assert!(!in_person.conflicts_with(start(), end(), Some("DCC 308"), true, None));
+    }
+
+    /// Location patterns match any spacing and are empty when the location cannot conflict.
+    #[test]
+    fn location_patterns() {
+        assert_eq!(location_pattern(Some("  DCC   308 "), false), "dcc%308");
+        assert_eq!(location_pattern(Some("DCC 308"), true), "");
+        assert_eq!(location_pattern(Some("   "), false), "");
+        assert_eq!(location_pattern(None, false), "");
+    }
+
+    /// The host and location pattern are sent to the API to filter on.
+    #[actix_rt::test]
+    async fn sends_filter_variables() {
+        let api = MockRcosApi::start();
+        api.stub("MeetingConflicts", json!({ "meetings": [] }));
+        let host: uuid = ::uuid::Uuid::new_v4();
+
+        MeetingConflicts::get(start(), end(), Some("DCC 308"), false, Some(host), Some(7))
+            .await
+            .unwrap();
+
+        api.assert_variables(
+            "MeetingConflicts",
+            json!({
+                "start": start(),
+                "end": end(),
+                "exclude_meeting_id": 7,
+                "host": host,
+                "location": "dcc%308",
+            }),
+        );
+    }
+
+    /// A remote meeting without a host is not checked at all.
+    #[actix_rt::test]
+    async fn skips_query_when_nothing_can_conflict() {
+        let api = MockRcosApi::start();
+        let conflicts = MeetingConflicts::get(start(), end(), Some("DCC 308"), true, None, None)
+            .await
+            .unwrap();
+        assert!(conflicts.is_empty());
+        api.assert_called("MeetingConflicts", 0);
+    }
+}
@@ -1,9 +1,69 @@
 //! GraphQL query to get context for meeting creation.
+//!
+//! The context is loaded on every meeting create and edit page, but the semesters in it rarely
+//! change, so results are cached in memory for [`CACHE_TTL_SECS`].
 
 use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
 use crate::error::TelescopeError;
-use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use dashmap::DashMap;
+
+/// How long a creation context is reused for.
+const CACHE_TTL_SECS: i64 = 60;
+
+/// The arguments a creation context was queried with. The host is kept as an `Option` so
+/// that no host never matches any real host (including the nil UUID).
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct CacheKey {
+    /// The host user, if there is one.
+    host: Option<uuid>,
+    /// The manually included semester IDs, sorted and without duplicates.
+    include_semesters: Vec<String>,
+    /// The day the context was queried on, which decides which semesters are ongoing.
+    today: NaiveDate,
+}
+
+/// A cached creation context.
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    /// When the context was queried.
+    fetched_at: DateTime<Utc>,
+    /// The query response.
+    context: creation_context::ResponseData,
+}
+
+impl CacheEntry {
+    /// Has this entry expired as of `now`?
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.fetched_at + Duration::seconds(CACHE_TTL_SECS) <= now
+    }
+}
+
+lazy_static! {
+    /// Recently queried creation contexts.
+    static ref CACHE: DashMap<CacheKey, CacheEntry> = DashMap::new();
+}
+
+/// Get the cached context for a key, if it has not expired as of `now`.
+fn cached(key: &CacheKey, now: DateTime<Utc>) -> Option<creation_context::ResponseData> {
+    CACHE
+        .get(key)
+        .filter(|entry| !entry.is_expired(now))
+        .map(|entry| entry.context.clone())
+}
+
+/// Cache a context queried at `now`, dropping any expired entries.
+fn save(key: CacheKey, now: DateTime<Utc>, context: creation_context::ResponseData) {
+    CACHE.retain(|_, entry| !entry.is_expired(now));
+    CACHE.insert(
+        key,
+        CacheEntry {
+            fetched_at: now,
+            context,
+        },
+    );
+}
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -19,15 +79,188 @@ impl CreationContext {
     ///
     /// For meeting edits, semesters may be manually included by ID. otherwise, only ongoing and
     /// future semesters will be included.
+    ///
+    /// If there is no host, the host filter is an empty list, which matches no users. The
+    /// context's host list is then empty rather than missing, so use
+    /// [`creation_context::ResponseData::host`] instead of indexing it.
+    ///
+    /// Results are reused for up to [`CACHE_TTL_SECS`] seconds.
     pub async fn execute(
         host: Option<uuid>,
         include_semesters: Vec<String>,
     ) -> Result<creation_context::ResponseData, TelescopeError> {
-        send_query::<Self>(creation_context::Variables {
-            host: host.map(|h| vec![h]).unwrap_or(vec![]),
-            today: Utc::today().naive_utc(),
-            include_semesters,
-        })
-        .await
+        let now: DateTime<Utc> = Utc::now();
+        let today: NaiveDate = now.naive_utc().date();
+
+        // Build the cache key. The order of included semesters does not change the result.
+        let mut key_semesters: Vec<String> = include_semesters.clone();
+        key_semesters.sort();
+        key_semesters.dedup();
+        let key = CacheKey {
+            host,
+            include_semesters: key_semesters,
+            today,
+        };
+
+        // Check the cache.
+        if let Some(context) = cached(&key, now) {
+            return Ok(context);
+        }
+
+        // No host is an empty filter rather than a null one (which Hasura would ignore).
+        let host_filter: Vec<uuid> = host.map(|h| vec![h]).unwrap_or(vec![]);
+        let context: creation_context::ResponseData =
+            send_query::<Self>(creation_context::Variables {
+                host: host_filter,
+                today,
+                include_semesters,
+            })
+            .await?;
+
+        // Save the context. Errors are not cached.
+        save(key, now, context.clone());
+        return Ok(context);
+    }
+
+    /// Forget every cached creation context. This should be called when semesters are
+    /// created or edited, so that the change shows up right away.
+    pub fn clear_cache() {
+        CACHE.clear();
+    }
+}
+
+impl creation_context::ResponseData {
+    /// Get the host in this context, if there is one.
+    pub fn host(&self) -> Option<&creation_context::CreationContextHost> {
+        self.host.first()
+    }
+
+    /// Get the user ID of the host in this context, if there is one.
+    pub fn host_id(&self) -> Option<uuid> {
+        self.host().map(|host| host.id)
+    }
+
+    /// Check that this context has the host it was queried for. A host ID that does not match
+    /// any user leaves the context without a host, which would otherwise silently turn the
+    /// meeting into a meeting without a host.
+    pub fn check_host(&self, host: Option<uuid>) -> Result<(), TelescopeError> {
+        if self.host_id() != host {
+            return Err(TelescopeError::BadRequest {
+                header: "Host Not Found".into(),
+                message: "Could not find the selected host user.".into(),
+                show_status_code: false,
+            });
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use ::uuid::Uuid;
+    use std::sync::Mutex;
+
+    lazy_static! {
+        /// Held by tests that clear the cache, so that they do not clear it under each other.
+        static ref CACHE_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    /// A cache key with a new host, so that tests do not share cache entries.
+    fn test_key(now: DateTime<Utc>) -> CacheKey {
+        CacheKey {
+            host: Some(Uuid::new_v4()),
+            include_semesters: vec![],
+            today: now.naive_utc().date(),
+        }
+    }
+
+    /// A context with one semester and no host.
+    fn test_context() -> creation_context::ResponseData {
+        serde_json::from_value(json!({
+            "available_semesters": [{
+                "semester_id": "202209",
+                "title": "Fall 2022",
+                "start_date": "2022-09-01",
+                "end_date": "2022-12-20"
+            }],
+            "host": []
+        }))
+        .unwrap()
+    }
+
+    /// Contexts are reused until they expire.
+    #[test]
+    fn hit_inside_ttl() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let now: DateTime<Utc> = Utc::now();
+        let key: CacheKey = test_key(now);
+        save(key.clone(), now, test_context());
+
+        assert!(cached(&key, now).is_some());
+        let almost: DateTime<Utc> = now + Duration::seconds(CACHE_TTL_SECS - 1);
+        let context = cached(&key, almost).unwrap();
+        assert_eq!(context.available_semesters[0].semester_id, "202209");
+    }
+
+    /// Contexts are not reused once they expire.
+    #[test]
+    fn miss_after_ttl() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let now: DateTime<Utc> = Utc::now();
+        let key: CacheKey = test_key(now);
+        save(key.clone(), now, test_context());
+
+        assert!(cached(&key, now + Duration::seconds(CACHE_TTL_SECS)).is_none());
+    }
+
+    /// Other keys miss.
+    #[test]
+    fn miss_other_key() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let now: DateTime<Utc> = Utc::now();
+        save(test_key(now), now, test_context());
+
+        assert!(cached(&test_key(now), now).is_none());
+    }
+
+    /// Clearing the cache forgets every context.
+    #[test]
+    fn clear_cache_invalidates() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let now: DateTime<Utc> = Utc::now();
+        let key: CacheKey = test_key(now);
+        save(key.clone(), now, test_context());
+
+        CreationContext::clear_cache();
+        assert!(cached(&key, now).is_none());
+    }
+
+    /// The API is only queried again once the cache is cleared.
+    #[actix_rt::test]
+    async fn execute_uses_cache() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let api = MockRcosApi::start();
+        api.stub(
+            "CreationContext",
+            serde_json::to_value(test_context()).unwrap(),
+        );
+        let host: Option<uuid> = Some(Uuid::new_v4());
+
+        CreationContext::execute(host, vec![]).await.unwrap();
+        // The order and duplicates of included semesters do not matter.
+        CreationContext::execute(host, vec!["b".into(), "a".into()])
+            .await
+            .unwrap();
+        CreationContext::execute(host, vec!["a".into(), "b".into(), "a".into()])
+            .await
+            .unwrap();
+        api.assert_called("CreationContext", 2);
+
+        CreationContext::clear_cache();
+        CreationContext::execute(host, vec![]).await.unwrap();
+        api.assert_called("CreationContext", 3);
     }
 }
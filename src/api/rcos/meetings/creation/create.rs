@@ -1,10 +1,10 @@
 //! GraphQL mutation to create a meeting.
 
-use crate::api::rcos::meetings::MeetingType;
+use crate::api::rcos::meetings::MeetingFields;
 use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
 use crate::error::TelescopeError;
-use chrono::{DateTime, Utc};
+use url::{Host, Url};
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -14,45 +14,133 @@ use chrono::{DateTime, Utc};
 )]
 pub struct CreateMeeting;
 
-/// Trim the whitespace off a string. If the trimmed string is empty default to None.
+/// Query parameters that only track where a link was shared from. These are removed from
+/// meeting URLs, along with any parameter starting with `utm_`.
+const TRACKING_PARAMETERS: [&'static str; 5] = ["fbclid", "gclid", "igshid", "mc_cid", "mc_eid"];
+
+/// Is a query parameter only used for tracking?
+fn is_tracking_parameter(name: &str) -> bool {
+    let name: String = name.to_lowercase();
+    return name.starts_with("utm_") || TRACKING_PARAMETERS.contains(&name.as_str());
+}
+
+/// Parse and clean up a URL pasted into a meeting form. Surrounding whitespace is removed,
+/// `https://` is added if there is no scheme, the host is lowercased, and tracking query
+/// parameters are removed. Return a message for the user if the input is not a usable web
+/// address (blank, only a word like `zoom`, not HTTP(S), or containing spaces).
+pub fn parse_url(input: &str) -> Result<String, &'static str> {
+    let input: &str = input.trim();
+    if input.is_empty() {
+        return Err("Enter a web address.");
+    }
+    if input.chars().any(char::is_whitespace) {
+        return Err("Web addresses cannot contain spaces.");
+    }
+
+    // Add a scheme to bare addresses like `rpi.zoom.us/j/123`.
+    let with_scheme: String = if input.contains("://") {
+        input.to_string()
+    } else {
+        format!("https://{}", input)
+    };
+
+    let mut url: Url =
+        Url::parse(with_scheme.as_str()).map_err(|_| "This is not a valid web address.")?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Web addresses must start with http:// or https://.");
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("Web addresses cannot contain a username or password.");
+    }
+
+    // Require a real domain (or IP address), which rules out single words like `zoom`. The
+    // URL parser already lowercases domains.
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let labels: Vec<&str> = domain.trim_end_matches('.').split('.').collect();
+            let has_tld: bool = labels.len() >= 2
+                && labels.iter().all(|label| !label.is_empty())
+                && labels[labels.len() - 1]
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || c == '-');
+            if !has_tld {
+                return Err("This is not a complete web address. Copy the full link instead.");
+            }
+        }
+        Some(_) => {}
+        None => return Err("This is not a complete web address. Copy the full link instead."),
+    }
+
+    // Remove tracking parameters, keeping the order of the rest.
+    if url.query().is_some() {
+        let kept: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_parameter(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if kept.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(kept);
+        }
+    }
+
+    return Ok(url.to_string());
+}
+
+/// Is a URL (as returned by [`parse_url`]) on one of the given domains or their subdomains?
+/// Every URL is allowed if there are no domains.
+pub fn url_on_domains(url: &str, domains: &[String]) -> bool {
+    if domains.is_empty() {
+        return true;
+    }
+
+    let host: String = match Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+    {
+        Some(host) => host,
+        None => return false,
+    };
+    return domains.iter().any(|domain| {
+        let domain: String = domain.trim().trim_start_matches('.').to_lowercase();
+        host == domain || host.ends_with(format!(".{}", domain).as_str())
+    });
+}
+
+/// Clean up a URL for storage with [`parse_url`]. Blank and invalid URLs become `None`, so
+/// that they are never stored. The meeting forms report invalid URLs before this is reached.
 pub fn normalize_url(url: Option<String>) -> Option<String> {
-    url.and_then(|string| (!string.trim().is_empty()).then(|| string))
+    url.and_then(|string| parse_url(string.as_str()).ok())
+}
+
+impl From<MeetingFields> for create_meeting::Variables {
+    fn from(fields: MeetingFields) -> Self {
+        create_meeting::Variables {
+            host: fields.host,
+            external_host_name: fields.external_host_name,
+            title: fields.title,
+            start: fields.start,
+            end: fields.end,
+            description: fields.description,
+            is_draft: fields.is_draft,
+            is_remote: fields.is_remote,
+            location: fields.location,
+            // Coerce an empty or whitespace string to none.
+            meeting_url: normalize_url(fields.meeting_url),
+            recording_url: normalize_url(fields.recording_url),
+            external_slides_url: normalize_url(fields.external_slides_url),
+            semester_id: fields.semester_id,
+            kind: fields.kind,
+        }
+    }
 }
 
 impl CreateMeeting {
     /// Execute a meeting creation mutation. Return the created meeting's ID.
-    pub async fn execute(
-        host: Option<uuid>,
-        title: Option<String>,
-        start: DateTime<Utc>,
-        end: DateTime<Utc>,
-        description: String,
-        is_draft: bool,
-        is_remote: bool,
-        location: Option<String>,
-        meeting_url: Option<String>,
-        recording_url: Option<String>,
-        external_slides_url: Option<String>,
-        semester_id: String,
-        kind: MeetingType,
-    ) -> Result<Option<i64>, TelescopeError> {
-        send_query::<Self>(create_meeting::Variables {
-            host,
-            title,
-            start,
-            end,
-            description,
-            is_draft,
-            is_remote,
-            location,
-            // Coerce an empty or whitespace string to none.
-            meeting_url: normalize_url(meeting_url),
-            recording_url: normalize_url(recording_url),
-            external_slides_url: normalize_url(external_slides_url),
-            semester_id,
-            kind,
-        })
-        .await
-        .map(|response| response.insert_meetings_one.map(|obj| obj.meeting_id))
+    pub async fn execute(fields: MeetingFields) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(create_meeting::Variables::from(fields))
+            .await
+            .map(|response| response.insert_meetings_one.map(|obj| obj.meeting_id))
     }
 }
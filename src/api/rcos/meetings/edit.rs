@@ -1,8 +1,12 @@
 //! Meeting edit mutation and host selection query.
 
+use crate::api::rcos::meetings::creation::create::normalize_url;
+use crate::api::rcos::meetings::history::FieldChange;
+use crate::api::rcos::meetings::MeetingFields;
 use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
 use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
 
 /// Type representing GraphQL meeting edit mutation.
 #[derive(GraphQLQuery)]
@@ -13,13 +17,73 @@ use crate::error::TelescopeError;
 )]
 pub struct EditMeeting;
 
+impl edit_meeting::Variables {
+    /// Build the variables to set every editable field of a meeting, recording who made the
+    /// edit and when, and the fields it changes in the meeting's history.
+    pub fn new(
+        meeting_id: i64,
+        fields: MeetingFields,
+        editor: uuid,
+        edited_at: DateTime<Utc>,
+        changes: Vec<FieldChange>,
+    ) -> Self {
+        let edits = changes
+            .into_iter()
+            .map(|change| edit_meeting::meeting_edits_insert_input {
+                meeting_id: Some(meeting_id),
+                edited_by: Some(editor),
+                edited_at: Some(edited_at),
+                field: Some(change.field),
+                old_value: change.old_value,
+                new_value: change.new_value,
+            })
+            .collect();
+
+        edit_meeting::Variables {
+            meeting_id,
+            title: fields.title,
+            start: fields.start,
+            end: fields.end,
+            semester_id: fields.semester_id,
+            kind: fields.kind,
+            description: fields.description,
+            is_remote: fields.is_remote,
+            is_draft: fields.is_draft,
+            // Coerce an empty or whitespace string to none.
+            meeting_url: normalize_url(fields.meeting_url),
+            location: fields.location,
+            recording_url: normalize_url(fields.recording_url),
+            external_slides_url: normalize_url(fields.external_slides_url),
+            host: fields.host,
+            external_host_name: fields.external_host_name,
+            updated_at: edited_at,
+            updated_by: editor,
+            edits,
+        }
+    }
+}
+
 impl EditMeeting {
-    /// Execute a meeting edit mutation. Return the ID of the edited meeting if any
-    /// changes were made.
-    pub async fn execute(vars: edit_meeting::Variables) -> Result<Option<i64>, TelescopeError> {
-        send_query::<Self>(vars)
-            .await
-            .map(|response| response.update_meetings_by_pk.map(|obj| obj.meeting_id))
+    /// Execute a meeting edit mutation on behalf of the editor, recording `changes` (the
+    /// fields the edit changes, from [`changes`]) in the meeting's history. Return the ID of
+    /// the edited meeting if any changes were made.
+    ///
+    /// [`changes`]: crate::api::rcos::meetings::history::changes
+    pub async fn execute(
+        meeting_id: i64,
+        fields: MeetingFields,
+        editor: uuid,
+        changes: Vec<FieldChange>,
+    ) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(edit_meeting::Variables::new(
+            meeting_id,
+            fields,
+            editor,
+            Utc::now(),
+            changes,
+        ))
+        .await
+        .map(|response| response.update_meetings_by_pk.map(|obj| obj.meeting_id))
     }
 }
 
@@ -38,3 +102,174 @@ impl EditHostSelection {
         send_query::<Self>(edit_host_selection::Variables { meeting_id }).await
     }
 }
+
+/// A user who can be selected as a meeting's host.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct HostCandidate {
+    /// The user's ID.
+    pub id: uuid,
+    /// The user's first name.
+    pub first_name: String,
+    /// The user's last name.
+    pub last_name: String,
+    /// The user's RCS ID, if they have linked it.
+    pub rcs_id: Option<String>,
+    /// The title of the small group the user mentors, for mentors.
+    pub small_group: Option<String>,
+}
+
+impl HostCandidate {
+    /// Make a candidate from the host info returned by the host selection query.
+    fn new(info: &edit_host_selection::HostInfo, small_group: Option<&str>) -> Self {
+        HostCandidate {
+            id: info.id,
+            first_name: info.first_name.clone(),
+            last_name: info.last_name.clone(),
+            rcs_id: info
+                .rcs_id
+                .first()
+                .map(|account| account.account_id.clone()),
+            small_group: small_group.map(str::to_string),
+        }
+    }
+}
+
+/// The host candidates for a meeting, grouped for display. Each user is only in one group:
+/// the current host comes first, then coordinators, then mentors, then everyone else enrolled
+/// in the meeting's semester.
+#[derive(Clone, Debug, Serialize)]
+pub struct HostSelectionGroups {
+    /// The ID of the meeting.
+    pub meeting_id: i64,
+    /// The title of the meeting's semester.
+    pub semester_title: String,
+    /// The current host of the meeting, if there is one.
+    pub current_host: Option<HostCandidate>,
+    /// Coordinators of the semester, alphabetically.
+    pub coordinators: Vec<HostCandidate>,
+    /// Mentors of the semester, by small group and then alphabetically.
+    pub mentors: Vec<HostCandidate>,
+    /// Everyone else enrolled in the semester, alphabetically.
+    pub members: Vec<HostCandidate>,
+}
+
+impl HostSelectionGroups {
+    /// Group the candidates in a host selection query response. Return `None` if the meeting
+    /// was not found.
+    pub fn from_response(data: &edit_host_selection::ResponseData) -> Option<Self> {
+        let meeting = data.meetings_by_pk.as_ref()?;
+        let semester = &meeting.semester;
+
+        // Users already placed in a group.
+        let mut seen: Vec<uuid> = Vec::new();
+        // Add a candidate to a group unless they are already in one.
+        let mut place = |group: &mut Vec<HostCandidate>, candidate: HostCandidate| {
+            if !seen.contains(&candidate.id) {
+                seen.push(candidate.id);
+                group.push(candidate);
+            }
+        };
+
+        let mut current_host: Vec<HostCandidate> = Vec::new();
+        if let Some(host) = meeting.host.as_ref() {
+            place(&mut current_host, HostCandidate::new(host, None));
+        }
+
+        let mut coordinators: Vec<HostCandidate> = Vec::new();
+        for coordinator in &semester.coordinators {
+            place(
+                &mut coordinators,
+                HostCandidate::new(&coordinator.user, None),
+            );
+        }
+
+        let mut mentors: Vec<HostCandidate> = Vec::new();
+        for small_group in &semester.small_groups {
+            for mentor in &small_group.small_group_mentors {
+                place(
+                    &mut mentors,
+                    HostCandidate::new(&mentor.user, Some(small_group.title.as_str())),
+                );
+            }
+        }
+
+        let mut members: Vec<HostCandidate> = Vec::new();
+        for enrollment in &semester.enrollments {
+            place(&mut members, HostCandidate::new(&enrollment.user, None));
+        }
+
+        return Some(HostSelectionGroups {
+            meeting_id: meeting.meeting_id,
+            semester_title: semester.title.clone(),
+            current_host: current_host.pop(),
+            coordinators,
+            mentors,
+            members,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditMeeting;
+    use crate::api::rcos::meetings::history::FieldChange;
+    use crate::api::rcos::meetings::{MeetingFields, MeetingType};
+    use crate::api::rcos::mock::MockRcosApi;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    /// Make the fields of a meeting edit.
+    fn fields() -> MeetingFields {
+        MeetingFields {
+            host: None,
+            external_host_name: None,
+            title: Some("Renamed".into()),
+            start: Utc::now(),
+            end: Utc::now() + Duration::hours(1),
+            description: String::new(),
+            is_draft: false,
+            is_remote: false,
+            location: None,
+            meeting_url: None,
+            recording_url: None,
+            external_slides_url: None,
+            semester_id: "202209".into(),
+            kind: MeetingType::LargeGroup,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn edit_records_history_in_same_mutation() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "EditMeeting",
+            json!({
+                "update_meetings_by_pk": { "meeting_id": 3 },
+                "insert_meeting_edits": { "affected_rows": 1 }
+            }),
+        );
+
+        let editor = Uuid::new_v4();
+        let changes = vec![FieldChange {
+            field: "title".into(),
+            old_value: Some("Original".into()),
+            new_value: Some("Renamed".into()),
+        }];
+        let edited = EditMeeting::execute(3, fields(), editor, changes)
+            .await
+            .unwrap();
+        assert_eq!(edited, Some(3));
+
+        // The edit and its history are sent in one request.
+        api.assert_called("EditMeeting", 1);
+        let variables = api.requests("EditMeeting").remove(0).variables;
+        let edits = variables["edits"].as_array().unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0]["meeting_id"], json!(3));
+        assert_eq!(edits[0]["edited_by"], json!(editor));
+        assert_eq!(edits[0]["edited_at"], variables["updated_at"]);
+        assert_eq!(edits[0]["field"], json!("title"));
+        assert_eq!(edits[0]["old_value"], json!("Original"));
+        assert_eq!(edits[0]["new_value"], json!("Renamed"));
+    }
+}
@@ -1,35 +1,130 @@
 //! List meetings query.
 
-use crate::api::rcos::meetings::MeetingType;
+use crate::api::rcos::meetings::{meeting_title, MeetingType};
 use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
 use crate::error::TelescopeError;
 use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::str::FromStr;
 
 /// Type representing public RCOS meetings.
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/rcos/schema.json",
     query_path = "graphql/rcos/meetings/get.graphql",
-    response_derives = "Debug,Clone,Serialize"
+    response_derives = "Debug,Clone,Serialize",
+    // Deserialize is needed to build the order_by input from JSON.
+    variables_derives = "Deserialize"
 )]
 pub struct Meetings;
 
-use self::meetings::{MeetingsMeetings, Variables};
+use self::meetings::{meetings_order_by, MeetingsMeetings, Variables};
+
+/// The orders that the meetings list can be sorted in.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingSort {
+    /// Earliest meetings first. Since the list starts from the current time by default, this
+    /// shows upcoming meetings first.
+    DateAsc,
+    /// Latest meetings first.
+    DateDesc,
+    /// Alphabetically by title, then by date. Meetings without a title go last.
+    Title,
+}
+
+impl Default for MeetingSort {
+    fn default() -> Self {
+        MeetingSort::DateAsc
+    }
+}
+
+impl MeetingSort {
+    /// All the valid sorts.
+    pub const ALL: [MeetingSort; 3] = [
+        MeetingSort::DateAsc,
+        MeetingSort::DateDesc,
+        MeetingSort::Title,
+    ];
+
+    /// The key used for this sort in the `sort` query parameter.
+    pub fn key(&self) -> &'static str {
+        match self {
+            MeetingSort::DateAsc => "date_asc",
+            MeetingSort::DateDesc => "date_desc",
+            MeetingSort::Title => "title",
+        }
+    }
+
+    /// The Hasura ordering for this sort, as JSON.
+    fn order_by_json(&self) -> Value {
+        match self {
+            MeetingSort::DateAsc => json!([{"start_date_time": "asc"}]),
+            MeetingSort::DateDesc => json!([{"start_date_time": "desc"}]),
+            MeetingSort::Title => json!([
+                {"title": "asc_nulls_last"},
+                {"start_date_time": "asc"}
+            ]),
+        }
+    }
+
+    /// Convert this sort to the ordering input of the meetings query.
+    fn order_by(&self) -> Result<Vec<meetings_order_by>, TelescopeError> {
+        serde_json::from_value(self.order_by_json())
+            .map_err(|e| TelescopeError::ise(format!("Could not build meetings ordering: {}", e)))
+    }
+}
+
+impl FromStr for MeetingSort {
+    type Err = TelescopeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Check against the list of valid sorts.
+        Self::ALL
+            .iter()
+            .find(|sort| sort.key() == s.trim())
+            .copied()
+            .ok_or_else(|| {
+                let valid: Vec<&str> = Self::ALL.iter().map(MeetingSort::key).collect();
+                TelescopeError::BadRequest {
+                    header: "Invalid Sort".into(),
+                    message: format!(
+                        "Cannot sort meetings by {:?}. Valid sorts are: {}.",
+                        s,
+                        valid.join(", ")
+                    ),
+                    show_status_code: true,
+                }
+            })
+    }
+}
+
+impl MeetingsMeetings {
+    /// Get the title of this meeting (see [`meeting_title`]).
+    pub fn title(&self) -> String {
+        meeting_title(self.title.as_deref(), self.type_, self.start_date_time)
+    }
+}
 
 impl Meetings {
-    /// Get the meetings between two times, optionally filter to finalized meetings only.
+    /// Get the meetings between two times, optionally filter to finalized meetings only
+    /// and/or meetings that have not been canceled.
     pub async fn get(
         start: DateTime<Utc>,
         end: DateTime<Utc>,
         include_drafts: bool,
+        include_canceled: bool,
         accept_types: Vec<MeetingType>,
+        sort: MeetingSort,
     ) -> Result<Vec<MeetingsMeetings>, TelescopeError> {
         Ok(send_query::<Self>(Variables {
             start,
             end,
             include_drafts,
+            include_canceled,
             accept_types,
+            order_by: sort.order_by()?,
         })
         .await?
         .meetings)
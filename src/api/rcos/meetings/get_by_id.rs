@@ -1,8 +1,17 @@
 //! GraphQL query to get a meeting by its ID.
 
+use crate::api::rcos::meetings::meeting_title;
 use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
 use crate::error::TelescopeError;
+use url::Url;
+
+/// The Google Calendar page that creates an event from URL parameters.
+const GOOGLE_CALENDAR_TEMPLATE_URL: &'static str =
+    "https://calendar.google.com/calendar/render?action=TEMPLATE";
+
+/// The timestamp format Google Calendar expects in event URLs (UTC).
+const GOOGLE_CALENDAR_TIME_FORMAT: &'static str = "%Y%m%dT%H%M%SZ";
 
 /// Type representing public RCOS meetings.
 #[derive(GraphQLQuery)]
@@ -30,16 +39,109 @@ impl MeetingMeeting {
     /// Get the title of this meeting. This is the user-defined title if there is one, otherwise
     /// a title is constructed from the start date and meeting type.
     pub fn title(&self) -> String {
-        // Check for a user-defined title.
-        if self.title.is_some() {
-            return self.title.clone().unwrap();
+        meeting_title(self.title.as_deref(), self.type_, self.start_date_time)
+    }
+
+    /// Get a link that opens a new Google Calendar event pre-filled with this meeting, for
+    /// viewers to add it to their own calendar. `meeting_page_url` is linked in the event
+    /// details. Canceled meetings have no link.
+    pub fn google_calendar_url(&self, meeting_page_url: &str) -> Option<Url> {
+        if self.is_canceled {
+            return None;
         }
 
-        // Otherwise create a title.
-        format!(
-            "RCOS {} - {}",
-            self.type_,
-            self.start_date_time.format("%B %_d, %Y")
-        )
+        let dates: String = format!(
+            "{}/{}",
+            self.start_date_time.format(GOOGLE_CALENDAR_TIME_FORMAT),
+            self.end_date_time.format(GOOGLE_CALENDAR_TIME_FORMAT)
+        );
+        let details: String = format!("{}\n\n{}", meeting_page_url, self.description)
+            .trim()
+            .to_string();
+        // Use the meeting URL as the location of remote meetings without one.
+        let location: &str = self
+            .location
+            .as_deref()
+            .filter(|location| !location.trim().is_empty())
+            .or(self.meeting_url.as_deref())
+            .unwrap_or("");
+
+        let mut url: Url =
+            Url::parse(GOOGLE_CALENDAR_TEMPLATE_URL).expect("Could not parse Google Calendar URL");
+        url.query_pairs_mut()
+            .append_pair("text", self.title().as_str())
+            .append_pair("dates", dates.as_str())
+            .append_pair("details", details.as_str())
+            .append_pair("location", location);
+        return Some(url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Meeting;
+    use crate::api::rcos::meetings::MeetingType;
+    use crate::api::rcos::mock::MockRcosApi;
+
+    #[actix_rt::test]
+    async fn gets_meeting_by_id() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "Meeting",
+            json!({
+                "meeting": {
+                    "meeting_id": 12,
+                    "start_date_time": "2022-02-01T18:00:00+00:00",
+                    "end_date_time": "2022-02-01T20:00:00+00:00",
+                    "title": null,
+                    "type": "large_group",
+                    "is_draft": false,
+                    "is_canceled": false,
+                    "is_remote": false,
+                    "meeting_url": null,
+                    "recording_url": null,
+                    "external_presentation_url": null,
+                    "location": "DCC 308",
+                    "description": "",
+                    "semester": { "semester_id": "202201", "title": "Spring 2022" },
+                    "host": null,
+                    "external_host_name": null,
+                    "updated_at": null,
+                    "editor": null,
+                    "attendances": { "aggregate": { "count": 3 } }
+                }
+            }),
+        );
+
+        let meeting = Meeting::get(12)
+            .await
+            .unwrap()
+            .expect("Meeting should exist");
+        assert_eq!(meeting.meeting_id, 12);
+        assert_eq!(meeting.type_, MeetingType::LargeGroup);
+        assert_eq!(meeting.location.as_deref(), Some("DCC 308"));
+        assert_eq!(meeting.title(), "RCOS Large Group - February  1, 2022");
+
+        api.assert_called("Meeting", 1);
+        api.assert_variables("Meeting", json!({ "id": 12 }));
+    }
+
+    #[actix_rt::test]
+    async fn missing_meeting_is_none() {
+        let api = MockRcosApi::start();
+        api.stub("Meeting", json!({ "meeting": null }));
+
+        assert!(Meeting::get(404).await.unwrap().is_none());
+        api.assert_variables("Meeting", json!({ "id": 404 }));
+    }
+
+    #[actix_rt::test]
+    async fn graphql_errors_are_reported() {
+        let api = MockRcosApi::start();
+        api.stub_error("Meeting", "field \"meetings_by_pk\" not found");
+
+        assert!(Meeting::get(12).await.is_err());
+        // GraphQL errors are not retried.
+        api.assert_called("Meeting", 1);
     }
 }
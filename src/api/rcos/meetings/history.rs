@@ -0,0 +1,203 @@
+//! Edit history of meetings.
+//!
+//! Each time a meeting is edited, the fields that actually changed are recorded with their
+//! values before and after the edit, along with who made the edit and when. Unchanged fields
+//! are not recorded, so an edit that changes nothing leaves no history. The changes are
+//! recorded by the edit mutation itself (see [`EditMeeting`]).
+//!
+//! [`EditMeeting`]: crate::api::rcos::meetings::edit::EditMeeting
+
+use crate::api::rcos::meetings::creation::create::normalize_url;
+use crate::api::rcos::meetings::get_by_id::meeting::MeetingMeeting;
+use crate::api::rcos::meetings::MeetingFields;
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
+
+/// The most changed fields shown in a meeting's history.
+const HISTORY_LIMIT: i64 = 200;
+
+/// The format of meeting times in the history.
+const TIME_FORMAT: &'static str = "%Y-%m-%d %H:%M UTC";
+
+/// Type representing the GraphQL query for the edit history of a meeting.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/edit/history.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct MeetingEditHistory;
+
+/// A field changed by a meeting edit.
+#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
+pub struct FieldChange {
+    /// The name of the field.
+    pub field: String,
+    /// The value before the edit, if it was set.
+    pub old_value: Option<String>,
+    /// The value after the edit, if it is set.
+    pub new_value: Option<String>,
+}
+
+/// Add a change to the list if the value of a field is different after the edit.
+fn compare(
+    changes: &mut Vec<FieldChange>,
+    field: &str,
+    old_value: Option<String>,
+    new_value: Option<String>,
+) {
+    if old_value != new_value {
+        changes.push(FieldChange {
+            field: field.to_string(),
+            old_value,
+            new_value,
+        });
+    }
+}
+
+/// Get the fields a meeting edit changes. `before` is the meeting as it was before the edit,
+/// and `after` is what the edit mutation will set. URLs are compared the way the edit
+/// mutation saves them, so they are not reported as changed just because of formatting.
+pub fn changes(before: &MeetingMeeting, after: &MeetingFields) -> Vec<FieldChange> {
+    let time = |timestamp: &DateTime<Utc>| Some(timestamp.format(TIME_FORMAT).to_string());
+    let mut changes: Vec<FieldChange> = Vec::new();
+
+    compare(
+        &mut changes,
+        "title",
+        before.title.clone(),
+        after.title.clone(),
+    );
+    compare(
+        &mut changes,
+        "start",
+        time(&before.start_date_time),
+        time(&after.start),
+    );
+    compare(
+        &mut changes,
+        "end",
+        time(&before.end_date_time),
+        time(&after.end),
+    );
+    compare(
+        &mut changes,
+        "semester",
+        Some(before.semester.semester_id.clone()),
+        Some(after.semester_id.clone()),
+    );
+    compare(
+        &mut changes,
+        "type",
+        Some(before.type_.to_string()),
+        Some(after.kind.to_string()),
+    );
+    compare(
+        &mut changes,
+        "description",
+        Some(before.description.clone()),
+        Some(after.description.clone()),
+    );
+    compare(
+        &mut changes,
+        "is_draft",
+        Some(before.is_draft.to_string()),
+        Some(after.is_draft.to_string()),
+    );
+    compare(
+        &mut changes,
+        "is_remote",
+        Some(before.is_remote.to_string()),
+        Some(after.is_remote.to_string()),
+    );
+    compare(
+        &mut changes,
+        "location",
+        before.location.clone(),
+        after.location.clone(),
+    );
+    compare(
+        &mut changes,
+        "meeting_url",
+        before.meeting_url.clone(),
+        normalize_url(after.meeting_url.clone()),
+    );
+    compare(
+        &mut changes,
+        "recording_url",
+        before.recording_url.clone(),
+        normalize_url(after.recording_url.clone()),
+    );
+    compare(
+        &mut changes,
+        "external_slides_url",
+        before.external_presentation_url.clone(),
+        normalize_url(after.external_slides_url.clone()),
+    );
+    compare(
+        &mut changes,
+        "host",
+        before.host.as_ref().map(|host| host.id.to_string()),
+        after.host.map(|host| host.to_string()),
+    );
+    compare(
+        &mut changes,
+        "external_host_name",
+        before.external_host_name.clone(),
+        after.external_host_name.clone(),
+    );
+
+    return changes;
+}
+
+/// An edit in a meeting's history.
+#[derive(Clone, Debug, Serialize)]
+pub struct MeetingEdit {
+    /// When the edit was made.
+    pub edited_at: DateTime<Utc>,
+    /// Who made the edit.
+    pub editor: meeting_edit_history::MeetingEditHistoryMeetingEditsEditor,
+    /// The fields the edit changed.
+    pub changes: Vec<FieldChange>,
+}
+
+impl MeetingEditHistory {
+    /// Get the edits made to a meeting, newest first. Only the most recent changes are
+    /// returned, so the oldest edit in a long history may be missing some of its fields.
+    pub async fn get(meeting_id: i64) -> Result<Vec<MeetingEdit>, TelescopeError> {
+        let rows = send_query::<Self>(meeting_edit_history::Variables {
+            meeting_id,
+            limit: HISTORY_LIMIT,
+        })
+        .await?
+        .meeting_edits;
+
+        // Group the changed fields of each edit. They are recorded together, so they share
+        // a timestamp and editor, and are next to each other in the results.
+        let mut edits: Vec<MeetingEdit> = Vec::new();
+        for row in rows {
+            let change = FieldChange {
+                field: row.field,
+                old_value: row.old_value,
+                new_value: row.new_value,
+            };
+
+            match edits.last_mut() {
+                Some(edit)
+                    if edit.edited_at == row.edited_at && edit.editor.id == row.editor.id =>
+                {
+                    edit.changes.push(change);
+                }
+                _ => edits.push(MeetingEdit {
+                    edited_at: row.edited_at,
+                    editor: row.editor,
+                    changes: vec![change],
+                }),
+            }
+        }
+
+        return Ok(edits);
+    }
+}
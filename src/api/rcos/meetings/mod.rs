@@ -1,12 +1,22 @@
 //! Queries and mutations to the RCOS API for meeting data.
 
+pub mod attendance_export;
 pub mod authorization_for;
+pub mod cancel;
+pub mod conflicts;
 pub mod creation;
 pub mod delete;
 pub mod edit;
 pub mod get;
 pub mod get_by_id;
 pub mod get_host;
+pub mod history;
+pub mod reminders;
+pub mod search;
+pub mod title_taken;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
 
 /// List of all existing meeting type variants.
 pub const ALL_MEETING_TYPES: [MeetingType; 8] = [
@@ -20,8 +30,20 @@ pub const ALL_MEETING_TYPES: [MeetingType; 8] = [
     MeetingType::Other,
 ];
 
+/// Get the title of a meeting. This is the user-defined title if there is one, otherwise a
+/// title is constructed from the start date and meeting type.
+pub fn meeting_title(title: Option<&str>, kind: MeetingType, start: DateTime<Utc>) -> String {
+    // Check for a user-defined title.
+    if let Some(title) = title {
+        return title.to_string();
+    }
+
+    // Otherwise create a title.
+    format!("RCOS {} - {}", kind, start.format("%B %_d, %Y"))
+}
+
 /// The type of a meeting.
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Display)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash, Display)]
 #[serde(rename_all = "snake_case")]
 pub enum MeetingType {
     #[display(fmt = "Large Group")]
@@ -41,3 +63,38 @@ pub enum MeetingType {
     #[display(fmt = "Uncategorized Meeting")]
     Other,
 }
+
+/// The fields of a meeting that are set when it is created or edited. This is built once from
+/// a validated meeting form and converted into the variables of the creation or edit mutation,
+/// so that the form fields are mapped to mutation variables in one place.
+#[derive(Clone, Debug)]
+pub struct MeetingFields {
+    /// The RCOS user hosting the meeting, if any.
+    pub host: Option<Uuid>,
+    /// The name of the host if they are not an RCOS user.
+    pub external_host_name: Option<String>,
+    /// The meeting title. `None` uses the default title for the meeting type.
+    pub title: Option<String>,
+    /// When the meeting starts.
+    pub start: DateTime<Utc>,
+    /// When the meeting ends.
+    pub end: DateTime<Utc>,
+    /// The markdown description of the meeting.
+    pub description: String,
+    /// Is the meeting a draft (not publicly listed)?
+    pub is_draft: bool,
+    /// Is the meeting remote?
+    pub is_remote: bool,
+    /// Where the meeting is held.
+    pub location: Option<String>,
+    /// The URL to join the meeting online.
+    pub meeting_url: Option<String>,
+    /// The URL of the meeting recording.
+    pub recording_url: Option<String>,
+    /// The URL of the slides for the meeting.
+    pub external_slides_url: Option<String>,
+    /// The semester the meeting is in.
+    pub semester_id: String,
+    /// The type of meeting.
+    pub kind: MeetingType,
+}
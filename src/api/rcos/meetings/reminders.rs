@@ -0,0 +1,87 @@
+//! Query for upcoming meetings that may need reminders sent, and mutation to record that a
+//! reminder was sent.
+
+use crate::api::rcos::{prelude::*, send_query};
+use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
+
+/// Type representing GraphQL query to get the meetings starting in a window of time.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/reminders.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UpcomingMeetings;
+
+/// Type representing GraphQL mutation to claim the reminder for a meeting.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/reminders.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct ClaimMeetingReminder;
+
+impl UpcomingMeetings {
+    /// Get the published, non-canceled meetings that start after `from` and no later
+    /// than `until`.
+    pub async fn get(
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<upcoming_meetings::UpcomingMeetingsMeetings>, TelescopeError> {
+        send_query::<Self>(upcoming_meetings::Variables { from, until })
+            .await
+            .map(|response| response.meetings)
+    }
+}
+
+impl ClaimMeetingReminder {
+    /// Record that the reminder for a meeting starting at `start` is being sent. Return
+    /// whether this call claimed it: `false` means the reminder was already claimed (by an
+    /// earlier run or another Telescope instance), or the meeting moved or was deleted.
+    pub async fn execute(meeting_id: i64, start: DateTime<Utc>) -> Result<bool, TelescopeError> {
+        send_query::<Self>(claim_meeting_reminder::Variables { meeting_id, start })
+            .await
+            .map(|response| {
+                response
+                    .update_meetings
+                    .map(|updated| updated.affected_rows > 0)
+                    .unwrap_or(false)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClaimMeetingReminder;
+    use crate::api::rcos::mock::MockRcosApi;
+    use chrono::Utc;
+
+    #[actix_rt::test]
+    async fn first_claim_succeeds() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "ClaimMeetingReminder",
+            json!({ "update_meetings": { "affected_rows": 1 } }),
+        );
+
+        let start = Utc::now();
+        assert!(ClaimMeetingReminder::execute(7, start).await.unwrap());
+        api.assert_variables(
+            "ClaimMeetingReminder",
+            json!({ "meeting_id": 7, "start": start }),
+        );
+    }
+
+    #[actix_rt::test]
+    async fn repeated_claim_fails() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "ClaimMeetingReminder",
+            json!({ "update_meetings": { "affected_rows": 0 } }),
+        );
+
+        assert!(!ClaimMeetingReminder::execute(7, Utc::now()).await.unwrap());
+    }
+}
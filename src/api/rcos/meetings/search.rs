@@ -0,0 +1,55 @@
+//! GraphQL query to search meetings by title and description.
+
+use crate::api::rcos::meetings::MeetingType;
+use crate::api::rcos::{prelude::*, search_strings::resolve_search_string, send_query};
+use crate::error::TelescopeError;
+use chrono::{DateTime, Utc};
+
+/// Meetings per page of search results.
+pub const PER_PAGE: u32 = 20;
+
+/// Type representing the GraphQL query to search meetings.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/search.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct SearchMeetings;
+
+impl SearchMeetings {
+    /// Search meetings for a given page number (zero indexed), in chronological order. A
+    /// blank or missing search matches every meeting in the date range. Either bound of the
+    /// range can be left out.
+    pub async fn get(
+        search: Option<String>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        include_drafts: bool,
+        accept_types: Vec<MeetingType>,
+        page: u32,
+    ) -> Result<search_meetings::ResponseData, TelescopeError> {
+        // Blank searches match everything rather than only meetings containing spaces.
+        let search: Option<String> = search
+            .map(|search| search.trim().to_string())
+            .filter(|search| !search.is_empty());
+
+        send_query::<Self>(search_meetings::Variables {
+            search: resolve_search_string(search),
+            start,
+            end,
+            include_drafts,
+            accept_types,
+            limit: PER_PAGE as i64,
+            offset: (PER_PAGE * page) as i64,
+        })
+        .await
+    }
+}
+
+impl search_meetings::ResponseData {
+    /// Get the number of meetings matching the search if available.
+    pub fn total(&self) -> Option<i64> {
+        Some(self.total.aggregate.as_ref()?.count)
+    }
+}
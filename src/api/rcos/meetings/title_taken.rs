@@ -0,0 +1,54 @@
+//! GraphQL query to check if a meeting title is already used in a semester.
+
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/meetings/title_taken.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct MeetingTitleTaken;
+
+/// Build a case-insensitive regular expression matching a title with any amount of
+/// whitespace between its words (and around it). Characters in the title are escaped so
+/// that they only match themselves.
+fn title_pattern(title: &str) -> String {
+    let words: Vec<String> = title
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .map(|c| {
+                    // Escape everything other than letters and numbers.
+                    if c.is_ascii_alphanumeric() || !c.is_ascii() {
+                        c.to_string()
+                    } else {
+                        format!("\\{}", c)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    return format!("^\\s*{}\\s*$", words.join("\\s+"));
+}
+
+impl MeetingTitleTaken {
+    /// Get the ID of a published meeting in the semester with the same title (ignoring case
+    /// and whitespace), if there is one. `exclude_meeting_id` is the meeting being edited, so
+    /// that it does not match itself. Meeting IDs start at 1, so 0 excludes nothing.
+    pub async fn get(
+        semester_id: String,
+        title: &str,
+        exclude_meeting_id: Option<i64>,
+    ) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(meeting_title_taken::Variables {
+            semester_id,
+            title_pattern: title_pattern(title),
+            exclude_meeting_id: exclude_meeting_id.unwrap_or(0),
+        })
+        .await
+        .map(|response| response.meetings.first().map(|meeting| meeting.meeting_id))
+    }
+}
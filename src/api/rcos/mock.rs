@@ -0,0 +1,217 @@
+//! Mock of the central RCOS API for unit tests.
+//!
+//! [`MockRcosApi::start`] runs a local GraphQL server and points [`send_query`] (and the other
+//! RCOS API functions) at it on the current thread, until the mock is dropped. Responses are
+//! stubbed by operation name, and every request is recorded so that tests can check the
+//! variables that were sent. Requests for operations without a stub get a GraphQL error.
+//! Operations can also be made to fail with HTTP error statuses a number of times before their
+//! stub is sent, to test retries, or to respond with a sequence of results, to test paging.
+//!
+//! Tests using the mock should run on the actix runtime (`#[actix_rt::test]`), which runs the
+//! test on a single thread.
+//!
+//! [`send_query`]: super::send_query
+
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestServer};
+use actix_web::web::{self, Data, Json};
+use actix_web::{App, HttpResponse};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+    /// The URL of the mock API running for the test on this thread, if there is one.
+    static API_URL: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Get the URL of the mock API running for the test on this thread, if there is one.
+pub fn api_url_override() -> Option<String> {
+    API_URL.with(|url| url.borrow().clone())
+}
+
+/// A GraphQL request received by the mock API.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    /// The name of the operation.
+    pub operation_name: String,
+    /// The GraphQL document.
+    pub query: String,
+    /// The variables sent with the operation.
+    pub variables: Value,
+}
+
+/// The stubs and received requests of a mock API, shared with its server thread.
+#[derive(Default)]
+struct MockState {
+    /// The response body to send for each operation name.
+    stubs: HashMap<String, Value>,
+    /// HTTP error statuses to respond to each operation name with (one per request) before
+    /// sending its stub.
+    failures: HashMap<String, VecDeque<u16>>,
+    /// Response bodies to send for each operation name (one per request) before sending its
+    /// stub.
+    sequences: HashMap<String, VecDeque<Value>>,
+    /// Every request received, oldest first.
+    requests: Vec<RecordedRequest>,
+}
+
+/// A running mock of the central RCOS API. Stop it by dropping it.
+pub struct MockRcosApi {
+    /// The local server.
+    server: TestServer,
+    /// The stubs and received requests.
+    state: Arc<Mutex<MockState>>,
+}
+
+/// Respond to a GraphQL request with the stub for its operation.
+async fn respond(state: Data<Arc<Mutex<MockState>>>, Json(body): Json<Value>) -> HttpResponse {
+    let request = RecordedRequest {
+        operation_name: body["operationName"].as_str().unwrap_or("").to_string(),
+        query: body["query"].as_str().unwrap_or("").to_string(),
+        variables: body["variables"].clone(),
+    };
+
+    let mut state = state.lock().unwrap();
+
+    // Fail with the next error status for this operation, if there is one.
+    let failure: Option<u16> = state
+        .failures
+        .get_mut(&request.operation_name)
+        .and_then(VecDeque::pop_front);
+    if let Some(status) = failure {
+        state.requests.push(request);
+        let status = StatusCode::from_u16(status).expect("Invalid stubbed status");
+        return HttpResponse::build(status).json(json!({
+            "errors": [{ "message": format!("Stubbed status {}", status) }]
+        }));
+    }
+
+    // Send the next response in the sequence for this operation if there is one, and its stub
+    // otherwise.
+    let next: Option<Value> = state
+        .sequences
+        .get_mut(&request.operation_name)
+        .and_then(VecDeque::pop_front);
+    let response: Value = next
+        .or_else(|| state.stubs.get(&request.operation_name).cloned())
+        .unwrap_or_else(|| {
+            json!({
+                "errors": [{
+                    "message": format!("No stub for operation {}", request.operation_name)
+                }]
+            })
+        });
+    state.requests.push(request);
+
+    return HttpResponse::Ok().json(response);
+}
+
+impl MockRcosApi {
+    /// Start a mock API and send the RCOS API requests made on this thread to it.
+    pub fn start() -> Self {
+        let state: Arc<Mutex<MockState>> = Arc::new(Mutex::new(MockState::default()));
+        let server_state = state.clone();
+        let server: TestServer = test::start(move || {
+            App::new()
+                .data(server_state.clone())
+                .default_service(web::route().to(respond))
+        });
+
+        let url: String = server.url("/v1/graphql");
+        API_URL.with(|api_url| *api_url.borrow_mut() = Some(url));
+
+        return MockRcosApi { server, state };
+    }
+
+    /// Get the URL the mock API is served at.
+    pub fn url(&self) -> String {
+        self.server.url("/v1/graphql")
+    }
+
+    /// Respond to an operation with the given `data`.
+    pub fn stub(&self, operation_name: &str, data: Value) {
+        self.stub_response(operation_name, json!({ "data": data }));
+    }
+
+    /// Respond to an operation with a GraphQL error with the given message.
+    pub fn stub_error(&self, operation_name: &str, message: &str) {
+        self.stub_response(
+            operation_name,
+            json!({ "errors": [{ "message": message }] }),
+        );
+    }
+
+    /// Respond to an operation with the given response body.
+    pub fn stub_response(&self, operation_name: &str, body: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .stubs
+            .insert(operation_name.to_string(), body);
+    }
+
+    /// Respond to the next requests for an operation with the given HTTP error statuses (one
+    /// per request), before sending its stub.
+    pub fn stub_failures(&self, operation_name: &str, statuses: &[u16]) {
+        self.state.lock().unwrap().failures.insert(
+            operation_name.to_string(),
+            statuses.iter().copied().collect(),
+        );
+    }
+
+    /// Respond to the next requests for an operation with each of the given `data` in turn (one
+    /// per request), before sending its stub.
+    pub fn stub_sequence(&self, operation_name: &str, data: Vec<Value>) {
+        self.state.lock().unwrap().sequences.insert(
+            operation_name.to_string(),
+            data.into_iter()
+                .map(|data| json!({ "data": data }))
+                .collect(),
+        );
+    }
+
+    /// Get the requests received for an operation, oldest first.
+    pub fn requests(&self, operation_name: &str) -> Vec<RecordedRequest> {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|request| request.operation_name == operation_name)
+            .cloned()
+            .collect()
+    }
+
+    /// Assert that an operation was requested exactly `times` times.
+    pub fn assert_called(&self, operation_name: &str, times: usize) {
+        let received: usize = self.requests(operation_name).len();
+        assert_eq!(
+            received, times,
+            "Expected {} request(s) for {}, got {}",
+            times, operation_name, received
+        );
+    }
+
+    /// Assert that the most recent request for an operation was sent with the given
+    /// variables.
+    pub fn assert_variables(&self, operation_name: &str, variables: Value) {
+        let last: RecordedRequest = self
+            .requests(operation_name)
+            .pop()
+            .unwrap_or_else(|| panic!("No requests for {}", operation_name));
+        assert_eq!(
+            last.variables, variables,
+            "Unexpected variables for {}",
+            operation_name
+        );
+    }
+}
+
+impl Drop for MockRcosApi {
+    fn drop(&mut self) {
+        // Send later requests on this thread to the configured API again.
+        API_URL.with(|api_url| *api_url.borrow_mut() = None);
+    }
+}
@@ -1,29 +1,63 @@
 //! API interactions and functionality.
 
-use crate::api::handle_graphql_response;
 use crate::api::rcos::auth::ApiJwtClaims;
+use crate::api::rcos::retry::RetryPolicy;
+use crate::api::{check_upstream_status, handle_graphql_response};
 use crate::env::global_config;
 use crate::error::TelescopeError;
+use actix_web::rt::time::delay_for;
 use graphql_client::{GraphQLQuery, QueryBody, Response as GraphQlResponse};
-use reqwest::{header::HeaderValue, header::ACCEPT, Client};
+use reqwest::{header::HeaderValue, header::ACCEPT, Client, Response as HttpResponse};
 use serde_json::Value;
 
+pub mod all_pages;
 mod auth;
+pub mod coordinator_summary;
+pub mod cursor_pages;
 pub mod discord_associations;
 pub mod landing_page_stats;
 pub mod meetings;
+#[cfg(test)]
+pub mod mock;
 pub mod prelude;
 pub mod projects;
+pub(crate) mod response_debug;
+mod response_shape;
+pub mod retry;
 pub mod search_strings;
+pub mod semester_stats;
 pub mod semesters;
 pub mod users;
 
 /// The name of this API in error messages.
 const API_NAME: &'static str = "RCOS Central Hasura GraphQL API";
 
-/// Send a GraphQL query to the central RCOS API.
+/// Get the URL of the central RCOS API.
+#[cfg(not(test))]
+fn api_url() -> String {
+    global_config().api_url.clone()
+}
+
+/// Get the URL of the central RCOS API. In unit tests this is the URL of the running
+/// [`mock::MockRcosApi`], if there is one.
+#[cfg(test)]
+fn api_url() -> String {
+    mock::api_url_override().unwrap_or_else(|| global_config().api_url.clone())
+}
+
+/// Send a GraphQL query to the central RCOS API. Transient failures are retried using the
+/// retry policy from the config.
 pub async fn send_query<T: GraphQLQuery>(
     variables: T::Variables,
+) -> Result<T::ResponseData, TelescopeError> {
+    send_query_with_retry::<T>(variables, RetryPolicy::from_config()).await
+}
+
+/// Send a GraphQL query to the central RCOS API, retrying transient failures with the given
+/// policy.
+pub async fn send_query_with_retry<T: GraphQLQuery>(
+    variables: T::Variables,
+    policy: RetryPolicy,
 ) -> Result<T::ResponseData, TelescopeError> {
     // Build the GraphQL query.
     let query = T::build_query(variables);
@@ -42,11 +76,27 @@ pub async fn send_query<T: GraphQLQuery>(
     })?;
 
     // Send the query and await the response.
-    let response: Value = send_json_query(operation_name, query, variables).await?;
+    let response: Value =
+        send_json_query_with_retry(operation_name, query, variables, policy).await?;
+
+    // Make sure every top-level field is in the response. Otherwise a missing field would be
+    // deserialized as `None`, which looks like a record that does not exist.
+    response_shape::check(query, operation_name, &response)
+        .map_err(|message| TelescopeError::schema_mismatch(API_NAME, message))?;
 
-    // Deserialize the response into the typed value and return.
+    // Deserialize the response into the typed value and return. Keep a copy of the response
+    // to show what went wrong if debugging is on.
+    let debug_response: Option<Value> = response_debug::enabled().then(|| response.clone());
     serde_json::from_value::<T::ResponseData>(response).map_err(|e| {
-        TelescopeError::ise(format!("Could not deserialize GraphQL API response: {}", e))
+        let mut message: String = format!(
+            "Could not deserialize the response to {}: {}",
+            operation_name, e
+        );
+        if let Some(response) = debug_response {
+            let snippet: String = response_debug::log_response(operation_name, &response);
+            message = format!("{}. Response (redacted): {}", message, snippet);
+        }
+        TelescopeError::schema_mismatch(API_NAME, message)
     })
 }
 
@@ -58,6 +108,90 @@ pub async fn send_json_query(
     query_name: &str,
     query_document: &str,
     variables: Value,
+) -> Result<Value, TelescopeError> {
+    send_json_query_with_retry(
+        query_name,
+        query_document,
+        variables,
+        RetryPolicy::from_config(),
+    )
+    .await
+}
+
+/// Post a GraphQL request body to the RCOS API. Connection errors, timeouts, and 5xx responses
+/// are retried according to the policy. A request that still times out on its last attempt is
+/// reported as [`TelescopeError::UpstreamTimeout`]. Mutations are only retried if the request was never
+/// sent (a connection error), since the API may have applied one that failed later. The last
+/// response is returned even if it is a 5xx, so that it is handled like any other.
+async fn post_with_retry(
+    request_body: &Value,
+    is_mutation: bool,
+    policy: RetryPolicy,
+) -> Result<HttpResponse, TelescopeError> {
+    let mut attempt: u32 = 0;
+    loop {
+        attempt += 1;
+
+        // Build a JWT token to authenticate with the RCOS API.
+        // Use no subject because currently we do not track the subject on
+        // the other end.
+        let jwt: String = ApiJwtClaims::new(None);
+
+        // Create a new reqwest client
+        let result = Client::new()
+            // Create a POST request to the API endpoint.
+            .post(api_url().as_str())
+            // With the serialized JSON of the GraphQL request
+            .json(request_body)
+            // And the JWT for authentication
+            .bearer_auth(jwt)
+            // Add the Accept header so that the server sends back JSON.
+            .header(ACCEPT, HeaderValue::from_static("application/json"))
+            // Give up on this attempt if the API hangs.
+            .timeout(policy.timeout)
+            // Send the request and wait for the response
+            .send()
+            .await;
+
+        // Decide whether this attempt can be retried.
+        let retry_reason: Option<String> = match &result {
+            Err(err) if err.is_connect() => Some(err.to_string()),
+            Err(err) if err.is_timeout() && !is_mutation => Some(err.to_string()),
+            Ok(response) if response.status().is_server_error() && !is_mutation => {
+                Some(format!("Responded with status {}", response.status()))
+            }
+            _ => None,
+        };
+
+        match retry_reason {
+            Some(reason) if policy.should_retry(attempt) => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    "Transient error from {} (attempt {} of {}), retrying in {:?}: {}",
+                    API_NAME, attempt, policy.max_attempts, delay, reason
+                );
+                delay_for(delay).await;
+            }
+
+            // Convert any errors.
+            _ => {
+                return result.map_err(TelescopeError::with_timeout(
+                    API_NAME,
+                    policy.timeout,
+                    TelescopeError::rcos_api_error,
+                ))
+            }
+        }
+    }
+}
+
+/// Send an API query using the GraphQL JSON format, retrying transient failures with the given
+/// policy. See [`send_json_query`].
+pub async fn send_json_query_with_retry(
+    query_name: &str,
+    query_document: &str,
+    variables: Value,
+    policy: RetryPolicy,
 ) -> Result<Value, TelescopeError> {
     // Build the GraphQL request body.
     let request_body: Value = json!({
@@ -66,46 +200,110 @@ pub async fn send_json_query(
         "variables": variables
     });
 
-    // Build a JWT token to authenticate with the RCOS API.
-    // Use no subject because currently we do not track the subject on
-    // the other end.
-    let jwt: String = ApiJwtClaims::new(None);
-
-    // Create a new reqwest client
-    return Client::new()
-        // Create a POST request to the API endpoint.
-        .post(global_config().api_url.as_str())
-        // With the serialized JSON of the GraphQL request
-        .json(&request_body)
-        // And the JWT for authentication
-        .bearer_auth(jwt)
-        // Add the Accept header so that the server sends back JSON.
-        .header(ACCEPT, HeaderValue::from_static("application/json"))
-        // Send the request and wait for the response
-        .send()
+    // Send the request (retrying transient failures) and wait for the response.
+    let is_mutation: bool = response_shape::is_mutation(query_document, query_name);
+    return post_with_retry(&request_body, is_mutation, policy)
         .await
-        // Convert and propagate any errors.
-        .map_err(TelescopeError::rcos_api_error)?
+        // Check that the API is not temporarily unavailable and propagate any errors.
+        .and_then(|response| check_upstream_status(API_NAME, response))?
         // Wait for the body to receive as a string
         .text()
         .await
         // Convert and propagate any errors on deserializing the response body.
-        .map_err(TelescopeError::rcos_api_error)
+        .map_err(TelescopeError::with_timeout(
+            API_NAME,
+            policy.timeout,
+            TelescopeError::rcos_api_error,
+        ))
         // Convert the body into the GraphQL response type.
         .and_then(|body| {
             serde_json::from_str::<GraphQlResponse<Value>>(body.as_str())
-                // Map Serde errors into telescope errors
+                // Map Serde errors into telescope errors. The response body is only logged
+                // (and added to the error) when debugging, and is redacted first.
                 .map_err(|err| {
-                    // Log the error and response body.
-                    error!(
-                        "Error querying RCOS API: {}\nresponse body: {}",
-                        err,
-                        body.as_str()
-                    );
-                    // Convert the error
-                    TelescopeError::RcosApiError(err.to_string())
+                    TelescopeError::RcosApiError(response_debug::malformed_body_error(
+                        API_NAME,
+                        &err,
+                        body.as_str(),
+                        response_debug::enabled(),
+                    ))
                 })
         })
         // Convert any GraphQL errors.
         .and_then(|response| handle_graphql_response(API_NAME, response));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use std::time::Duration;
+
+    /// A retry policy that does not keep tests waiting.
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// A query that fails with a 5xx response succeeds when it is retried.
+    #[actix_rt::test]
+    async fn retries_server_errors() {
+        let api = MockRcosApi::start();
+        api.stub_failures("Flaky", &[503, 500]);
+        api.stub("Flaky", json!({ "ok": true }));
+
+        let data: Value =
+            send_json_query_with_retry("Flaky", "query Flaky { ok }", json!({}), fast_policy())
+                .await
+                .unwrap();
+        assert_eq!(data, json!({ "ok": true }));
+        api.assert_called("Flaky", 3);
+    }
+
+    /// A query that keeps failing with 5xx responses gives up after the last attempt.
+    #[actix_rt::test]
+    async fn gives_up_after_max_attempts() {
+        let api = MockRcosApi::start();
+        api.stub_failures("Down", &[503, 503, 503, 503]);
+        api.stub("Down", json!({ "ok": true }));
+
+        let result =
+            send_json_query_with_retry("Down", "query Down { ok }", json!({}), fast_policy()).await;
+        assert!(matches!(
+            result,
+            Err(TelescopeError::UpstreamUnavailable { .. })
+        ));
+        api.assert_called("Down", 3);
+    }
+
+    /// A 400 response is not retried.
+    #[actix_rt::test]
+    async fn does_not_retry_client_errors() {
+        let api = MockRcosApi::start();
+        api.stub_failures("Invalid", &[400]);
+        api.stub("Invalid", json!({ "ok": true }));
+
+        let result =
+            send_json_query_with_retry("Invalid", "query Invalid { ok }", json!({}), fast_policy())
+                .await;
+        assert!(result.is_err());
+        api.assert_called("Invalid", 1);
+    }
+
+    /// Mutations are not retried after a 5xx response, since they may have been applied.
+    #[actix_rt::test]
+    async fn does_not_retry_mutations() {
+        let api = MockRcosApi::start();
+        api.stub_failures("Save", &[503]);
+        api.stub("Save", json!({ "ok": true }));
+
+        let result =
+            send_json_query_with_retry("Save", "mutation Save { ok }", json!({}), fast_policy())
+                .await;
+        assert!(result.is_err());
+        api.assert_called("Save", 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! Checks that GraphQL responses have the shape their query asked for.
+//!
+//! The generated response types make nullable fields `Option`s, and serde treats a missing
+//! `Option` field as `None`. If the API schema drifts (for example a field is renamed), the
+//! response can be missing a field entirely, which would otherwise look the same as a query
+//! that legitimately found nothing. This module finds the top-level fields an operation
+//! selects, so that a missing one can be reported as a schema mismatch instead. A top-level
+//! field that is present but `null` is still a legitimate empty result.
+
+use serde_json::Value;
+
+/// A token of a GraphQL document. Only the tokens needed to find field names are kept apart;
+/// everything else is punctuation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// A name, keyword, or number.
+    Name(String),
+    /// The `...` of a fragment spread or inline fragment.
+    Spread,
+    /// A string value.
+    Str,
+    /// Any other punctuation character.
+    Punct(char),
+}
+
+/// Split a GraphQL document into tokens. Whitespace, commas, and comments are dropped.
+fn tokenize(document: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = document.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // Insignificant characters.
+            c if c.is_whitespace() || c == ',' => {}
+
+            // Comments run to the end of the line.
+            '#' => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+
+            // Strings (with escapes). Block strings are read as three strings, which is fine
+            // since only their position matters.
+            '"' => {
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+                tokens.push(Token::Str);
+            }
+
+            // Spreads.
+            '.' => {
+                if chars.peek() == Some(&'.') {
+                    chars.next();
+                    chars.next();
+                    tokens.push(Token::Spread);
+                }
+            }
+
+            // Names, keywords, and numbers.
+            c if c.is_alphanumeric() || c == '_' || c == '-' => {
+                let mut name = c.to_string();
+                // Only numbers can contain a decimal point.
+                let numeric: bool = c.is_numeric() || c == '-';
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || (numeric && next == '.') {
+                        name.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Name(name));
+            }
+
+            // Everything else.
+            other => tokens.push(Token::Punct(other)),
+        }
+    }
+
+    return tokens;
+}
+
+/// Get the response keys of the top-level fields selected by an operation in a GraphQL
+/// document. Aliases are used where given. Fields selected through fragments are not
+/// included. Return `None` if the operation is not in the document.
+pub fn top_level_fields(document: &str, operation_name: &str) -> Option<Vec<String>> {
+    let tokens: Vec<Token> = tokenize(document);
+
+    // Find the start of the operation.
+    let start: usize = tokens.windows(2).position(|pair| match pair {
+        [Token::Name(keyword), Token::Name(name)] => {
+            ["query", "mutation", "subscription"].contains(&keyword.as_str())
+                && name == operation_name
+        }
+        _ => false,
+    })?;
+
+    // Find the opening brace of its selection set, skipping the variable definitions (whose
+    // default values may contain braces).
+    let mut parens: usize = 0;
+    let mut position: usize = start + 2;
+    loop {
+        match tokens.get(position)? {
+            Token::Punct('(') => parens += 1,
+            Token::Punct(')') => parens = parens.saturating_sub(1),
+            Token::Punct('{') if parens == 0 => break,
+            _ => {}
+        }
+        position += 1;
+    }
+
+    // Walk the selection set, collecting names at the top level.
+    let mut fields: Vec<String> = Vec::new();
+    let mut depth: usize = 0;
+    // Names that follow a spread, a directive, or an alias are not response keys.
+    let mut skip_names: usize = 0;
+    for (index, token) in tokens.iter().enumerate().skip(position) {
+        match token {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(fields);
+                }
+            }
+            Token::Punct('(') => parens += 1,
+            Token::Punct(')') => parens = parens.saturating_sub(1),
+
+            // Only the top level of the selection set matters.
+            _ if depth != 1 || parens != 0 => {}
+
+            // Skip the fragment name, or the `on` and type name of an inline fragment.
+            Token::Spread => {
+                skip_names = match tokens.get(index + 1) {
+                    Some(Token::Name(name)) if name == "on" => 2,
+                    _ => 1,
+                }
+            }
+            // Skip directive names.
+            Token::Punct('@') => skip_names = 1,
+
+            Token::Name(_) if skip_names > 0 => skip_names -= 1,
+            Token::Name(name) => {
+                fields.push(name.clone());
+                // An alias is followed by a colon and the field name.
+                if tokens.get(index + 1) == Some(&Token::Punct(':')) {
+                    skip_names = 1;
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    // The selection set was never closed.
+    return None;
+}
+
+/// Is the named operation in a GraphQL document a mutation? Mutations are not safe to retry
+/// once they may have reached the API.
+pub fn is_mutation(document: &str, operation_name: &str) -> bool {
+    tokenize(document).windows(2).any(|pair| match pair {
+        [Token::Name(keyword), Token::Name(name)] => {
+            keyword == "mutation" && name == operation_name
+        }
+        _ => false,
+    })
+}
+
+/// Check that the data of a GraphQL response has every top-level field the operation
+/// selected. Return a description of the problem if it does not. Fields that are `null` are
+/// fine.
+pub fn check(document: &str, operation_name: &str, data: &Value) -> Result<(), String> {
+    let object = data.as_object().ok_or(format!(
+        "The data of {} is not an object: {}",
+        operation_name, data
+    ))?;
+
+    // Documents are generated from the files in the graphql directory, so an operation that
+    // cannot be parsed here is a bug in this module rather than the response. Log it and let
+    // deserialization decide.
+    let fields: Vec<String> = match top_level_fields(document, operation_name) {
+        Some(fields) => fields,
+        None => {
+            warn!(
+                "Could not find the top-level fields of GraphQL operation {}.",
+                operation_name
+            );
+            return Ok(());
+        }
+    };
+
+    let missing: Vec<&str> = fields
+        .iter()
+        .map(String::as_str)
+        .filter(|field| !object.contains_key(*field))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    return Err(format!(
+        "The response to {} is missing the field(s) {}",
+        operation_name,
+        missing.join(", ")
+    ));
+}
@@ -0,0 +1,55 @@
+//! Retry policy for requests to the RCOS API.
+//!
+//! Requests that fail with a connection error or a 5xx response (usually a momentary problem
+//! with Hasura or the proxy in front of it) are tried again with exponential backoff and random
+//! jitter, so a blip does not take down a whole page load. Anything the API actually answered
+//! (including GraphQL errors) is not retried.
+
+use crate::env::global_config;
+use rand::Rng;
+use std::time::Duration;
+
+/// How requests to the RCOS API are retried.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// How many times to try a request in total. A policy with one attempt never retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry. This doubles for each retry after that.
+    pub base_delay: Duration,
+    /// The longest each attempt can take before it fails with a timeout. Long running queries
+    /// can use a policy with a longer timeout.
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// The retry policy from the config (three attempts with a 200ms base delay and a 30
+    /// second timeout by default).
+    pub fn from_config() -> Self {
+        let config = global_config();
+        RetryPolicy {
+            max_attempts: config.rcos_api_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.rcos_api_retry_base_ms),
+            timeout: Duration::from_secs(config.upstream_timeout_seconds),
+        }
+    }
+
+    /// This policy with a different timeout for each attempt.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        RetryPolicy { timeout, ..self }
+    }
+
+    /// Can a request be tried again after `attempt` tries?
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// How long to wait after `attempt` tries before the next one. This is the base delay
+    /// doubled for each try after the first, plus up to half of that again at random so that
+    /// concurrent requests do not all retry at once.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent: u32 = attempt.saturating_sub(1).min(16);
+        let backoff: Duration = self.base_delay * 2u32.pow(exponent);
+        let jitter_ms: u64 = rand::thread_rng().gen_range(0..=(backoff.as_millis() as u64 / 2));
+        return backoff + Duration::from_millis(jitter_ms);
+    }
+}
@@ -0,0 +1,118 @@
+//! Aggregate meeting statistics for a semester.
+
+use crate::api::rcos::meetings::MeetingType;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+use std::collections::HashMap;
+
+/// Query for the meeting counts of a semester.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/semester_meetings.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct SemesterMeetingCounts;
+
+/// Query for the number of attendances recorded in a semester.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/stats/semester_meetings.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct SemesterAttendanceCount;
+
+/// Attendance statistics of a semester. These are only shown to coordinators.
+#[derive(Clone, Debug, Serialize)]
+pub struct AttendanceStats {
+    /// The number of attendances recorded at published meetings.
+    pub total_attendance: i64,
+    /// The average number of attendances per published meeting.
+    pub average_attendance: f64,
+}
+
+/// Meeting statistics of a semester.
+#[derive(Clone, Debug, Serialize)]
+pub struct SemesterMeetingStats {
+    /// The semester ID.
+    pub semester_id: String,
+    /// The number of published (non-draft, non-canceled) meetings of each type.
+    pub meetings_by_type: HashMap<MeetingType, i64>,
+    /// The total number of published meetings.
+    pub total_meetings: i64,
+    /// The number of draft meetings.
+    pub draft_meetings: i64,
+    /// Attendance statistics, if the viewer may see them.
+    pub attendance: Option<AttendanceStats>,
+}
+
+/// Get the count from an aggregate.
+fn count(aggregate: &semester_meeting_counts::MeetingCount) -> i64 {
+    aggregate.aggregate.as_ref().map(|a| a.count).unwrap_or(0)
+}
+
+impl SemesterMeetingStats {
+    /// Get the meeting statistics of a semester, including attendance. The aggregates are
+    /// queried concurrently. Return `None` if the semester does not exist.
+    pub async fn get(semester_id: String) -> Result<Option<Self>, TelescopeError> {
+        // Send both queries at once.
+        let (counts, attendance) = futures::try_join!(
+            send_query::<SemesterMeetingCounts>(semester_meeting_counts::Variables {
+                semester_id: semester_id.clone(),
+            }),
+            send_query::<SemesterAttendanceCount>(semester_attendance_count::Variables {
+                semester_id: semester_id.clone(),
+            }),
+        )?;
+
+        if counts.semesters_by_pk.is_none() {
+            return Ok(None);
+        }
+
+        // Count meetings by type.
+        let meetings_by_type: HashMap<MeetingType, i64> = vec![
+            (MeetingType::LargeGroup, count(&counts.large_group)),
+            (MeetingType::SmallGroup, count(&counts.small_group)),
+            (MeetingType::Presentations, count(&counts.presentations)),
+            (MeetingType::BonusSession, count(&counts.bonus_session)),
+            (MeetingType::Grading, count(&counts.grading)),
+            (MeetingType::Mentors, count(&counts.mentors)),
+            (MeetingType::Coordinators, count(&counts.coordinators)),
+            (MeetingType::Other, count(&counts.other)),
+        ]
+        .into_iter()
+        .collect();
+
+        let total_meetings: i64 = meetings_by_type.values().sum();
+        let total_attendance: i64 = attendance
+            .meeting_attendances_aggregate
+            .aggregate
+            .map(|a| a.count)
+            .unwrap_or(0);
+
+        return Ok(Some(SemesterMeetingStats {
+            semester_id,
+            meetings_by_type,
+            total_meetings,
+            draft_meetings: count(&counts.drafts),
+            attendance: Some(AttendanceStats {
+                total_attendance,
+                // Avoid dividing by zero in semesters without meetings.
+                average_attendance: if total_meetings == 0 {
+                    0.0
+                } else {
+                    total_attendance as f64 / total_meetings as f64
+                },
+            }),
+        }));
+    }
+
+    /// Remove the attendance statistics, for viewers who may not see them.
+    pub fn without_attendance(self) -> Self {
+        SemesterMeetingStats {
+            attendance: None,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Query for the meetings a user has attended.
+
+use crate::api::rcos::{prelude::*, send_query};
+use crate::error::TelescopeError;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/attendance.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UserAttendance;
+
+impl UserAttendance {
+    /// Get the meetings a user has attended.
+    pub async fn get(user_id: uuid) -> Result<user_attendance::ResponseData, TelescopeError> {
+        send_query::<Self>(user_attendance::Variables { user_id }).await
+    }
+}
@@ -0,0 +1,49 @@
+//! Query and mutation for changing the roles of many users at once.
+
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::api::rcos::users::UserRole;
+use crate::error::TelescopeError;
+
+/// Type representing GraphQL query to get the current roles of a list of users.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/bulk_role.graphql",
+    variables_derives = "Debug,Clone",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UserRoles;
+
+/// Type representing GraphQL mutation to set the role of a list of users.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/bulk_role.graphql",
+    variables_derives = "Debug,Clone",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct BulkSetRole;
+
+impl UserRoles {
+    /// Get the users in a list of user IDs. Users that do not exist are left out.
+    pub async fn get(ids: Vec<uuid>) -> Result<Vec<user_roles::UserRolesUsers>, TelescopeError> {
+        send_query::<Self>(user_roles::Variables { ids })
+            .await
+            .map(|response| response.users)
+    }
+}
+
+impl BulkSetRole {
+    /// Set the role of every user in a list. Return the IDs of the users that were updated.
+    pub async fn execute(ids: Vec<uuid>, role: UserRole) -> Result<Vec<uuid>, TelescopeError> {
+        send_query::<Self>(bulk_set_role::Variables { ids, role })
+            .await
+            .map(|response| {
+                response
+                    .update_users
+                    .map(|updated| updated.returning.into_iter().map(|user| user.id).collect())
+                    .unwrap_or_default()
+            })
+    }
+}
@@ -0,0 +1,40 @@
+//! RCOS API mutation to deactivate or reactivate a user.
+
+use crate::api::rcos::{prelude::*, send_query};
+use crate::error::TelescopeError;
+use chrono::Utc;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/deactivate.graphql"
+)]
+pub struct SetUserDeactivated;
+
+use set_user_deactivated::Variables;
+
+impl SetUserDeactivated {
+    /// Send the mutation. Return the user's ID if the user was found.
+    async fn execute(
+        user_id: uuid,
+        deactivated_at: Option<timestamptz>,
+    ) -> Result<Option<uuid>, TelescopeError> {
+        send_query::<Self>(Variables {
+            user_id,
+            deactivated_at,
+        })
+        .await
+        .map(|response| response.update_users_by_pk.map(|user| user.id))
+    }
+
+    /// Deactivate a user as of now, hiding them from listings without deleting any of their
+    /// records. Return the user's ID if the user was found.
+    pub async fn deactivate(user_id: uuid) -> Result<Option<uuid>, TelescopeError> {
+        Self::execute(user_id, Some(Utc::now())).await
+    }
+
+    /// Reactivate a deactivated user. Return the user's ID if the user was found.
+    pub async fn reactivate(user_id: uuid) -> Result<Option<uuid>, TelescopeError> {
+        Self::execute(user_id, None).await
+    }
+}
@@ -9,6 +9,12 @@ use graphql_client::GraphQLQuery;
 /// The query returns 20 developers per page.
 pub const PER_PAGE: u32 = 20;
 
+/// Convert whether to include deactivated users to the `active_only` query variable. The
+/// comparison is ignored (including everyone) when it is `None`.
+fn active_only(include_deactivated: bool) -> Option<bool> {
+    (!include_deactivated).then(|| true)
+}
+
 /// Type representing GraphQL query to get a list of all users and their
 /// account associations for the developers page.
 #[derive(GraphQLQuery)]
@@ -31,14 +37,17 @@ pub struct CurrentDevelopers;
 
 impl AllDevelopers {
     /// Send the query to get all the developers (including old ones) and wait for a response.
+    /// Deactivated users are only included if `include_deactivated` is set.
     pub async fn get(
         page_num: u32,
         search: Option<String>,
+        include_deactivated: bool,
     ) -> Result<<Self as GraphQLQuery>::ResponseData, TelescopeError> {
         send_query::<Self>(all_developers::Variables {
             limit: PER_PAGE as i64,
             offset: (PER_PAGE * page_num) as i64,
             search: resolve_search_string(search),
+            active_only: active_only(include_deactivated),
         })
         .await
     }
@@ -46,15 +55,18 @@ impl AllDevelopers {
 
 impl CurrentDevelopers {
     /// Send the developers page query (and limit to current developers) and wait for a response.
+    /// Deactivated users are only included if `include_deactivated` is set.
     pub async fn get(
         page_num: u32,
         search: Option<String>,
+        include_deactivated: bool,
     ) -> Result<<Self as GraphQLQuery>::ResponseData, TelescopeError> {
         send_query::<Self>(current_developers::Variables {
             limit: PER_PAGE as i64,
             offset: (PER_PAGE * page_num) as i64,
             search: resolve_search_string(search),
             now: Utc::today().naive_utc(),
+            active_only: active_only(include_deactivated),
         })
         .await
     }
@@ -36,21 +36,48 @@ impl EditProfileContext {
     }
 }
 
+/// The fields of a profile that are saved by the profile edit form.
+#[derive(Clone, Debug)]
+pub struct ProfileFields {
+    /// The user's legal first name.
+    pub first_name: String,
+    /// The user's last name.
+    pub last_name: String,
+    /// The first name to show instead of the legal one, if any.
+    pub preferred_name: Option<String>,
+    /// A short biography, if any.
+    pub bio: Option<String>,
+    /// Links to show on the profile (already normalized).
+    pub external_links: Vec<String>,
+    /// Entry year for RPI students.
+    pub cohort: Option<i64>,
+    /// The user's role.
+    pub role: UserRole,
+    /// The IANA name of the user's preferred timezone. Empty for no preference.
+    pub timezone: String,
+}
+
 impl SaveProfileEdits {
-    /// Save edits to a user's profile, returning their user ID if the user was found.
+    /// Save edits to a user's profile, returning their user ID if the user was found. An empty
+    /// timezone clears the user's timezone preference.
     pub async fn execute(
         user_id: uuid,
-        first_name: String,
-        last_name: String,
-        cohort: Option<i64>,
-        role: UserRole,
+        fields: ProfileFields,
     ) -> Result<Option<uuid>, TelescopeError> {
+        // Links are stored one per line. No links are stored as null.
+        let external_links: Option<String> =
+            (!fields.external_links.is_empty()).then(|| fields.external_links.join("\n"));
+
         send_query::<Self>(save_profile_edits::Variables {
             user_id,
-            fname: first_name,
-            lname: last_name,
-            cohort,
-            role,
+            fname: fields.first_name,
+            lname: fields.last_name,
+            preferred_name: fields.preferred_name,
+            bio: fields.bio,
+            external_links,
+            cohort: fields.cohort,
+            role: fields.role,
+            timezone: fields.timezone,
         })
         .await
         .map(|response| response.update_users_by_pk.map(|obj| obj.id))
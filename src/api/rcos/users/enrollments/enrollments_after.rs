@@ -0,0 +1,50 @@
+//! RCOS API query to page through the enrollments of a semester by user ID.
+
+use crate::api::rcos::cursor_pages::CursorQuery;
+use crate::api::rcos::prelude::*;
+
+/// The number of enrollments requested at a time.
+const PER_PAGE: u32 = 500;
+
+/// Type representing GraphQL query to get the enrollments in a semester after a user.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/enrollments/enrollments_after.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct EnrollmentsAfter;
+
+/// The enrollments of a semester, paged through by user ID. Use with
+/// [`pages`](crate::api::rcos::cursor_pages::pages).
+#[derive(Clone, Debug)]
+pub struct SemesterEnrollments {
+    /// The semester.
+    pub semester_id: String,
+}
+
+impl CursorQuery for SemesterEnrollments {
+    type Query = EnrollmentsAfter;
+    type Row = enrollments_after::EnrollmentsAfterEnrollments;
+    type Cursor = uuid;
+
+    fn page_size(&self) -> u32 {
+        PER_PAGE
+    }
+
+    fn variables(&self, after: Option<uuid>, limit: u32) -> enrollments_after::Variables {
+        enrollments_after::Variables {
+            semester_id: self.semester_id.clone(),
+            after,
+            limit: limit as i64,
+        }
+    }
+
+    fn rows(response: enrollments_after::ResponseData) -> Vec<Self::Row> {
+        response.enrollments
+    }
+
+    fn cursor(row: &Self::Row) -> uuid {
+        row.user_id
+    }
+}
@@ -1,9 +1,16 @@
 //! RCOS API query to get enrollment record.
 
+use crate::api::rcos::all_pages::{fetch_all_pages, Batch};
+use crate::api::rcos::prelude::*;
 use crate::api::rcos::send_query;
-use crate::api::rcos::{prelude::*, search_strings::resolve_search_string};
 use crate::error::TelescopeError;
 
+/// The most enrollments that can be requested at a time.
+pub const MAX_PER_PAGE: u32 = 500;
+
+/// The most enrollments that will be fetched for one semester.
+const MAX_ROWS: usize = 20_000;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/rcos/schema.json",
@@ -13,13 +20,196 @@ use crate::error::TelescopeError;
 
 pub struct EnrollmentsLookup;
 
+/// A page of the enrollments in a semester.
+#[derive(Clone, Debug, Serialize)]
+pub struct EnrollmentsPage {
+    /// The enrollments on this page.
+    pub enrollments: Vec<enrollments_lookup::EnrollmentsLookupEnrollments>,
+    /// The number of enrollments in the semester, across all pages.
+    pub total: u64,
+}
+
 impl EnrollmentsLookup {
+    /// Check the limit and offset of a page request. The limit must be between 1 and
+    /// [`MAX_PER_PAGE`], and the offset cannot be past the most rows a semester can have.
+    fn check_page_bounds(limit: u32, offset: u32) -> Result<(), TelescopeError> {
+        if limit == 0 || limit > MAX_PER_PAGE {
+            return Err(TelescopeError::BadRequest {
+                header: "Invalid Page Size".into(),
+                message: format!(
+                    "Enrollments can be requested {} at a time at most.",
+                    MAX_PER_PAGE
+                ),
+                show_status_code: true,
+            });
+        }
+
+        if offset as usize > MAX_ROWS {
+            return Err(TelescopeError::BadRequest {
+                header: "Invalid Page".into(),
+                message: format!(
+                    "Enrollments past the first {} cannot be requested.",
+                    MAX_ROWS
+                ),
+                show_status_code: true,
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Get a page of the enrollments in a semester (ordered by user ID), along with the total
+    /// number of enrollments in the semester.
     pub async fn get(
         semester_id: String,
-    ) -> Result<enrollments_lookup::ResponseData, TelescopeError> {
-        send_query::<Self>(enrollments_lookup::Variables {
-            semester_id: semester_id,
+        limit: u32,
+        offset: u32,
+    ) -> Result<EnrollmentsPage, TelescopeError> {
+        Self::check_page_bounds(limit, offset)?;
+
+        let response: enrollments_lookup::ResponseData =
+            send_query::<Self>(enrollments_lookup::Variables {
+                semester_id,
+                limit: limit as i64,
+                offset: offset as i64,
+            })
+            .await?;
+
+        // The count is only missing if there are no enrollments to count.
+        let total: u64 = response
+            .total
+            .aggregate
+            .map(|aggregate| aggregate.count.max(0) as u64)
+            .unwrap_or(0);
+
+        return Ok(EnrollmentsPage {
+            enrollments: response.enrollments,
+            total,
+        });
+    }
+
+    /// Get every enrollment in a semester, a page at a time.
+    pub async fn get_all(
+        semester_id: String,
+    ) -> Result<Vec<enrollments_lookup::EnrollmentsLookupEnrollments>, TelescopeError> {
+        fetch_all_pages(MAX_ROWS, |page_num| {
+            let semester_id = semester_id.clone();
+            async move {
+                let page: EnrollmentsPage =
+                    Self::get(semester_id, MAX_PER_PAGE, MAX_PER_PAGE * page_num).await?;
+                Ok(Batch::from_page(page.enrollments, MAX_PER_PAGE))
+            }
         })
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use ::uuid::Uuid;
+    use serde_json::Value;
+
+    /// An enrollment row as the API would send it.
+    fn enrollment() -> Value {
+        json!({
+            "semester_id": "202209",
+            "project_id": null,
+            "is_project_lead": false,
+            "is_coordinator": false,
+            "credits": 4,
+            "is_for_pay": false,
+            "mid_year_grade": null,
+            "final_grade": null,
+            "created_at": "2022-09-01T12:00:00+00:00",
+            "user_id": Uuid::new_v4()
+        })
+    }
+
+    /// Pages are requested with the semester, limit, and offset asked for.
+    #[actix_rt::test]
+    async fn page_variables() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "EnrollmentsLookup",
+            json!({
+                "enrollments": [enrollment(), enrollment()],
+                "total": { "aggregate": { "count": 52 } }
+            }),
+        );
+
+        let page = EnrollmentsLookup::get("202209".into(), 25, 50)
+            .await
+            .unwrap();
+        assert_eq!(page.enrollments.len(), 2);
+        assert_eq!(page.total, 52);
+        api.assert_called("EnrollmentsLookup", 1);
+        api.assert_variables(
+            "EnrollmentsLookup",
+            json!({ "semester_id": "202209", "limit": 25, "offset": 50 }),
+        );
+    }
+
+    /// A missing count means there are no enrollments.
+    #[actix_rt::test]
+    async fn missing_count_is_zero() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "EnrollmentsLookup",
+            json!({ "enrollments": [], "total": { "aggregate": null } }),
+        );
+
+        let page = EnrollmentsLookup::get("202209".into(), 10, 0)
+            .await
+            .unwrap();
+        assert!(page.enrollments.is_empty());
+        assert_eq!(page.total, 0);
+    }
+
+    /// Invalid pages are refused without querying the API.
+    #[actix_rt::test]
+    async fn invalid_pages_are_not_requested() {
+        let api = MockRcosApi::start();
+
+        for (limit, offset) in [(0, 0), (MAX_PER_PAGE + 1, 0), (10, MAX_ROWS as u32 + 1)] {
+            let result = EnrollmentsLookup::get("202209".into(), limit, offset).await;
+            assert!(
+                matches!(result, Err(TelescopeError::BadRequest { .. })),
+                "limit {} and offset {} should be refused",
+                limit,
+                offset
+            );
+        }
+        api.assert_called("EnrollmentsLookup", 0);
+    }
+
+    /// The largest valid page is allowed.
+    #[test]
+    fn page_bounds() {
+        assert!(EnrollmentsLookup::check_page_bounds(1, 0).is_ok());
+        assert!(EnrollmentsLookup::check_page_bounds(MAX_PER_PAGE, MAX_ROWS as u32).is_ok());
+    }
+
+    /// Getting every enrollment starts at the first full-size page, and stops after a
+    /// partial one.
+    #[actix_rt::test]
+    async fn get_all_variables() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "EnrollmentsLookup",
+            json!({
+                "enrollments": [enrollment(), enrollment(), enrollment()],
+                "total": { "aggregate": { "count": 3 } }
+            }),
+        );
+
+        let all = EnrollmentsLookup::get_all("202209".into()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        api.assert_called("EnrollmentsLookup", 1);
+        api.assert_variables(
+            "EnrollmentsLookup",
+            json!({ "semester_id": "202209", "limit": MAX_PER_PAGE, "offset": 0 }),
+        );
+    }
+}
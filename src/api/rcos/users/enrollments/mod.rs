@@ -1,4 +1,5 @@
 //! GraphQL operations on user enrollments.
 
+pub mod enrollments_after;
 pub mod enrollments_lookup;
 pub mod user_enrollment_lookup;
@@ -1,7 +1,10 @@
 //! API interactions for RCOS users from the central RCOS API.
 
 pub mod accounts;
+pub mod attendance;
+pub mod bulk_role;
 pub mod create;
+pub mod deactivate;
 pub mod delete;
 pub mod developers_page;
 pub mod discord_whois;
@@ -10,6 +13,13 @@ pub mod enrollments;
 pub mod navbar_auth;
 pub mod profile;
 pub mod role_lookup;
+pub mod session_version;
+pub mod timezone;
+pub mod unlinked;
+
+use crate::error::TelescopeError;
+use std::fmt;
+use std::str::FromStr;
 
 /// The valid user roles for all users in the RCOS database.
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash, Display)]
@@ -39,26 +49,16 @@ pub enum UserRole {
     Sysadmin,
 }
 
-/// The valid account types for all user accounts stored in the RCOS database.
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, Display, Eq, PartialEq, Hash)]
+/// The valid account types for all user accounts stored in the RCOS database. Names, icons,
+/// and links for each type are defined in the methods below.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, Eq, PartialEq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum UserAccountType {
-    #[display(fmt = "RCS")]
     Rpi,
-
-    #[display(fmt = "Discord")]
     Discord,
-
-    #[display(fmt = "Mattermost")]
     Mattermost,
-
-    #[display(fmt = "GitHub")]
     GitHub,
-
-    #[display(fmt = "GitLab")]
     GitLab,
-
-    #[display(fmt = "BitBucket")]
     BitBucket,
 }
 
@@ -116,3 +116,147 @@ impl UserRole {
         }
     }
 }
+
+impl UserAccountType {
+    /// Constant array of all user account types.
+    pub const ALL: [UserAccountType; 6] = [
+        UserAccountType::Rpi,
+        UserAccountType::Discord,
+        UserAccountType::Mattermost,
+        UserAccountType::GitHub,
+        UserAccountType::GitLab,
+        UserAccountType::BitBucket,
+    ];
+
+    /// The name of this account type in the database and API (and in serialized JSON).
+    pub fn key(self) -> &'static str {
+        match self {
+            UserAccountType::Rpi => "rpi",
+            UserAccountType::Discord => "discord",
+            UserAccountType::Mattermost => "mattermost",
+            UserAccountType::GitHub => "github",
+            UserAccountType::GitLab => "gitlab",
+            UserAccountType::BitBucket => "bitbucket",
+        }
+    }
+
+    /// The name of this account type to show to users. This is also the `Display`
+    /// implementation.
+    pub fn display_name(self) -> &'static str {
+        match self {
+            UserAccountType::Rpi => "RCS",
+            UserAccountType::Discord => "Discord",
+            UserAccountType::Mattermost => "Mattermost",
+            UserAccountType::GitHub => "GitHub",
+            UserAccountType::GitLab => "GitLab",
+            UserAccountType::BitBucket => "BitBucket",
+        }
+    }
+
+    /// The icon for this account type in templates. This is a feather icon name, except for
+    /// Discord (which feather does not have), which templates render with font-awesome.
+    pub fn icon(self) -> &'static str {
+        match self {
+            UserAccountType::Rpi => "mail",
+            UserAccountType::Discord => "discord",
+            UserAccountType::Mattermost => "message-square",
+            UserAccountType::GitHub => "github",
+            UserAccountType::GitLab => "gitlab",
+            UserAccountType::BitBucket => "git-branch",
+        }
+    }
+
+    /// Get a link to a user's profile on this platform from their account ID, if one can be
+    /// built from the ID alone. RPI accounts are stored by RCS ID, so they link to the user's
+    /// RPI email. GitHub accounts are stored by GraphQL node ID, which does not appear in
+    /// profile URLs, so their profile URLs have to come from the GitHub API instead. The other
+    /// platforms cannot be linked to Telescope accounts yet, so there is no ID format to link
+    /// from.
+    pub fn profile_url(self, platform_id: &str) -> Option<String> {
+        match self {
+            UserAccountType::Rpi => Some(format!("mailto:{}@rpi.edu", platform_id)),
+            UserAccountType::Discord => Some(format!("https://discord.com/users/{}", platform_id)),
+            UserAccountType::GitHub => None,
+            UserAccountType::Mattermost | UserAccountType::GitLab | UserAccountType::BitBucket => {
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for UserAccountType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
+impl FromStr for UserAccountType {
+    type Err = TelescopeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Accept the database name or the display name, ignoring case.
+        let s: &str = s.trim();
+        Self::ALL
+            .iter()
+            .find(|ty| {
+                ty.key().eq_ignore_ascii_case(s) || ty.display_name().eq_ignore_ascii_case(s)
+            })
+            .copied()
+            .ok_or_else(|| TelescopeError::BadRequest {
+                header: "Invalid Account Type".into(),
+                message: format!("{:?} is not a user account type.", s),
+                show_status_code: true,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account types are shown to users by their display names.
+    #[test]
+    fn display_names() {
+        assert_eq!(UserAccountType::Rpi.to_string(), "RCS");
+        assert_eq!(UserAccountType::GitHub.to_string(), "GitHub");
+        assert_eq!(UserAccountType::Discord.to_string(), "Discord");
+    }
+
+    /// Profile URLs are built for the platforms whose IDs appear in them.
+    #[test]
+    fn profile_urls() {
+        assert_eq!(
+            UserAccountType::Rpi.profile_url("smithj2").as_deref(),
+            Some("mailto:smithj2@rpi.edu")
+        );
+        assert_eq!(
+            UserAccountType::Discord
+                .profile_url("123456789012345678")
+                .as_deref(),
+            Some("https://discord.com/users/123456789012345678")
+        );
+        assert_eq!(UserAccountType::GitHub.profile_url("MDQ6VXNlcjE="), None);
+        assert_eq!(UserAccountType::GitLab.profile_url("smithj2"), None);
+    }
+
+    /// Every account type parses from its key and display name, in any case.
+    #[test]
+    fn from_str_round_trip() {
+        for ty in UserAccountType::ALL.iter() {
+            assert_eq!(ty.key().parse::<UserAccountType>().unwrap(), *ty);
+            assert_eq!(ty.to_string().parse::<UserAccountType>().unwrap(), *ty);
+            assert_eq!(
+                ty.to_string()
+                    .to_uppercase()
+                    .parse::<UserAccountType>()
+                    .unwrap(),
+                *ty
+            );
+        }
+        assert!(" github ".parse::<UserAccountType>().is_ok());
+        assert!(matches!(
+            "myspace".parse::<UserAccountType>(),
+            Err(TelescopeError::BadRequest { .. })
+        ));
+    }
+}
@@ -50,6 +50,18 @@ impl authentication::ResponseData {
             .unwrap_or(false)
     }
 
+    /// Get this user's role if the user exists.
+    pub fn role(&self) -> Option<UserRole> {
+        self.users_by_pk.as_ref().map(|u| u.role)
+    }
+
+    /// Get this user's full name if the user exists.
+    pub fn display_name(&self) -> Option<String> {
+        self.users_by_pk
+            .as_ref()
+            .map(|u| format!("{} {}", u.first_name, u.last_name))
+    }
+
     /// Is this user's role student?
     pub fn is_student(&self) -> bool {
         self.users_by_pk
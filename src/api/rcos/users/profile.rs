@@ -13,28 +13,60 @@ use chrono::Utc;
 pub struct Profile;
 
 // import generated types.
-use profile::{ResponseData, Variables};
+use profile::{ProfileTarget, ProfileViewer, ResponseData, Variables};
+
+/// The profile data of a user, and info about the viewer.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProfileData {
+    /// The owner of the profile, if they exist (and are not hidden).
+    pub target: Option<ProfileTarget>,
+    /// The viewer, if there is one.
+    pub viewer: Vec<ProfileViewer>,
+}
+
+impl From<ResponseData> for ProfileData {
+    fn from(response: ResponseData) -> Self {
+        ProfileData {
+            target: response.target.into_iter().next(),
+            viewer: response.viewer,
+        }
+    }
+}
 
 impl Profile {
-    /// Get the profile data for a given user ID..
+    /// Make the variables to get a user's profile. Deactivated users are filtered out by the
+    /// API unless `include_deactivated` is set.
+    fn variables(target: uuid, viewer: Option<uuid>, include_deactivated: bool) -> Variables {
+        Variables {
+            target,
+            // Convert viewer to a vec with one or zero user IDs in it.
+            viewer: viewer.map(|v| vec![v]).unwrap_or_default(),
+            now: Utc::today().naive_utc(),
+            hide_deactivated: !include_deactivated,
+        }
+    }
+
+    /// Get the profile data for a given user ID. Deactivated users are treated as missing.
     pub async fn for_user(
         target: uuid,
         viewer: Option<uuid>,
-    ) -> Result<ResponseData, TelescopeError> {
-        // Convert viewer to a vec with one or zero user IDs in it.
-        let viewer = viewer.map(|v| vec![v]).unwrap_or(Vec::new());
+    ) -> Result<ProfileData, TelescopeError> {
+        Self::get(target, viewer, false).await
+    }
 
-        // Send the query and await the response.
-        send_query::<Self>(Variables {
-            target,
-            viewer,
-            now: Utc::today().naive_utc(),
-        })
-        .await
+    /// Get the profile data for a given user ID. If `include_deactivated` is false, a
+    /// deactivated target is treated as missing.
+    pub async fn get(
+        target: uuid,
+        viewer: Option<uuid>,
+        include_deactivated: bool,
+    ) -> Result<ProfileData, TelescopeError> {
+        let variables: Variables = Self::variables(target, viewer, include_deactivated);
+        return send_query::<Self>(variables).await.map(ProfileData::from);
     }
 }
 
-impl ResponseData {
+impl ProfileData {
     /// Get the target user's Discord ID if available.
     pub fn discord(&self) -> Option<&str> {
         self.target
@@ -44,3 +76,28 @@ impl ResponseData {
             .map(|disc| disc.account_id.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+
+    /// Deactivated users are hidden by the API unless they are asked for.
+    #[actix_rt::test]
+    async fn deactivated_filter_is_sent() {
+        let api = MockRcosApi::start();
+        api.stub("Profile", json!({ "target": [], "viewer": [] }));
+        let target: uuid = ::uuid::Uuid::new_v4();
+
+        let hidden: ProfileData = Profile::get(target, None, false).await.unwrap();
+        assert!(hidden.target.is_none());
+        let variables = api.requests("Profile").pop().unwrap().variables;
+        assert_eq!(variables["target"], json!(target));
+        assert_eq!(variables["hide_deactivated"], json!(true));
+
+        Profile::get(target, None, true).await.unwrap();
+        let variables = api.requests("Profile").pop().unwrap().variables;
+        assert_eq!(variables["hide_deactivated"], json!(false));
+        assert_eq!(variables["viewer"], json!([]));
+    }
+}
@@ -0,0 +1,90 @@
+//! GraphQL query and mutation for a user's session version, which is used to log a user out
+//! everywhere at once.
+
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/session_version.graphql"
+)]
+pub struct SessionVersion;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/session_version.graphql"
+)]
+pub struct BumpSessionVersion;
+
+impl SessionVersion {
+    /// Get a user's session version. Users that have never logged out everywhere are at
+    /// version 0. Return `Ok(None)` if there is no user with this ID.
+    pub async fn get(user_id: uuid) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(session_version::Variables { user_id })
+            .await
+            .map(|response| {
+                response
+                    .users_by_pk
+                    .map(|user| user.session_version.unwrap_or(0))
+            })
+    }
+}
+
+impl BumpSessionVersion {
+    /// Increment a user's session version, invalidating every identity cookie issued before.
+    /// Return the new version, or `Ok(None)` if there is no user with this ID.
+    pub async fn execute(user_id: uuid) -> Result<Option<i64>, TelescopeError> {
+        send_query::<Self>(bump_session_version::Variables { user_id })
+            .await
+            .map(|response| {
+                response
+                    .update_users_by_pk
+                    .map(|user| user.session_version.unwrap_or(0))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BumpSessionVersion, SessionVersion};
+    use crate::api::rcos::mock::MockRcosApi;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn unset_session_version_is_zero() {
+        let api = MockRcosApi::start();
+        let user_id = Uuid::new_v4();
+        api.stub(
+            "SessionVersion",
+            json!({ "users_by_pk": { "session_version": null } }),
+        );
+
+        assert_eq!(SessionVersion::get(user_id).await.unwrap(), Some(0));
+        api.assert_variables("SessionVersion", json!({ "user_id": user_id }));
+    }
+
+    #[actix_rt::test]
+    async fn bump_returns_new_version() {
+        let api = MockRcosApi::start();
+        let user_id = Uuid::new_v4();
+        api.stub(
+            "BumpSessionVersion",
+            json!({ "update_users_by_pk": { "session_version": 4 } }),
+        );
+
+        assert_eq!(BumpSessionVersion::execute(user_id).await.unwrap(), Some(4));
+        api.assert_called("BumpSessionVersion", 1);
+        api.assert_variables("BumpSessionVersion", json!({ "user_id": user_id }));
+    }
+
+    #[actix_rt::test]
+    async fn missing_user_is_none() {
+        let api = MockRcosApi::start();
+        api.stub("SessionVersion", json!({ "users_by_pk": null }));
+
+        assert_eq!(SessionVersion::get(Uuid::new_v4()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! GraphQL lookup to get a user's preferred timezone.
+
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::error::TelescopeError;
+use chrono_tz::Tz;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/timezone.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UserTimezone;
+
+/// Parse a stored timezone preference (an IANA name like `America/New_York`). Empty or unknown
+/// names mean the user has no preference.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    let name: &str = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    return name
+        .parse::<Tz>()
+        .map_err(|err| warn!("Ignoring unknown timezone {:?}: {}", name, err))
+        .ok();
+}
+
+impl UserTimezone {
+    /// Get a user's preferred timezone. Return `Ok(None)` if there is no user record for this
+    /// user ID or they have no (valid) preference.
+    pub async fn get(user_id: uuid) -> Result<Option<Tz>, TelescopeError> {
+        send_query::<Self>(user_timezone::Variables { user_id })
+            .await
+            .map(|result| {
+                result
+                    .users_by_pk
+                    .and_then(|user| parse_timezone(user.timezone.as_str()))
+            })
+    }
+}
@@ -0,0 +1,93 @@
+//! GraphQL queries to list users who are missing a linked GitHub or Discord account.
+
+use crate::api::rcos::prelude::*;
+use crate::api::rcos::send_query;
+use crate::api::rcos::users::UserAccountType;
+use crate::error::TelescopeError;
+use serde_json::Value;
+
+/// The unlinked users queries return 20 users per page.
+pub const PER_PAGE: u32 = 20;
+
+/// Type representing GraphQL query to get users missing an account on either of two platforms.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/unlinked.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UsersMissingEither;
+
+/// Type representing GraphQL query to get users missing accounts on both of two platforms.
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rcos/schema.json",
+    query_path = "graphql/rcos/users/unlinked.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct UsersMissingBoth;
+
+/// Which linked platform(s) a listed user should be missing.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPlatform {
+    /// Users without a linked GitHub account.
+    GitHub,
+    /// Users without a linked Discord account.
+    Discord,
+    /// Users missing a GitHub account, a Discord account, or both.
+    Either,
+    /// Users missing both a GitHub and a Discord account.
+    Both,
+}
+
+impl Default for MissingPlatform {
+    fn default() -> Self {
+        MissingPlatform::Either
+    }
+}
+
+/// Get a page of users missing a linked account on the given platform(s). The result is
+/// returned as a JSON value since the two underlying queries have different response types.
+pub async fn get(missing: MissingPlatform, page_num: u32) -> Result<Value, TelescopeError> {
+    // Calculate pagination variables.
+    let limit: i64 = PER_PAGE as i64;
+    let offset: i64 = (PER_PAGE * page_num) as i64;
+
+    // Send the appropriate query.
+    let response: Value = match missing {
+        MissingPlatform::GitHub | MissingPlatform::Discord | MissingPlatform::Either => {
+            // Determine which platforms to check. Single platform filters
+            // pass the same platform twice.
+            let (first, second) = match missing {
+                MissingPlatform::GitHub => (UserAccountType::GitHub, UserAccountType::GitHub),
+                MissingPlatform::Discord => (UserAccountType::Discord, UserAccountType::Discord),
+                _ => (UserAccountType::GitHub, UserAccountType::Discord),
+            };
+
+            let data = send_query::<UsersMissingEither>(users_missing_either::Variables {
+                limit,
+                offset,
+                first,
+                second,
+            })
+            .await?;
+
+            json!(data)
+        }
+
+        MissingPlatform::Both => {
+            let data = send_query::<UsersMissingBoth>(users_missing_both::Variables {
+                limit,
+                offset,
+                first: UserAccountType::GitHub,
+                second: UserAccountType::Discord,
+            })
+            .await?;
+
+            json!(data)
+        }
+    };
+
+    return Ok(response);
+}
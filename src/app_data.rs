@@ -1,6 +1,12 @@
+use crate::announcement::Announcement;
+use crate::email;
+use crate::env::global_config;
+use crate::error::TelescopeError;
 use crate::templates::helpers::register_helpers;
+use chrono::Utc;
 use handlebars::Handlebars;
-use std::sync::Arc;
+use lettre::SmtpTransport;
+use std::sync::{Arc, RwLock};
 
 lazy_static! {
     /// Lazy Static to store app data at runtime.
@@ -14,6 +20,12 @@ lazy_static! {
 pub struct AppData {
     /// The handlebars template registry.
     template_registry: Arc<Handlebars<'static>>,
+
+    /// The current site-wide announcement, if there is one.
+    announcement: Arc<RwLock<Option<Announcement>>>,
+
+    /// The pooled SMTP transport, if email is configured.
+    smtp_transport: Option<SmtpTransport>,
 }
 
 impl AppData {
@@ -37,8 +49,28 @@ impl AppData {
         register_helpers(&mut template_registry);
         info!("Handlebars templates registered.");
 
+        // Load the saved announcement.
+        let announcement: Option<Announcement> = Announcement::load();
+
+        // Build the SMTP transport once, so that its connections are reused.
+        let smtp_transport: Option<SmtpTransport> =
+            global_config().email.as_ref().and_then(|config| {
+                match email::build_transport(config) {
+                    Ok(transport) => Some(transport),
+                    Err(err) => {
+                        error!(
+                            "Could not build the SMTP transport. Emails will not be sent: {}",
+                            err
+                        );
+                        None
+                    }
+                }
+            });
+
         Self {
             template_registry: Arc::new(template_registry),
+            announcement: Arc::new(RwLock::new(announcement)),
+            smtp_transport,
         }
     }
 
@@ -51,4 +83,40 @@ impl AppData {
     pub fn get_handlebars_registry(&self) -> Arc<Handlebars<'static>> {
         self.template_registry.clone()
     }
+
+    /// Get the shared SMTP transport, if email is configured. Clones share one connection pool.
+    pub fn smtp_transport(&self) -> Option<SmtpTransport> {
+        self.smtp_transport.clone()
+    }
+
+    /// Get the current announcement, if there is one (even if it has expired).
+    pub fn announcement(&self) -> Option<Announcement> {
+        self.announcement
+            .read()
+            .map(|announcement| announcement.clone())
+            // A poisoned lock means a writer panicked. Don't show anything.
+            .unwrap_or(None)
+    }
+
+    /// Get the current announcement if there is one that has not expired.
+    pub fn active_announcement(&self) -> Option<Announcement> {
+        self.announcement()
+            .filter(|announcement| announcement.is_active(Utc::now()))
+    }
+
+    /// Replace (or remove) the current announcement and save it to the announcement file.
+    pub fn set_announcement(
+        &self,
+        announcement: Option<Announcement>,
+    ) -> Result<(), TelescopeError> {
+        // Save first so that the file and memory do not disagree if saving fails.
+        Announcement::save(announcement.as_ref())?;
+
+        let mut current = self
+            .announcement
+            .write()
+            .map_err(|_| TelescopeError::ise("Announcement lock poisoned."))?;
+        *current = announcement;
+        return Ok(());
+    }
 }
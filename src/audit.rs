@@ -0,0 +1,17 @@
+//! Audit log of administrative actions.
+//!
+//! Audit records are written to the log under their own target (`telescope::audit`), so they
+//! can be filtered or routed separately with the `log_level` setting (for example
+//! `info,telescope::audit=info`).
+
+use std::fmt::Display;
+use uuid::Uuid;
+
+/// The log target audit records are written to.
+const AUDIT_TARGET: &'static str = "telescope::audit";
+
+/// Record an action taken by a user. `action` is a short machine readable name for the kind of
+/// action, and `details` describes what was changed.
+pub fn record(actor: Uuid, action: &str, details: impl Display) {
+    info!(target: AUDIT_TARGET, "user={} action={} {}", actor, action, details);
+}
@@ -41,7 +41,10 @@ pub fn has_permission(invoker: &RoleId, roles: &Vec<Role>) -> bool {
 }
 
 // Grant permission for certain users
-fn generate_permission(project_role: Option<RoleId>, roles: Vec<Role>) -> Vec<PermissionOverwrite> {
+pub(crate) fn generate_permission(
+    project_role: Option<RoleId>,
+    roles: Vec<Role>,
+) -> Vec<PermissionOverwrite> {
     let mut overwrite = Vec::new();
     // set channel to be private
 
@@ -9,7 +9,7 @@ use serenity::model::interactions::application_command::ApplicationCommand;
 use serenity::model::interactions::application_command::ApplicationCommandInteraction;
 
 mod associate;
-mod generate;
+pub(super) mod generate;
 mod whois;
 /// Interactions return a boxed future of a serenity result.
 type InteractionResult<'a> = BoxFuture<'a, serenity::Result<()>>;
@@ -2,6 +2,8 @@
 
 mod commands;
 mod event_handler;
+pub mod project_sync;
+pub mod resync;
 
 use crate::discord_bot::event_handler::Handler;
 use crate::env::{global_config, DiscordConfig};
@@ -0,0 +1,324 @@
+//! Sync the Discord role and channels of a single project.
+//!
+//! Unlike a full [resync](super::resync), which only creates what is missing, syncing a project
+//! also brings existing Discord entities up to date with the project: a role or channel that
+//! was renamed on Discord (or whose project was renamed) gets the project's name again, and
+//! one that was deleted on Discord is created again and saved in place of the old one. Syncing
+//! a project twice does nothing the second time.
+//!
+//! Serenity's HTTP client waits out Discord's rate limits (using the `Retry-After` of 429
+//! responses) before retrying. If a request is still rate limited, it is reported as a
+//! [`TelescopeError::RateLimited`] error.
+
+use crate::api::discord::global_discord_client;
+use crate::api::rcos::discord_associations::project::project_info::{
+    find_project::FindProjectProjects, FindProject,
+};
+use crate::api::rcos::discord_associations::project::save_project_channel::SaveProjectChannel;
+use crate::api::rcos::discord_associations::project::save_project_role::SaveProjectRole;
+use crate::api::rcos::discord_associations::ChannelType;
+use crate::discord_bot::commands::generate::generate_permission;
+use crate::discord_bot::resync::{create_channel, create_role, parse_role, permitted_roles};
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use serenity::http::{Http, HttpError};
+use serenity::model::channel::{ChannelType as SerenityChannelType, GuildChannel};
+use serenity::model::guild::Role;
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+
+/// The kinds of channels every project gets.
+const PROJECT_CHANNEL_KINDS: [(SerenityChannelType, ChannelType); 2] = [
+    (SerenityChannelType::Voice, ChannelType::DiscordVoice),
+    (SerenityChannelType::Text, ChannelType::DiscordText),
+];
+
+/// What syncing did to a Discord role or channel.
+#[derive(Copy, Clone, Debug, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    /// There was none saved, so one was created and saved.
+    Created,
+    /// The saved one no longer exists on Discord, so a new one was created and saved instead.
+    Recreated,
+    /// The saved one exists but had the wrong name, so it was renamed.
+    Renamed,
+    /// The saved one exists and is up to date.
+    Unchanged,
+}
+
+/// What syncing a project did.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProjectSyncReport {
+    /// The project ID.
+    pub project_id: i64,
+    /// What happened to the project's role.
+    pub role: SyncAction,
+    /// What happened to each of the project's channels.
+    pub channels: Vec<(ChannelType, SyncAction)>,
+}
+
+/// Decide what to do with a saved Discord entity. `saved` is the ID saved in the database (if
+/// any), `current_name` is the name of the entity with that ID on Discord (`None` if it does
+/// not exist), and `wanted_name` is the name it should have.
+pub fn plan(saved: Option<u64>, current_name: Option<&str>, wanted_name: &str) -> SyncAction {
+    match (saved, current_name) {
+        (None, _) => SyncAction::Created,
+        (Some(_), None) => SyncAction::Recreated,
+        (Some(_), Some(name)) if name == wanted_name => SyncAction::Unchanged,
+        (Some(_), Some(_)) => SyncAction::Renamed,
+    }
+}
+
+/// Get the name a project channel should have. Discord lowercases text channel names and
+/// replaces spaces with dashes, so the project title is converted the same way for comparison.
+pub fn channel_name(title: &str, kind: SerenityChannelType) -> String {
+    if kind != SerenityChannelType::Text {
+        return title.to_string();
+    }
+
+    return title
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .to_lowercase();
+}
+
+/// Convert a Discord error, reporting rate limits that Serenity gave up on separately.
+fn discord_error(err: serenity::Error) -> TelescopeError {
+    if let serenity::Error::Http(http_error) = &err {
+        if let HttpError::UnsuccessfulRequest(response) = http_error.as_ref() {
+            if response.status_code.as_u16() == 429 {
+                warn!("Discord rate limit exceeded: {}", response.error.message);
+                return TelescopeError::rate_limited("Discord", None);
+            }
+        }
+    }
+    return TelescopeError::serenity_error(err);
+}
+
+/// Save the role of a project.
+async fn save_role(project_id: i64, role: RoleId) -> Result<(), TelescopeError> {
+    SaveProjectRole::execute(project_id, role.to_string()).await?;
+    return Ok(());
+}
+
+/// Save a channel for a project.
+async fn save_channel(
+    project_id: i64,
+    channel: ChannelId,
+    kind: ChannelType,
+) -> Result<(), TelescopeError> {
+    SaveProjectChannel::execute(project_id, channel.to_string(), kind).await?;
+    return Ok(());
+}
+
+/// Sync the role of a project. Return what was done and the ID of the role.
+async fn sync_role(
+    http: &Http,
+    guild: GuildId,
+    project: &FindProjectProjects,
+    guild_roles: &[Role],
+) -> Result<(SyncAction, RoleId), TelescopeError> {
+    let saved: Option<RoleId> = project
+        .project_role
+        .as_ref()
+        .and_then(|role| parse_role(&role.role_id));
+    let current: Option<&Role> = saved.and_then(|id| guild_roles.iter().find(|r| r.id == id));
+
+    let action: SyncAction = plan(
+        saved.map(|id| id.0),
+        current.map(|role| role.name.as_str()),
+        project.title.as_str(),
+    );
+
+    let role: RoleId = match (action, saved) {
+        (SyncAction::Unchanged, Some(role)) => role,
+        (SyncAction::Renamed, Some(role)) => {
+            guild
+                .edit_role(http, role, |r| r.name(project.title.as_str()))
+                .await
+                .map_err(discord_error)?;
+            role
+        }
+        // Created or recreated.
+        _ => {
+            let role: RoleId = create_role(http, guild, &project.title).await?;
+            save_role(project.project_id, role).await?;
+            role
+        }
+    };
+
+    return Ok((action, role));
+}
+
+/// Sync one channel of a project.
+async fn sync_channel(
+    http: &Http,
+    guild: GuildId,
+    project: &FindProjectProjects,
+    guild_channels: &[GuildChannel],
+    permitted: &[Role],
+    role: RoleId,
+    (serenity_kind, kind): (SerenityChannelType, ChannelType),
+) -> Result<SyncAction, TelescopeError> {
+    let saved: Option<ChannelId> = project
+        .project_channels
+        .iter()
+        .find(|channel| channel.kind == kind)
+        .and_then(|channel| channel.channel_id.parse::<u64>().ok())
+        .map(ChannelId);
+    let current: Option<&GuildChannel> =
+        saved.and_then(|id| guild_channels.iter().find(|c| c.id == id));
+    let wanted_name: String = channel_name(&project.title, serenity_kind);
+
+    let action: SyncAction = plan(
+        saved.map(|id| id.0),
+        current.map(|channel| channel.name.as_str()),
+        wanted_name.as_str(),
+    );
+
+    let overwrite = generate_permission(Some(role), permitted.to_vec());
+    match (action, saved) {
+        (SyncAction::Unchanged, _) => {}
+        (SyncAction::Renamed, Some(channel)) => {
+            channel
+                .edit(http, |c| {
+                    c.name(wanted_name.as_str()).permissions(overwrite)
+                })
+                .await
+                .map_err(discord_error)?;
+        }
+        // Created or recreated. The channel is not put in a category, since a project can be
+        // in more than one small group.
+        _ => {
+            let channel: ChannelId =
+                create_channel(http, guild, &project.title, serenity_kind, overwrite, None).await?;
+            save_channel(project.project_id, channel, kind).await?;
+        }
+    }
+
+    return Ok(action);
+}
+
+/// Create or update the Discord role and channels of a project, and save any new IDs.
+pub async fn sync_project(project_id: i64) -> Result<ProjectSyncReport, TelescopeError> {
+    let project: FindProjectProjects = FindProject::get_by_id(project_id)
+        .await?
+        .projects
+        .into_iter()
+        .next()
+        .ok_or(TelescopeError::resource_not_found(
+            "Project Not Found",
+            "Could not find a project with this ID.",
+        ))?;
+
+    let http: &Http = global_discord_client();
+    let guild: GuildId = GuildId(global_config().discord_config.rcos_guild_id());
+
+    // Get what currently exists on Discord.
+    let guild_roles: Vec<Role> = http.get_guild_roles(guild.0).await.map_err(discord_error)?;
+    let guild_channels: Vec<GuildChannel> =
+        http.get_channels(guild.0).await.map_err(discord_error)?;
+    let permitted: Vec<Role> = permitted_roles(http, guild).await?;
+
+    // Sync the role first, so that the channels can give it access.
+    let (role_action, role) = sync_role(http, guild, &project, &guild_roles).await?;
+
+    let mut channels: Vec<(ChannelType, SyncAction)> = Vec::new();
+    for kinds in PROJECT_CHANNEL_KINDS {
+        let action: SyncAction = sync_channel(
+            http,
+            guild,
+            &project,
+            &guild_channels,
+            &permitted,
+            role,
+            kinds,
+        )
+        .await?;
+        channels.push((kinds.1, action));
+    }
+
+    return Ok(ProjectSyncReport {
+        project_id,
+        role: role_action,
+        channels,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Projects without a saved role or channel get a new one.
+    #[test]
+    fn plan_create() {
+        assert_eq!(plan(None, None, "Telescope"), SyncAction::Created);
+        // Even if something with the right name exists, it was not made for the project.
+        assert_eq!(
+            plan(None, Some("Telescope"), "Telescope"),
+            SyncAction::Created
+        );
+    }
+
+    /// Saved roles or channels that were deleted (or archived out of the guild) are recreated.
+    #[test]
+    fn plan_recreate() {
+        assert_eq!(plan(Some(1234), None, "Telescope"), SyncAction::Recreated);
+    }
+
+    /// Saved roles or channels with another name are renamed.
+    #[test]
+    fn plan_rename() {
+        assert_eq!(
+            plan(Some(1234), Some("Old Telescope"), "Telescope"),
+            SyncAction::Renamed
+        );
+        // Names are compared exactly.
+        assert_eq!(
+            plan(Some(1234), Some("telescope"), "Telescope"),
+            SyncAction::Renamed
+        );
+    }
+
+    /// Saved roles or channels that are up to date are left alone.
+    #[test]
+    fn plan_unchanged() {
+        assert_eq!(
+            plan(Some(1234), Some("Telescope"), "Telescope"),
+            SyncAction::Unchanged
+        );
+    }
+
+    /// Text channel names are normalised the way Discord does it.
+    #[test]
+    fn text_channel_names_are_normalised() {
+        assert_eq!(
+            channel_name("RCOS  Telescope Web", SerenityChannelType::Text),
+            "rcos-telescope-web"
+        );
+        assert_eq!(
+            channel_name(" Telescope ", SerenityChannelType::Text),
+            "telescope"
+        );
+    }
+
+    /// Voice channels keep the project title.
+    #[test]
+    fn voice_channel_names_are_kept() {
+        assert_eq!(
+            channel_name("RCOS Telescope", SerenityChannelType::Voice),
+            "RCOS Telescope"
+        );
+    }
+
+    /// A synced channel's normalised name plans as unchanged, so syncing twice does nothing.
+    #[test]
+    fn normalised_names_are_stable() {
+        let wanted: String = channel_name("RCOS Telescope", SerenityChannelType::Text);
+        assert_eq!(
+            plan(Some(1234), Some(wanted.as_str()), wanted.as_str()),
+            SyncAction::Unchanged
+        );
+    }
+}
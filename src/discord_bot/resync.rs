@@ -0,0 +1,546 @@
+//! Full resynchronization of the RCOS Discord with the RCOS database.
+//!
+//! A resync does everything the `/generate all` slash command does, and then gives every
+//! enrolled user with a linked Discord account the role of their project. It runs as a
+//! background job so that the request starting it can return immediately, and its progress is
+//! kept in memory so it can be polled.
+//!
+//! Discord requests are made one at a time. Serenity's HTTP client waits out Discord's rate
+//! limits (including 429 responses) before retrying, so a resync slows down rather than
+//! failing when it hits them.
+
+use crate::api::discord::global_discord_client;
+use crate::api::rcos::discord_associations::project::{
+    create_project_channel, create_project_role, project_info,
+};
+use crate::api::rcos::discord_associations::small_group::{
+    create_small_group_category, create_small_group_role, small_group_info,
+};
+use crate::api::rcos::discord_associations::ChannelType;
+use crate::api::rcos::users::accounts::lookup::AccountLookup;
+use crate::api::rcos::users::UserAccountType;
+use crate::discord_bot::commands::generate::generate_permission;
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serenity::http::Http;
+use serenity::model::channel::{ChannelType as SerenityChannelType, PermissionOverwrite};
+use serenity::model::guild::Role;
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use uuid::Uuid;
+
+/// How many hours a finished job is kept for polling.
+const FINISHED_JOB_RETENTION_HOURS: i64 = 24;
+
+/// The most error messages kept for a job. The failure count is still exact past this.
+const MAX_ERRORS: usize = 50;
+
+lazy_static! {
+    /// Resync jobs, keyed by job ID.
+    static ref JOBS: DashMap<Uuid, ResyncJob> = DashMap::new();
+}
+
+/// The state of a resync job.
+#[derive(Copy, Clone, Debug, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResyncStatus {
+    /// The job is still running.
+    Running,
+    /// The job went through everything. Individual items may still have failed.
+    Completed,
+    /// The job stopped early because data it needed could not be loaded.
+    Failed,
+}
+
+/// Counts of what a resync job did.
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct ResyncSummary {
+    /// Roles, categories, and channels created.
+    pub created: u32,
+    /// Users given their project role.
+    pub updated: u32,
+    /// Items that were already in sync or could not be synced (e.g. no linked Discord).
+    pub skipped: u32,
+    /// Items that could not be synced because of an error.
+    pub failed: u32,
+}
+
+/// A resync job and its progress.
+#[derive(Clone, Debug, Serialize)]
+pub struct ResyncJob {
+    /// The job ID.
+    pub id: Uuid,
+    /// The user who started the job.
+    pub started_by: Uuid,
+    /// When the job started.
+    pub started_at: DateTime<Utc>,
+    /// When the job finished, if it has.
+    pub finished_at: Option<DateTime<Utc>>,
+    /// The state of the job.
+    pub status: ResyncStatus,
+    /// What the job has done so far.
+    pub summary: ResyncSummary,
+    /// Messages describing the failures so far (up to [`MAX_ERRORS`]).
+    pub errors: Vec<String>,
+}
+
+/// Get a resync job by ID.
+pub fn get(job_id: Uuid) -> Option<ResyncJob> {
+    JOBS.get(&job_id).map(|job| job.value().clone())
+}
+
+/// Start a resync job in the background and return its ID. If a job is already running, no
+/// new job is started and the ID of the running one is returned instead.
+pub fn start(started_by: Uuid) -> Uuid {
+    // Forget old finished jobs.
+    let cutoff: DateTime<Utc> = Utc::now() - Duration::hours(FINISHED_JOB_RETENTION_HOURS);
+    JOBS.retain(|_, job| job.finished_at.map(|at| at > cutoff).unwrap_or(true));
+
+    // Only one resync at a time.
+    if let Some(running) = JOBS.iter().find(|job| job.status == ResyncStatus::Running) {
+        return running.id;
+    }
+
+    // Register the job.
+    let job_id: Uuid = Uuid::new_v4();
+    JOBS.insert(
+        job_id,
+        ResyncJob {
+            id: job_id,
+            started_by,
+            started_at: Utc::now(),
+            finished_at: None,
+            status: ResyncStatus::Running,
+            summary: ResyncSummary::default(),
+            errors: Vec::new(),
+        },
+    );
+
+    // Run it in the background.
+    actix::spawn(async move {
+        let status: ResyncStatus = match run(job_id).await {
+            Ok(()) => ResyncStatus::Completed,
+            Err(err) => {
+                error!("Discord resync {} stopped early: {}", job_id, err);
+                record_failure(job_id, format!("Resync stopped early: {}", err));
+                ResyncStatus::Failed
+            }
+        };
+
+        if let Some(mut job) = JOBS.get_mut(&job_id) {
+            job.status = status;
+            job.finished_at = Some(Utc::now());
+            info!("Discord resync {} finished: {:?}", job_id, job.summary);
+        }
+    });
+
+    return job_id;
+}
+
+/// Update a job's summary.
+fn update_summary(job_id: Uuid, f: impl FnOnce(&mut ResyncSummary)) {
+    if let Some(mut job) = JOBS.get_mut(&job_id) {
+        f(&mut job.summary);
+    }
+}
+
+/// Count a failed item and keep its message.
+fn record_failure(job_id: Uuid, message: String) {
+    if let Some(mut job) = JOBS.get_mut(&job_id) {
+        job.summary.failed += 1;
+        if job.errors.len() < MAX_ERRORS {
+            job.errors.push(message);
+        }
+    }
+}
+
+/// Count the outcome of creating a Discord entity.
+fn record_created<T, E: std::fmt::Display>(job_id: Uuid, what: &str, result: Result<T, E>) {
+    match result {
+        Ok(_) => update_summary(job_id, |s| s.created += 1),
+        Err(err) => {
+            warn!("Discord resync could not create {}: {}", what, err);
+            record_failure(job_id, format!("Could not create {}: {}", what, err));
+        }
+    }
+}
+
+/// Get the roles that can see every generated channel (`@everyone` is denied instead).
+pub(super) async fn permitted_roles(
+    http: &Http,
+    guild: GuildId,
+) -> Result<Vec<Role>, TelescopeError> {
+    let roles: Vec<Role> = http
+        .get_guild_roles(guild.0)
+        .await
+        .map_err(TelescopeError::serenity_error)?;
+
+    return Ok(roles
+        .into_iter()
+        .filter(|role| {
+            role.name == "@everyone"
+                || role.name == "Faculty Advisors"
+                || role.name == "Coordinators"
+        })
+        .collect());
+}
+
+/// Parse a role ID stored in the database.
+pub(super) fn parse_role(role_id: &str) -> Option<RoleId> {
+    role_id.parse::<u64>().ok().map(RoleId)
+}
+
+/// Get the category to create a small group project's channels in. `category_id` is the saved
+/// category of the small group. Return `None` if the channels should not be created, because
+/// the project already has channels or the small group has no usable category.
+fn channel_category(category_id: Option<&str>, has_channels: bool) -> Option<ChannelId> {
+    if has_channels {
+        return None;
+    }
+    return category_id
+        .and_then(|id| id.parse::<u64>().ok())
+        .map(ChannelId);
+}
+
+/// Check if a member with the given roles still needs to be given a role.
+fn needs_role(member_roles: &[RoleId], role: RoleId) -> bool {
+    !member_roles.contains(&role)
+}
+
+/// Run a resync job.
+async fn run(job_id: Uuid) -> Result<(), TelescopeError> {
+    let http: &Http = global_discord_client();
+    let guild: GuildId = GuildId(global_config().discord_config.rcos_guild_id());
+    let roles: Vec<Role> = permitted_roles(http, guild).await?;
+
+    // Roles for current projects.
+    for project in project_info::CurrProjects::get(0, None).await?.projects {
+        if project.project_role.is_some() {
+            update_summary(job_id, |s| s.skipped += 1);
+            continue;
+        }
+
+        let result = match create_role(http, guild, &project.title).await {
+            Ok(role) => create_project_role::CreateOneProjectRole::execute(
+                project.project_id,
+                role.to_string(),
+            )
+            .await
+            .map(|_| ()),
+            Err(err) => Err(err),
+        };
+        record_created(job_id, &format!("a role for {}", project.title), result);
+    }
+
+    // Roles for small groups.
+    for small_group in small_group_info::CurrSmallGroups::get(0, None)
+        .await?
+        .small_groups
+    {
+        if small_group.small_group_role.is_some() {
+            update_summary(job_id, |s| s.skipped += 1);
+            continue;
+        }
+
+        let result = match create_role(http, guild, &small_group.title).await {
+            Ok(role) => create_small_group_role::CreateOneSmallGroupRole::execute(
+                small_group.small_group_id,
+                role.to_string(),
+            )
+            .await
+            .map(|_| ()),
+            Err(err) => Err(err),
+        };
+        record_created(job_id, &format!("a role for {}", small_group.title), result);
+    }
+
+    // Categories for small groups. Reload them to see the roles made above.
+    for small_group in small_group_info::CurrSmallGroups::get(0, None)
+        .await?
+        .small_groups
+    {
+        if !small_group.small_group_categories.is_empty() {
+            update_summary(job_id, |s| s.skipped += 1);
+            continue;
+        }
+
+        let overwrite: Vec<PermissionOverwrite> = generate_permission(
+            small_group
+                .small_group_role
+                .as_ref()
+                .and_then(|role| parse_role(&role.role_id)),
+            roles.clone(),
+        );
+
+        let result = match create_channel(
+            http,
+            guild,
+            &small_group.title,
+            SerenityChannelType::Category,
+            overwrite,
+            None,
+        )
+        .await
+        {
+            Ok(category) => create_small_group_category::CreateOneSmallGroupCategory::execute(
+                small_group.small_group_id,
+                category.to_string(),
+            )
+            .await
+            .map(|_| ()),
+            Err(err) => Err(err),
+        };
+        record_created(
+            job_id,
+            &format!("a category for {}", small_group.title),
+            result,
+        );
+    }
+
+    // Channels for small group projects, under the small group's category.
+    for small_group in small_group_info::CurrSmallGroups::get(0, None)
+        .await?
+        .small_groups
+    {
+        let category_id: Option<&str> = small_group
+            .small_group_categories
+            .first()
+            .map(|category| category.category_id.as_str());
+
+        for small_group_project in small_group.small_group_projects {
+            let project = small_group_project.project;
+            let category: ChannelId =
+                match channel_category(category_id, !project.project_channels.is_empty()) {
+                    Some(category) => category,
+                    None => {
+                        update_summary(job_id, |s| s.skipped += 1);
+                        continue;
+                    }
+                };
+
+            let overwrite: Vec<PermissionOverwrite> = generate_permission(
+                project
+                    .project_role
+                    .as_ref()
+                    .and_then(|role| parse_role(&role.role_id)),
+                roles.clone(),
+            );
+
+            // Create a voice and a text channel.
+            for (kind, channel_type) in [
+                (SerenityChannelType::Voice, ChannelType::DiscordVoice),
+                (SerenityChannelType::Text, ChannelType::DiscordText),
+            ] {
+                let result = match create_channel(
+                    http,
+                    guild,
+                    &project.title,
+                    kind,
+                    overwrite.clone(),
+                    Some(category),
+                )
+                .await
+                {
+                    Ok(channel) => create_project_channel::CreateOneProjectChannel::execute(
+                        project.project_id,
+                        channel.to_string(),
+                        channel_type,
+                    )
+                    .await
+                    .map(|_| ()),
+                    Err(err) => Err(err),
+                };
+                record_created(
+                    job_id,
+                    &format!("a {:?} channel for {}", channel_type, project.title),
+                    result,
+                );
+            }
+        }
+    }
+
+    // Give enrolled users their project role. Reload projects to see the roles made above.
+    for project in project_info::CurrProjects::get(0, None).await?.projects {
+        let role: RoleId = match project
+            .project_role
+            .as_ref()
+            .and_then(|role| parse_role(&role.role_id))
+        {
+            Some(role) => role,
+            // The role could not be created above; that was already counted.
+            None => continue,
+        };
+
+        for enrollment in project.enrollments {
+            sync_member_role(job_id, http, guild, enrollment.user_id, role).await;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Give a user a role if they have a linked Discord account and do not have it yet.
+async fn sync_member_role(job_id: Uuid, http: &Http, guild: GuildId, user_id: Uuid, role: RoleId) {
+    // Find the user's Discord account.
+    let discord_id: u64 = match AccountLookup::send(user_id, UserAccountType::Discord).await {
+        Ok(Some(id)) => match id.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => {
+                record_failure(
+                    job_id,
+                    format!("User {} has an invalid Discord ID.", user_id),
+                );
+                return;
+            }
+        },
+        // No linked Discord account.
+        Ok(None) => {
+            update_summary(job_id, |s| s.skipped += 1);
+            return;
+        }
+        Err(err) => {
+            record_failure(
+                job_id,
+                format!(
+                    "Could not look up the Discord account of {}: {}",
+                    user_id, err
+                ),
+            );
+            return;
+        }
+    };
+
+    // Check their current roles.
+    let needs_role: bool = match http.get_member(guild.0, discord_id).await {
+        Ok(member) => needs_role(&member.roles, role),
+        Err(err) => {
+            record_failure(
+                job_id,
+                format!("Could not get the Discord member of {}: {}", user_id, err),
+            );
+            return;
+        }
+    };
+
+    if !needs_role {
+        update_summary(job_id, |s| s.skipped += 1);
+        return;
+    }
+
+    match http.add_member_role(guild.0, discord_id, role.0).await {
+        Ok(()) => update_summary(job_id, |s| s.updated += 1),
+        Err(err) => record_failure(
+            job_id,
+            format!("Could not give {} their project role: {}", user_id, err),
+        ),
+    }
+}
+
+/// Create a mentionable role and return its ID.
+pub(super) async fn create_role(
+    http: &Http,
+    guild: GuildId,
+    name: &str,
+) -> Result<RoleId, TelescopeError> {
+    return guild
+        .create_role(http, |r| r.name(name).mentionable(true))
+        .await
+        .map(|role| role.id)
+        .map_err(TelescopeError::serenity_error);
+}
+
+/// Create a channel and return its ID.
+pub(super) async fn create_channel(
+    http: &Http,
+    guild: GuildId,
+    name: &str,
+    kind: SerenityChannelType,
+    overwrite: Vec<PermissionOverwrite>,
+    category: Option<ChannelId>,
+) -> Result<ChannelId, TelescopeError> {
+    return guild
+        .create_channel(http, |c| {
+            c.name(name).kind(kind).permissions(overwrite);
+            if let Some(category) = category {
+                c.category(category);
+            }
+            c
+        })
+        .await
+        .map(|channel| channel.id)
+        .map_err(TelescopeError::serenity_error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Register a running job with no progress.
+    fn test_job() -> Uuid {
+        let job_id: Uuid = Uuid::new_v4();
+        JOBS.insert(
+            job_id,
+            ResyncJob {
+                id: job_id,
+                started_by: Uuid::nil(),
+                started_at: Utc::now(),
+                finished_at: None,
+                status: ResyncStatus::Running,
+                summary: ResyncSummary::default(),
+                errors: Vec::new(),
+            },
+        );
+        return job_id;
+    }
+
+    /// Only valid numeric IDs are parsed as roles.
+    #[test]
+    fn role_ids() {
+        assert_eq!(
+            parse_role("882345678901234567"),
+            Some(RoleId(882345678901234567))
+        );
+        assert_eq!(parse_role(""), None);
+        assert_eq!(parse_role("not a role"), None);
+        assert_eq!(parse_role("-1"), None);
+    }
+
+    /// Channels are created in the small group's category, only if the project has none.
+    #[test]
+    fn channel_categories() {
+        assert_eq!(channel_category(Some("1234"), false), Some(ChannelId(1234)));
+        // Already has channels.
+        assert_eq!(channel_category(Some("1234"), true), None);
+        // No category, or one that is not a Discord ID.
+        assert_eq!(channel_category(None, false), None);
+        assert_eq!(channel_category(Some("general"), false), None);
+    }
+
+    /// Members are only given roles they do not have yet.
+    #[test]
+    fn member_roles() {
+        let role: RoleId = RoleId(42);
+        assert!(needs_role(&[], role));
+        assert!(needs_role(&[RoleId(7)], role));
+        assert!(!needs_role(&[RoleId(7), role], role));
+    }
+
+    /// Outcomes are counted, and error messages are capped.
+    #[test]
+    fn outcomes_are_counted() {
+        let job_id: Uuid = test_job();
+        record_created::<(), &str>(job_id, "a role", Ok(()));
+        update_summary(job_id, |s| s.skipped += 1);
+        for _ in 0..MAX_ERRORS + 5 {
+            record_created::<(), &str>(job_id, "a channel", Err("Missing Access"));
+        }
+
+        let job: ResyncJob = get(job_id).unwrap();
+        assert_eq!(job.summary.created, 1);
+        assert_eq!(job.summary.skipped, 1);
+        assert_eq!(job.summary.failed, (MAX_ERRORS + 5) as u32);
+        assert_eq!(job.errors.len(), MAX_ERRORS);
+        assert_eq!(job.errors[0], "Could not create a channel: Missing Access");
+    }
+}
@@ -0,0 +1,178 @@
+//! Validated email addresses.
+
+use crate::error::TelescopeError;
+use std::str::FromStr;
+
+/// The longest allowed email address, in bytes.
+const MAX_ADDRESS_LENGTH: usize = 254;
+
+/// The longest allowed local part (before the `@`), in bytes.
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+
+/// The longest allowed domain label (between dots), in bytes.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// An email address that has been checked for basic validity. The domain is normalized to
+/// lowercase, since domains are case insensitive. The local part is left as is.
+///
+/// This is stricter than lettre's address parsing in places (no quoted local parts, domains
+/// must have a dot) so that anything we try to send to is a plausible real address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[display(fmt = "{}", _0)]
+pub struct Email(String);
+
+/// Is this a valid character in the local part of an email address? This is the unquoted
+/// "atext" set from RFC 5322 plus dots.
+fn is_local_part_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c)
+}
+
+/// Is this a valid domain label (a letter/digit/hyphen string that does not start or end with
+/// a hyphen)?
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LENGTH
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+impl Email {
+    /// Validate and normalize an email address. Surrounding whitespace is ignored.
+    pub fn parse(address: &str) -> Result<Self, TelescopeError> {
+        let address: &str = address.trim();
+
+        // Build the error for this address.
+        let invalid = |reason: &str| {
+            TelescopeError::email_error(format!("Invalid email address {:?}: {}", address, reason))
+        };
+
+        // Check the overall length.
+        if address.len() > MAX_ADDRESS_LENGTH {
+            return Err(invalid("address is too long"));
+        }
+
+        // Split off the domain at the last '@'. There should be exactly one.
+        let (local, domain) = address
+            .rsplit_once('@')
+            .ok_or_else(|| invalid("missing '@'"))?;
+        if local.contains('@') {
+            return Err(invalid("more than one '@'"));
+        }
+
+        // Check the local part.
+        if local.is_empty() || local.len() > MAX_LOCAL_PART_LENGTH {
+            return Err(invalid("the part before '@' must be 1 to 64 characters"));
+        }
+        if !local.chars().all(is_local_part_char) {
+            return Err(invalid("the part before '@' has invalid characters"));
+        }
+        if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+            return Err(invalid("misplaced '.' before '@'"));
+        }
+
+        // Check the domain. It needs at least two labels (e.g. "rpi.edu").
+        let domain: String = domain.to_ascii_lowercase();
+        if !domain.contains('.') || !domain.split('.').all(is_valid_label) {
+            return Err(invalid("invalid domain"));
+        }
+
+        return Ok(Email(format!("{}@{}", local, domain)));
+    }
+
+    /// Get the address as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FromStr for Email {
+    type Err = TelescopeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Email::parse(s)
+    }
+}
+
+impl TryFrom<String> for Email {
+    type Error = TelescopeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Email::parse(value.as_str())
+    }
+}
+
+impl From<Email> for String {
+    fn from(email: Email) -> Self {
+        email.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plausible addresses are accepted, with the domain lowercased.
+    #[test]
+    fn accepts_valid_addresses() {
+        assert_eq!(
+            Email::parse("montl@rpi.edu").unwrap().as_str(),
+            "montl@rpi.edu"
+        );
+        assert_eq!(
+            Email::parse("  Leon.Montealegre+rcos@Mail.RPI.EDU ")
+                .unwrap()
+                .as_str(),
+            "Leon.Montealegre+rcos@mail.rpi.edu"
+        );
+        assert!(Email::parse("o'brien@example.co.uk").is_ok());
+        assert!(Email::parse("a@b-c.io").is_ok());
+    }
+
+    /// Addresses without exactly one '@' are rejected.
+    #[test]
+    fn rejects_bad_at_signs() {
+        assert!(Email::parse("montl.rpi.edu").is_err());
+        assert!(Email::parse("mont@l@rpi.edu").is_err());
+        assert!(Email::parse("").is_err());
+    }
+
+    /// Bad local parts are rejected.
+    #[test]
+    fn rejects_bad_local_parts() {
+        assert!(Email::parse("@rpi.edu").is_err());
+        assert!(Email::parse(".montl@rpi.edu").is_err());
+        assert!(Email::parse("montl.@rpi.edu").is_err());
+        assert!(Email::parse("mont..l@rpi.edu").is_err());
+        assert!(Email::parse("mont l@rpi.edu").is_err());
+        assert!(Email::parse("\"montl\"@rpi.edu").is_err());
+        assert!(Email::parse(format!("{}@rpi.edu", "a".repeat(65)).as_str()).is_err());
+    }
+
+    /// Bad domains are rejected.
+    #[test]
+    fn rejects_bad_domains() {
+        assert!(Email::parse("montl@localhost").is_err());
+        assert!(Email::parse("montl@rpi..edu").is_err());
+        assert!(Email::parse("montl@-rpi.edu").is_err());
+        assert!(Email::parse("montl@rpi-.edu").is_err());
+        assert!(Email::parse("montl@rpi_edu.com").is_err());
+        assert!(Email::parse(format!("montl@{}.edu", "a".repeat(64)).as_str()).is_err());
+    }
+
+    /// Addresses over the length limit are rejected.
+    #[test]
+    fn rejects_long_addresses() {
+        let domain: String = vec!["a".repeat(60); 5].join(".");
+        assert!(Email::parse(format!("montl@{}.edu", domain).as_str()).is_err());
+    }
+
+    /// Addresses are validated when deserialized, and serialize as strings.
+    #[test]
+    fn serde_round_trip() {
+        let email: Email = serde_json::from_str("\"montl@RPI.edu\"").unwrap();
+        assert_eq!(serde_json::to_string(&email).unwrap(), "\"montl@rpi.edu\"");
+        assert!(serde_json::from_str::<Email>("\"not an email\"").is_err());
+    }
+}
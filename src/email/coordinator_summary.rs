@@ -0,0 +1,241 @@
+//! Weekly summary email for coordinators.
+//!
+//! This is sent to coordinators of ongoing semesters, faculty advisors, and sysadmins on a
+//! configured day and hour. It is off unless enabled in the config.
+
+use crate::api::rcos::coordinator_summary::{
+    CoordinatorSummaryRecipients, SummaryRecipient, SummaryStats,
+};
+use crate::email;
+use crate::email::address::Email;
+use crate::env::{global_config, EmailConfig};
+use crate::error::TelescopeError;
+use actix::{Actor, AsyncContext, Context};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Weekday};
+
+/// The directory of the handlebars templates of the summary.
+const TEMPLATE: &'static str = "email/coordinator_summary";
+
+/// The subject line of the summary email.
+const SUBJECT: &'static str = "RCOS Weekly Coordinator Summary";
+
+/// Zero sized actor that sends the coordinator summary every week.
+pub struct CoordinatorSummary;
+
+/// Get the next time after `now` that falls on the given weekday and hour. Hours past 23
+/// are treated as 23.
+fn next_send_time(now: DateTime<Local>, weekday: Weekday, hour: u32) -> DateTime<Local> {
+    // Get the number of days until the next matching weekday (possibly today).
+    let days_ahead: i64 = (7 + weekday.num_days_from_monday() as i64
+        - now.weekday().num_days_from_monday() as i64)
+        % 7;
+
+    // Build the candidate time on that day.
+    let naive: NaiveDateTime =
+        (now.date().naive_local() + Duration::days(days_ahead)).and_hms(hour.min(23), 0, 0);
+    let candidate: DateTime<Local> = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        // If the local time does not exist (daylight savings), fall back to UTC.
+        .unwrap_or_else(|| Local.from_utc_datetime(&naive));
+
+    // If that time has already passed, use the same time next week.
+    if candidate <= now {
+        return candidate + Duration::weeks(1);
+    } else {
+        return candidate;
+    }
+}
+
+impl CoordinatorSummary {
+    /// Should the coordinator summary be sent? This requires both the summary and emails
+    /// to be enabled in the config.
+    pub fn is_enabled() -> bool {
+        let config = global_config();
+        config.coordinator_summary.enabled && config.email.is_some()
+    }
+
+    /// Build the summary email for one recipient.
+    fn build_email(
+        config: &EmailConfig,
+        recipient: &SummaryRecipient,
+        stats: &SummaryStats,
+    ) -> Result<Option<lettre::Message>, TelescopeError> {
+        // Skip suppressed addresses.
+        let address: Email = email::rpi_address(recipient.rcs_id.as_str())?;
+        if email::is_suppressed(config, &address) {
+            info!(
+                "Skipping coordinator summary to suppressed address {}",
+                address
+            );
+            return Ok(None);
+        }
+
+        // Fill in the templates.
+        let fields = json!({
+            "recipient": recipient,
+            "stats": stats,
+            "since": stats.since.with_timezone(&Local).format("%B %-d").to_string(),
+            "until": stats.until.with_timezone(&Local).format("%B %-d").to_string(),
+        });
+
+        // Build the message.
+        let to = email::mailbox(
+            format!("{} {}", recipient.first_name, recipient.last_name),
+            &address,
+        )?;
+
+        return email::templated_message(config, to, SUBJECT, TEMPLATE, fields).map(Some);
+    }
+
+    /// Query the summary statistics and recipients and send the summary to each recipient.
+    /// Return the number of emails sent.
+    async fn send_all() -> Result<usize, TelescopeError> {
+        // Get the email config.
+        let config: EmailConfig =
+            email::email_config().ok_or(TelescopeError::ise("Email is not configured."))?;
+
+        // Get the summary data and the recipients at the same time.
+        let (stats, recipients) = futures::try_join!(
+            SummaryStats::for_past_week(),
+            CoordinatorSummaryRecipients::get()
+        )?;
+
+        let mut sent: usize = 0;
+        for recipient in recipients.iter() {
+            // Build and send each email. Log failures without stopping the rest.
+            let result = match Self::build_email(&config, recipient, &stats) {
+                Ok(Some(message)) => email::send(message).await.map(|_| 1),
+                Ok(None) => Ok(0),
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(count) => sent += count,
+                Err(err) => error!(
+                    "Could not send coordinator summary to {}: {}",
+                    recipient.rcs_id, err
+                ),
+            }
+        }
+
+        return Ok(sent);
+    }
+
+    /// Schedule the next summary.
+    fn schedule_next(&self, ctx: &mut Context<Self>) {
+        // Get the configured send time.
+        let config = global_config();
+        let summary_config = &config.coordinator_summary;
+
+        // Look from a minute in the future so that a timer firing slightly early does not
+        // send the summary twice.
+        let now: DateTime<Local> = Local::now();
+        let next: DateTime<Local> = next_send_time(
+            now + Duration::minutes(1),
+            summary_config.weekday,
+            summary_config.hour,
+        );
+        info!("Next coordinator summary scheduled for {}", next);
+
+        // Wait until then and send the summary.
+        let delay = (next - now).to_std().unwrap_or_default();
+        ctx.run_later(delay, |actor, ctx| {
+            info!("Sending coordinator summary.");
+            actix::spawn(async {
+                match Self::send_all().await {
+                    Ok(sent) => info!("Sent {} coordinator summary emails.", sent),
+                    Err(err) => error!("Could not send coordinator summary: {}", err),
+                }
+            });
+
+            // Schedule the following week's summary.
+            actor.schedule_next(ctx);
+        });
+    }
+}
+
+impl Actor for CoordinatorSummary {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Coordinator Summary Started");
+        self.schedule_next(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get a local time. These dates are away from daylight savings changes.
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.ymd(year, month, day).and_hms(hour, minute, 0)
+    }
+
+    /// Before the send time on the send day, the summary is sent later that day.
+    #[test]
+    fn before_send_time_same_day() {
+        // June 15th, 2022 was a Wednesday.
+        let now = local(2022, 6, 15, 8, 30);
+        assert_eq!(
+            next_send_time(now, Weekday::Wed, 9),
+            local(2022, 6, 15, 9, 0)
+        );
+    }
+
+    /// After the send time on the send day, the summary is sent the next week.
+    #[test]
+    fn after_send_time_same_day() {
+        let now = local(2022, 6, 15, 9, 30);
+        assert_eq!(
+            next_send_time(now, Weekday::Wed, 9),
+            local(2022, 6, 22, 9, 0)
+        );
+
+        // Exactly at the send time counts as passed, so it is not sent twice.
+        let now = local(2022, 6, 15, 9, 0);
+        assert_eq!(
+            next_send_time(now, Weekday::Wed, 9),
+            local(2022, 6, 22, 9, 0)
+        );
+    }
+
+    /// Send days earlier in the week than today roll over into the next week.
+    #[test]
+    fn week_rollover() {
+        // Saturday to Monday.
+        let now = local(2022, 6, 18, 12, 0);
+        assert_eq!(
+            next_send_time(now, Weekday::Mon, 9),
+            local(2022, 6, 20, 9, 0)
+        );
+
+        // Across the end of the year.
+        let now = local(2022, 12, 30, 12, 0);
+        assert_eq!(
+            next_send_time(now, Weekday::Mon, 9),
+            local(2023, 1, 2, 9, 0)
+        );
+    }
+
+    /// Later days in the same week are used as is.
+    #[test]
+    fn later_in_week() {
+        let now = local(2022, 6, 13, 12, 0);
+        assert_eq!(
+            next_send_time(now, Weekday::Fri, 17),
+            local(2022, 6, 17, 17, 0)
+        );
+    }
+
+    /// Hours past the end of the day are treated as the last hour.
+    #[test]
+    fn hour_is_clamped() {
+        let now = local(2022, 6, 15, 8, 0);
+        assert_eq!(
+            next_send_time(now, Weekday::Wed, 30),
+            local(2022, 6, 15, 23, 0)
+        );
+    }
+}
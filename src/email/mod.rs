@@ -0,0 +1,343 @@
+//! Email sending over SMTP.
+//!
+//! Emails are only sent if the `[email]` section is set in the config. Users are emailed at
+//! the RPI address of their linked RCS ID. One SMTP transport is built at startup and kept in
+//! the app data. It keeps a pool of connections open, so a batch of emails does not connect
+//! to the relay once per message.
+
+pub mod address;
+pub mod coordinator_summary;
+
+use crate::app_data::AppData;
+use crate::email::address::Email;
+use crate::env::{global_config, EmailConfig};
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use actix_web::web::block;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::{Error as SmtpError, PoolConfig};
+use lettre::{Message, SmtpTransport, Transport};
+use serde_json::Value;
+use std::fmt;
+use std::time::Duration;
+
+/// The email domain of RPI accounts.
+const RPI_EMAIL_DOMAIN: &'static str = "rpi.edu";
+
+/// How many times to try sending an email before giving up.
+const SEND_ATTEMPTS: u32 = 2;
+
+/// Get the email config, if emails are enabled.
+pub fn email_config() -> Option<EmailConfig> {
+    global_config().email.clone()
+}
+
+/// Get the email address for an RCS ID. This fails if the RCS ID would not make a valid
+/// address.
+pub fn rpi_address(rcs_id: &str) -> Result<Email, TelescopeError> {
+    Email::parse(format!("{}@{}", rcs_id.trim().to_lowercase(), RPI_EMAIL_DOMAIN).as_str())
+}
+
+/// Is an address on the suppression list? Addresses are compared case-insensitively.
+pub fn is_suppressed(config: &EmailConfig, address: &Email) -> bool {
+    config
+        .suppressed_addresses
+        .iter()
+        .any(|suppressed| suppressed.trim().eq_ignore_ascii_case(address.as_str()))
+}
+
+/// Build a mailbox from a display name and validated address.
+pub fn mailbox(name: impl Into<String>, address: &Email) -> Result<Mailbox, TelescopeError> {
+    let address = address
+        .as_str()
+        .parse()
+        .map_err(TelescopeError::email_error)?;
+    return Ok(Mailbox::new(Some(name.into()), address));
+}
+
+/// Build an email with plain text and HTML versions of the body.
+pub fn build_message(
+    config: &EmailConfig,
+    to: Mailbox,
+    subject: impl Into<String>,
+    text: String,
+    html: String,
+) -> Result<Message, TelescopeError> {
+    // Parse the sender mailbox from the config.
+    let from: Mailbox = config.from.parse().map_err(TelescopeError::email_error)?;
+
+    return Message::builder()
+        .from(from)
+        .to(to)
+        .subject(subject)
+        .multipart(MultiPart::alternative_plain_html(text, html))
+        .map_err(TelescopeError::email_error);
+}
+
+/// Render the plain text and HTML versions of an email from handlebars templates and build a
+/// message of them. `template` is a directory of templates (like `email/meeting_reminder`)
+/// with `text.hbs` and `html.hbs` files. HTML versions should use the `email/layout` partial
+/// so that emails look alike. `telescope_url` is added to the fields for links.
+pub fn templated_message(
+    config: &EmailConfig,
+    to: Mailbox,
+    subject: impl Into<String>,
+    template: &str,
+    mut fields: Value,
+) -> Result<Message, TelescopeError> {
+    fields["telescope_url"] = json!(global_config().telescope_url.trim_end_matches('/'));
+
+    let mut text = Template::new(format!("{}/text", template));
+    text.fields = fields.clone();
+    let mut html = Template::new(format!("{}/html", template));
+    html.fields = fields;
+
+    return build_message(config, to, subject, text.render()?, html.render()?);
+}
+
+/// Build the SMTP transport for the configured relay. The transport reuses up to
+/// `smtp_pool_size` connections, and can be cloned cheaply to share the pool.
+pub fn build_transport(config: &EmailConfig) -> Result<SmtpTransport, TelescopeError> {
+    let timeout = Duration::from_secs(config.smtp_timeout_seconds);
+    let pool = PoolConfig::new()
+        // A pool of zero connections could never send anything.
+        .max_size(config.smtp_pool_size.max(1))
+        .idle_timeout(timeout);
+
+    let transport = SmtpTransport::relay(config.smtp_host.as_str())?
+        .credentials(Credentials::new(
+            config.smtp_username.clone(),
+            config.smtp_password.clone(),
+        ))
+        .timeout(Some(timeout))
+        .pool_config(pool)
+        .build();
+
+    return Ok(transport);
+}
+
+/// Can a failed send be tried again? Permanent rejections (5xx responses) and client errors
+/// (a problem with the message itself) would fail again. Anything else, such as a transient
+/// rejection or a pooled connection the relay has closed, may not.
+fn is_retryable(err: &SmtpError) -> bool {
+    !err.is_permanent() && !err.is_client()
+}
+
+/// Send an email through a transport, trying up to [`SEND_ATTEMPTS`] times while the failure
+/// is one that `retryable` says may not happen again. This blocks.
+fn send_with_retry<T>(
+    transport: &T,
+    message: &Message,
+    retryable: impl Fn(&T::Error) -> bool,
+) -> Result<(), T::Error>
+where
+    T: Transport,
+    T::Error: fmt::Display,
+{
+    let mut attempt: u32 = 1;
+    loop {
+        match transport.send(message) {
+            Ok(_) => return Ok(()),
+            Err(err) if attempt < SEND_ATTEMPTS && retryable(&err) => {
+                warn!(
+                    "Could not send email (attempt {} of {}), retrying: {}",
+                    attempt, SEND_ATTEMPTS, err
+                );
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Send an email through the configured SMTP relay, using the shared connection pool. The SMTP
+/// client blocks, so the message is sent on the actix blocking thread pool. Failures that may
+/// not happen again are retried (see [`send_with_retry`]).
+pub async fn send(message: Message) -> Result<(), TelescopeError> {
+    // Get the shared transport.
+    let transport: SmtpTransport =
+        AppData::global()
+            .smtp_transport()
+            .ok_or(TelescopeError::ise(
+                "Email is not configured, or the SMTP transport could not be built.",
+            ))?;
+
+    // Send the message.
+    block(move || send_with_retry(&transport, &message, is_retryable)).await?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lettre::address::Envelope;
+    use std::cell::{Cell, RefCell};
+    use std::collections::VecDeque;
+
+    /// An email config for a relay that is never connected to.
+    fn test_config() -> EmailConfig {
+        EmailConfig {
+            smtp_host: "smtp.example.com".into(),
+            smtp_username: "telescope".into(),
+            smtp_password: "password".into(),
+            from: "Telescope <telescope@example.com>".into(),
+            suppressed_addresses: vec![" Bounced@RPI.edu ".into()],
+            smtp_pool_size: 4,
+            smtp_timeout_seconds: 30,
+        }
+    }
+
+    /// A transport is built from the config without connecting to the relay.
+    #[test]
+    fn transport_from_config() {
+        assert!(build_transport(&test_config()).is_ok());
+
+        // A pool size of zero is raised to one rather than failing.
+        let mut config: EmailConfig = test_config();
+        config.smtp_pool_size = 0;
+        assert!(build_transport(&config).is_ok());
+    }
+
+    /// RCS IDs are normalized into RPI addresses, and invalid ones are rejected.
+    #[test]
+    fn rpi_addresses() {
+        assert_eq!(rpi_address(" MontL ").unwrap().as_str(), "montl@rpi.edu");
+        assert!(rpi_address("").is_err());
+        assert!(rpi_address("mont l").is_err());
+        assert!(rpi_address("montl@gmail.com").is_err());
+    }
+
+    /// Suppressed addresses are matched ignoring case and surrounding whitespace.
+    #[test]
+    fn suppressed_addresses() {
+        let config: EmailConfig = test_config();
+        assert!(is_suppressed(
+            &config,
+            &Email::parse("bounced@rpi.edu").unwrap()
+        ));
+        assert!(!is_suppressed(
+            &config,
+            &Email::parse("montl@rpi.edu").unwrap()
+        ));
+    }
+
+    /// Validated addresses make mailboxes with the display name.
+    #[test]
+    fn mailboxes() {
+        let mailbox: Mailbox = mailbox("Leon", &rpi_address("montl").unwrap()).unwrap();
+        assert_eq!(mailbox.to_string(), "Leon <montl@rpi.edu>");
+    }
+
+    /// The ways a send through [`FlakyTransport`] can fail.
+    #[derive(Clone, Debug, Display, Eq, PartialEq)]
+    enum SendFailure {
+        /// A failure that may not happen again.
+        #[display(fmt = "connection closed")]
+        Transient,
+        /// A failure that will happen again.
+        #[display(fmt = "mailbox unavailable")]
+        Permanent,
+    }
+
+    /// A transport that fails with the given failures (one per send) and then succeeds.
+    struct FlakyTransport {
+        /// The failures left, in order.
+        failures: RefCell<VecDeque<SendFailure>>,
+        /// The number of sends.
+        sends: Cell<u32>,
+    }
+
+    impl FlakyTransport {
+        fn new(failures: Vec<SendFailure>) -> Self {
+            FlakyTransport {
+                failures: RefCell::new(failures.into()),
+                sends: Cell::new(0),
+            }
+        }
+    }
+
+    impl Transport for FlakyTransport {
+        type Ok = ();
+        type Error = SendFailure;
+
+        fn send_raw(&self, _: &Envelope, _: &[u8]) -> Result<(), SendFailure> {
+            self.sends.set(self.sends.get() + 1);
+            match self.failures.borrow_mut().pop_front() {
+                Some(failure) => Err(failure),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// A test message.
+    fn test_message() -> Message {
+        let to: Mailbox = mailbox("Leon", &rpi_address("montl").unwrap()).unwrap();
+        build_message(
+            &test_config(),
+            to,
+            "Test",
+            "Hello".into(),
+            "<p>Hello</p>".into(),
+        )
+        .unwrap()
+    }
+
+    /// Only transient failures are retried.
+    fn retry_transient(failure: &SendFailure) -> bool {
+        *failure == SendFailure::Transient
+    }
+
+    /// A message is sent once if nothing fails.
+    #[test]
+    fn sends_once() {
+        let transport = FlakyTransport::new(vec![]);
+        assert!(send_with_retry(&transport, &test_message(), retry_transient).is_ok());
+        assert_eq!(transport.sends.get(), 1);
+    }
+
+    /// A transient failure is retried, and the retry can succeed.
+    #[test]
+    fn retries_transient_failure() {
+        let transport = FlakyTransport::new(vec![SendFailure::Transient]);
+        assert!(send_with_retry(&transport, &test_message(), retry_transient).is_ok());
+        assert_eq!(transport.sends.get(), 2);
+    }
+
+    /// Sending gives up after the last attempt.
+    #[test]
+    fn gives_up_after_attempts() {
+        let transport = FlakyTransport::new(vec![SendFailure::Transient; SEND_ATTEMPTS as usize]);
+        assert_eq!(
+            send_with_retry(&transport, &test_message(), retry_transient),
+            Err(SendFailure::Transient)
+        );
+        assert_eq!(transport.sends.get(), SEND_ATTEMPTS);
+    }
+
+    /// A permanent failure is not retried.
+    #[test]
+    fn does_not_retry_permanent_failure() {
+        let transport = FlakyTransport::new(vec![SendFailure::Permanent]);
+        assert_eq!(
+            send_with_retry(&transport, &test_message(), retry_transient),
+            Err(SendFailure::Permanent)
+        );
+        assert_eq!(transport.sends.get(), 1);
+    }
+
+    /// A relay that cannot be reached fails with an SMTP error, after being retried.
+    #[actix_rt::test]
+    async fn unreachable_relay_fails() {
+        // Nothing listens on port 1.
+        let transport = SmtpTransport::builder_dangerous("127.0.0.1")
+            .port(1)
+            .timeout(Some(Duration::from_secs(5)))
+            .build();
+        let message: Message = test_message();
+
+        let result = block(move || send_with_retry(&transport, &message, is_retryable)).await;
+        let err: TelescopeError = result.unwrap_err().into();
+        assert!(matches!(err, TelescopeError::LettreSmtpError(_)));
+    }
+}
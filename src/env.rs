@@ -1,4 +1,8 @@
+use crate::api::rcos::meetings::MeetingType;
+use chrono::Weekday;
 use oauth2::{ClientId, ClientSecret};
+use serde::de::{Deserializer, IntoDeserializer};
+use serde::Deserialize;
 use std::sync::Arc;
 use std::{collections::HashMap, env, path::PathBuf};
 use std::{fs::File, io::Read, process::exit};
@@ -15,6 +19,22 @@ pub struct GithubOauthConfig {
     pub client_secret: ClientSecret,
 }
 
+/// A GitHub App installed on the RCOS organization, used for server-to-server queries that
+/// should not depend on the signed in user's access (e.g. organization data).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GithubAppConfig {
+    /// The ID of the GitHub App.
+    pub app_id: u64,
+    /// The ID of the app's installation on the RCOS organization.
+    pub installation_id: u64,
+    /// The file containing the app's private key (PEM encoded).
+    pub private_key_file: String,
+    /// The secret GitHub signs the app's webhook deliveries with. Webhooks are refused unless
+    /// this is set. This is secret, so it is not logged with the rest of the config.
+    #[serde(default, skip_serializing)]
+    pub webhook_secret: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DiscordConfig {
     /// The Discord application client id.
@@ -29,6 +49,11 @@ pub struct DiscordConfig {
 
     /// The RCOS Discord Guild ID.
     pub rcos_guild_id: String,
+
+    /// The application's public key (hex), used to check the signatures of interactions sent
+    /// to the interactions endpoint. The endpoint is disabled unless this is set.
+    #[serde(default)]
+    pub public_key: Option<String>,
 }
 
 impl DiscordConfig {
@@ -41,6 +66,232 @@ impl DiscordConfig {
     }
 }
 
+/// SMTP credentials and settings used to send emails.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// The hostname of the SMTP relay to send emails through.
+    pub smtp_host: String,
+
+    /// The username to authenticate with the SMTP relay.
+    pub smtp_username: String,
+
+    /// The password to authenticate with the SMTP relay. This is secret, so it is not logged
+    /// with the rest of the config.
+    #[serde(skip_serializing)]
+    pub smtp_password: String,
+
+    /// The mailbox that emails are sent from (e.g. `Telescope <telescope@rcos.io>`).
+    pub from: String,
+
+    /// Addresses that should never be emailed (e.g. because mail to them has bounced).
+    #[serde(default)]
+    pub suppressed_addresses: Vec<String>,
+
+    /// The most SMTP connections to keep open (and reuse) at once.
+    #[serde(default = "EmailConfig::default_pool_size")]
+    pub smtp_pool_size: u32,
+
+    /// How long (in seconds) an SMTP connection or command can take before it fails. Unused
+    /// pooled connections are also closed after this long.
+    #[serde(default = "EmailConfig::default_timeout_seconds")]
+    pub smtp_timeout_seconds: u64,
+}
+
+impl EmailConfig {
+    /// The default [`EmailConfig::smtp_pool_size`].
+    fn default_pool_size() -> u32 {
+        4
+    }
+
+    /// The default [`EmailConfig::smtp_timeout_seconds`].
+    fn default_timeout_seconds() -> u64 {
+        30
+    }
+}
+
+/// Settings for the weekly summary email sent to coordinators.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoordinatorSummaryConfig {
+    /// Should the summary be sent? This is off unless enabled in the config.
+    pub enabled: bool,
+
+    /// The day of the week to send the summary on.
+    pub weekday: Weekday,
+
+    /// The hour (in server local time, 0 to 23) to send the summary at.
+    pub hour: u32,
+}
+
+impl Default for CoordinatorSummaryConfig {
+    fn default() -> Self {
+        CoordinatorSummaryConfig {
+            enabled: false,
+            weekday: Weekday::Mon,
+            hour: 9,
+        }
+    }
+}
+
+/// Settings for meeting reminders sent to hosts (and optionally attendees) before meetings
+/// start.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeetingRemindersConfig {
+    /// Should reminders be sent? This is off unless enabled in the config.
+    pub enabled: bool,
+
+    /// How often (in minutes) to check for meetings that need reminders.
+    pub check_interval_minutes: u32,
+
+    /// How many minutes before a meeting starts to send its reminder.
+    pub lead_minutes: u32,
+
+    /// Lead times (in minutes) for specific meeting types. Meeting types not listed here use
+    /// `lead_minutes`.
+    #[serde(deserialize_with = "deserialize_meeting_type_map")]
+    pub lead_minutes_by_type: HashMap<MeetingType, u32>,
+
+    /// Should users registered for a meeting be reminded as well as the host?
+    pub remind_attendees: bool,
+
+    /// Should reminders be emailed? This requires the `[email]` section.
+    pub email: bool,
+
+    /// Should reminders be sent as Discord direct messages?
+    pub discord: bool,
+}
+
+/// Deserialize a map keyed by meeting type. TOML tables only have string keys, which the
+/// TOML deserializer cannot turn into enums directly, so the keys are read as strings first.
+fn deserialize_meeting_type_map<'de, D, V>(
+    deserializer: D,
+) -> Result<HashMap<MeetingType, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    HashMap::<String, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, value)| {
+            let meeting_type = MeetingType::deserialize(name.into_deserializer())?;
+            Ok((meeting_type, value))
+        })
+        .collect()
+}
+
+impl MeetingRemindersConfig {
+    /// Get how many minutes before a meeting of the given type to send its reminder.
+    pub fn lead_minutes_for(&self, meeting_type: MeetingType) -> u32 {
+        self.lead_minutes_by_type
+            .get(&meeting_type)
+            .copied()
+            .unwrap_or(self.lead_minutes)
+    }
+
+    /// Get the longest lead time of any meeting type, in minutes.
+    pub fn max_lead_minutes(&self) -> u32 {
+        self.lead_minutes_by_type
+            .values()
+            .copied()
+            .fold(self.lead_minutes, u32::max)
+    }
+}
+
+impl Default for MeetingRemindersConfig {
+    fn default() -> Self {
+        MeetingRemindersConfig {
+            enabled: false,
+            check_interval_minutes: 5,
+            lead_minutes: 60,
+            lead_minutes_by_type: HashMap::new(),
+            remind_attendees: false,
+            email: true,
+            discord: false,
+        }
+    }
+}
+
+/// Settings for redirecting plain HTTP requests to HTTPS. This is for deployments that are
+/// not behind a proxy that already does so.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpsRedirectConfig {
+    /// Should HTTP requests be redirected to HTTPS? This is off unless enabled in the config.
+    pub enabled: bool,
+
+    /// IP addresses of reverse proxies whose `X-Forwarded-Proto` header is trusted. Requests
+    /// from any other address are judged by their own scheme.
+    pub trusted_proxies: Vec<String>,
+}
+
+/// Limits on the meeting creation and edit forms. Lengths of the free text fields are in
+/// unicode scalar values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeetingLimitsConfig {
+    /// The maximum length of a meeting title.
+    pub max_title_length: usize,
+
+    /// The maximum length of a meeting description.
+    pub max_description_length: usize,
+
+    /// The maximum length of a meeting location.
+    pub max_location_length: usize,
+
+    /// The maximum length of the name of an external (non-RCOS) meeting host.
+    pub max_external_host_name_length: usize,
+
+    /// How many days into the future meetings can be scheduled. This catches typos in the
+    /// year, and is checked separately from the semester bounds.
+    pub max_scheduling_horizon_days: u32,
+}
+
+impl Default for MeetingLimitsConfig {
+    fn default() -> Self {
+        MeetingLimitsConfig {
+            max_title_length: 200,
+            max_description_length: 20_000,
+            max_location_length: 200,
+            max_external_host_name_length: 100,
+            // About 18 months.
+            max_scheduling_horizon_days: 548,
+        }
+    }
+}
+
+/// A filter for terms that should not appear in public meeting titles and descriptions. The
+/// filter is off unless this is in the config.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeywordFilterConfig {
+    /// The filtered terms. Matching ignores case and the spacing between words, and a term
+    /// only matches whole words.
+    pub words: Vec<String>,
+
+    /// A file with more filtered terms, one per line. Blank lines and lines starting with `#`
+    /// are ignored. This is read into `words` at startup.
+    pub words_file: Option<String>,
+
+    /// Should flagged submissions be rejected outright? Otherwise the user is warned and can
+    /// submit again to publish anyway.
+    pub block: bool,
+
+    /// Should coordinators, faculty advisors, and sysadmins skip the filter?
+    pub skip_coordinators: bool,
+}
+
+impl Default for KeywordFilterConfig {
+    fn default() -> Self {
+        KeywordFilterConfig {
+            words: Vec::new(),
+            words_file: None,
+            block: false,
+            skip_coordinators: true,
+        }
+    }
+}
+
 /// The config of the server instance.
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 struct TelescopeConfig {
@@ -51,12 +302,24 @@ struct TelescopeConfig {
     /// GitHub OAuth application credentials.
     github_credentials: Option<GithubOauthConfig>,
 
+    /// GitHub App used for server-to-server GitHub queries.
+    github_app: Option<GithubAppConfig>,
+
     /// Discord application config and credentials.
     discord_config: Option<DiscordConfig>,
 
     /// The URL of the RCOS central API (in the OpenAPI Spec via RCOS-data).
     api_url: Option<String>,
 
+    /// The host (IP address or name) the web server binds to.
+    bind_host: Option<String>,
+
+    /// The port the web server binds to.
+    bind_port: Option<u16>,
+
+    /// How many seconds to wait for in-flight requests to finish when shutting down.
+    shutdown_timeout_secs: Option<u64>,
+
     /// The JWT secret used to authenticate with the central API.
     jwt_secret: Option<String>,
 
@@ -67,6 +330,90 @@ struct TelescopeConfig {
     /// The URL that Telescope is running at. This is used in Discord embeds
     /// and the Open Graph Protocol meta tags. Should not end with a slash.
     telescope_url: Option<String>,
+
+    /// Length limits on meeting form fields.
+    meeting_limits: Option<MeetingLimitsConfig>,
+
+    /// The fraction of requests (between 0 and 1) to log verbosely.
+    request_log_sample_rate: Option<f64>,
+
+    /// SMTP settings. Emails are not sent if this is not set.
+    email: Option<EmailConfig>,
+
+    /// Weekly coordinator summary email settings.
+    coordinator_summary: Option<CoordinatorSummaryConfig>,
+
+    /// Meeting reminder settings.
+    meeting_reminders: Option<MeetingRemindersConfig>,
+
+    /// HTTP to HTTPS redirect settings.
+    https_redirect: Option<HttpsRedirectConfig>,
+
+    /// Keys used to encrypt identity cookies, newest first.
+    cookie_keys: Option<Vec<String>>,
+
+    /// Should recording URLs on meetings that have not ended yet be rejected outright
+    /// (rather than just warned about)?
+    block_future_recording_urls: Option<bool>,
+
+    /// Domains recording URLs must be on (subdomains included). Any domain is allowed if this
+    /// is not set.
+    recording_url_domains: Option<Vec<String>>,
+
+    /// Should the meeting forms warn when a published meeting in the same semester already
+    /// has the same title?
+    warn_duplicate_meeting_titles: Option<bool>,
+
+    /// Should duplicate meeting titles be rejected outright (rather than just warned about)?
+    block_duplicate_meeting_titles: Option<bool>,
+
+    /// How many hours after a meeting ends its host can still edit it. Coordinators can always
+    /// edit meetings. There is no limit if this is not set.
+    meeting_edit_grace_period_hours: Option<u32>,
+
+    /// The file to save the site-wide announcement to.
+    announcement_file: Option<String>,
+
+    /// How many seconds a browser's clock can differ from the server's before meeting forms
+    /// warn the user that their clock may be wrong.
+    clock_skew_warning_seconds: Option<u32>,
+
+    /// Origins allowed to make cross-origin requests to the JSON API (`/api`).
+    api_allowed_origins: Option<Vec<String>>,
+
+    /// How many times to retry refreshing an identity provider token after a transient
+    /// (network or server) error before giving up for the current request.
+    identity_refresh_retries: Option<u32>,
+
+    /// How many seconds before an identity provider access token expires it is refreshed.
+    identity_refresh_skew_seconds: Option<u32>,
+
+    /// The file to save revoked login session IDs to.
+    revoked_sessions_file: Option<String>,
+
+    /// The directory static files are served from (under `/static`).
+    static_dir: Option<String>,
+
+    /// Should requests for directories under `/static` list their contents?
+    static_files_listing: Option<bool>,
+
+    /// Filter for terms in public meeting titles and descriptions.
+    keyword_filter: Option<KeywordFilterConfig>,
+
+    /// Should GraphQL responses that cannot be deserialized be logged (redacted) and included
+    /// in errors? This is for development only.
+    debug_graphql_responses: Option<bool>,
+
+    /// How many times to try a request to the RCOS API that fails with a connection error or
+    /// a 5xx response (including the first try).
+    rcos_api_max_attempts: Option<u32>,
+
+    /// The delay in milliseconds before the first retry of a request to the RCOS API. This
+    /// doubles for each retry after that (plus some random jitter).
+    rcos_api_retry_base_ms: Option<u64>,
+
+    /// How long (in seconds) a request to the RCOS or GitHub API can take before it fails.
+    upstream_timeout_seconds: Option<u64>,
 }
 
 /// A concrete config found by searching the specified profile and parents
@@ -80,14 +427,75 @@ pub struct ConcreteConfig {
     log_level: String,
     /// The GitHub OAuth Application Credentials.
     pub github_credentials: GithubOauthConfig,
+    /// The GitHub App used for server-to-server queries, if there is one.
+    pub github_app: Option<GithubAppConfig>,
     /// The Discord Config and Credentials.
     pub discord_config: DiscordConfig,
     /// The url of the RCOS API that telescope will read and write to.
     pub api_url: String,
+    /// The host the web server binds to.
+    pub bind_host: String,
+    /// The port the web server binds to.
+    pub bind_port: u16,
+    /// How many seconds to wait for in-flight requests to finish when shutting down.
+    pub shutdown_timeout_secs: u64,
     /// The domain that telescope is available at. Should not end with a slash.
     pub telescope_url: String,
     /// The JWT secret used to authenticate with the central API.
     pub jwt_secret: String,
+    /// Length limits on meeting form fields.
+    pub meeting_limits: MeetingLimitsConfig,
+    /// The fraction of requests (between 0 and 1) to log verbosely.
+    pub request_log_sample_rate: f64,
+    /// SMTP settings, if emails are enabled.
+    pub email: Option<EmailConfig>,
+    /// Weekly coordinator summary email settings.
+    pub coordinator_summary: CoordinatorSummaryConfig,
+    /// Meeting reminder settings.
+    pub meeting_reminders: MeetingRemindersConfig,
+    /// HTTP to HTTPS redirect settings.
+    pub https_redirect: HttpsRedirectConfig,
+    /// Keys used to encrypt identity cookies, newest first. These are secret, so they are
+    /// not logged with the rest of the config.
+    #[serde(skip_serializing)]
+    pub cookie_keys: Vec<String>,
+    /// Should recording URLs on meetings that have not ended yet be rejected outright?
+    pub block_future_recording_urls: bool,
+    /// Domains recording URLs must be on (subdomains included). Empty to allow any domain.
+    pub recording_url_domains: Vec<String>,
+    /// Should the meeting forms warn about duplicate meeting titles in a semester?
+    pub warn_duplicate_meeting_titles: bool,
+    /// Should duplicate meeting titles in a semester be rejected outright?
+    pub block_duplicate_meeting_titles: bool,
+    /// How many hours after a meeting ends non-coordinators can still edit it, if limited.
+    pub meeting_edit_grace_period_hours: Option<u32>,
+    /// The file to save the site-wide announcement to.
+    pub announcement_file: String,
+    /// How many seconds a browser's clock can differ from the server's before meeting forms
+    /// show a warning.
+    pub clock_skew_warning_seconds: u32,
+    /// Origins allowed to make cross-origin requests to the JSON API (`/api`).
+    pub api_allowed_origins: Vec<String>,
+    /// How many times to retry refreshing an identity provider token after a transient error.
+    pub identity_refresh_retries: u32,
+    /// How many seconds before an identity provider access token expires it is refreshed.
+    pub identity_refresh_skew_seconds: u32,
+    /// The file to save revoked login session IDs to.
+    pub revoked_sessions_file: String,
+    /// The directory static files are served from.
+    pub static_dir: String,
+    /// Should requests for static directories list their contents?
+    pub static_files_listing: bool,
+    /// Filter for terms in public meeting titles and descriptions, if enabled.
+    pub keyword_filter: Option<KeywordFilterConfig>,
+    /// Should GraphQL responses that cannot be deserialized be logged and included in errors?
+    pub debug_graphql_responses: bool,
+    /// How many times to try a request to the RCOS API with a transient failure.
+    pub rcos_api_max_attempts: u32,
+    /// The delay in milliseconds before the first retry of a request to the RCOS API.
+    pub rcos_api_retry_base_ms: u64,
+    /// How long (in seconds) a request to the RCOS or GitHub API can take before it fails.
+    pub upstream_timeout_seconds: u64,
 }
 
 impl TelescopeConfig {
@@ -120,18 +528,98 @@ impl TelescopeConfig {
             github_credentials: self
                 .reverse_lookup(profile_slice, |c| c.github_credentials.clone())
                 .expect("Could not resolve GitHub OAuth credentials."),
+            github_app: self.reverse_lookup(profile_slice, |c| c.github_app.clone()),
             discord_config: self
                 .reverse_lookup(profile_slice, |c| c.discord_config.clone())
                 .expect("Could not resolve Discord credentials"),
             api_url: self
                 .reverse_lookup(profile_slice, |c| c.api_url.clone())
                 .expect("Could not resolve RCOS central API URL."),
+            bind_host: self
+                .reverse_lookup(profile_slice, |c| c.bind_host.clone())
+                .unwrap_or("0.0.0.0".into()),
+            bind_port: self
+                .reverse_lookup(profile_slice, |c| c.bind_port)
+                .unwrap_or(80),
+            shutdown_timeout_secs: self
+                .reverse_lookup(profile_slice, |c| c.shutdown_timeout_secs)
+                .unwrap_or(30),
             jwt_secret: self
                 .reverse_lookup(profile_slice, |c| c.jwt_secret.clone())
                 .expect("Could not resolve JWT secret."),
             telescope_url: self
                 .reverse_lookup(profile_slice, |c| c.telescope_url.clone())
                 .expect("Could not resolve Telescope URl."),
+            meeting_limits: self
+                .reverse_lookup(profile_slice, |c| c.meeting_limits.clone())
+                .unwrap_or_default(),
+            request_log_sample_rate: self
+                .reverse_lookup(profile_slice, |c| c.request_log_sample_rate)
+                .unwrap_or(0.0),
+            email: self.reverse_lookup(profile_slice, |c| c.email.clone()),
+            coordinator_summary: self
+                .reverse_lookup(profile_slice, |c| c.coordinator_summary.clone())
+                .unwrap_or_default(),
+            meeting_reminders: self
+                .reverse_lookup(profile_slice, |c| c.meeting_reminders.clone())
+                .unwrap_or_default(),
+            https_redirect: self
+                .reverse_lookup(profile_slice, |c| c.https_redirect.clone())
+                .unwrap_or_default(),
+            cookie_keys: self
+                .reverse_lookup(profile_slice, |c| c.cookie_keys.clone())
+                .unwrap_or_default(),
+            block_future_recording_urls: self
+                .reverse_lookup(profile_slice, |c| c.block_future_recording_urls)
+                .unwrap_or(false),
+            recording_url_domains: self
+                .reverse_lookup(profile_slice, |c| c.recording_url_domains.clone())
+                .unwrap_or_default(),
+            warn_duplicate_meeting_titles: self
+                .reverse_lookup(profile_slice, |c| c.warn_duplicate_meeting_titles)
+                .unwrap_or(true),
+            block_duplicate_meeting_titles: self
+                .reverse_lookup(profile_slice, |c| c.block_duplicate_meeting_titles)
+                .unwrap_or(false),
+            meeting_edit_grace_period_hours: self
+                .reverse_lookup(profile_slice, |c| c.meeting_edit_grace_period_hours),
+            announcement_file: self
+                .reverse_lookup(profile_slice, |c| c.announcement_file.clone())
+                .unwrap_or("announcement.json".into()),
+            clock_skew_warning_seconds: self
+                .reverse_lookup(profile_slice, |c| c.clock_skew_warning_seconds)
+                .unwrap_or(300),
+            api_allowed_origins: self
+                .reverse_lookup(profile_slice, |c| c.api_allowed_origins.clone())
+                .unwrap_or_default(),
+            identity_refresh_retries: self
+                .reverse_lookup(profile_slice, |c| c.identity_refresh_retries)
+                .unwrap_or(2),
+            identity_refresh_skew_seconds: self
+                .reverse_lookup(profile_slice, |c| c.identity_refresh_skew_seconds)
+                .unwrap_or(60),
+            revoked_sessions_file: self
+                .reverse_lookup(profile_slice, |c| c.revoked_sessions_file.clone())
+                .unwrap_or("revoked_sessions.json".into()),
+            static_dir: self
+                .reverse_lookup(profile_slice, |c| c.static_dir.clone())
+                .unwrap_or("static".into()),
+            static_files_listing: self
+                .reverse_lookup(profile_slice, |c| c.static_files_listing)
+                .unwrap_or(false),
+            keyword_filter: self.reverse_lookup(profile_slice, |c| c.keyword_filter.clone()),
+            debug_graphql_responses: self
+                .reverse_lookup(profile_slice, |c| c.debug_graphql_responses)
+                .unwrap_or(false),
+            rcos_api_max_attempts: self
+                .reverse_lookup(profile_slice, |c| c.rcos_api_max_attempts)
+                .unwrap_or(3),
+            rcos_api_retry_base_ms: self
+                .reverse_lookup(profile_slice, |c| c.rcos_api_retry_base_ms)
+                .unwrap_or(200),
+            upstream_timeout_seconds: self
+                .reverse_lookup(profile_slice, |c| c.upstream_timeout_seconds)
+                .unwrap_or(30),
         }
     }
 
@@ -176,6 +664,7 @@ impl TelescopeConfig {
 /// Stores the configuration of the telescope server. An instance of this is created and stored in
 /// a lazy static before the server is launched.
 #[derive(Debug, Serialize, StructOpt)]
+#[cfg_attr(test, allow(dead_code))]
 #[structopt(about = "The RCOS webapp", rename_all = "screaming-snake")]
 struct CommandLine {
     /// The config file for this Telescope instance. See config_example.toml
@@ -188,11 +677,59 @@ struct CommandLine {
     /// 'dev.local'
     #[structopt(short = "p", long = "profile", env)]
     profile: Option<String>,
+    /// Override the URL of the RCOS central API from the config file.
+    ///
+    /// This is useful for pointing Telescope at a local or mock GraphQL
+    /// server without editing the config file.
+    #[structopt(long = "api-url", env)]
+    api_url: Option<String>,
+    /// Override the host (IP address or name) the web server binds to.
+    #[structopt(long = "bind-host", env)]
+    bind_host: Option<String>,
+    /// Override the port the web server binds to.
+    #[structopt(long = "bind-port", env)]
+    bind_port: Option<u16>,
+    /// A file containing the keys used to encrypt identity cookies, one per
+    /// line and newest first. This overrides the keys in the config file.
+    #[structopt(long = "cookie-keys-file", env)]
+    cookie_keys_file: Option<PathBuf>,
+    /// Override how many times requests to the RCOS central API are tried
+    /// when they fail with a connection error or a 5xx response.
+    #[structopt(long = "rcos-api-max-attempts", env)]
+    rcos_api_max_attempts: Option<u32>,
+    /// Override the delay (in milliseconds) before the first retry of a
+    /// request to the RCOS central API.
+    #[structopt(long = "rcos-api-retry-base-ms", env)]
+    rcos_api_retry_base_ms: Option<u64>,
+    /// Override how often (in minutes) to check for meetings that need
+    /// reminders.
+    #[structopt(long = "reminder-check-interval-minutes", env)]
+    reminder_check_interval_minutes: Option<u32>,
+    /// Override how many minutes before a meeting starts to send its
+    /// reminder. Lead times set for specific meeting types in the config
+    /// file still apply.
+    #[structopt(long = "reminder-lead-minutes", env)]
+    reminder_lead_minutes: Option<u32>,
 }
 
 lazy_static! {
     /// Global web server configuration.
-    pub static ref CONFIG: Arc<ConcreteConfig> = Arc::new(cli());
+    pub static ref CONFIG: Arc<ConcreteConfig> = Arc::new(load());
+}
+
+/// Load the configuration from the command line and the config file.
+#[cfg(not(test))]
+fn load() -> ConcreteConfig {
+    cli()
+}
+
+/// Unit tests cannot be given Telescope's command line arguments, so they use the default
+/// profile of the example config instead.
+#[cfg(test)]
+fn load() -> ConcreteConfig {
+    toml::from_str::<TelescopeConfig>(include_str!("../config_example.toml"))
+        .expect("Could not parse config_example.toml")
+        .make_concrete(Vec::new())
 }
 
 /// After the global configuration is initialized, log it as info.
@@ -207,6 +744,27 @@ pub fn init() {
     trace!("Config: \n{}", serde_json::to_string_pretty(cfg).unwrap());
 }
 
+impl ConcreteConfig {
+    /// The address the web server binds to, for logging. IPv6 hosts are put in brackets.
+    pub fn bind_address(&self) -> String {
+        if self.bind_host.contains(':') {
+            return format!("[{}]:{}", self.bind_host, self.bind_port);
+        }
+        return format!("{}:{}", self.bind_host, self.bind_port);
+    }
+}
+
+/// Override the configured bind host and port with the ones from the command line (or
+/// environment), if there are any.
+fn override_bind_address(config: &mut ConcreteConfig, host: Option<String>, port: Option<u16>) {
+    if let Some(host) = host {
+        config.bind_host = host;
+    }
+    if let Some(port) = port {
+        config.bind_port = port;
+    }
+}
+
 /// Get the global configuration.
 pub fn global_config() -> Arc<ConcreteConfig> {
     CONFIG.clone()
@@ -215,6 +773,7 @@ pub fn global_config() -> Arc<ConcreteConfig> {
 /// Digest and handle arguments from the command line. Read arguments from environment
 /// variables where necessary. Construct and return the configuration specified.
 /// Initializes logging and returns config.
+#[cfg_attr(test, allow(dead_code))]
 fn cli() -> ConcreteConfig {
     // Set env vars from a ".env" file if available.
     dotenv::dotenv().ok();
@@ -259,5 +818,172 @@ fn cli() -> ConcreteConfig {
         .map(|s| s.split(".").map(|p| p.to_string()).collect())
         .unwrap_or(Vec::new());
 
-    return parsed.make_concrete(profile_path);
+    // Resolve the concrete config from the profile.
+    let mut concrete: ConcreteConfig = parsed.make_concrete(profile_path);
+
+    // Apply the API URL override if there is one.
+    if let Some(api_url) = commandline.api_url {
+        concrete.api_url = api_url;
+    }
+
+    // Apply the bind address overrides if there are any.
+    override_bind_address(&mut concrete, commandline.bind_host, commandline.bind_port);
+
+    // Apply the RCOS API retry policy overrides if there are any.
+    if let Some(max_attempts) = commandline.rcos_api_max_attempts {
+        concrete.rcos_api_max_attempts = max_attempts;
+    }
+    if let Some(base_ms) = commandline.rcos_api_retry_base_ms {
+        concrete.rcos_api_retry_base_ms = base_ms;
+    }
+
+    // Apply the meeting reminder overrides if there are any.
+    if let Some(minutes) = commandline.reminder_check_interval_minutes {
+        concrete.meeting_reminders.check_interval_minutes = minutes;
+    }
+    if let Some(minutes) = commandline.reminder_lead_minutes {
+        concrete.meeting_reminders.lead_minutes = minutes;
+    }
+
+    // Read the cookie keys from the keys file if there is one.
+    if let Some(keys_file) = commandline.cookie_keys_file {
+        let keys: String = std::fs::read_to_string(&keys_file)
+            .map_err(|e| {
+                eprintln!(
+                    "Could not read cookie keys file at {}: {}",
+                    keys_file.display(),
+                    e
+                );
+                e
+            })
+            .unwrap();
+
+        // Use each non-empty line as a key.
+        concrete.cookie_keys = keys
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+
+    // Read the filtered keywords from the word list file if there is one.
+    if let Some(filter) = concrete.keyword_filter.as_mut() {
+        if let Some(words_file) = filter.words_file.as_ref() {
+            let words: String = std::fs::read_to_string(words_file)
+                .map_err(|e| {
+                    eprintln!(
+                        "Could not read keyword filter file at {}: {}",
+                        words_file, e
+                    );
+                    e
+                })
+                .unwrap();
+
+            // Use each non-empty line that is not a comment as a term.
+            filter.words.extend(
+                words
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+    }
+
+    return concrete;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example config file.
+    const EXAMPLE_CONFIG: &'static str = include_str!("../config_example.toml");
+
+    /// Resolve a config file with the given profile.
+    fn resolve(config: &str, profile: &[&str]) -> ConcreteConfig {
+        toml::from_str::<TelescopeConfig>(config)
+            .unwrap()
+            .make_concrete(profile.iter().map(|p| p.to_string()).collect())
+    }
+
+    /// The example config with a bind host and port set at the top level.
+    fn configured_example(host: &str, port: u16) -> String {
+        EXAMPLE_CONFIG
+            .replace(
+                "# bind_host = \"0.0.0.0\"",
+                format!("bind_host = \"{}\"", host).as_str(),
+            )
+            .replace("# bind_port = 80", format!("bind_port = {}", port).as_str())
+    }
+
+    /// Without a configured address, the server binds to port 80 on every interface.
+    #[test]
+    fn default_bind_address() {
+        let config: ConcreteConfig = resolve(EXAMPLE_CONFIG, &[]);
+        assert_eq!(config.bind_host, "0.0.0.0");
+        assert_eq!(config.bind_port, 80);
+        assert_eq!(config.bind_address(), "0.0.0.0:80");
+    }
+
+    /// A configured address is used, including in profiles that do not set one.
+    #[test]
+    fn configured_bind_address() {
+        let example: String = configured_example("127.0.0.1", 8080);
+        let config: ConcreteConfig = resolve(example.as_str(), &[]);
+        assert_eq!(config.bind_address(), "127.0.0.1:8080");
+
+        let config: ConcreteConfig = resolve(example.as_str(), &["dev"]);
+        assert_eq!(config.bind_address(), "127.0.0.1:8080");
+    }
+
+    /// IPv6 hosts are put in brackets.
+    #[test]
+    fn ipv6_bind_address() {
+        let example: String = configured_example("::1", 8080);
+        let config: ConcreteConfig = resolve(example.as_str(), &[]);
+        assert_eq!(config.bind_address(), "[::1]:8080");
+    }
+
+    /// The command line overrides the configured address, one part at a time.
+    #[test]
+    fn command_line_bind_address() {
+        let commandline: CommandLine =
+            CommandLine::from_iter_safe(&["telescope", "--bind-port", "8081"]).unwrap();
+        let mut config: ConcreteConfig = resolve(EXAMPLE_CONFIG, &[]);
+        override_bind_address(&mut config, commandline.bind_host, commandline.bind_port);
+        assert_eq!(config.bind_address(), "0.0.0.0:8081");
+
+        let commandline: CommandLine = CommandLine::from_iter_safe(&[
+            "telescope",
+            "--bind-host",
+            "127.0.0.1",
+            "--bind-port",
+            "8082",
+        ])
+        .unwrap();
+        override_bind_address(&mut config, commandline.bind_host, commandline.bind_port);
+        assert_eq!(config.bind_address(), "127.0.0.1:8082");
+    }
+
+    /// Reminder lead times are read for the meeting types listed in the config.
+    #[test]
+    fn reminder_lead_minutes_by_type() {
+        let config: ConcreteConfig = resolve(EXAMPLE_CONFIG, &[]);
+        let reminders: &MeetingRemindersConfig = &config.meeting_reminders;
+        assert_eq!(reminders.lead_minutes_for(MeetingType::LargeGroup), 30);
+        assert_eq!(reminders.lead_minutes_for(MeetingType::BonusSession), 1440);
+        assert_eq!(
+            reminders.lead_minutes_for(MeetingType::SmallGroup),
+            reminders.lead_minutes
+        );
+    }
+
+    /// Ports that are not valid are refused.
+    #[test]
+    fn invalid_bind_port() {
+        let result = CommandLine::from_iter_safe(&["telescope", "--bind-port", "70000"]);
+        assert!(result.is_err());
+    }
 }
@@ -1,23 +1,55 @@
 //! Error handling.
 
 use crate::templates::page::Page;
-use crate::templates::{jumbotron, Template};
+use crate::templates::{is_partial_request, jumbotron, Template};
+use crate::web::middlewares::request_id::request_id;
 use actix_web::dev::HttpResponseBuilder;
-use actix_web::error::Error as ActixError;
+use actix_web::error::{Error as ActixError, JsonPayloadError};
 use actix_web::http::header::CONTENT_TYPE;
 use actix_web::http::StatusCode;
 use actix_web::rt::blocking::BlockingError;
 use actix_web::{HttpRequest, HttpResponse, ResponseError};
 use graphql_client::Error as GraphQlError;
 use handlebars::RenderError;
+use lettre::transport::smtp::Error as SmtpError;
 use reqwest::Error as ReqwestError;
+use reqwest::StatusCode as ReqwestStatusCode;
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 /// Custom MIME Type for telescope errors. Should only be used internally
 /// as a signal value.
 pub const TELESCOPE_ERROR_MIME: &'static str = "application/prs.telescope.error+json";
 
+/// The log target of structured error records (see [`TelescopeError::log_structured`]). Filter
+/// on this target to send only the JSON records to a log aggregator.
+pub const STRUCTURED_LOG_TARGET: &'static str = "telescope::errors";
+
+/// How long (in seconds) clients are asked to wait before retrying when an upstream API is
+/// unavailable.
+const UNAVAILABLE_RETRY_AFTER_SECONDS: u64 = 30;
+
+/// The error body sent to clients that ask for JSON. Unlike the internal serialization of
+/// [`TelescopeError`] (sent with [`TELESCOPE_ERROR_MIME`]), this is a public format and should
+/// stay the same when the error enum changes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublicErrorBody {
+    /// The error.
+    pub error: PublicError,
+}
+
+/// The fields of a public JSON error.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublicError {
+    /// A short, stable, machine readable code for the kind of error (e.g. `"not_found"`).
+    pub code: String,
+    /// The HTTP status code.
+    pub status: u16,
+    /// A human readable description of the error. Internal details are left out.
+    pub message: String,
+}
+
 /// All major errors that can occur while responding to a request.
 #[derive(Debug, From, Error, Display, Serialize, Deserialize)]
 pub enum TelescopeError {
@@ -119,6 +151,18 @@ pub enum TelescopeError {
         errors: Vec<GraphQlError>,
     },
 
+    #[error(ignore)]
+    #[display(fmt = "{} response did not match schema: {}", platform, message)]
+    /// A GraphQL API responded successfully, but the response did not have the shape the query
+    /// expected (for example a field was renamed in the API schema). This is different from a
+    /// query that legitimately found nothing, and should report as an internal server error.
+    SchemaMismatch {
+        /// The API platform.
+        platform: String,
+        /// What was wrong with the response.
+        message: String,
+    },
+
     #[error(ignore)]
     #[display(fmt = "Invalid form submission")]
     /// The user submitted invalid data to a form. This should be reported as a
@@ -131,10 +175,92 @@ pub enum TelescopeError {
     /// authentication. Report as unauthorized and direct them to try again.
     NotAuthenticated,
 
-    #[display(fmt = "Authenticated Request Forbidden")]
+    #[display(fmt = "Authenticated identity has no RCOS account")]
+    /// The user is signed in with a valid identity, but there is no RCOS account linked to it.
+    /// This happens when an account is deleted while the user still has an identity cookie.
+    /// This should report as forbidden and direct the user to register again.
+    AccountNotFound,
+
+    #[display(fmt = "Authenticated Request Forbidden: {}", resource)]
     /// An authenticated user tried to access a resource that they do not have
-    /// sufficient permissions to access.
-    Forbidden,
+    /// sufficient permissions to access. This should report as forbidden.
+    Forbidden {
+        /// A short description of the resource, used in the middle of a sentence
+        /// (e.g. "this meeting").
+        resource: String,
+    },
+
+    #[display(fmt = "Forbidden - {}: {}", header, message)]
+    /// An authenticated user tried to do something that they are not allowed to do,
+    /// for a reason that should be explained to them. This should report as forbidden.
+    ForbiddenAction {
+        /// The header of the jumbotron to be displayed.
+        header: String,
+        /// The message explaining why the action is not allowed.
+        message: String,
+    },
+
+    #[display(fmt = "{} unavailable: {}", platform, message)]
+    /// An upstream API could not be reached or reported that it is temporarily
+    /// unavailable (connection failure, timeout, or a 502/503/504 response).
+    /// This should report as service unavailable, since trying again later
+    /// may succeed.
+    UpstreamUnavailable {
+        /// The API platform
+        platform: String,
+        /// A description of the failure.
+        message: String,
+    },
+
+    #[display(fmt = "{} rate limited", platform)]
+    /// An upstream API is rate limiting Telescope (a 429 response, or a 403 with a
+    /// `Retry-After` header for GitHub's secondary rate limits). This should report as too
+    /// many requests, and tell the user when to try again if the API said.
+    RateLimited {
+        /// The API platform
+        platform: String,
+        /// How long the API asked us to wait, if it said.
+        retry_after: Option<Duration>,
+    },
+
+    #[display(fmt = "{} timed out after {:?}", platform, timeout)]
+    /// An upstream API did not respond within the request timeout. This should report as a
+    /// gateway timeout, since the API may be hung.
+    UpstreamTimeout {
+        /// The API platform
+        platform: String,
+        /// How long the request was given.
+        timeout: Duration,
+    },
+
+    #[display(fmt = "Timed out waiting for another {} refresh", platform)]
+    /// Another request was refreshing the same identity token and did not finish in time.
+    /// This should report as service unavailable, since the refresh may be done by the
+    /// next request.
+    RefreshContention {
+        /// The identity platform
+        platform: String,
+        /// How long this request waited.
+        timeout: Duration,
+    },
+
+    #[error(ignore)]
+    #[display(fmt = "Error building email: {}", _0)]
+    /// Error building an email message (usually a malformed address).
+    /// This should report as an internal server error.
+    LettreEmailError(String),
+
+    #[error(ignore)]
+    #[display(fmt = "Error sending email: {}", _0)]
+    /// Error connecting to or communicating with the SMTP server.
+    /// This should report as an internal server error.
+    LettreSmtpError(String),
+
+    #[error(ignore)]
+    #[display(fmt = "SMTP server rejected email: {}", _0)]
+    /// The SMTP server responded negatively when sending an email.
+    /// This should report as a gateway error.
+    NegativeSmtpResponse(String),
 
     #[error(ignore)]
     #[display(fmt = "RPI CAS error: {}", _0)]
@@ -152,20 +278,147 @@ impl TelescopeError {
         }
     }
 
+    /// Create a forbidden error for a resource, given as it would appear in the middle of a
+    /// sentence (e.g. "this meeting").
+    pub fn forbidden(resource: impl Into<String>) -> Self {
+        Self::Forbidden {
+            resource: resource.into(),
+        }
+    }
+
+    /// Create a forbidden action error with converted fields.
+    pub fn forbidden_action(header: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::ForbiddenAction {
+            header: header.into(),
+            message: message.into(),
+        }
+    }
+
     /// Construct an Internal Server Error and convert the message.
     pub fn ise(message: impl Into<String>) -> Self {
         Self::InternalServerError(message.into())
     }
 
+    /// Create an upstream unavailable error with converted fields.
+    pub fn upstream_unavailable(platform: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::UpstreamUnavailable {
+            platform: platform.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Create an upstream timeout error with converted fields.
+    pub fn upstream_timeout(platform: impl Into<String>, timeout: Duration) -> Self {
+        Self::UpstreamTimeout {
+            platform: platform.into(),
+            timeout,
+        }
+    }
+
+    /// Create a refresh contention error with converted fields.
+    pub fn refresh_contention(platform: impl Into<String>, timeout: Duration) -> Self {
+        Self::RefreshContention {
+            platform: platform.into(),
+            timeout,
+        }
+    }
+
+    /// Create a rate limited error with converted fields.
+    pub fn rate_limited(platform: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self::RateLimited {
+            platform: platform.into(),
+            retry_after,
+        }
+    }
+
+    /// How long the client should wait before trying again, if this error says. This is sent
+    /// as the `Retry-After` header of the error response. Unavailable upstream APIs did not say
+    /// how long they will be down, so clients are asked to wait a short while.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TelescopeError::RateLimited { retry_after, .. } => *retry_after,
+            TelescopeError::UpstreamUnavailable { .. } => {
+                Some(Duration::from_secs(UNAVAILABLE_RETRY_AFTER_SECONDS))
+            }
+            _ => None,
+        }
+    }
+
+    /// Describe how long to wait before trying again, for error messages.
+    fn describe_wait(retry_after: &Option<Duration>) -> String {
+        match retry_after.map(|wait| wait.as_secs()) {
+            None => "Please try again in a few minutes.".into(),
+            Some(0) => "Please try again now.".into(),
+            Some(1) => "Please try again in 1 second.".into(),
+            Some(secs) if secs < 120 => format!("Please try again in {} seconds.", secs),
+            Some(secs) => format!("Please try again in {} minutes.", (secs + 59) / 60),
+        }
+    }
+
+    /// Is this HTTP status one that an upstream API or proxy uses to indicate that it is
+    /// temporarily unavailable?
+    pub fn is_unavailable_status(status: ReqwestStatusCode) -> bool {
+        status == ReqwestStatusCode::BAD_GATEWAY
+            || status == ReqwestStatusCode::SERVICE_UNAVAILABLE
+            || status == ReqwestStatusCode::GATEWAY_TIMEOUT
+    }
+
+    /// Does this reqwest error indicate that the upstream API is unreachable or temporarily
+    /// unavailable (rather than a bug on either end)?
+    fn is_upstream_unavailable(err: &ReqwestError) -> bool {
+        err.is_connect()
+            || err.is_timeout()
+            || err
+                .status()
+                .map(Self::is_unavailable_status)
+                .unwrap_or(false)
+    }
+
+    /// Was this reqwest error caused by a 429 response? Reqwest errors do not keep the
+    /// response headers, so the retry time is not known here. Responses checked with
+    /// `check_upstream_status` report it when the API sends one.
+    fn is_rate_limited(err: &ReqwestError) -> bool {
+        err.status() == Some(ReqwestStatusCode::TOO_MANY_REQUESTS)
+    }
+
     /// Convert a reqwest error from the RCOS API into a telescope error.
     pub fn rcos_api_error(err: ReqwestError) -> Self {
         error!("Error querying RCOS API: {}", err);
+        if Self::is_rate_limited(&err) {
+            return Self::rate_limited("RCOS API", None);
+        }
+        if Self::is_upstream_unavailable(&err) {
+            return Self::upstream_unavailable("RCOS API", err.to_string());
+        }
         Self::RcosApiError(err.to_string())
     }
 
+    /// Convert a reqwest error from an upstream API that was sent with a timeout. Timeouts
+    /// become [`TelescopeError::UpstreamTimeout`], and anything else is converted with
+    /// `convert` (such as [`TelescopeError::rcos_api_error`]).
+    pub fn with_timeout(
+        platform: &str,
+        timeout: Duration,
+        convert: fn(ReqwestError) -> Self,
+    ) -> impl FnOnce(ReqwestError) -> Self + '_ {
+        move |err| {
+            if err.is_timeout() {
+                error!("{} did not respond within {:?}: {}", platform, timeout, err);
+                return Self::upstream_timeout(platform, timeout);
+            }
+            return convert(err);
+        }
+    }
+
     /// Convert a reqwest error from the GitHub API into a telescope error.
     pub fn github_api_error(err: ReqwestError) -> Self {
         error!("Error querying GitHub API: {}", err);
+        if Self::is_rate_limited(&err) {
+            return Self::rate_limited("GitHub API", None);
+        }
+        if Self::is_upstream_unavailable(&err) {
+            return Self::upstream_unavailable("GitHub API", err.to_string());
+        }
         Self::GitHubApiError(err.to_string())
     }
 
@@ -175,12 +428,202 @@ impl TelescopeError {
         Self::SerenityError(err.to_string())
     }
 
+    /// Convert an error building an email message into a Telescope error.
+    pub fn email_error(err: impl fmt::Display) -> Self {
+        error!("Error building email: {}", err);
+        TelescopeError::LettreEmailError(err.to_string())
+    }
+
+    /// Convert an SMTP error into a Telescope error. Rejections from the SMTP server are
+    /// reported separately from connection and protocol errors.
+    pub fn smtp_error(err: SmtpError) -> Self {
+        error!("SMTP error: {}", err);
+        if err.is_permanent() || err.is_transient() {
+            TelescopeError::NegativeSmtpResponse(err.to_string())
+        } else {
+            TelescopeError::LettreSmtpError(err.to_string())
+        }
+    }
+
+    /// Create a schema mismatch error for an API response. The error is logged, since it
+    /// means Telescope needs to be updated.
+    pub fn schema_mismatch(platform: impl Into<String>, message: impl Into<String>) -> Self {
+        let platform: String = platform.into();
+        let message: String = message.into();
+        error!("{} schema mismatch: {}", platform, message);
+        TelescopeError::SchemaMismatch { platform, message }
+    }
+
     /// Convert reqwest error from RPI CAS service into a Telescope error.
     pub fn rpi_cas_error(err: ReqwestError) -> Self {
         error!("Error querying RPI CAS endpoint: {}", err);
         TelescopeError::RpiCasError(err.to_string())
     }
 
+    /// Get the name of this error's variant, for structured logs.
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            TelescopeError::PageNotFound => "PageNotFound",
+            TelescopeError::ResourceNotFound { .. } => "ResourceNotFound",
+            TelescopeError::GatewayError { .. } => "GatewayError",
+            TelescopeError::RenderingError(_) => "RenderingError",
+            TelescopeError::FutureCanceled => "FutureCanceled",
+            TelescopeError::InternalServerError(_) => "InternalServerError",
+            TelescopeError::BadRequest { .. } => "BadRequest",
+            TelescopeError::NotImplemented => "NotImplemented",
+            TelescopeError::IpExtractionError => "IpExtractionError",
+            TelescopeError::CsrfTokenNotFound => "CsrfTokenNotFound",
+            TelescopeError::CsrfTokenMismatch => "CsrfTokenMismatch",
+            TelescopeError::RcosApiError(_) => "RcosApiError",
+            TelescopeError::GitHubApiError(_) => "GitHubApiError",
+            TelescopeError::SerenityError(_) => "SerenityError",
+            TelescopeError::GraphQLError { .. } => "GraphQLError",
+            TelescopeError::SchemaMismatch { .. } => "SchemaMismatch",
+            TelescopeError::InvalidForm(_) => "InvalidForm",
+            TelescopeError::NotAuthenticated => "NotAuthenticated",
+            TelescopeError::AccountNotFound => "AccountNotFound",
+            TelescopeError::Forbidden { .. } => "Forbidden",
+            TelescopeError::ForbiddenAction { .. } => "ForbiddenAction",
+            TelescopeError::UpstreamUnavailable { .. } => "UpstreamUnavailable",
+            TelescopeError::RateLimited { .. } => "RateLimited",
+            TelescopeError::UpstreamTimeout { .. } => "UpstreamTimeout",
+            TelescopeError::RefreshContention { .. } => "RefreshContention",
+            TelescopeError::LettreEmailError(_) => "LettreEmailError",
+            TelescopeError::LettreSmtpError(_) => "LettreSmtpError",
+            TelescopeError::NegativeSmtpResponse(_) => "NegativeSmtpResponse",
+            TelescopeError::RpiCasError(_) => "RpiCasError",
+        }
+    }
+
+    /// Build the structured log record of this error. `request_id` is the correlation ID of the
+    /// request that caused it, if there is one.
+    pub fn log_record(&self, req: &HttpRequest, request_id: Option<&str>) -> serde_json::Value {
+        json!({
+            "error": self.variant_name(),
+            "code": self.public_code(),
+            "status": self.status_code().as_u16(),
+            "method": req.method().as_str(),
+            "path": req.path(),
+            "request_id": request_id,
+            "message": self.to_string(),
+        })
+    }
+
+    /// Log this error as a single line of JSON, so that it can be collected by a log
+    /// aggregator. Server errors are logged at the error level and everything else at the
+    /// info level. This is done when the error is rendered, since that is where the request is
+    /// available.
+    pub fn log_structured(&self, req: &HttpRequest, request_id: Option<&str>) {
+        let record: String = self.log_record(req, request_id).to_string();
+        if self.status_code().is_server_error() {
+            error!(target: STRUCTURED_LOG_TARGET, "{}", record);
+        } else {
+            info!(target: STRUCTURED_LOG_TARGET, "{}", record);
+        }
+    }
+
+    /// Get the public error code for this error. These are part of the public JSON error format
+    /// and should not change. Several variants can share a code.
+    pub fn public_code(&self) -> &'static str {
+        match self {
+            TelescopeError::PageNotFound | TelescopeError::ResourceNotFound { .. } => "not_found",
+            TelescopeError::BadRequest { .. } => "bad_request",
+            TelescopeError::InvalidForm(_) => "invalid_form",
+            TelescopeError::CsrfTokenNotFound | TelescopeError::CsrfTokenMismatch => "csrf_failure",
+            TelescopeError::NotAuthenticated => "not_authenticated",
+            TelescopeError::AccountNotFound => "account_not_found",
+            TelescopeError::Forbidden { .. } | TelescopeError::ForbiddenAction { .. } => {
+                "forbidden"
+            }
+            TelescopeError::NotImplemented => "not_implemented",
+            TelescopeError::UpstreamUnavailable { .. }
+            | TelescopeError::RefreshContention { .. } => "upstream_unavailable",
+            TelescopeError::RateLimited { .. } => "rate_limited",
+            TelescopeError::UpstreamTimeout { .. } => "upstream_timeout",
+            TelescopeError::GatewayError { .. }
+            | TelescopeError::NegativeSmtpResponse(_)
+            | TelescopeError::RpiCasError(_) => "bad_gateway",
+            TelescopeError::RenderingError(_)
+            | TelescopeError::FutureCanceled
+            | TelescopeError::InternalServerError(_)
+            | TelescopeError::IpExtractionError
+            | TelescopeError::RcosApiError(_)
+            | TelescopeError::GitHubApiError(_)
+            | TelescopeError::SerenityError(_)
+            | TelescopeError::GraphQLError { .. }
+            | TelescopeError::SchemaMismatch { .. }
+            | TelescopeError::LettreEmailError(_)
+            | TelescopeError::LettreSmtpError(_) => "internal_error",
+        }
+    }
+
+    /// Get a message for this error that is safe to send to API clients. Messages written for
+    /// users are passed through, but internal error details are replaced with a generic message.
+    pub fn public_message(&self) -> String {
+        match self {
+            TelescopeError::ResourceNotFound { header, message }
+            | TelescopeError::GatewayError { header, message }
+            | TelescopeError::BadRequest {
+                header, message, ..
+            }
+            | TelescopeError::ForbiddenAction { header, message } => {
+                format!("{}: {}", header, message)
+            }
+            TelescopeError::PageNotFound => "Page not found.".into(),
+            TelescopeError::InvalidForm(_) => "Invalid form submission.".into(),
+            TelescopeError::CsrfTokenNotFound | TelescopeError::CsrfTokenMismatch => {
+                "Could not verify the request. Please try again.".into()
+            }
+            TelescopeError::NotAuthenticated => "Authentication required.".into(),
+            TelescopeError::AccountNotFound => {
+                "There is no RCOS account linked to this sign-in. Please register again.".into()
+            }
+            TelescopeError::Forbidden { resource } => {
+                format!("You do not have permission to access {}.", resource)
+            }
+            TelescopeError::NotImplemented => "Not implemented.".into(),
+            TelescopeError::UpstreamUnavailable { platform, .. } => {
+                format!(
+                    "{} is temporarily unavailable. Please try again shortly.",
+                    platform
+                )
+            }
+            TelescopeError::RateLimited {
+                platform,
+                retry_after,
+            } => format!(
+                "{} is limiting requests from Telescope. {}",
+                platform,
+                Self::describe_wait(retry_after)
+            ),
+            TelescopeError::UpstreamTimeout { platform, .. } => {
+                format!("{} did not respond in time. Please try again.", platform)
+            }
+            TelescopeError::RefreshContention { platform, .. } => {
+                format!(
+                    "Your {} sign-in is being refreshed. Please try again shortly.",
+                    platform
+                )
+            }
+            TelescopeError::NegativeSmtpResponse(_) => {
+                "The email server rejected a message.".into()
+            }
+            TelescopeError::RpiCasError(_) => "Could not reach RPI CAS.".into(),
+            _ => "Internal server error.".into(),
+        }
+    }
+
+    /// Get the public JSON body for this error.
+    pub fn public_body(&self) -> PublicErrorBody {
+        PublicErrorBody {
+            error: PublicError {
+                code: self.public_code().to_string(),
+                status: self.status_code().as_u16(),
+                message: self.public_message(),
+            },
+        }
+    }
+
     /// Function that should only be used by the middleware to render a
     /// telescope error into an error page.
     pub async fn render_error_page(&self, req: &HttpRequest) -> Result<String, ActixError> {
@@ -192,7 +635,7 @@ impl TelescopeError {
             .unwrap_or("Unknown Error");
 
         // Create an inner template depending on the error.
-        let inner_template: Template = match self {
+        let mut inner_template: Template = match self {
             TelescopeError::PageNotFound => jumbotron::new(
                 format!("{} - Page Not Found", status_code),
                 "We could not find the page you are looking for. If you think this is in \
@@ -209,6 +652,11 @@ impl TelescopeError {
                 jumbotron::new(format!("{} - {}", status_code, header), message)
             }
 
+            TelescopeError::ForbiddenAction { header, message } => jumbotron::new(
+                format!("{} - {}", status_code, header),
+                format!("{} If you think this is in error, please contact a coordinator.", message)
+            ),
+
             TelescopeError::GatewayError { header, message } => jumbotron::new(
                 format!("{} - {}", status_code, header),
                 format!("{} Please contact a coordinator or faculty advisor.", message)
@@ -256,7 +704,7 @@ impl TelescopeError {
             TelescopeError::CsrfTokenMismatch => jumbotron::new(
                 format!("{} - Bad CSRF Token", status_code),
                 "The CSRF token supplied to the server by this request does not match the \
-                one the server generated for this identity provider for this IP. If you believe \
+                one the server generated for this identity provider for this browser. If you believe \
                 this is in error, please contact a coordinator and file a GitHub issue.",
             ),
 
@@ -281,6 +729,52 @@ impl TelescopeError {
                     description: {}", err)
             ),
 
+            TelescopeError::UpstreamUnavailable { platform, message } => jumbotron::new(
+                format!("{} - {} Unavailable", status_code, platform),
+                format!("Telescope could not reach the {} right now. Please try again \
+                shortly. If this keeps happening, please contact a coordinator. Internal error \
+                description: {}", platform, message)
+            ),
+
+            TelescopeError::RateLimited { platform, retry_after } => jumbotron::new(
+                format!("{} - {} Rate Limited", status_code, platform),
+                format!("The {} is receiving too many requests from Telescope right now. {} \
+                If this keeps happening, please contact a coordinator.",
+                    platform, Self::describe_wait(retry_after))
+            ),
+
+            TelescopeError::UpstreamTimeout { platform, timeout } => jumbotron::new(
+                format!("{} - {} Timed Out", status_code, platform),
+                format!("The {} did not respond within {} seconds. Please try again. If this \
+                keeps happening, please contact a coordinator.", platform, timeout.as_secs())
+            ),
+
+            TelescopeError::RefreshContention { platform, timeout } => jumbotron::new(
+                format!("{} - {} Sign-In Busy", status_code, platform),
+                format!("Another request was refreshing your {} sign-in and did not finish \
+                within {} seconds. Please try again shortly. If this keeps happening, please \
+                contact a coordinator.", platform, timeout.as_secs())
+            ),
+
+            TelescopeError::LettreEmailError(err) => jumbotron::new(
+                format!("{} - Email Error", status_code),
+                format!("Telescope could not build an email. Please contact a coordinator and \
+                file a GitHub issue. Internal error description: {}", err)
+            ),
+
+            TelescopeError::LettreSmtpError(err) => jumbotron::new(
+                format!("{} - Email Error", status_code),
+                format!("Telescope could not send an email. Please try again. If this error \
+                persists, please contact a coordinator and file a GitHub issue. Internal error \
+                description: {}", err)
+            ),
+
+            TelescopeError::NegativeSmtpResponse(err) => jumbotron::new(
+                format!("{} - Email Rejected", status_code),
+                format!("The email server rejected an email sent by Telescope. Please contact \
+                a coordinator. Internal error description: {}", err)
+            ),
+
             TelescopeError::RpiCasError(err) => jumbotron::new(
                 format!("{} - RPI CAS Error", status_code),
                 format!("Issue communicating with the RPI CAS service. Please try again. \
@@ -300,6 +794,14 @@ impl TelescopeError {
                 )
             }
 
+            TelescopeError::SchemaMismatch { platform, message } => jumbotron::new(
+                format!("{} - {} Schema Mismatch", status_code, platform),
+                format!("The {} returned a response Telescope did not expect. This usually \
+                means Telescope and the API are out of date with each other. Please contact a \
+                coordinator and create an issue on the Telescope GitHub. Internal error \
+                description: {}", platform, message)
+            ),
+
             TelescopeError::InternalServerError(message) => jumbotron::new(
                 format!("{} - {}", status_code, canonical_reason),
                 format!(
@@ -310,6 +812,11 @@ impl TelescopeError {
             ),
 
             TelescopeError::InvalidForm(page) => {
+                // Partial requests only get the form back, so it can be swapped in place.
+                if is_partial_request(req) {
+                    return page.content.render().map_err(ActixError::from);
+                }
+
                 // Render page, converting errors as necessary.
                 return page
                     .render()
@@ -323,13 +830,26 @@ impl TelescopeError {
                 page is unexpected, please contact a coordinator and create a GitHub issue.",
             ),
 
-            TelescopeError::Forbidden => jumbotron::new(
+            TelescopeError::AccountNotFound => jumbotron::with_link(
+                format!("{} - Account Not Found", status_code),
+                "You are signed in, but there is no RCOS account linked to your sign-in. Your \
+                account may have been deleted. You can create a new account to keep using Telescope.",
+                "/register",
+                "Register",
+            ),
+
+            TelescopeError::Forbidden { resource } => jumbotron::new(
                 format!("{} - {}", status_code, canonical_reason),
-                "You do not have the necessary permissions to access this page. If you \
-                think this is in error, please contact a coordinator or faculty advisor."
+                format!("You do not have the necessary permissions to view or edit {}. If you \
+                think you should have access, please contact a coordinator.", resource)
             ),
         };
 
+        // Show the request ID, so that users can include it when reporting the error.
+        if let Some(id) = request_id(req) {
+            inner_template["request_id"] = json!(id);
+        }
+
         // Put jumbotron in a page and return the content.
         return Page::new(req, "RCOS - Error", inner_template)
             .await
@@ -354,6 +874,36 @@ where
     }
 }
 
+impl From<SmtpError> for TelescopeError {
+    fn from(err: SmtpError) -> Self {
+        TelescopeError::smtp_error(err)
+    }
+}
+
+impl From<JsonPayloadError> for TelescopeError {
+    fn from(err: JsonPayloadError) -> Self {
+        let message: String = match err {
+            JsonPayloadError::Overflow => "The request body is too large.".into(),
+            JsonPayloadError::ContentType => {
+                "The request body must be JSON (with Content-Type: application/json).".into()
+            }
+            // Serde errors say where the problem is (field, line, and column).
+            JsonPayloadError::Deserialize(e) => {
+                format!("The request body could not be read: {}.", e)
+            }
+            JsonPayloadError::Payload(e) => {
+                format!("The request body could not be received: {}.", e)
+            }
+        };
+
+        TelescopeError::BadRequest {
+            header: "Malformed JSON Request".into(),
+            message,
+            show_status_code: true,
+        }
+    }
+}
+
 // This may produce a warning in some IDEs because the `Display` trait
 // is derived. You can safely ignore it.
 impl ResponseError for TelescopeError {
@@ -368,9 +918,16 @@ impl ResponseError for TelescopeError {
             TelescopeError::CsrfTokenMismatch => StatusCode::BAD_REQUEST,
             TelescopeError::InvalidForm(_) => StatusCode::BAD_REQUEST,
             TelescopeError::NotAuthenticated => StatusCode::UNAUTHORIZED,
-            TelescopeError::Forbidden => StatusCode::FORBIDDEN,
+            TelescopeError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            TelescopeError::AccountNotFound => StatusCode::FORBIDDEN,
+            TelescopeError::ForbiddenAction { .. } => StatusCode::FORBIDDEN,
             TelescopeError::RpiCasError(_) => StatusCode::BAD_GATEWAY,
             TelescopeError::GatewayError { .. } => StatusCode::BAD_GATEWAY,
+            TelescopeError::NegativeSmtpResponse(_) => StatusCode::BAD_GATEWAY,
+            TelescopeError::UpstreamUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            TelescopeError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            TelescopeError::UpstreamTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            TelescopeError::RefreshContention { .. } => StatusCode::SERVICE_UNAVAILABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -378,9 +935,14 @@ impl ResponseError for TelescopeError {
     // Override the default http response here.
     // Panic if the error cannot be serialized.
     fn error_response(&self) -> HttpResponse {
-        // Firstly log the error, so we at least know what it was before
-        // being serialized.
-        error!("Service generated error: {}", self);
+        // Log the error, so we at least know what it was before being serialized. The
+        // structured record is logged by the error rendering middleware, which has the
+        // request. Server errors are still logged here as well, in case it is not rendered.
+        if self.status_code().is_server_error() {
+            error!("Service generated error: {}", self);
+        } else {
+            debug!("Service generated error: {}", self);
+        }
 
         // Since we cannot render the html page here, we serialize
         // it to JSON and let the custom error handling middleware
@@ -426,3 +988,214 @@ impl From<RenderErrorDef> for RenderError {
         return new;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::sync::Once;
+
+    thread_local! {
+        /// The level, target, and message of each record logged on this thread.
+        static CAPTURED: RefCell<Vec<(Level, String, String)>> = RefCell::new(Vec::new());
+    }
+
+    /// Logger that keeps records on the thread that logged them, so that tests running at the
+    /// same time do not see each other's records.
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            CAPTURED.with(|captured| {
+                captured.borrow_mut().push((
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ))
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// The capturing logger.
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    /// Run `f` and get the records it logged.
+    fn capture_logs(f: impl FnOnce()) -> Vec<(Level, String, String)> {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&LOGGER).expect("Another logger is already installed");
+            log::set_max_level(LevelFilter::Trace);
+        });
+
+        CAPTURED.with(|captured| captured.borrow_mut().clear());
+        f();
+        return CAPTURED.with(|captured| captured.borrow_mut().drain(..).collect());
+    }
+
+    /// Server errors are logged as structured JSON and as the plain text error-level line.
+    #[test]
+    fn logs_server_errors() {
+        let req = TestRequest::get().uri("/meetings").to_http_request();
+        let err = TelescopeError::RcosApiError("connection reset".into());
+
+        let logs = capture_logs(|| {
+            err.log_structured(&req, Some("req-1234"));
+            err.error_response();
+        });
+
+        let (level, _, structured) = logs
+            .iter()
+            .find(|(_, target, _)| target == STRUCTURED_LOG_TARGET)
+            .expect("No structured error record");
+        assert_eq!(*level, Level::Error);
+        let record: Value = serde_json::from_str(structured).unwrap();
+        assert_eq!(record["error"], "RcosApiError");
+        assert_eq!(record["status"], 500);
+        assert_eq!(record["request_id"], "req-1234");
+        assert_eq!(record["path"], "/meetings");
+
+        assert!(logs.iter().any(|(level, _, message)| *level == Level::Error
+            && message.starts_with("Service generated error:")));
+    }
+
+    /// Client errors are logged at the info level, and the plain text line only when debugging.
+    #[test]
+    fn logs_client_errors_quietly() {
+        let req = TestRequest::get().uri("/nowhere").to_http_request();
+        let err = TelescopeError::PageNotFound;
+
+        let logs = capture_logs(|| {
+            err.log_structured(&req, None);
+            err.error_response();
+        });
+
+        let (level, _, structured) = logs
+            .iter()
+            .find(|(_, target, _)| target == STRUCTURED_LOG_TARGET)
+            .expect("No structured error record");
+        assert_eq!(*level, Level::Info);
+        let record: Value = serde_json::from_str(structured).unwrap();
+        assert_eq!(record["error"], "PageNotFound");
+        assert_eq!(record["status"], 404);
+        assert_eq!(record["request_id"], Value::Null);
+
+        assert!(logs
+            .iter()
+            .filter(|(_, _, message)| message.starts_with("Service generated error:"))
+            .all(|(level, _, _)| *level == Level::Debug));
+    }
+
+    /// Schema mismatches are internal server errors.
+    #[test]
+    fn schema_mismatch_status() {
+        let err = TelescopeError::schema_mismatch("RCOS API", "missing field `users`");
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    /// Forbidden resources are 403s.
+    #[test]
+    fn forbidden_status() {
+        let err = TelescopeError::forbidden("this meeting");
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    /// Forbidden actions are 403s.
+    #[test]
+    fn forbidden_action_status() {
+        let err = TelescopeError::forbidden_action("Cannot Unlink", "This is your only sign-in.");
+        assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    /// Identities without an account are 403s.
+    #[test]
+    fn account_not_found_status() {
+        assert_eq!(
+            TelescopeError::AccountNotFound.status_code(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    /// Unavailable upstream APIs are 503s.
+    #[test]
+    fn upstream_unavailable_status() {
+        let err = TelescopeError::upstream_unavailable("GitHub API", "Responded with status 502.");
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// Upstream timeouts are 504s.
+    #[test]
+    fn upstream_timeout_status() {
+        let err = TelescopeError::upstream_timeout("RCOS API", Duration::from_secs(30));
+        assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    /// Waiting too long for another refresh is a 503.
+    #[test]
+    fn refresh_contention_status() {
+        let err = TelescopeError::refresh_contention("Discord", Duration::from_secs(5));
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// Rate limits are 429s.
+    #[test]
+    fn rate_limited_status() {
+        let err = TelescopeError::rate_limited("GitHub API", None);
+        assert_eq!(err.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    /// Only gateway statuses mean that an upstream API is unavailable. Other server errors are
+    /// bugs in the API.
+    #[test]
+    fn unavailable_statuses() {
+        for status in [
+            ReqwestStatusCode::BAD_GATEWAY,
+            ReqwestStatusCode::SERVICE_UNAVAILABLE,
+            ReqwestStatusCode::GATEWAY_TIMEOUT,
+        ] {
+            assert!(TelescopeError::is_unavailable_status(status));
+        }
+        assert!(!TelescopeError::is_unavailable_status(
+            ReqwestStatusCode::INTERNAL_SERVER_ERROR
+        ));
+        assert!(!TelescopeError::is_unavailable_status(
+            ReqwestStatusCode::BAD_REQUEST
+        ));
+    }
+
+    /// A connection error is reported as an unavailable upstream (503), not a bug (500).
+    #[actix_rt::test]
+    async fn connection_error_is_unavailable() {
+        // Nothing listens on port 1.
+        let err: ReqwestError = reqwest::Client::new()
+            .get("http://127.0.0.1:1/v1/graphql")
+            .send()
+            .await
+            .unwrap_err();
+
+        let err: TelescopeError = TelescopeError::rcos_api_error(err);
+        assert!(matches!(err, TelescopeError::UpstreamUnavailable { .. }));
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// Errors from a logic problem in Telescope stay internal server errors.
+    #[test]
+    fn logic_errors_are_internal() {
+        assert_eq!(
+            TelescopeError::ise("Unexpected state").status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            TelescopeError::RcosApiError("Could not parse response".into()).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
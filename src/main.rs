@@ -20,24 +20,29 @@ extern crate derive_more;
 extern crate graphql_client;
 
 use crate::discord_bot::DiscordBot;
-use crate::templates::static_pages::sponsors::SponsorsPage;
-use crate::templates::static_pages::StaticPage;
+use crate::email::coordinator_summary::CoordinatorSummary;
+use crate::meeting_reminders::MeetingReminders;
+use crate::web::cookie_keys::{self, RotatingCookieIdentityPolicy};
 use crate::web::csrf::CsrfJanitor;
 use crate::web::middlewares;
 use actix::prelude::*;
 use actix_files as afs;
-use actix_identity::{CookieIdentityPolicy, IdentityService};
+use actix_identity::IdentityService;
 use actix_web::cookie::SameSite;
-use actix_web::{middleware, web as aweb, web::get, App, HttpServer};
+use actix_web::dev::Service;
+use actix_web::{middleware, web as aweb, App, HttpServer};
 use chrono::Offset;
-use rand::rngs::OsRng;
-use rand::Rng;
+use futures::TryFutureExt;
 
+mod announcement;
 pub mod api;
 mod app_data;
+mod audit;
 mod discord_bot;
+mod email;
 mod env;
 mod error;
+mod meeting_reminders;
 mod templates;
 mod web;
 
@@ -55,48 +60,276 @@ async fn main() -> std::io::Result<()> {
     // restart it if it crashes.
     Supervisor::start(|_| DiscordBot);
 
+    // Start the weekly coordinator summary email if it is enabled.
+    if CoordinatorSummary::is_enabled() {
+        CoordinatorSummary.start();
+    }
+
+    // Start the meeting reminder job if it is enabled.
+    if MeetingReminders::is_enabled() {
+        MeetingReminders.start();
+    }
+
+    // Make sure the static files directory exists before serving from it. Report a missing
+    // directory as a startup error rather than a panic.
+    let static_dir: String = env::global_config().static_dir.clone();
+    check_static_dir(static_dir.as_str()).map_err(|err| {
+        error!("{}", err);
+        err
+    })?;
+
+    // Resolve the address to serve on. This is usually reverse proxied by Caddy.
+    let config = env::global_config();
+    let bind_host: String = config.bind_host.clone();
+    let bind_port: u16 = config.bind_port;
+    let bind_address: String = config.bind_address();
+    let shutdown_timeout: u64 = config.shutdown_timeout_secs;
+    info!("Binding to http://{}", bind_address);
+
     // Setup identity middleware.
-    // Create secure random sequence to encrypt cookie identities.
-    let cookie_key: [u8; 32] = OsRng::default().gen::<[u8; 32]>();
+    // Load the keys to encrypt cookie identities (or generate one).
+    let cookie_keys: Vec<Vec<u8>> = cookie_keys::resolve_keys();
 
     // Construct and start main server instance.
     let web_server = HttpServer::new(move || {
         // Create cookie policy.
-        let cookie_policy = CookieIdentityPolicy::new(&cookie_key)
-            // Transmit cookies over HTTPS only.
-            .secure(true)
-            .name("telescope_auth")
-            // Same-Site needs to be Lax because of the caddy proxy it seems?
-            .same_site(SameSite::Lax)
-            // Cookies expire after a day.
-            .max_age_time(time::Duration::days(1));
+        let cookie_policy = RotatingCookieIdentityPolicy::new(&cookie_keys, |policy| {
+            policy
+                // Transmit cookies over HTTPS only.
+                .secure(true)
+                .name("telescope_auth")
+                // Same-Site needs to be Lax because of the caddy proxy it seems?
+                .same_site(SameSite::Lax)
+                // Cookies expire after a day.
+                .max_age_time(time::Duration::days(1))
+        });
 
         App::new()
             // Middleware to render telescope errors into pages
             .wrap(middlewares::error_rendering::TelescopeErrorHandler)
+            // Count response body sizes before compression. Compression is registered after
+            // this, so that sampled logs can report both sizes.
+            .wrap(middlewares::response_size::BodySizeCounter)
+            // Compress responses for clients that accept it.
+            .wrap(middleware::Compress::default())
+            // The middlewares outside compression expect plain response bodies.
+            .wrap_fn(|req, srv| srv.call(req).map_ok(middlewares::response_size::box_body))
             // Cookie Identity middleware.
             .wrap(IdentityService::new(cookie_policy))
+            // Redirect plain HTTP requests to HTTPS (if enabled).
+            .wrap(middlewares::https_redirect::HttpsRedirect::from_config())
             // Logger middleware
             .wrap(middleware::Logger::default())
+            // The logger also changes the response body type.
+            .wrap_fn(|req, srv| srv.call(req).map_ok(middlewares::response_size::box_body))
+            // Verbose logging of a sample of requests
+            .wrap(middlewares::sampled_logging::SampledLogging::new(
+                env::global_config().request_log_sample_rate,
+            ))
+            // Count in-flight requests, to report on them at shutdown.
+            .wrap(middlewares::in_flight::InFlightCounter)
+            // Give every request an ID. This is outermost so that the other middlewares
+            // can read it.
+            .wrap(middlewares::request_id::RequestIds)
+            // Render JSON body errors like other Telescope errors.
+            .app_data(
+                aweb::JsonConfig::default()
+                    .error_handler(|err, _| error::TelescopeError::from(err).into()),
+            )
             // Register Services
             .configure(web::services::register)
             // static files service
-            .service(
-                afs::Files::new("/static", "static")
-                    // Text responses are UTF-8
-                    .prefer_utf8(true)
-                    // Show listings of directories
-                    .show_files_listing(),
-            )
-            .route("/sponsors", get().to(SponsorsPage::page))
+            .service(static_files(static_dir.as_str()))
             .default_service(aweb::to(web::services::not_found::not_found))
     })
-    // Bind to 80 (this gets reversed proxied by Caddy later)
-    .bind("0.0.0.0:80")
-    .expect("Could not bind http://localhost:80")
+    // Bind to the configured address. Report failures (e.g. the port is taken) as an error
+    // rather than a panic.
+    .bind((bind_host.as_str(), bind_port))
+    .map_err(|err| {
+        error!("Could not bind http://{}: {}", bind_address, err);
+        err
+    })?
+    // Shutdown signals are handled below, so that draining can be logged.
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout)
     // Start the server running.
     .run();
 
-    // Wait on server to produce an error.
+    // Stop gracefully on SIGTERM or SIGINT.
+    actix_web::rt::spawn(graceful_shutdown(web_server.clone(), shutdown_timeout));
+
+    // Wait on server to produce an error or stop.
     return web_server.await;
 }
+
+/// Wait for SIGTERM or SIGINT (Ctrl-C).
+async fn shutdown_signal() {
+    let ctrl_c = Box::pin(async {
+        if let Err(err) = actix_web::rt::signal::ctrl_c().await {
+            error!("Could not listen for SIGINT: {}", err);
+            futures::future::pending::<()>().await;
+        }
+    });
+
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                let terminate = Box::pin(async move {
+                    terminate.recv().await;
+                });
+                futures::future::select(ctrl_c, terminate).await;
+            }
+            Err(err) => {
+                error!("Could not listen for SIGTERM: {}", err);
+                ctrl_c.await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    ctrl_c.await;
+}
+
+/// Stop the web server once a shutdown signal is received. The server stops accepting
+/// connections right away, and waits up to `timeout_secs` seconds for in-flight requests to
+/// finish, since a request cut off part way through may have applied only some of its
+/// mutations.
+async fn graceful_shutdown(server: actix_web::dev::Server, timeout_secs: u64) {
+    shutdown_signal().await;
+    drain(server, timeout_secs).await;
+}
+
+/// Stop the web server from accepting connections, and wait up to `timeout_secs` seconds (the
+/// server's shutdown timeout) for in-flight requests to finish. Requests still running after
+/// that are cut off.
+async fn drain(server: actix_web::dev::Server, timeout_secs: u64) {
+    info!(
+        "Shutting down. Waiting up to {}s for {} in-flight request(s) to finish.",
+        timeout_secs,
+        middlewares::in_flight::in_flight()
+    );
+
+    // Stop accepting connections and drain.
+    server.stop(true).await;
+
+    let remaining: usize = middlewares::in_flight::in_flight();
+    if remaining > 0 {
+        warn!(
+            "Shutdown timed out with {} request(s) still in flight.",
+            remaining
+        );
+    } else {
+        info!("All in-flight requests finished.");
+    }
+}
+
+/// Check that the static files directory exists. The error names the directory, so that a
+/// misconfigured path is easy to spot in the startup logs.
+fn check_static_dir(static_dir: &str) -> std::io::Result<()> {
+    if std::path::Path::new(static_dir).is_dir() {
+        return Ok(());
+    }
+
+    return Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("Static files directory {:?} does not exist.", static_dir),
+    ));
+}
+
+/// Build the static files service for a directory. Directory listings are only shown if
+/// enabled in the config. Otherwise requests for directories get a 404.
+fn static_files(static_dir: &str) -> afs::Files {
+    let files = afs::Files::new("/static", static_dir)
+        // Text responses are UTF-8
+        .prefer_utf8(true);
+
+    if env::global_config().static_files_listing {
+        // Show listings of directories
+        return files.show_files_listing();
+    }
+
+    return files;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The static directory in the repository is accepted.
+    #[test]
+    fn static_dir_exists() {
+        let static_dir: String = format!("{}/static", env!("CARGO_MANIFEST_DIR"));
+        assert!(check_static_dir(static_dir.as_str()).is_ok());
+    }
+
+    /// A missing directory (or a file) is an error naming the path.
+    #[test]
+    fn static_dir_missing() {
+        let missing: String = format!("{}/no-such-static-dir", env!("CARGO_MANIFEST_DIR"));
+        let err: std::io::Error = check_static_dir(missing.as_str()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().contains(missing.as_str()));
+
+        let file: String = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        assert!(check_static_dir(file.as_str()).is_err());
+    }
+
+    /// Start a server on a free local port with the given shutdown timeout. Requests to
+    /// `/sleep/{ms}` take that many milliseconds. Return the server and its URL.
+    fn sleeping_server(timeout_secs: u64) -> (actix_web::dev::Server, String) {
+        /// Respond after the requested number of milliseconds.
+        async fn sleep(ms: aweb::Path<u64>) -> actix_web::HttpResponse {
+            actix_rt::time::delay_for(std::time::Duration::from_millis(*ms)).await;
+            actix_web::HttpResponse::Ok().finish()
+        }
+
+        let server = HttpServer::new(|| App::new().route("/sleep/{ms}", aweb::get().to(sleep)))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let url: String = format!("http://{}", server.addrs()[0]);
+        let server = server
+            .disable_signals()
+            .shutdown_timeout(timeout_secs)
+            .run();
+        return (server, url);
+    }
+
+    /// Requests that finish within the shutdown timeout are answered.
+    #[actix_rt::test]
+    async fn drain_finishes_requests() {
+        let (server, url) = sleeping_server(5);
+        let client = reqwest::Client::new();
+
+        let (response, _) =
+            futures::join!(client.get(&format!("{}/sleep/500", url)).send(), async {
+                // Stop once the request is in flight.
+                actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+                drain(server, 5).await;
+            });
+        assert_eq!(response.unwrap().status(), reqwest::StatusCode::OK);
+    }
+
+    /// Requests still running after the shutdown timeout are cut off, rather than holding up
+    /// the shutdown.
+    #[actix_rt::test]
+    async fn drain_times_out() {
+        let (server, url) = sleeping_server(1);
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let (response, _) =
+            futures::join!(client.get(&format!("{}/sleep/20000", url)).send(), async {
+                // Stop once the request is in flight.
+                actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+                drain(server, 1).await;
+            });
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(response.is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! Reminders sent before meetings start.
+//!
+//! A job runs on a configured interval and reminds the hosts of meetings starting soon (and
+//! optionally users registered for them) by email and/or Discord direct message. Drafts and
+//! canceled meetings are never reminded about. This is off unless enabled in the config.
+//!
+//! Sent reminders are recorded in the RCOS database (`meetings.reminder_sent_for`), so they
+//! are not sent again after a restart or by another Telescope instance.
+
+use crate::api::discord::global_discord_client;
+use crate::api::rcos::meetings::reminders::{
+    upcoming_meetings::{ReminderRecipient, UpcomingMeetingsMeetings},
+    ClaimMeetingReminder, UpcomingMeetings,
+};
+use crate::email;
+use crate::email::address::Email;
+use crate::env::{global_config, EmailConfig, MeetingRemindersConfig};
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use actix::{Actor, AsyncContext, Context};
+use chrono::{DateTime, Duration, Local, Utc};
+use std::time::Duration as StdDuration;
+
+/// The directory of the handlebars templates of the reminder email.
+const TEMPLATE: &'static str = "email/meeting_reminder";
+
+/// The handlebars template for the plain text version of the reminder, which is also used for
+/// Discord messages.
+const TEXT_TEMPLATE: &'static str = "email/meeting_reminder/text";
+
+/// Get the meetings that are due for a reminder at `now` and have not been reminded about
+/// yet. Meetings are due once they are within their type's lead time of starting. Reminders
+/// are recorded with the start time they were sent for, so that a meeting moved to a later
+/// time is reminded about again.
+fn due<'a>(
+    now: DateTime<Utc>,
+    meetings: &'a [UpcomingMeetingsMeetings],
+    config: &MeetingRemindersConfig,
+) -> Vec<&'a UpcomingMeetingsMeetings> {
+    meetings
+        .iter()
+        .filter(|meeting| {
+            // Check that the meeting is in its reminder window.
+            let start: DateTime<Utc> = meeting.start_date_time;
+            let lead = Duration::minutes(config.lead_minutes_for(meeting.type_) as i64);
+            now < start && start - lead <= now
+        })
+        // Skip meetings already reminded about for this start time.
+        .filter(|meeting| meeting.reminder_sent_for != Some(meeting.start_date_time))
+        .collect()
+}
+
+/// Actor that periodically sends meeting reminders.
+#[derive(Default)]
+pub struct MeetingReminders;
+
+impl MeetingReminders {
+    /// Should meeting reminders be sent? This requires reminders to be enabled and at least
+    /// one way to send them.
+    pub fn is_enabled() -> bool {
+        let config = global_config();
+        let reminders: &MeetingRemindersConfig = &config.meeting_reminders;
+        reminders.enabled && ((reminders.email && config.email.is_some()) || reminders.discord)
+    }
+
+    /// Get everyone who should be reminded about a meeting. The host comes first, and nobody
+    /// is listed twice.
+    fn recipients<'a>(
+        meeting: &'a UpcomingMeetingsMeetings,
+        config: &MeetingRemindersConfig,
+    ) -> Vec<&'a ReminderRecipient> {
+        // Start with the host.
+        let mut recipients: Vec<&ReminderRecipient> = meeting.host.iter().collect();
+
+        // Add registered users if enabled.
+        if config.remind_attendees {
+            for attendance in meeting.meeting_attendances.iter() {
+                if recipients.iter().all(|r| r.id != attendance.user.id) {
+                    recipients.push(&attendance.user);
+                }
+            }
+        }
+
+        return recipients;
+    }
+
+    /// Get the template fields describing a meeting reminder.
+    fn fields(
+        meeting: &UpcomingMeetingsMeetings,
+        recipient: &ReminderRecipient,
+    ) -> serde_json::Value {
+        json!({
+            "recipient": recipient,
+            "meeting": meeting,
+            "meeting_type": meeting.type_.to_string(),
+            "starts": meeting
+                .start_date_time
+                .with_timezone(&Local)
+                .format("%A, %B %-d at %-I:%M %p")
+                .to_string(),
+            "meeting_link": format!("{}/meeting/{}", global_config().telescope_url, meeting.meeting_id),
+        })
+    }
+
+    /// Email a reminder to a recipient, if they have an RCS ID and are not suppressed. Return
+    /// whether an email was sent.
+    async fn send_email(
+        config: &EmailConfig,
+        meeting: &UpcomingMeetingsMeetings,
+        recipient: &ReminderRecipient,
+    ) -> Result<bool, TelescopeError> {
+        // Skip users without an RCS ID.
+        let rcs_id: &str = match recipient.rcs_id.first() {
+            Some(account) => account.account_id.as_str(),
+            None => return Ok(false),
+        };
+
+        // Skip suppressed addresses.
+        let address: Email = email::rpi_address(rcs_id)?;
+        if email::is_suppressed(config, &address) {
+            info!(
+                "Skipping meeting reminder to suppressed address {}",
+                address
+            );
+            return Ok(false);
+        }
+
+        // Render and send the message.
+        let to = email::mailbox(
+            format!("{} {}", recipient.first_name, recipient.last_name),
+            &address,
+        )?;
+        let subject = format!("Reminder: {}", meeting_name(meeting));
+        let fields = Self::fields(meeting, recipient);
+        let message = email::templated_message(config, to, subject, TEMPLATE, fields)?;
+        email::send(message).await?;
+        return Ok(true);
+    }
+
+    /// Send a reminder to a recipient as a Discord direct message, if they have a linked
+    /// Discord account. Return whether a message was sent.
+    async fn send_discord(
+        meeting: &UpcomingMeetingsMeetings,
+        recipient: &ReminderRecipient,
+    ) -> Result<bool, TelescopeError> {
+        // Skip users without a Discord account.
+        let discord_id: u64 = match recipient
+            .discord
+            .first()
+            .and_then(|account| account.account_id.parse::<u64>().ok())
+        {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+
+        // Render the message.
+        let mut text = Template::new(TEXT_TEMPLATE);
+        text.fields = Self::fields(meeting, recipient);
+        let content: String = text.render()?;
+
+        // Open a direct message channel and send the reminder.
+        let channel = global_discord_client()
+            .create_private_channel(&json!({ "recipient_id": discord_id }))
+            .await
+            .map_err(TelescopeError::serenity_error)?;
+        global_discord_client()
+            .send_message(channel.id.0, &json!({ "content": content }))
+            .await
+            .map_err(TelescopeError::serenity_error)?;
+        return Ok(true);
+    }
+
+    /// Query meetings starting soon and send reminders for any that are due. Return the number
+    /// of reminders sent.
+    async fn run() -> Result<usize, TelescopeError> {
+        let config = global_config();
+        let reminder_config: &MeetingRemindersConfig = &config.meeting_reminders;
+        let email_config: Option<EmailConfig> =
+            email::email_config().filter(|_| reminder_config.email);
+
+        // Get every meeting that could be in its reminder window.
+        let now: DateTime<Utc> = Utc::now();
+        let until: DateTime<Utc> =
+            now + Duration::minutes(reminder_config.max_lead_minutes() as i64);
+        let meetings = UpcomingMeetings::get(now, until).await?;
+
+        let mut sent: usize = 0;
+        for meeting in due(now, meetings.as_slice(), reminder_config) {
+            // Claim the reminder before sending, so a failed or slow send is never repeated
+            // by a later run. Skip it if an overlapping run or another instance claimed it.
+            if !ClaimMeetingReminder::execute(meeting.meeting_id, meeting.start_date_time).await? {
+                continue;
+            }
+
+            for recipient in Self::recipients(meeting, reminder_config) {
+                // Send each reminder. Log failures without stopping the rest.
+                if let Some(email_config) = email_config.as_ref() {
+                    match Self::send_email(email_config, meeting, recipient).await {
+                        Ok(delivered) => sent += delivered as usize,
+                        Err(err) => error!(
+                            "Could not email reminder for meeting {} to user {}: {}",
+                            meeting.meeting_id, recipient.id, err
+                        ),
+                    }
+                }
+
+                if reminder_config.discord {
+                    match Self::send_discord(meeting, recipient).await {
+                        Ok(delivered) => sent += delivered as usize,
+                        Err(err) => error!(
+                            "Could not send Discord reminder for meeting {} to user {}: {}",
+                            meeting.meeting_id, recipient.id, err
+                        ),
+                    }
+                }
+            }
+        }
+
+        return Ok(sent);
+    }
+}
+
+/// Get the name to use for a meeting in reminders.
+fn meeting_name(meeting: &UpcomingMeetingsMeetings) -> String {
+    meeting
+        .title
+        .clone()
+        .unwrap_or_else(|| meeting.type_.to_string())
+}
+
+impl Actor for MeetingReminders {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Meeting Reminders Started");
+
+        // Check for due reminders on the configured interval.
+        let minutes: u64 = global_config()
+            .meeting_reminders
+            .check_interval_minutes
+            .max(1) as u64;
+        let interval: StdDuration = StdDuration::from_secs(minutes * 60);
+
+        ctx.run_interval(interval, |_, _| {
+            actix::spawn(async move {
+                match Self::run().await {
+                    Ok(sent) => info!("Sent {} meeting reminders.", sent),
+                    Err(err) => error!("Could not send meeting reminders: {}", err),
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::meetings::MeetingType;
+
+    /// Make an upcoming meeting starting at `start`.
+    fn meeting(
+        meeting_id: i64,
+        start: DateTime<Utc>,
+        reminder_sent_for: Option<DateTime<Utc>>,
+    ) -> UpcomingMeetingsMeetings {
+        serde_json::from_value(json!({
+            "meeting_id": meeting_id,
+            "title": null,
+            "type": "large_group",
+            "start_date_time": start,
+            "location": null,
+            "meeting_url": null,
+            "is_remote": false,
+            "reminder_sent_for": reminder_sent_for,
+            "host": null,
+            "meeting_attendances": [],
+        }))
+        .expect("Could not make test meeting")
+    }
+
+    /// Get the IDs of the meetings due for a reminder.
+    fn due_ids(
+        now: DateTime<Utc>,
+        meetings: &[UpcomingMeetingsMeetings],
+        config: &MeetingRemindersConfig,
+    ) -> Vec<i64> {
+        due(now, meetings, config)
+            .into_iter()
+            .map(|meeting| meeting.meeting_id)
+            .collect()
+    }
+
+    /// Only meetings within their lead time of starting are due.
+    #[test]
+    fn selects_meetings_in_window() {
+        let now: DateTime<Utc> = Utc::now();
+        let config = MeetingRemindersConfig::default();
+        let meetings = vec![
+            meeting(1, now + Duration::minutes(30), None),
+            meeting(2, now + Duration::minutes(90), None),
+            meeting(3, now - Duration::minutes(1), None),
+            meeting(4, now + Duration::minutes(60), None),
+        ];
+        assert_eq!(due_ids(now, &meetings, &config), vec![1, 4]);
+    }
+
+    /// Lead times can be set per meeting type.
+    #[test]
+    fn uses_lead_time_of_meeting_type() {
+        let now: DateTime<Utc> = Utc::now();
+        let mut config = MeetingRemindersConfig::default();
+        config
+            .lead_minutes_by_type
+            .insert(MeetingType::LargeGroup, 120);
+        let meetings = vec![meeting(1, now + Duration::minutes(90), None)];
+        assert_eq!(due_ids(now, &meetings, &config), vec![1]);
+    }
+
+    /// A meeting reminded about on one tick is not due on the next, unless it moved.
+    #[test]
+    fn reminds_once_per_start_time() {
+        let now: DateTime<Utc> = Utc::now();
+        let config = MeetingRemindersConfig::default();
+        let start: DateTime<Utc> = now + Duration::minutes(30);
+
+        // First tick: not reminded yet.
+        assert_eq!(due_ids(now, &[meeting(1, start, None)], &config), vec![1]);
+
+        // Second tick: the first tick's claim is recorded.
+        let later: DateTime<Utc> = now + Duration::minutes(5);
+        assert!(due_ids(later, &[meeting(1, start, Some(start))], &config).is_empty());
+
+        // The meeting moved: remind about the new time.
+        let moved: DateTime<Utc> = start + Duration::minutes(10);
+        assert_eq!(
+            due_ids(later, &[meeting(1, moved, Some(start))], &config),
+            vec![1]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Message shown in place of a list with no items.
+//!
+//! List pages put one of these in their template fields and render it with the `empty_state`
+//! partial in the `{{else}}` branch of their `{{#each}}` block.
+
+/// A link shown under an empty state message prompting the user to do something about it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallToAction {
+    /// The text of the link button.
+    pub text: String,
+    /// Where the link button goes.
+    pub url: String,
+}
+
+/// The message and optional call to action to show when a list is empty.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmptyState {
+    /// The message to show.
+    pub message: String,
+    /// An optional link to show under the message.
+    pub action: Option<CallToAction>,
+}
+
+impl EmptyState {
+    /// Create an empty state with a message and no call to action.
+    pub fn new(message: impl Into<String>) -> Self {
+        EmptyState {
+            message: message.into(),
+            action: None,
+        }
+    }
+
+    /// Add a call to action to this empty state.
+    pub fn with_action(mut self, text: impl Into<String>, url: impl Into<String>) -> Self {
+        self.action = Some(CallToAction {
+            text: text.into(),
+            url: url.into(),
+        });
+        return self;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::Template;
+
+    /// Render the `empty_state` partial with an empty state, as list templates do.
+    fn render(empty_state: &EmptyState) -> String {
+        let mut template = Template::new("empty_state");
+        template.fields = json!(empty_state);
+        return template.render().unwrap();
+    }
+
+    /// The fields match the ones the partial uses.
+    #[test]
+    fn serializes_partial_fields() {
+        let empty = EmptyState::new("No meetings.").with_action("Create one", "/meetings/create");
+        assert_eq!(
+            json!(empty),
+            json!({
+                "message": "No meetings.",
+                "action": { "text": "Create one", "url": "/meetings/create" }
+            })
+        );
+        assert_eq!(
+            json!(EmptyState::new("No meetings.")),
+            json!({ "message": "No meetings.", "action": null })
+        );
+    }
+
+    /// The message is rendered, without a link if there is no call to action.
+    #[test]
+    fn renders_message() {
+        let rendered: String = render(&EmptyState::new("No meetings this week."));
+        assert!(rendered.contains("No meetings this week."));
+        assert!(!rendered.contains("<a "));
+    }
+
+    /// The call to action is rendered as a link.
+    #[test]
+    fn renders_action() {
+        let empty = EmptyState::new("No projects.").with_action("Add a project", "/projects/new");
+        let rendered: String = render(&empty);
+        assert!(rendered.contains("No projects."));
+        assert!(rendered.contains(r#"href="/projects/new""#));
+        assert!(rendered.contains("Add a project"));
+    }
+
+    /// The message is escaped.
+    #[test]
+    fn escapes_message() {
+        let rendered: String = render(&EmptyState::new("<script>alert(1)</script>"));
+        assert!(!rendered.contains("<script>"));
+        assert!(rendered.contains("&lt;script&gt;"));
+    }
+}
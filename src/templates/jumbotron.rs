@@ -11,3 +11,19 @@ pub fn new(heading: impl Into<String>, message: impl Into<String>) -> Template {
 
     return template;
 }
+
+/// Create a new jumbotron template with a button linking to another page.
+pub fn with_link(
+    heading: impl Into<String>,
+    message: impl Into<String>,
+    href: impl Into<String>,
+    link_text: impl Into<String>,
+) -> Template {
+    let mut template = new(heading, message);
+    template["link"] = json!({
+        "href": href.into(),
+        "text": link_text.into()
+    });
+
+    return template;
+}
@@ -1,6 +1,8 @@
 use crate::app_data::AppData;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
+use actix_web::http::header::VARY;
+use actix_web::web::Query;
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use futures::future::{ready, Ready};
 use serde::Serialize;
@@ -8,6 +10,7 @@ use serde_json::Value;
 use std::ops::{Index, IndexMut};
 
 pub mod auth;
+pub mod empty_state;
 pub mod helpers;
 pub mod jumbotron;
 pub mod navbar;
@@ -16,6 +19,44 @@ pub mod pagination;
 pub mod static_pages;
 pub mod tags;
 
+/// The header HTMX adds to the requests it makes.
+pub const HX_REQUEST_HEADER: &'static str = "HX-Request";
+
+/// Query parameters that ask for a partial response.
+#[derive(Deserialize, Debug, Default)]
+struct PartialQuery {
+    /// Only render the page content.
+    #[serde(default)]
+    partial: bool,
+}
+
+/// Does a request want just the page content (without the navbar and the rest of the page
+/// around it)? This is the case for requests made by HTMX (which send an `HX-Request: true`
+/// header) and requests with `?partial=true` in the query string.
+pub fn is_partial_request(req: &HttpRequest) -> bool {
+    let htmx: bool = req
+        .headers()
+        .get(HX_REQUEST_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+
+    let query: bool = Query::<PartialQuery>::from_query(req.query_string())
+        .map(|query| query.partial)
+        .unwrap_or(false);
+
+    return htmx || query;
+}
+
+/// Make an HTML response from a rendered template. The response varies on the `HX-Request`
+/// header, since the same URL can return a page or a partial.
+pub fn html_response(rendered: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html;charset=UTF-8")
+        .header(VARY, HX_REQUEST_HEADER)
+        .body(rendered)
+}
+
 /// A template that can be rendered using the handlebars template registry.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Template {
@@ -29,7 +70,7 @@ pub struct Template {
 impl Template {
     /// Create a new template object with the path to the handlebars file from
     /// the templates directory.
-    pub fn new(path: &'static str) -> Self {
+    pub fn new(path: impl Into<String>) -> Self {
         Self {
             handlebars_file: path.into(),
             fields: json!({}),
@@ -47,6 +88,12 @@ impl Template {
             .map_err(TelescopeError::RenderingError)
     }
 
+    /// Render this template by itself as a partial HTML response, to be swapped into a page
+    /// that is already loaded.
+    pub fn render_partial(&self) -> Result<HttpResponse, TelescopeError> {
+        self.render().map(html_response)
+    }
+
     /// Render this template as the content of a page.
     pub async fn in_page(
         self,
@@ -100,3 +147,61 @@ impl Responder for Template {
         return ready(result);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    /// HTMX requests are partial.
+    #[test]
+    fn htmx_header_is_partial() {
+        let req = TestRequest::get()
+            .header(HX_REQUEST_HEADER, "true")
+            .to_http_request();
+        assert!(is_partial_request(&req));
+
+        let req = TestRequest::get()
+            .header(HX_REQUEST_HEADER, "TRUE")
+            .to_http_request();
+        assert!(is_partial_request(&req));
+    }
+
+    /// Requests without the header (or with it set to something else) are not partial.
+    #[test]
+    fn no_htmx_header_is_full_page() {
+        assert!(!is_partial_request(&TestRequest::get().to_http_request()));
+
+        let req = TestRequest::get()
+            .header(HX_REQUEST_HEADER, "false")
+            .to_http_request();
+        assert!(!is_partial_request(&req));
+    }
+
+    /// The query string can also ask for a partial.
+    #[test]
+    fn partial_query() {
+        let req = TestRequest::get()
+            .uri("/projects?partial=true")
+            .to_http_request();
+        assert!(is_partial_request(&req));
+
+        let req = TestRequest::get()
+            .uri("/projects?partial=false")
+            .to_http_request();
+        assert!(!is_partial_request(&req));
+
+        // Unrelated or malformed query strings are not partial.
+        let req = TestRequest::get()
+            .uri("/projects?page=2&partial=maybe")
+            .to_http_request();
+        assert!(!is_partial_request(&req));
+    }
+
+    /// Responses vary on the HTMX header.
+    #[test]
+    fn html_response_varies_on_htmx() {
+        let response = html_response("<p>Hi</p>".into());
+        assert_eq!(response.headers().get(VARY).unwrap(), HX_REQUEST_HEADER);
+    }
+}
@@ -1,12 +1,70 @@
 //! Navbar template constants and functions.
 
 use crate::api::rcos::users::navbar_auth::Authentication;
+use crate::api::rcos::users::UserRole;
 use crate::error::TelescopeError;
 use crate::web::services::auth::identity::{AuthenticationCookie, Identity};
 use actix_web::FromRequest;
 use actix_web::HttpRequest;
 use uuid::Uuid;
 
+/// Who a navbar link is shown to. Each level includes the levels above it (e.g. coordinator
+/// links are also shown to admins).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Visibility {
+    /// Shown to everyone, signed in or not.
+    Everyone,
+    /// Shown to students, mentors, coordinators, and admins.
+    Students,
+    /// Shown to mentors, coordinators, and admins.
+    Mentors,
+    /// Shown to coordinators and admins.
+    Coordinators,
+    /// Shown only to admins.
+    Admins,
+}
+
+/// The links on the left side of the navbar in order, with their labels and who they are
+/// shown to.
+const NAV_LINKS: [(&'static str, &'static str, Visibility); 9] = [
+    ("Home", "/", Visibility::Everyone),
+    ("Projects", "/projects", Visibility::Everyone),
+    ("Developers", "/developers", Visibility::Everyone),
+    ("Sponsors", "/sponsors", Visibility::Everyone),
+    ("Meetings", "/meetings", Visibility::Everyone),
+    ("Admin", "/admin", Visibility::Admins),
+    ("Coordinate", "/coordinate", Visibility::Coordinators),
+    ("Mentor", "/mentor", Visibility::Mentors),
+    // Student engagement panel -- attendance and assignments
+    ("Engage", "/engage", Visibility::Students),
+];
+
+/// A link on the navbar.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NavLink {
+    /// The text of the link.
+    pub label: String,
+    /// Where the link goes.
+    pub href: String,
+    /// Is this the link to the current page (or a page under it)?
+    pub active: bool,
+}
+
+/// Is a navbar link to `href` active on the page at `path`? Links are active on their own
+/// page and any page under it, except the home link which is only active on the home page.
+fn is_active(href: &str, path: &str) -> bool {
+    let path: &str = path.trim_end_matches('/');
+    let href: &str = href.trim_end_matches('/');
+    if href.is_empty() {
+        return path.is_empty();
+    }
+    return path == href
+        || path
+            .strip_prefix(href)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false);
+}
+
 /// The values used for rendering the navbar template at the top of every page.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Navbar {
@@ -20,10 +78,16 @@ pub struct Navbar {
     is_student: bool,
     /// The user ID of the currently signed in user.
     user_id: Option<Uuid>,
+    /// The role of the currently signed in user.
+    role: Option<UserRole>,
+    /// The name of the currently signed in user.
+    username: Option<String>,
     /// If the viewer is creating an account.
     creating_account: bool,
     /// The path of the request to mark a navbar item as active or not.
     req_path: String,
+    /// The links visible to this viewer.
+    links: Vec<NavLink>,
 }
 
 impl Navbar {
@@ -35,11 +99,41 @@ impl Navbar {
             is_mentor: false,
             is_student: false,
             user_id: None,
+            role: None,
+            username: None,
             creating_account: false,
             req_path: "".to_string(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Can the viewer this navbar is for see links with the given visibility?
+    fn can_see(&self, visibility: Visibility) -> bool {
+        match visibility {
+            Visibility::Everyone => true,
+            Visibility::Students => {
+                self.is_student || self.is_mentor || self.is_coordinator || self.is_admin
+            }
+            Visibility::Mentors => self.is_mentor || self.is_coordinator || self.is_admin,
+            Visibility::Coordinators => self.is_coordinator || self.is_admin,
+            Visibility::Admins => self.is_admin,
         }
     }
 
+    /// Fill in the links that this viewer can see, marking the one for the current page.
+    fn with_links(mut self) -> Self {
+        self.links = NAV_LINKS
+            .iter()
+            .filter(|(_, _, visibility)| self.can_see(*visibility))
+            .map(|(label, href, _)| NavLink {
+                label: label.to_string(),
+                href: href.to_string(),
+                active: is_active(href, self.req_path.as_str()),
+            })
+            .collect();
+        return self;
+    }
+
     /// Create a navbar for a viewer without an account. This is the default navbar.
     fn userless(request: &HttpRequest) -> Self {
         Navbar {
@@ -49,8 +143,22 @@ impl Navbar {
         }
     }
 
-    /// Create a navbar and fill appropriately based on request parameters.
+    /// Create a navbar and fill appropriately based on request parameters. The navbar is
+    /// cached in the request's extensions, so the API is only queried once per request.
     pub async fn for_request(request: &HttpRequest) -> Result<Self, TelescopeError> {
+        // Check for a navbar already made for this request.
+        if let Some(navbar) = request.extensions().get::<Navbar>() {
+            return Ok(navbar.clone());
+        }
+
+        // Build the navbar and cache it on the request.
+        let navbar: Navbar = Self::build(request).await?.with_links();
+        request.extensions_mut().insert(navbar.clone());
+        return Ok(navbar);
+    }
+
+    /// Build a navbar for a request by querying the API for the viewer's roles.
+    async fn build(request: &HttpRequest) -> Result<Self, TelescopeError> {
         // Extract the authenticated identities from the request.
         let identity: Option<AuthenticationCookie> =
             Identity::extract(request).await?.identity().await;
@@ -67,6 +175,8 @@ impl Navbar {
                 let navbar_auth = Authentication::get(user_id).await?;
                 // Modify navbar as necessary.
                 navbar.user_id = Some(user_id);
+                navbar.role = navbar_auth.role();
+                navbar.username = navbar_auth.display_name();
                 navbar.is_admin = navbar_auth.is_admin();
                 navbar.is_coordinator = navbar_auth.is_coordinating();
                 navbar.is_mentor = navbar_auth.is_mentoring();
@@ -84,3 +194,131 @@ impl Navbar {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Links are active on their own page.
+    #[test]
+    fn active_on_exact_match() {
+        assert!(is_active("/projects", "/projects"));
+        assert!(is_active("/projects", "/projects/"));
+        assert!(is_active("/", "/"));
+        assert!(is_active("/", ""));
+    }
+
+    /// Links are active on pages under them.
+    #[test]
+    fn active_on_prefix_match() {
+        assert!(is_active("/projects", "/projects/42"));
+        assert!(is_active("/meetings", "/meetings/7/edit"));
+    }
+
+    /// Links are not active on pages that only share the start of their path.
+    #[test]
+    fn inactive_on_partial_segment() {
+        assert!(!is_active("/projects", "/projectsfoo"));
+        assert!(!is_active("/mentor", "/mentors"));
+        assert!(!is_active("/projects", "/developers"));
+    }
+
+    /// The home link is only active on the home page.
+    #[test]
+    fn home_only_active_on_home() {
+        assert!(!is_active("/", "/projects"));
+        assert!(!is_active("/", "/meetings/7"));
+    }
+
+    /// A navbar for a viewer with the given roles, with its links filled in.
+    fn navbar(is_student: bool, is_mentor: bool, is_coordinator: bool, is_admin: bool) -> Navbar {
+        Navbar {
+            is_student,
+            is_mentor,
+            is_coordinator,
+            is_admin,
+            ..Navbar::empty()
+        }
+        .with_links()
+    }
+
+    /// The labels of the links a navbar shows.
+    fn labels(navbar: &Navbar) -> Vec<&str> {
+        navbar
+            .links
+            .iter()
+            .map(|link| link.label.as_str())
+            .collect()
+    }
+
+    /// The links everyone can see.
+    const PUBLIC: [&'static str; 5] = ["Home", "Projects", "Developers", "Sponsors", "Meetings"];
+
+    /// Viewers without a role only see the public links.
+    #[test]
+    fn visitor_links() {
+        let navbar = navbar(false, false, false, false);
+        assert!(navbar.can_see(Visibility::Everyone));
+        assert!(!navbar.can_see(Visibility::Students));
+        assert_eq!(labels(&navbar), PUBLIC.to_vec());
+    }
+
+    /// Students also see the engagement panel.
+    #[test]
+    fn student_links() {
+        let navbar = navbar(true, false, false, false);
+        assert!(navbar.can_see(Visibility::Students));
+        assert!(!navbar.can_see(Visibility::Mentors));
+        assert_eq!(labels(&navbar), [&PUBLIC[..], &["Engage"]].concat());
+    }
+
+    /// Mentors also see the mentor panel.
+    #[test]
+    fn mentor_links() {
+        let navbar = navbar(false, true, false, false);
+        assert!(navbar.can_see(Visibility::Mentors));
+        assert!(!navbar.can_see(Visibility::Coordinators));
+        assert_eq!(
+            labels(&navbar),
+            [&PUBLIC[..], &["Mentor", "Engage"]].concat()
+        );
+    }
+
+    /// Coordinators also see the coordinator panel.
+    #[test]
+    fn coordinator_links() {
+        let navbar = navbar(false, false, true, false);
+        assert!(navbar.can_see(Visibility::Coordinators));
+        assert!(!navbar.can_see(Visibility::Admins));
+        assert_eq!(
+            labels(&navbar),
+            [&PUBLIC[..], &["Coordinate", "Mentor", "Engage"]].concat()
+        );
+    }
+
+    /// Admins see every link.
+    #[test]
+    fn admin_links() {
+        let navbar = navbar(false, false, false, true);
+        assert!(navbar.can_see(Visibility::Admins));
+        let all: Vec<&str> = NAV_LINKS.iter().map(|(label, _, _)| *label).collect();
+        assert_eq!(labels(&navbar), all);
+    }
+
+    /// The link for the current page is marked active.
+    #[test]
+    fn current_link_is_active() {
+        let navbar = Navbar {
+            req_path: "/projects/42".into(),
+            ..Navbar::empty()
+        }
+        .with_links();
+        let active: Vec<&str> = navbar
+            .links
+            .iter()
+            .filter(|link| link.active)
+            .map(|link| link.label.as_str())
+            .collect();
+        assert_eq!(active, vec!["Projects"]);
+    }
+}
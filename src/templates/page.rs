@@ -1,7 +1,9 @@
+use crate::announcement::Announcement;
+use crate::app_data::AppData;
 use crate::error::TelescopeError;
 use crate::templates::navbar::Navbar;
 use crate::templates::tags::Tags;
-use crate::templates::Template;
+use crate::templates::{html_response, is_partial_request, Template};
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use futures::future::{ready, Ready};
 
@@ -23,6 +25,9 @@ pub struct Page {
 
     /// Open Graph Protocol tags.
     pub ogp_tags: Tags,
+
+    /// The site-wide announcement to show at the top of the page, if there is one.
+    pub announcement: Option<Announcement>,
 }
 
 impl Page {
@@ -41,6 +46,7 @@ impl Page {
             content,
             version: env!("CARGO_PKG_VERSION").to_string(),
             ogp_tags: Tags::for_request(request),
+            announcement: AppData::global().active_announcement(),
         })
     }
 
@@ -70,9 +76,14 @@ impl Responder for Page {
     type Future = Ready<Result<HttpResponse, Self::Error>>;
 
     fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        // Partial requests only get the page content.
+        if is_partial_request(req) {
+            return ready(self.content.render_partial());
+        }
+
         match self.as_template() {
-            // If content can be rendered, respond using the normal template responder.
-            Ok(template) => template.respond_to(req),
+            // If content can be rendered, render the full page.
+            Ok(template) => ready(template.render().map(html_response)),
             // Otherwise return the error immediately.
             Err(err) => ready(Err(err)),
         }
@@ -1,11 +1,41 @@
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
-use crate::templates::Template;
-use actix_web::HttpRequest;
+use crate::templates::{html_response, is_partial_request, Template};
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use actix_web::{HttpRequest, HttpResponse};
 use futures::future::LocalBoxFuture;
+use ring::digest;
 
 pub mod sponsors;
 
+/// The number of bytes of the content hash used in ETags. This is plenty to tell versions of
+/// a page apart.
+const ETAG_HASH_BYTES: usize = 16;
+
+/// The `Cache-Control` header of static pages. Pages change with who is signed in, so shared
+/// caches should not store them, and browsers should check the ETag before reusing them.
+const CACHE_CONTROL_VALUE: &'static str = "private, no-cache";
+
+/// Does the `If-None-Match` header of a request match an ETag? The header can list several
+/// ETags, or be `*` to match any. Weak comparison is used, since that is what conditional
+/// GETs call for.
+fn if_none_match(req: &HttpRequest, etag: &str) -> bool {
+    let header: &str = match req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(header) => header,
+        None => return false,
+    };
+
+    return header
+        .split(',')
+        .map(str::trim)
+        .map(|tag| tag.trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag);
+}
+
 /// A piece of static content. This is just a reference to a
 /// handlebars file and some metadata for rendering the page.
 pub trait StaticPage {
@@ -20,12 +50,134 @@ pub trait StaticPage {
         Template::new(Self::TEMPLATE_NAME)
     }
 
-    /// Create a page containing the static content. This is also the actix handler
-    fn page(req: HttpRequest) -> LocalBoxFuture<'static, Result<Page, TelescopeError>> {
+    /// Compute the (strong, quoted) ETag of a rendered page from a hash of its content.
+    fn etag(rendered: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, rendered.as_bytes());
+        let hex: String = hash.as_ref()[..ETAG_HASH_BYTES]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        return format!("\"{}\"", hex);
+    }
+
+    /// Create a page containing the static content. This is also the actix handler.
+    ///
+    /// The response has an ETag of the rendered page, and if the request's `If-None-Match`
+    /// matches it, `304 Not Modified` is sent without the body. The page is still rendered
+    /// every time, since the navbar depends on who is signed in, so this only saves bandwidth.
+    fn page(req: HttpRequest) -> LocalBoxFuture<'static, Result<HttpResponse, TelescopeError>> {
         Box::pin(async move {
             // We have to double wrap this future to avoid lifetime constraint issue?
             // Or at least adding the async block seems to fix it since it moves the template.
-            Page::new(&req, Self::PAGE_TITLE, Self::template()).await
+            let page: Page = Page::new(&req, Self::PAGE_TITLE, Self::template()).await?;
+
+            // Partial requests only get the page content.
+            let rendered: String = if is_partial_request(&req) {
+                page.content.render()?
+            } else {
+                page.render()?
+            };
+
+            let etag: String = Self::etag(rendered.as_str());
+            return Ok(conditional_response(&req, rendered, etag));
         })
     }
 }
+
+/// Respond to a request with a rendered page and its ETag. If the request's `If-None-Match`
+/// matches the ETag, send `304 Not Modified` without the body instead.
+fn conditional_response(req: &HttpRequest, rendered: String, etag: String) -> HttpResponse {
+    // Send only the headers if the client already has this version.
+    if if_none_match(req, etag.as_str()) {
+        return HttpResponse::NotModified()
+            .header(ETAG, etag)
+            .header(CACHE_CONTROL, CACHE_CONTROL_VALUE)
+            .finish();
+    }
+
+    let mut response: HttpResponse = html_response(rendered);
+    let headers = response.headers_mut();
+    // The ETag is quoted hex, so it is always a valid header value.
+    headers.insert(ETAG, HeaderValue::from_str(etag.as_str()).unwrap());
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_VALUE));
+    return response;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+
+    /// A static page to test the default methods with.
+    struct TestPage;
+
+    impl StaticPage for TestPage {
+        const TEMPLATE_NAME: &'static str = "static/test";
+        const PAGE_TITLE: &'static str = "Test";
+    }
+
+    /// ETags are quoted, the same for the same content, and different for other content.
+    #[test]
+    fn etag_of_content() {
+        let etag: String = TestPage::etag("<p>Sponsors</p>");
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(etag.len(), ETAG_HASH_BYTES * 2 + 2);
+        assert_eq!(etag, TestPage::etag("<p>Sponsors</p>"));
+        assert_ne!(etag, TestPage::etag("<p>About</p>"));
+    }
+
+    /// `If-None-Match` can list several ETags, use weak ETags, or be `*`.
+    #[test]
+    fn if_none_match_forms() {
+        let matches = |header: &str| {
+            let req = TestRequest::default()
+                .header(IF_NONE_MATCH, header)
+                .to_http_request();
+            if_none_match(&req, "\"abc\"")
+        };
+
+        assert!(matches("\"abc\""));
+        assert!(matches("W/\"abc\""));
+        assert!(matches("\"xyz\", \"abc\""));
+        assert!(matches("*"));
+        assert!(!matches("\"xyz\""));
+        assert!(!if_none_match(
+            &TestRequest::default().to_http_request(),
+            "\"abc\""
+        ));
+    }
+
+    /// A matching `If-None-Match` gets `304 Not Modified` without the body.
+    #[actix_rt::test]
+    async fn matching_etag_is_not_modified() {
+        let rendered: String = "<p>Sponsors</p>".into();
+        let etag: String = TestPage::etag(rendered.as_str());
+        let req = TestRequest::default()
+            .header(IF_NONE_MATCH, etag.as_str())
+            .to_http_request();
+
+        let response: HttpResponse = conditional_response(&req, rendered, etag.clone());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(ETAG).unwrap(), etag.as_str());
+        let body = test::read_body(ServiceResponse::new(req, response)).await;
+        assert!(body.is_empty());
+    }
+
+    /// A different `If-None-Match` gets the page and its ETag.
+    #[actix_rt::test]
+    async fn mismatched_etag_gets_page() {
+        let rendered: String = "<p>Sponsors</p>".into();
+        let etag: String = TestPage::etag(rendered.as_str());
+        let req = TestRequest::default()
+            .header(IF_NONE_MATCH, "\"stale\"")
+            .to_http_request();
+
+        let response: HttpResponse = conditional_response(&req, rendered.clone(), etag.clone());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(ETAG).unwrap(), etag.as_str());
+        let body = test::read_body(ServiceResponse::new(req, response)).await;
+        assert_eq!(body, rendered.as_bytes());
+    }
+}
@@ -0,0 +1,247 @@
+//! Identity cookie encryption keys and key rotation.
+//!
+//! Keys are loaded from the config (or a keys file) so that restarting Telescope does not log
+//! everyone out. Several keys may be valid at once. Cookies are always encrypted with the newest
+//! key, but are accepted if any of the keys can decrypt them. Cookies that were decrypted with
+//! an older key are re-issued with the newest one, so old keys can be removed once every active
+//! session has made a request.
+
+use crate::env::global_config;
+use actix_identity::{CookieIdentityPolicy, IdentityPolicy};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::Error as ActixError;
+use actix_web::HttpMessage;
+use futures::future::{ready, Ready};
+use rand::rngs::OsRng;
+use rand::Rng;
+
+/// The minimum length of a cookie key in bytes.
+const MIN_KEY_LENGTH: usize = 32;
+
+/// Get the keys to encrypt identity cookies with, newest first. If no keys are configured, a
+/// random key is generated, and all sessions will be lost when Telescope restarts. This is
+/// fine for development but should not be used in production.
+///
+/// Panics if any configured key is shorter than 32 bytes.
+pub fn resolve_keys() -> Vec<Vec<u8>> {
+    let configured: Vec<Vec<u8>> = global_config()
+        .cookie_keys
+        .iter()
+        .map(|key| key.as_bytes().to_vec())
+        .collect();
+
+    // Fall back to an ephemeral key if there are none configured.
+    if configured.is_empty() {
+        warn!(
+            "No cookie keys configured. Using a random key -- restarting will log out all users."
+        );
+        return vec![OsRng.gen::<[u8; 32]>().to_vec()];
+    }
+
+    // Check that all the keys are long enough.
+    for (index, key) in configured.iter().enumerate() {
+        if key.len() < MIN_KEY_LENGTH {
+            panic!(
+                "Cookie key {} is {} bytes long. Cookie keys must be at least {} bytes.",
+                index,
+                key.len(),
+                MIN_KEY_LENGTH
+            );
+        }
+    }
+
+    info!("Loaded {} cookie key(s).", configured.len());
+    return configured;
+}
+
+/// Marker in request extensions that the identity cookie was decrypted with an older key and
+/// should be re-issued with the newest one.
+struct ReissueCookie;
+
+/// Identity policy that accepts cookies encrypted with any of several keys and encrypts new
+/// cookies with the newest.
+pub struct RotatingCookieIdentityPolicy {
+    /// One cookie policy for each key, newest first.
+    policies: Vec<CookieIdentityPolicy>,
+}
+
+impl RotatingCookieIdentityPolicy {
+    /// Create a rotating policy from a list of keys (newest first). The cookie policy for each
+    /// key is set up using the `configure` function, so that they all share the same settings.
+    ///
+    /// Panics if there are no keys.
+    pub fn new(
+        keys: &[Vec<u8>],
+        configure: impl Fn(CookieIdentityPolicy) -> CookieIdentityPolicy,
+    ) -> Self {
+        assert!(!keys.is_empty(), "At least one cookie key is required.");
+
+        RotatingCookieIdentityPolicy {
+            policies: keys
+                .iter()
+                .map(|key| configure(CookieIdentityPolicy::new(key.as_slice())))
+                .collect(),
+        }
+    }
+}
+
+impl IdentityPolicy for RotatingCookieIdentityPolicy {
+    type Future = Ready<Result<Option<String>, ActixError>>;
+    type ResponseFuture = <CookieIdentityPolicy as IdentityPolicy>::ResponseFuture;
+
+    fn from_request(&self, request: &mut ServiceRequest) -> Self::Future {
+        // Try each key from newest to oldest.
+        for (index, policy) in self.policies.iter().enumerate() {
+            match policy.from_request(request).into_inner() {
+                // Found an identity.
+                Ok(Some(identity)) => {
+                    // Mark the cookie for re-issue if it used an older key.
+                    if index > 0 {
+                        request.extensions_mut().insert(ReissueCookie);
+                    }
+                    return ready(Ok(Some(identity)));
+                }
+
+                // This key could not decrypt the cookie (or there is no cookie).
+                Ok(None) => continue,
+
+                // Propagate any other errors.
+                Err(err) => return ready(Err(err)),
+            }
+        }
+
+        // None of the keys worked.
+        return ready(Ok(None));
+    }
+
+    fn to_response<B>(
+        &self,
+        identity: Option<String>,
+        changed: bool,
+        response: &mut ServiceResponse<B>,
+    ) -> Self::ResponseFuture {
+        // Re-issue cookies that were decrypted with an older key.
+        let reissue: bool = identity.is_some()
+            && response
+                .request()
+                .extensions()
+                .get::<ReissueCookie>()
+                .is_some();
+
+        // Always write cookies with the newest key.
+        return self.policies[0].to_response(identity, changed || reissue, response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_identity::{Identity, IdentityService};
+    use actix_web::cookie::Cookie;
+    use actix_web::{test, web, App, HttpResponse};
+
+    /// The key cookies were encrypted with before rotation.
+    const OLD_KEY: [u8; 32] = [1; 32];
+
+    /// The key added by the rotation.
+    const NEW_KEY: [u8; 32] = [2; 32];
+
+    /// Set up each cookie policy the same way, like `main` does.
+    fn configure(policy: CookieIdentityPolicy) -> CookieIdentityPolicy {
+        policy.name("telescope_auth").secure(false)
+    }
+
+    /// Remember the visitor as "user".
+    async fn login(id: Identity) -> HttpResponse {
+        id.remember("user".into());
+        HttpResponse::Ok().finish()
+    }
+
+    /// Respond with the identity the visitor authenticated as.
+    async fn whoami(id: Identity) -> HttpResponse {
+        HttpResponse::Ok().body(id.identity().unwrap_or_default())
+    }
+
+    /// Log in with a policy using only the given keys, and get the identity cookie.
+    async fn login_cookie(keys: &[Vec<u8>]) -> Cookie<'static> {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(IdentityService::new(RotatingCookieIdentityPolicy::new(
+                    keys, configure,
+                )))
+                .route("/login", web::get().to(login)),
+        )
+        .await;
+
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::get().uri("/login").to_request(),
+        )
+        .await;
+        return response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "telescope_auth")
+            .expect("No identity cookie set")
+            .into_owned();
+    }
+
+    /// Send a cookie to a policy with the given keys. Return the identity it authenticated
+    /// as, and whether the cookie was re-issued.
+    async fn authenticate(keys: &[Vec<u8>], cookie: Cookie<'static>) -> (Option<String>, bool) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(IdentityService::new(RotatingCookieIdentityPolicy::new(
+                    keys, configure,
+                )))
+                .route("/whoami", web::get().to(whoami)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/whoami")
+            .cookie(cookie)
+            .to_request();
+        let response = test::call_service(&mut app, request).await;
+        let reissued: bool = response
+            .response()
+            .cookies()
+            .any(|cookie| cookie.name() == "telescope_auth");
+        let body = test::read_body(response).await;
+        let identity: Option<String> =
+            Some(String::from_utf8(body.to_vec()).unwrap()).filter(|identity| !identity.is_empty());
+        return (identity, reissued);
+    }
+
+    /// A cookie encrypted with an older key still authenticates after rotation, and is
+    /// re-issued with the newest key.
+    #[actix_rt::test]
+    async fn old_key_still_authenticates() {
+        let cookie = login_cookie(&[OLD_KEY.to_vec()]).await;
+        let rotated: Vec<Vec<u8>> = vec![NEW_KEY.to_vec(), OLD_KEY.to_vec()];
+
+        let (identity, reissued) = authenticate(&rotated, cookie).await;
+        assert_eq!(identity.as_deref(), Some("user"));
+        assert!(reissued);
+    }
+
+    /// A cookie encrypted with the newest key is not re-issued.
+    #[actix_rt::test]
+    async fn newest_key_is_not_reissued() {
+        let rotated: Vec<Vec<u8>> = vec![NEW_KEY.to_vec(), OLD_KEY.to_vec()];
+        let cookie = login_cookie(&rotated).await;
+
+        let (identity, reissued) = authenticate(&rotated, cookie).await;
+        assert_eq!(identity.as_deref(), Some("user"));
+        assert!(!reissued);
+    }
+
+    /// A cookie encrypted with a key that has been removed no longer authenticates.
+    #[actix_rt::test]
+    async fn removed_key_does_not_authenticate() {
+        let cookie = login_cookie(&[OLD_KEY.to_vec()]).await;
+
+        let (identity, _) = authenticate(&[NEW_KEY.to_vec()], cookie).await;
+        assert_eq!(identity, None);
+    }
+}
@@ -1,79 +1,114 @@
 //! Cross Site Request Forging protection via a global static DashMap.
+//!
+//! Tokens are scoped to the browser that started the authorization request, using a random
+//! scope ID kept in a cookie. (Scoping by IP address made users behind the same campus NAT or
+//! proxy overwrite each other's tokens.) Each token can only be used once: it is removed as
+//! soon as it is checked, whether or not it matched, and the next authorization request
+//! generates a new one.
 
 use crate::error::TelescopeError;
 use actix::{Actor, AsyncContext, Context};
-use actix_web::HttpRequest;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use oauth2::CsrfToken;
 use std::sync::Arc;
 use std::time::Duration as StdDuration;
+use uuid::Uuid;
 
-/// Extract the remote IP address string from an HTTP request's headers.
-fn extract_ip_addr(req: &HttpRequest) -> Result<String, TelescopeError> {
-    req.connection_info()
-        .realip_remote_addr()
-        .map(str::to_string)
-        .ok_or(TelescopeError::IpExtractionError)
-}
+/// The cookie holding the browser's CSRF scope ID.
+const SCOPE_COOKIE: &'static str = "telescope_csrf_scope";
+
+/// How long a CSRF token is valid for.
+const TOKEN_LIFETIME_MINUTES: i64 = 10;
+
+/// How long the scope cookie is kept by the browser.
+const SCOPE_COOKIE_DAYS: i64 = 1;
 
 lazy_static! {
-    static ref GLOBAL_CSRF_MAP: Arc<DashMap<(&'static str, String), (CsrfToken, DateTime<Utc>)>> =
+    static ref GLOBAL_CSRF_MAP: Arc<DashMap<(String, String), (CsrfToken, DateTime<Utc>)>> =
         Arc::new(DashMap::new());
 }
 
 /// Get the global lazy static CSRF map.
-fn global_csrf_map() -> Arc<DashMap<(&'static str, String), (CsrfToken, DateTime<Utc>)>> {
+fn global_csrf_map() -> Arc<DashMap<(String, String), (CsrfToken, DateTime<Utc>)>> {
     GLOBAL_CSRF_MAP.clone()
 }
 
-/// Get the CSRF Token for a request's IP from the global CSRF map.
-fn get(idp_name: &'static str, req: &HttpRequest) -> Result<CsrfToken, TelescopeError> {
-    // Extract the IP address from the HTTP Request.
-    let ip_addr: String = extract_ip_addr(req)?;
-    return global_csrf_map()
-        // Get the record from the global CSRF map.
-        .get(&(idp_name, ip_addr))
-        // Filter out expired CSRF tokens.
-        .filter(|record| record.value().1 > Utc::now())
-        // Strip away the expiration data.
-        .map(|record| record.value().0.clone())
-        // Return an error if the record was not found.
-        .ok_or(TelescopeError::CsrfTokenNotFound);
+/// Get the CSRF scope ID of the browser that sent a request, if it has a valid one.
+fn request_scope(req: &HttpRequest) -> Option<String> {
+    let cookie = req.cookie(SCOPE_COOKIE)?;
+    // Scope IDs are UUIDs. Anything else was not set by Telescope.
+    let scope: Uuid = cookie.value().parse().ok()?;
+    return Some(scope.to_simple().to_string());
 }
 
-/// Save a CSRF token linked to the remote IP of the Http Request that created it.
+/// Get the scope to save a new CSRF token under. This is the browser's existing scope if it
+/// has one, or a new one otherwise. Return the scope and, if it is new, the cookie to set.
+fn scope_for_save(req: &HttpRequest) -> (String, Option<Cookie<'static>>) {
+    if let Some(scope) = request_scope(req) {
+        return (scope, None);
+    }
+
+    let scope: String = Uuid::new_v4().to_simple().to_string();
+    let cookie: Cookie<'static> = Cookie::build(SCOPE_COOKIE, scope.clone())
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        // Lax so that the cookie is sent when the identity provider redirects back.
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(SCOPE_COOKIE_DAYS))
+        .finish();
+    return (scope, Some(cookie));
+}
+
+/// Save a CSRF token for the browser that sent a request. `name` is the identity provider
+/// or form the token is for. Forms acting on a specific record should include its ID in the
+/// name, so that a token cannot be used for another record. If the browser does not have a
+/// CSRF scope yet, a cookie giving it one is added to `response`. Any earlier token with the
+/// same name for the same browser is replaced.
 pub fn save(
-    idp_name: &'static str,
+    name: &str,
     req: &HttpRequest,
+    response: &mut HttpResponse,
     token: CsrfToken,
 ) -> Result<(), TelescopeError> {
-    // Get the remote IP address string.
-    let ip_addr: String = extract_ip_addr(req)?;
-    // Get the current time and add the expiration duration (10 minutes) to get the
-    // expiration time.
-    let expiration_time: DateTime<Utc> = Utc::now() + Duration::minutes(10);
-    // Save the IP Address to the CSRF map and return OK.
-    global_csrf_map().insert((idp_name, ip_addr), (token, expiration_time));
+    let (scope, new_cookie) = scope_for_save(req);
+    if let Some(cookie) = new_cookie {
+        response.add_cookie(&cookie).map_err(|e| {
+            TelescopeError::ise(format!("Could not set the CSRF scope cookie: {}", e))
+        })?;
+    }
+
+    // Get the current time and add the expiration duration to get the expiration time.
+    let expiration_time: DateTime<Utc> = Utc::now() + Duration::minutes(TOKEN_LIFETIME_MINUTES);
+    // Save the token under the scope and return OK.
+    global_csrf_map().insert((name.to_string(), scope), (token, expiration_time));
     return Ok(());
 }
 
-/// Verify a CSRF token returned from an Identity provider. If there is an issue
-/// return a [`TelescopeError`].
-pub fn verify(
-    idp_name: &'static str,
-    req: &HttpRequest,
-    token: CsrfToken,
-) -> Result<(), TelescopeError> {
-    // Get the CSRF token from the global table.
-    let actual_token: CsrfToken = get(idp_name, req)?;
-    // Remove the CSRF record from the global table.
-    // We do this here because it should happen regardless of whether
-    // the tokens match.
-    // Extract the IP first.
-    let ip_addr: String = extract_ip_addr(req)?;
-    // Remove the CSRF record.
-    global_csrf_map().remove(&(idp_name, ip_addr));
+/// Verify a CSRF token returned from an Identity provider (or submitted with a form), saved
+/// under the same name. If there is an issue return a [`TelescopeError`].
+///
+/// The saved token is removed whether or not it matches, so it cannot be replayed.
+pub fn verify(name: &str, req: &HttpRequest, token: CsrfToken) -> Result<(), TelescopeError> {
+    // Find the browser's scope.
+    let scope: String = request_scope(req).ok_or(TelescopeError::CsrfTokenNotFound)?;
+
+    // Take the CSRF record out of the global table. We do this here because it should
+    // happen regardless of whether the tokens match.
+    let (actual_token, expiration) = global_csrf_map()
+        .remove(&(name.to_string(), scope))
+        .map(|(_, record)| record)
+        // Return an error if the record was not found.
+        .ok_or(TelescopeError::CsrfTokenNotFound)?;
+
+    // Expired tokens are treated as missing.
+    if expiration <= Utc::now() {
+        return Err(TelescopeError::CsrfTokenNotFound);
+    }
+
     // Check for a mismatch.
     return (actual_token.secret() == token.secret())
         // Return Ok(()) on match.
@@ -124,3 +159,136 @@ impl Actor for CsrfJanitor {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    /// Save a token for a new browser. Return the browser's scope cookie.
+    fn save_new(name: &str, token: &str) -> Cookie<'static> {
+        let req: HttpRequest = TestRequest::default().to_http_request();
+        let mut response: HttpResponse = HttpResponse::Ok().finish();
+        save(name, &req, &mut response, CsrfToken::new(token.into())).unwrap();
+        return response
+            .cookies()
+            .find(|cookie| cookie.name() == SCOPE_COOKIE)
+            .expect("No scope cookie set")
+            .into_owned();
+    }
+
+    /// Make a request from the browser with the given scope cookie.
+    fn request_with(cookie: &Cookie<'static>) -> HttpRequest {
+        TestRequest::default()
+            .cookie(cookie.clone())
+            .to_http_request()
+    }
+
+    /// Browsers without a scope get a new one, and browsers with one keep it.
+    #[test]
+    fn scope_selection() {
+        let req: HttpRequest = TestRequest::default().to_http_request();
+        let (scope, cookie) = scope_for_save(&req);
+        let cookie: Cookie<'static> = cookie.expect("New scopes set a cookie");
+        assert_eq!(cookie.value(), scope);
+
+        let (same_scope, new_cookie) = scope_for_save(&request_with(&cookie));
+        assert_eq!(same_scope, scope);
+        assert!(new_cookie.is_none());
+    }
+
+    /// Scope cookies that were not set by Telescope are ignored.
+    #[test]
+    fn malformed_scope_is_replaced() {
+        let forged: Cookie<'static> = Cookie::new(SCOPE_COOKIE, "../../etc");
+        assert_eq!(request_scope(&request_with(&forged)), None);
+
+        let (scope, cookie) = scope_for_save(&request_with(&forged));
+        assert_ne!(scope, "../../etc");
+        assert!(cookie.is_some());
+    }
+
+    /// A token can be used once. Using it again fails.
+    #[test]
+    fn token_is_single_use() {
+        let cookie = save_new("test_single_use", "secret");
+        let req: HttpRequest = request_with(&cookie);
+
+        assert!(verify("test_single_use", &req, CsrfToken::new("secret".into())).is_ok());
+        assert!(matches!(
+            verify("test_single_use", &req, CsrfToken::new("secret".into())),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+    }
+
+    /// A wrong token fails, and the saved token cannot be used after that either.
+    #[test]
+    fn mismatch_invalidates_token() {
+        let cookie = save_new("test_mismatch", "secret");
+        let req: HttpRequest = request_with(&cookie);
+
+        assert!(matches!(
+            verify("test_mismatch", &req, CsrfToken::new("guess".into())),
+            Err(TelescopeError::CsrfTokenMismatch)
+        ));
+        assert!(matches!(
+            verify("test_mismatch", &req, CsrfToken::new("secret".into())),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+    }
+
+    /// Tokens are only valid for the browser and form they were saved for.
+    #[test]
+    fn tokens_are_scoped() {
+        let first = save_new("test_scoped", "first secret");
+        let second = save_new("test_scoped", "second secret");
+
+        // Another browser's token does not match.
+        assert!(matches!(
+            verify(
+                "test_scoped",
+                &request_with(&first),
+                CsrfToken::new("second secret".into())
+            ),
+            Err(TelescopeError::CsrfTokenMismatch)
+        ));
+        // Neither does another form's token.
+        assert!(matches!(
+            verify(
+                "test_other_form",
+                &request_with(&second),
+                CsrfToken::new("second secret".into())
+            ),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+        assert!(verify(
+            "test_scoped",
+            &request_with(&second),
+            CsrfToken::new("second secret".into())
+        )
+        .is_ok());
+    }
+
+    /// Expired tokens are rejected, and the janitor removes them.
+    #[test]
+    fn expired_tokens() {
+        let cookie = save_new("test_expired", "secret");
+        let scope: String = request_scope(&request_with(&cookie)).unwrap();
+        let key = ("test_expired".to_string(), scope);
+        let expired: DateTime<Utc> = Utc::now() - Duration::minutes(TOKEN_LIFETIME_MINUTES);
+
+        global_csrf_map().insert(key.clone(), (CsrfToken::new("secret".into()), expired));
+        assert!(matches!(
+            verify(
+                "test_expired",
+                &request_with(&cookie),
+                CsrfToken::new("secret".into())
+            ),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+
+        global_csrf_map().insert(key.clone(), (CsrfToken::new("secret".into()), expired));
+        assert!(CsrfJanitor.call() >= 1);
+        assert!(!global_csrf_map().contains_key(&key));
+    }
+}
@@ -0,0 +1,108 @@
+//! Streaming CSV responses.
+//!
+//! Like [`json_stream`](super::json_stream), rows are serialized one at a time as the response
+//! body is sent. The header row is written before the first row, using the field names of the
+//! row type, so an empty export is an empty body.
+
+use actix_web::error::{Error as ActixError, ErrorInternalServerError};
+use actix_web::web::Bytes;
+use csv::WriterBuilder;
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+
+/// Serialize one row of a CSV stream, with the header row before it if it is the first.
+fn csv_row<T: Serialize>(index: usize, row: &T) -> Result<Bytes, csv::Error> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(index == 0)
+        .from_writer(Vec::new());
+    writer.serialize(row)?;
+    let chunk: Vec<u8> = writer.into_inner().map_err(|e| {
+        csv::Error::from(std::io::Error::new(e.error().kind(), e.error().to_string()))
+    })?;
+    return Ok(Bytes::from(chunk));
+}
+
+/// Stream rows as a CSV file. If a row cannot be serialized the stream ends with an error,
+/// which drops the connection instead of sending a truncated file with a success status.
+pub fn csv_rows<I>(rows: I) -> impl Stream<Item = Result<Bytes, ActixError>> + Unpin + 'static
+where
+    I: IntoIterator,
+    I::IntoIter: 'static,
+    I::Item: Serialize,
+{
+    stream::iter(rows.into_iter().enumerate()).map(|(index, row)| {
+        csv_row(index, &row).map_err(|e| {
+            error!("Could not serialize row {} of CSV stream: {}", index, e);
+            ErrorInternalServerError(e)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::TryStreamExt;
+
+    /// A sample row.
+    #[derive(Serialize)]
+    struct Row {
+        id: u32,
+        title: &'static str,
+        location: Option<&'static str>,
+    }
+
+    /// Collect a stream into one string.
+    async fn collect(stream: impl Stream<Item = Result<Bytes, ActixError>> + Unpin) -> String {
+        let body: Vec<u8> = stream
+            .try_fold(Vec::new(), |mut body, chunk| async move {
+                body.extend_from_slice(chunk.as_ref());
+                Ok(body)
+            })
+            .await
+            .unwrap();
+        return String::from_utf8(body).unwrap();
+    }
+
+    /// The header row is written once, before the first row.
+    #[actix_rt::test]
+    async fn header_row() {
+        let body: String = collect(csv_rows(vec![
+            Row {
+                id: 1,
+                title: "Large Group",
+                location: Some("DCC 308"),
+            },
+            Row {
+                id: 2,
+                title: "Small Group",
+                location: None,
+            },
+        ]))
+        .await;
+        assert_eq!(
+            body,
+            "id,title,location\n1,Large Group,DCC 308\n2,Small Group,\n"
+        );
+    }
+
+    /// Values with commas, quotes, or line breaks are quoted.
+    #[actix_rt::test]
+    async fn quoting() {
+        let body: String = collect(csv_rows(vec![Row {
+            id: 3,
+            title: "Mentor \"Office\" Hours, Week 2",
+            location: Some("Line one\nLine two"),
+        }]))
+        .await;
+        assert_eq!(
+            body,
+            "id,title,location\n3,\"Mentor \"\"Office\"\" Hours, Week 2\",\"Line one\nLine two\"\n"
+        );
+    }
+
+    /// An empty export is an empty body, without a header row.
+    #[actix_rt::test]
+    async fn empty() {
+        assert_eq!(collect(csv_rows(Vec::<Row>::new())).await, "");
+    }
+}
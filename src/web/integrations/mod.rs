@@ -0,0 +1,4 @@
+//! Utilities shared by integrations with outside services (such as webhooks from GitHub and
+//! Discord).
+
+pub mod signatures;
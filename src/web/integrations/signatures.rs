@@ -0,0 +1,320 @@
+//! Webhook signature verification.
+//!
+//! Webhook endpoints must check that requests really come from the service that claims to
+//! send them. Each service signs the raw request body its own way: GitHub uses HMAC-SHA256
+//! with a shared secret, and Discord uses Ed25519 with the application's public key. All
+//! comparisons here are constant-time (through `ring`), so response timing does not reveal how
+//! much of a forged signature was correct.
+//!
+//! Signatures must be checked against the exact bytes of the request body, before it is
+//! parsed. A valid signature only shows who sent a request, not when, so endpoints should also
+//! reject requests they have already accepted (using a [`ReplayGuard`]) and, where the service
+//! signs a timestamp, requests signed too long ago.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use dashmap::DashMap;
+use ring::{hmac, signature};
+
+/// The header GitHub puts the HMAC-SHA256 signature of a webhook payload in.
+pub const GITHUB_SIGNATURE_HEADER: &'static str = "X-Hub-Signature-256";
+
+/// The prefix of the value of [`GITHUB_SIGNATURE_HEADER`].
+const GITHUB_SIGNATURE_PREFIX: &'static str = "sha256=";
+
+/// The header Discord puts the Ed25519 signature of an interaction payload in.
+pub const DISCORD_SIGNATURE_HEADER: &'static str = "X-Signature-Ed25519";
+
+/// The header Discord puts the timestamp signed with an interaction payload in.
+pub const DISCORD_TIMESTAMP_HEADER: &'static str = "X-Signature-Timestamp";
+
+/// How far (in seconds) a signed timestamp may be from the current time.
+const MAX_TIMESTAMP_SKEW_SECONDS: i64 = 5 * 60;
+
+/// Decode a hexadecimal string (either case). Return `None` if it is not valid hex.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    return hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some((high * 16 + low) as u8)
+        })
+        .collect();
+}
+
+/// Check the HMAC-SHA256 signature of a payload, given as hex. This is the scheme GitHub
+/// uses, and may be reused by other services that sign payloads with a shared secret.
+pub fn verify_hmac_sha256(secret: &[u8], payload: &[u8], signature_hex: &str) -> bool {
+    let signature: Vec<u8> = match decode_hex(signature_hex.trim()) {
+        Some(signature) => signature,
+        None => return false,
+    };
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    return hmac::verify(&key, payload, signature.as_slice()).is_ok();
+}
+
+/// Check a GitHub webhook delivery. `signature_header` is the value of the
+/// [`GITHUB_SIGNATURE_HEADER`] header (`sha256=` followed by the hex signature) and `payload`
+/// is the raw request body.
+pub fn verify_github(secret: &[u8], payload: &[u8], signature_header: &str) -> bool {
+    return signature_header
+        .trim()
+        .strip_prefix(GITHUB_SIGNATURE_PREFIX)
+        .map(|signature_hex| verify_hmac_sha256(secret, payload, signature_hex))
+        .unwrap_or(false);
+}
+
+/// Check an Ed25519 signature of a message. The public key and signature are given as hex.
+pub fn verify_ed25519(public_key_hex: &str, message: &[u8], signature_hex: &str) -> bool {
+    let (public_key, signature) = match (
+        decode_hex(public_key_hex.trim()),
+        decode_hex(signature_hex.trim()),
+    ) {
+        (Some(public_key), Some(signature)) => (public_key, signature),
+        _ => return false,
+    };
+
+    return signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(message, signature.as_slice())
+        .is_ok();
+}
+
+/// Check a Discord interaction webhook. Discord signs the value of the
+/// [`DISCORD_TIMESTAMP_HEADER`] header followed by the raw request body, and puts the
+/// signature in the [`DISCORD_SIGNATURE_HEADER`] header. `public_key_hex` is the application's
+/// public key from the Discord developer portal.
+pub fn verify_discord(
+    public_key_hex: &str,
+    timestamp: &str,
+    payload: &[u8],
+    signature_hex: &str,
+) -> bool {
+    let mut message: Vec<u8> = Vec::with_capacity(timestamp.len() + payload.len());
+    message.extend_from_slice(timestamp.as_bytes());
+    message.extend_from_slice(payload);
+    return verify_ed25519(public_key_hex, message.as_slice(), signature_hex);
+}
+
+/// Check that a signed UNIX timestamp (in seconds) is close enough to `now`, so that an old
+/// signed request cannot be sent again later.
+pub fn is_fresh_timestamp(timestamp: &str, now: DateTime<Utc>) -> bool {
+    let signed_at: DateTime<Utc> = match timestamp.trim().parse::<i64>() {
+        Ok(seconds) => match Utc.timestamp_opt(seconds, 0).single() {
+            Some(signed_at) => signed_at,
+            None => return false,
+        },
+        Err(_) => return false,
+    };
+
+    return (now - signed_at).num_seconds().abs() <= MAX_TIMESTAMP_SKEW_SECONDS;
+}
+
+/// Remembers the signed requests an endpoint accepted recently, so that a captured request
+/// cannot be accepted twice.
+pub struct ReplayGuard {
+    /// How long accepted requests are remembered.
+    window: Duration,
+    /// When each remembered request was accepted, keyed by its signature or delivery ID.
+    seen: DashMap<String, DateTime<Utc>>,
+}
+
+impl ReplayGuard {
+    /// Create a guard that remembers requests for the given time.
+    pub fn new(window: Duration) -> Self {
+        ReplayGuard {
+            window,
+            seen: DashMap::new(),
+        }
+    }
+
+    /// Record a request by its signature or delivery ID. Return `false` if the same one was
+    /// already recorded within the window.
+    pub fn first_use(&self, key: &str, now: DateTime<Utc>) -> bool {
+        // Forget requests from before the window.
+        let cutoff: DateTime<Utc> = now - self.window;
+        self.seen.retain(|_, seen_at| *seen_at > cutoff);
+
+        return self.seen.insert(key.to_string(), now).is_none();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    /// Encode bytes as lowercase hex.
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Sign a payload the way GitHub does, returning the header value.
+    fn github_signature(secret: &[u8], payload: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        return format!("sha256={}", hex(hmac::sign(&key, payload).as_ref()));
+    }
+
+    /// A key pair for Discord signatures. Return it with its public key as hex.
+    fn discord_key() -> (Ed25519KeyPair, String) {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let public_key: String = hex(key_pair.public_key().as_ref());
+        return (key_pair, public_key);
+    }
+
+    /// Sign a payload the way Discord does, returning the signature as hex.
+    fn discord_signature(key_pair: &Ed25519KeyPair, timestamp: &str, payload: &[u8]) -> String {
+        let mut message: Vec<u8> = timestamp.as_bytes().to_vec();
+        message.extend_from_slice(payload);
+        return hex(key_pair.sign(message.as_slice()).as_ref());
+    }
+
+    /// Hex is decoded in either case, and anything else is rejected.
+    #[test]
+    fn hex_decoding() {
+        assert_eq!(decode_hex("00fF10"), Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    /// A GitHub delivery signed with the secret is accepted.
+    #[test]
+    fn github_valid() {
+        let payload: &[u8] = br#"{"zen":"Keep it logically awesome."}"#;
+        let header: String = github_signature(b"secret", payload);
+        assert!(verify_github(b"secret", payload, header.as_str()));
+        // Uppercase hex is fine too.
+        assert!(verify_github(
+            b"secret",
+            payload,
+            header.to_uppercase().replace("SHA256=", "sha256=").as_str()
+        ));
+    }
+
+    /// Changing the payload, the signature, or the secret fails.
+    #[test]
+    fn github_tampered() {
+        let payload: &[u8] = br#"{"action":"opened"}"#;
+        let header: String = github_signature(b"secret", payload);
+
+        assert!(!verify_github(
+            b"secret",
+            br#"{"action":"closed"}"#,
+            header.as_str()
+        ));
+        assert!(!verify_github(b"other secret", payload, header.as_str()));
+        let mut flipped: String = header.clone();
+        let last: char = if flipped.ends_with('0') { '1' } else { '0' };
+        flipped.pop();
+        flipped.push(last);
+        assert!(!verify_github(b"secret", payload, flipped.as_str()));
+        // The signature must have the right prefix.
+        assert!(!verify_github(
+            b"secret",
+            payload,
+            header.trim_start_matches("sha256=")
+        ));
+        assert!(!verify_github(b"secret", payload, ""));
+    }
+
+    /// A Discord interaction signed with the application's key is accepted.
+    #[test]
+    fn discord_valid() {
+        let (key_pair, public_key) = discord_key();
+        let payload: &[u8] = br#"{"type":1}"#;
+        let signature: String = discord_signature(&key_pair, "1700000000", payload);
+        assert!(verify_discord(
+            public_key.as_str(),
+            "1700000000",
+            payload,
+            signature.as_str()
+        ));
+    }
+
+    /// Changing the payload, the timestamp, or the signature fails.
+    #[test]
+    fn discord_tampered() {
+        let (key_pair, public_key) = discord_key();
+        let payload: &[u8] = br#"{"type":1}"#;
+        let signature: String = discord_signature(&key_pair, "1700000000", payload);
+
+        assert!(!verify_discord(
+            public_key.as_str(),
+            "1700000000",
+            br#"{"type":2}"#,
+            signature.as_str()
+        ));
+        assert!(!verify_discord(
+            public_key.as_str(),
+            "1700000001",
+            payload,
+            signature.as_str()
+        ));
+        assert!(!verify_discord(
+            public_key.as_str(),
+            "1700000000",
+            payload,
+            &signature[2..]
+        ));
+        assert!(!verify_discord(
+            "not hex",
+            "1700000000",
+            payload,
+            signature.as_str()
+        ));
+    }
+
+    /// Timestamps are only fresh within a few minutes of now.
+    #[test]
+    fn timestamp_freshness() {
+        let now: DateTime<Utc> = Utc.timestamp(1_700_000_000, 0);
+        assert!(is_fresh_timestamp("1700000000", now));
+        assert!(is_fresh_timestamp("1699999800", now));
+        assert!(!is_fresh_timestamp("1699999000", now));
+        assert!(!is_fresh_timestamp("1700001000", now));
+        assert!(!is_fresh_timestamp("yesterday", now));
+    }
+
+    /// A replayed signature is refused within the window and forgotten after it.
+    #[test]
+    fn replayed_signatures() {
+        let guard = ReplayGuard::new(Duration::minutes(5));
+        let now: DateTime<Utc> = Utc.timestamp(1_700_000_000, 0);
+
+        assert!(guard.first_use("signature", now));
+        assert!(!guard.first_use("signature", now + Duration::minutes(1)));
+        assert!(guard.first_use("other signature", now + Duration::minutes(1)));
+        assert!(guard.first_use("signature", now + Duration::minutes(6)));
+    }
+
+    /// A replayed Discord interaction is refused even though its signature is valid.
+    #[test]
+    fn replayed_discord_interaction() {
+        let (key_pair, public_key) = discord_key();
+        let guard = ReplayGuard::new(Duration::minutes(5));
+        let now: DateTime<Utc> = Utc.timestamp(1_700_000_000, 0);
+        let payload: &[u8] = br#"{"type":1}"#;
+        let signature: String = discord_signature(&key_pair, "1700000000", payload);
+
+        let accept = |at: DateTime<Utc>| {
+            verify_discord(
+                public_key.as_str(),
+                "1700000000",
+                payload,
+                signature.as_str(),
+            ) && is_fresh_timestamp("1700000000", at)
+                && guard.first_use(signature.as_str(), at)
+        };
+        assert!(accept(now));
+        // Sent again soon after: the guard remembers it.
+        assert!(!accept(now + Duration::seconds(30)));
+        // Sent again much later: the timestamp is too old.
+        assert!(!accept(now + Duration::hours(1)));
+    }
+}
@@ -0,0 +1,120 @@
+//! Streaming JSON array responses.
+//!
+//! Large list exports are serialized one row at a time as the response body is sent, rather
+//! than into a single buffer up front. The status code and headers go out before the first
+//! row, so anything that can fail (API queries, authorization) has to happen before the
+//! stream is created.
+
+use actix_web::error::{Error as ActixError, ErrorInternalServerError};
+use actix_web::web::Bytes;
+use futures::future::ready;
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+
+/// Stream rows as a JSON array. Each row is serialized when the response body asks for it.
+/// If a row cannot be serialized the stream ends with an error, which drops the connection
+/// instead of sending a truncated array with a success status.
+pub fn json_array<I>(rows: I) -> impl Stream<Item = Result<Bytes, ActixError>> + Unpin + 'static
+where
+    I: IntoIterator,
+    I::IntoIter: 'static,
+    I::Item: Serialize,
+{
+    // Opening bracket.
+    let open = stream::once(ready(Ok(Bytes::from_static(b"["))));
+
+    // One chunk per row, with a comma before every row but the first.
+    let body = stream::iter(rows.into_iter().enumerate()).map(
+        |(index, row)| -> Result<Bytes, ActixError> {
+            let mut chunk: Vec<u8> = if index == 0 { Vec::new() } else { vec![b','] };
+            serde_json::to_writer(&mut chunk, &row).map_err(|e| {
+                error!("Could not serialize row {} of JSON stream: {}", index, e);
+                ErrorInternalServerError(e)
+            })?;
+            return Ok(Bytes::from(chunk));
+        },
+    );
+
+    // Closing bracket.
+    let close = stream::once(ready(Ok(Bytes::from_static(b"]"))));
+
+    return open.chain(body).chain(close);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::TryStreamExt;
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+
+    /// A sample row.
+    #[derive(Serialize)]
+    struct Row {
+        id: u32,
+        title: &'static str,
+    }
+
+    /// Collect a stream into one body.
+    async fn collect(stream: impl Stream<Item = Result<Bytes, ActixError>> + Unpin) -> Vec<u8> {
+        stream
+            .try_fold(Vec::new(), |mut body, chunk| async move {
+                body.extend_from_slice(chunk.as_ref());
+                Ok(body)
+            })
+            .await
+            .unwrap()
+    }
+
+    /// No rows stream as an empty array.
+    #[actix_rt::test]
+    async fn empty_array() {
+        let body: Vec<u8> = collect(json_array(Vec::<Row>::new())).await;
+        assert_eq!(body, b"[]");
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!([]));
+    }
+
+    /// One row streams as an array without a comma.
+    #[actix_rt::test]
+    async fn single_row() {
+        let body: Vec<u8> = collect(json_array(vec![Row {
+            id: 1,
+            title: "Large Group",
+        }]))
+        .await;
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!([{ "id": 1, "title": "Large Group" }])
+        );
+    }
+
+    /// Many rows stream as an array in order.
+    #[actix_rt::test]
+    async fn many_rows() {
+        let rows = (1..=50).map(|id| Row {
+            id,
+            title: "Mentor, \"Meeting\"",
+        });
+        let body: Vec<u8> = collect(json_array(rows.collect::<Vec<Row>>())).await;
+
+        let parsed: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed.len(), 50);
+        for (index, row) in parsed.iter().enumerate() {
+            assert_eq!(row["id"], index as u64 + 1);
+            assert_eq!(row["title"], "Mentor, \"Meeting\"");
+        }
+    }
+
+    /// A row that cannot be serialized ends the stream with an error.
+    #[actix_rt::test]
+    async fn unserializable_row_fails() {
+        // JSON object keys must be strings.
+        let mut row: BTreeMap<(u32, u32), u32> = BTreeMap::new();
+        row.insert((1, 2), 3);
+
+        let result = json_array(vec![row])
+            .try_fold(0, |rows: usize, _| async move { Ok(rows + 1) })
+            .await;
+        assert!(result.is_err());
+    }
+}
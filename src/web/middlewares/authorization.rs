@@ -139,6 +139,6 @@ async fn extract_user_id(req: &ServiceRequest) -> Result<Uuid, TelescopeError> {
         // Get the RCOS user ID associated with the authenticated user.
         .get_user_id()
         .await?
-        // Respond with an error if the user is not found.
-        .ok_or(TelescopeError::NotAuthenticated)
+        // The identity is valid, but if there is no user linked to it, direct them to register.
+        .ok_or(TelescopeError::AccountNotFound)
 }
@@ -0,0 +1,286 @@
+//! Cross-origin resource sharing (CORS) middleware for the JSON API.
+//!
+//! This should only wrap the `/api` scope. Requests from origins on the configured allowlist
+//! get CORS headers (including permission to send credentials). Requests from any other origin
+//! get no CORS headers at all, so browsers will not let cross-origin pages read the response.
+
+use crate::env::global_config;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use actix_web::http::Method;
+use actix_web::HttpResponse;
+use futures::future::{ok, Ready};
+use futures::task::{Context, Poll};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// The methods cross-origin requests to the API may use.
+const ALLOWED_METHODS: &'static str = "GET, POST, OPTIONS";
+
+/// How long (in seconds) browsers may cache the result of a preflight request.
+const PREFLIGHT_MAX_AGE: &'static str = "3600";
+
+/// Factory for the CORS middleware.
+#[derive(Clone, Debug)]
+pub struct Cors {
+    /// The origins allowed to make cross-origin requests.
+    allowed_origins: Rc<Vec<String>>,
+}
+
+/// Middleware that adds CORS headers for allowed origins.
+pub struct CorsMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+    /// The origins allowed to make cross-origin requests.
+    allowed_origins: Rc<Vec<String>>,
+}
+
+impl Cors {
+    /// Create a CORS middleware allowing the origins in the global config.
+    pub fn from_config() -> Self {
+        Cors {
+            allowed_origins: Rc::new(global_config().api_allowed_origins.clone()),
+        }
+    }
+}
+
+/// Is an origin on the allowlist? Origins are compared case-insensitively and ignoring any
+/// trailing slash.
+pub fn is_allowed_origin(allowed_origins: &[String], origin: &str) -> bool {
+    let origin: &str = origin.trim_end_matches('/');
+    allowed_origins
+        .iter()
+        .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+}
+
+/// Get the request's origin if it is allowed.
+fn allowed_origin(allowed_origins: &[String], req: &ServiceRequest) -> Option<HeaderValue> {
+    let origin: &HeaderValue = req.headers().get(ORIGIN)?;
+    if is_allowed_origin(allowed_origins, origin.to_str().ok()?) {
+        return Some(origin.clone());
+    } else {
+        return None;
+    }
+}
+
+impl<S> Transform<S> for Cors
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = CorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CorsMiddleware {
+            service,
+            allowed_origins: self.allowed_origins.clone(),
+        })
+    }
+}
+
+impl<S> Service for CorsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // Check the origin before the request is consumed.
+        let origin: Option<HeaderValue> = allowed_origin(self.allowed_origins.as_slice(), &req);
+
+        // Answer preflight requests directly.
+        if req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let mut response = HttpResponse::NoContent();
+            response.header(VARY, "Origin");
+
+            // Only allowed origins get permission.
+            if let Some(origin) = origin {
+                response
+                    .header(ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+                    .header(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true")
+                    .header(ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS)
+                    .header(ACCESS_CONTROL_MAX_AGE, PREFLIGHT_MAX_AGE);
+
+                // Allow whatever headers were asked for.
+                if let Some(headers) = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS) {
+                    response.header(ACCESS_CONTROL_ALLOW_HEADERS, headers.clone());
+                }
+            }
+
+            return Box::pin(ok(req.into_response(response.finish())));
+        }
+
+        // Otherwise call the wrapped service.
+        let service_response_future = self.service.call(req);
+
+        Box::pin(async move {
+            let mut service_response: ServiceResponse = service_response_future.await?;
+
+            // Responses vary by origin whether or not this one was allowed.
+            let headers = service_response.headers_mut();
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+
+            // Add the CORS headers for allowed origins.
+            if let Some(origin) = origin {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                headers.insert(
+                    ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                    HeaderValue::from_static("true"),
+                );
+            }
+
+            return Ok(service_response);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App};
+
+    /// The origin allowed in these tests.
+    const ALLOWED: &'static str = "https://rcos.github.io";
+
+    /// Send a request through the middleware (to a service that responds with 200) and return
+    /// the response.
+    async fn respond(req: TestRequest) -> ServiceResponse {
+        let middleware = Cors {
+            allowed_origins: Rc::new(vec![format!("{}/", ALLOWED)]),
+        };
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware)
+                .default_service(web::to(HttpResponse::Ok)),
+        )
+        .await;
+        return test::call_service(&mut app, req.to_request()).await;
+    }
+
+    /// Get a header of a response as a string.
+    fn header<'a>(response: &'a ServiceResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Origins are matched ignoring case and trailing slashes.
+    #[test]
+    fn origin_matching() {
+        let allowed: Vec<String> = vec![ALLOWED.to_string()];
+        assert!(is_allowed_origin(&allowed, "https://RCOS.github.io/"));
+        assert!(!is_allowed_origin(&allowed, "http://rcos.github.io"));
+        assert!(!is_allowed_origin(
+            &allowed,
+            "https://rcos.github.io.evil.com"
+        ));
+    }
+
+    /// Allowed origins have their origin echoed back, with credentials allowed.
+    #[actix_rt::test]
+    async fn allowed_origin_is_echoed() {
+        let response = respond(
+            TestRequest::get()
+                .uri("/api/v1/meetings")
+                .header(ORIGIN, ALLOWED),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            header(&response, "access-control-allow-origin"),
+            Some(ALLOWED)
+        );
+        assert_eq!(
+            header(&response, "access-control-allow-credentials"),
+            Some("true")
+        );
+        assert_eq!(header(&response, "vary"), Some("Origin"));
+    }
+
+    /// Other origins get no CORS headers, but still get the response.
+    #[actix_rt::test]
+    async fn disallowed_origin_gets_none() {
+        let response = respond(
+            TestRequest::get()
+                .uri("/api/v1/meetings")
+                .header(ORIGIN, "https://evil.example"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(header(&response, "access-control-allow-origin").is_none());
+        assert!(header(&response, "access-control-allow-credentials").is_none());
+        assert_eq!(header(&response, "vary"), Some("Origin"));
+    }
+
+    /// Preflight requests from allowed origins are answered with the allowed methods and the
+    /// requested headers.
+    #[actix_rt::test]
+    async fn preflight_is_answered() {
+        let response = respond(
+            TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri("/api/v1/meetings")
+                .header(ORIGIN, ALLOWED)
+                .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(ACCESS_CONTROL_REQUEST_HEADERS, "content-type"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            header(&response, "access-control-allow-origin"),
+            Some(ALLOWED)
+        );
+        assert_eq!(
+            header(&response, "access-control-allow-methods"),
+            Some(ALLOWED_METHODS)
+        );
+        assert_eq!(
+            header(&response, "access-control-allow-headers"),
+            Some("content-type")
+        );
+        assert_eq!(
+            header(&response, "access-control-max-age"),
+            Some(PREFLIGHT_MAX_AGE)
+        );
+    }
+
+    /// Preflight requests from other origins are answered without permission.
+    #[actix_rt::test]
+    async fn disallowed_preflight_gets_no_permission() {
+        let response = respond(
+            TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri("/api/v1/meetings")
+                .header(ORIGIN, "https://evil.example")
+                .header(ACCESS_CONTROL_REQUEST_METHOD, "POST"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(header(&response, "access-control-allow-origin").is_none());
+        assert!(header(&response, "access-control-allow-methods").is_none());
+    }
+}
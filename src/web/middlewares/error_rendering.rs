@@ -1,10 +1,16 @@
 //! Middleware for rendering telescope errors into full pages on the way out.
+//!
+//! Requests that accept JSON but not HTML get the public JSON error format
+//! ([`PublicErrorBody`](crate::error::PublicErrorBody)) instead of a page.
+//! If the error page itself cannot be rendered, a static fallback page is sent.
 
 use crate::error::{TelescopeError, TELESCOPE_ERROR_MIME};
+use crate::web::middlewares::request_id::request_id;
 use actix_web::body::{Body, ResponseBody};
 use actix_web::dev::{HttpResponseBuilder, Service, ServiceRequest, ServiceResponse, Transform};
 use actix_web::error::Error as ActixError;
-use actix_web::http::header::CONTENT_TYPE;
+use actix_web::http::header::{ACCEPT, CONTENT_TYPE, RETRY_AFTER};
+use actix_web::http::StatusCode;
 use actix_web::HttpRequest;
 use actix_web::{HttpResponse, ResponseError};
 use futures::future::{ok, Ready};
@@ -13,6 +19,40 @@ use futures::TryStreamExt;
 use std::future::Future;
 use std::pin::Pin;
 
+/// Last resort error page, compiled into the binary. This does not go through handlebars.
+const FALLBACK_ERROR_PAGE: &'static str = include_str!("../../../templates/fallback_error.html");
+
+/// Start the response for a rendered error, with its status code and, if the error says how
+/// long to wait, a `Retry-After` header.
+fn error_response_builder(err: &TelescopeError) -> HttpResponseBuilder {
+    let mut builder: HttpResponseBuilder = HttpResponseBuilder::new(err.status_code());
+    if let Some(wait) = err.retry_after() {
+        builder.header(RETRY_AFTER, wait.as_secs().to_string());
+    }
+    return builder;
+}
+
+/// Build the static fallback error page for a status code. This is used when the normal
+/// error page cannot be rendered, and cannot fail.
+pub fn fallback_error_page(status_code: StatusCode) -> String {
+    FALLBACK_ERROR_PAGE
+        .replace("STATUS_CODE", status_code.as_str())
+        .replace(
+            "REASON",
+            status_code.canonical_reason().unwrap_or("Unknown Error"),
+        )
+}
+
+/// Does a request want a JSON response rather than an HTML page? This is true if the
+/// `Accept` header lists JSON but not HTML (browsers always accept HTML).
+fn wants_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("application/json") && !accept.contains("text/html"))
+        .unwrap_or(false)
+}
+
 /// The factory to create handlers for telescope errors.
 pub struct TelescopeErrorHandler;
 
@@ -98,10 +138,31 @@ where
 
             // Get a reference to the original request.
             let req: &HttpRequest = service_response.request();
-            // Render the error page to a string
-            let rendered: String = err.render_error_page(req).await?;
+
+            // Log the error for aggregation.
+            err.log_structured(req, request_id(req).as_deref());
+
+            // Send JSON clients the public error format.
+            if wants_json(req) {
+                let json_response: HttpResponse =
+                    error_response_builder(&err).json(err.public_body());
+                return Ok(service_response.into_response(json_response));
+            }
+
+            // Render the error page to a string. If that fails too, log the second error and
+            // use the static fallback page so the user still gets something coherent.
+            let rendered: String = match err.render_error_page(req).await {
+                Ok(rendered) => rendered,
+                Err(render_err) => {
+                    error!(
+                        "Could not render error page for error {}: {}",
+                        err, render_err
+                    );
+                    fallback_error_page(err.status_code())
+                }
+            };
             // Convert the rendered page into a response with the right headers and status code.
-            let intermediate_response: HttpResponse = HttpResponseBuilder::new(err.status_code())
+            let intermediate_response: HttpResponse = error_response_builder(&err)
                 .header(CONTENT_TYPE, "text/html;charset=UTF-8")
                 .body(rendered);
             // Construct and return the appropriate service response.
@@ -111,3 +172,151 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    /// Respond to a request with the given error, through the error handler. `accept` is the
+    /// `Accept` header to send, if any.
+    async fn error_response(make: fn() -> TelescopeError, accept: Option<&str>) -> ServiceResponse {
+        let mut app = test::init_service(App::new().wrap(TelescopeErrorHandler).default_service(
+            web::to(move || async move { Err::<HttpResponse, TelescopeError>(make()) }),
+        ))
+        .await;
+
+        let mut req = TestRequest::get().uri("/");
+        if let Some(accept) = accept {
+            req = req.header(ACCEPT, accept);
+        }
+        return test::call_service(&mut app, req.to_request()).await;
+    }
+
+    /// Get the content type of a response.
+    fn content_type(response: &ServiceResponse) -> String {
+        response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /// Only requests that accept JSON and not HTML want JSON.
+    #[test]
+    fn json_is_wanted_without_html() {
+        let with_accept = |accept: &str| {
+            TestRequest::default()
+                .header(ACCEPT, accept)
+                .to_http_request()
+        };
+        assert!(wants_json(&with_accept("application/json")));
+        assert!(!wants_json(&with_accept(
+            "text/html,application/xhtml+xml,application/json;q=0.9"
+        )));
+        assert!(!wants_json(&with_accept("*/*")));
+        assert!(!wants_json(&TestRequest::default().to_http_request()));
+    }
+
+    /// JSON clients get the public error body, with the status code of the error.
+    #[actix_rt::test]
+    async fn json_clients_get_public_body() {
+        let response = error_response(
+            || TelescopeError::resource_not_found("Meeting Not Found", "No such meeting."),
+            Some("application/json"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(content_type(&response).starts_with("application/json"));
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            json!({
+                "error": {
+                    "code": "not_found",
+                    "status": 404,
+                    "message": "Meeting Not Found: No such meeting."
+                }
+            })
+        );
+    }
+
+    /// Browsers (and clients that do not say) get an HTML page.
+    #[actix_rt::test]
+    async fn other_clients_get_html() {
+        for accept in [Some("text/html,application/json;q=0.9"), None] {
+            let response = error_response(|| TelescopeError::PageNotFound, accept).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(content_type(&response).starts_with("text/html"));
+        }
+    }
+
+    /// Internal error details are not sent to JSON clients.
+    #[actix_rt::test]
+    async fn internal_details_are_not_leaked() {
+        let response = error_response(
+            || TelescopeError::ise("Could not connect to postgres://telescope:hunter2@db"),
+            Some("application/json"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = test::read_body(response).await;
+        let body: &str = std::str::from_utf8(body.as_ref()).unwrap();
+        assert!(!body.contains("hunter2"));
+        assert!(!body.contains("InternalServerError"));
+
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["error"]["code"], "internal_error");
+        assert_eq!(body["error"]["message"], "Internal server error.");
+    }
+
+    /// Rate limited errors are 429s and say when to try again, in both formats.
+    #[actix_rt::test]
+    async fn rate_limited_sets_retry_after() {
+        for accept in [Some("application/json"), None] {
+            let response = error_response(
+                || TelescopeError::rate_limited("GitHub", Some(Duration::from_secs(42))),
+                accept,
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "42");
+        }
+
+        // Without a wait from the API, there is no header.
+        let response = error_response(
+            || TelescopeError::rate_limited("GitHub", None),
+            Some("application/json"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+    }
+
+    /// Unavailable upstream errors are 503s and ask clients to wait a short while.
+    #[actix_rt::test]
+    async fn upstream_unavailable_sets_retry_after() {
+        for accept in [Some("application/json"), None] {
+            let response = error_response(
+                || TelescopeError::upstream_unavailable("RCOS API", "Responded with status 503."),
+                accept,
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "30");
+        }
+    }
+
+    /// Other errors do not have a `Retry-After` header.
+    #[actix_rt::test]
+    async fn other_errors_have_no_retry_after() {
+        let response = error_response(|| TelescopeError::PageNotFound, None).await;
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+    }
+}
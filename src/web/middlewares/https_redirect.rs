@@ -0,0 +1,271 @@
+//! Middleware that redirects plain HTTP requests to HTTPS.
+//!
+//! This is only needed when Telescope is not behind a proxy that already redirects to HTTPS,
+//! and is off unless enabled in the config. When a request comes from a trusted proxy, its
+//! `X-Forwarded-Proto` header decides whether the client used HTTPS. Otherwise the scheme of
+//! the connection itself is used. Trusting the proxy is what prevents redirect loops when the
+//! proxy terminates HTTPS and talks to Telescope over plain HTTP.
+
+use crate::env::{global_config, HttpsRedirectConfig};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header::LOCATION;
+use actix_web::http::Method;
+use actix_web::HttpResponse;
+use futures::future::{ok, Either, Ready};
+use futures::task::{Context, Poll};
+use std::net::IpAddr;
+use std::rc::Rc;
+
+/// The header reverse proxies use to say which scheme the client connected with.
+const X_FORWARDED_PROTO: &'static str = "X-Forwarded-Proto";
+
+/// Paths that are never redirected, so that health checks and metrics scrapers can use
+/// plain HTTP.
+const EXEMPT_PATHS: [&'static str; 4] = ["/health", "/healthz", "/readyz", "/metrics"];
+
+/// Factory for the HTTPS redirect middleware.
+#[derive(Clone, Debug)]
+pub struct HttpsRedirect {
+    /// Is the redirect enabled?
+    enabled: bool,
+    /// The addresses of trusted reverse proxies.
+    trusted_proxies: Rc<Vec<IpAddr>>,
+}
+
+/// Middleware that redirects HTTP requests to HTTPS.
+pub struct HttpsRedirectMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+    /// Is the redirect enabled?
+    enabled: bool,
+    /// The addresses of trusted reverse proxies.
+    trusted_proxies: Rc<Vec<IpAddr>>,
+}
+
+impl HttpsRedirect {
+    /// Create an HTTPS redirect middleware using the settings in the global config.
+    /// Trusted proxy addresses that cannot be parsed are logged and ignored.
+    pub fn from_config() -> Self {
+        let config: &HttpsRedirectConfig = &global_config().https_redirect;
+        let trusted_proxies: Vec<IpAddr> = config
+            .trusted_proxies
+            .iter()
+            .filter_map(|address| {
+                address
+                    .parse::<IpAddr>()
+                    .map_err(|e| error!("Invalid trusted proxy address {:?}: {}", address, e))
+                    .ok()
+            })
+            .collect();
+
+        HttpsRedirect {
+            enabled: config.enabled,
+            trusted_proxies: Rc::new(trusted_proxies),
+        }
+    }
+}
+
+/// Did the client make this request over HTTPS? The `X-Forwarded-Proto` header is only
+/// believed if the request came directly from a trusted proxy.
+fn is_https(trusted_proxies: &[IpAddr], req: &ServiceRequest) -> bool {
+    // Check if the request came through a trusted proxy.
+    let from_trusted_proxy: bool = req
+        .peer_addr()
+        .map(|peer| trusted_proxies.contains(&peer.ip()))
+        .unwrap_or(false);
+
+    if from_trusted_proxy {
+        // Use the scheme the proxy reports. A proxy may append to an existing header, so
+        // the first value is the one the client used.
+        if let Some(proto) = req
+            .headers()
+            .get(X_FORWARDED_PROTO)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+        {
+            return proto.trim().eq_ignore_ascii_case("https");
+        }
+    }
+
+    // Otherwise use the scheme of the connection.
+    return req.app_config().secure();
+}
+
+/// Is this path exempt from the redirect?
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PATHS.iter().any(|exempt| {
+        path == *exempt
+            || path
+                .strip_prefix(exempt)
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+    })
+}
+
+/// Build the HTTPS URL for a request. The host comes from the configured Telescope URL rather
+/// than the request, so that a forged `Host` header cannot redirect users to another site.
+fn https_url(req: &ServiceRequest) -> String {
+    let config = global_config();
+    let telescope_url: &str = config.telescope_url.as_str();
+    let host: &str = telescope_url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+
+    let path_and_query: &str = req
+        .uri()
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+
+    return format!("https://{}{}", host, path_and_query);
+}
+
+impl<S> Transform<S> for HttpsRedirect
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = HttpsRedirectMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(HttpsRedirectMiddleware {
+            service,
+            enabled: self.enabled,
+            trusted_proxies: self.trusted_proxies.clone(),
+        })
+    }
+}
+
+impl<S> Service for HttpsRedirectMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // Pass through HTTPS requests, exempt paths, and everything when disabled.
+        if !self.enabled || is_exempt(req.path()) || is_https(self.trusted_proxies.as_slice(), &req)
+        {
+            return Either::Left(self.service.call(req));
+        }
+
+        // Redirect to HTTPS. Requests other than GET and HEAD use 308 so that browsers keep
+        // the method and body.
+        let location: String = https_url(&req);
+        let mut response = if req.method() == Method::GET || req.method() == Method::HEAD {
+            HttpResponse::MovedPermanently()
+        } else {
+            HttpResponse::PermanentRedirect()
+        };
+
+        let response: HttpResponse = response.header(LOCATION, location).finish();
+        return Either::Right(ok(req.into_response(response)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App};
+    use std::net::SocketAddr;
+
+    /// The address of the trusted proxy in these tests.
+    const PROXY: &'static str = "10.0.0.1:443";
+
+    /// Send a request through the middleware (to a service that responds with 200) and return
+    /// the response.
+    async fn respond(enabled: bool, req: TestRequest) -> ServiceResponse {
+        let proxy: SocketAddr = PROXY.parse().unwrap();
+        let middleware = HttpsRedirect {
+            enabled,
+            trusted_proxies: Rc::new(vec![proxy.ip()]),
+        };
+        let mut app = test::init_service(
+            App::new()
+                .wrap(middleware)
+                .default_service(web::to(HttpResponse::Ok)),
+        )
+        .await;
+        return test::call_service(&mut app, req.to_request()).await;
+    }
+
+    /// Get the `Location` header of a response.
+    fn location(response: &ServiceResponse) -> &str {
+        response.headers().get(LOCATION).unwrap().to_str().unwrap()
+    }
+
+    /// Plain HTTP GETs are moved permanently to the same path and query over HTTPS.
+    #[actix_rt::test]
+    async fn get_is_redirected() {
+        let response = respond(true, TestRequest::get().uri("/meetings?semester=202201")).await;
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            location(&response),
+            "https://rcos.io/meetings?semester=202201"
+        );
+    }
+
+    /// Other methods get a permanent redirect that keeps the method and body.
+    #[actix_rt::test]
+    async fn post_keeps_method() {
+        let response = respond(true, TestRequest::post().uri("/meeting/5/edit?x=1")).await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(location(&response), "https://rcos.io/meeting/5/edit?x=1");
+    }
+
+    /// Requests a trusted proxy received over HTTPS pass through.
+    #[actix_rt::test]
+    async fn forwarded_https_passes() {
+        let req = TestRequest::get()
+            .uri("/meetings")
+            .peer_addr(PROXY.parse().unwrap())
+            .header(X_FORWARDED_PROTO, "https");
+        assert_eq!(respond(true, req).await.status(), StatusCode::OK);
+    }
+
+    /// The forwarded scheme is ignored from anyone but a trusted proxy.
+    #[actix_rt::test]
+    async fn untrusted_forwarded_https_is_redirected() {
+        let req = TestRequest::get()
+            .uri("/meetings")
+            .peer_addr("192.0.2.7:50000".parse().unwrap())
+            .header(X_FORWARDED_PROTO, "https");
+        assert_eq!(
+            respond(true, req).await.status(),
+            StatusCode::MOVED_PERMANENTLY
+        );
+    }
+
+    /// Nothing is redirected when the redirect is disabled.
+    #[actix_rt::test]
+    async fn disabled_passes() {
+        let response = respond(false, TestRequest::get().uri("/meetings")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Health checks are never redirected.
+    #[actix_rt::test]
+    async fn exempt_paths_pass() {
+        let response = respond(true, TestRequest::get().uri("/healthz")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(is_exempt("/metrics/requests"));
+        assert!(!is_exempt("/healthzz"));
+    }
+}
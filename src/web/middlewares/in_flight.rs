@@ -0,0 +1,170 @@
+//! Counting of in-flight requests.
+//!
+//! A request is in flight from when it reaches Telescope until its handler has produced a
+//! response. This is used at shutdown to report how many requests were still being handled,
+//! since a request cut off part way through may have applied only some of its mutations.
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error as ActixError;
+use futures::future::{ok, Ready};
+use futures::task::{Context, Poll};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of requests in flight across all workers.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Get the number of requests currently in flight.
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+/// Counts a request as in flight until it is dropped. This also covers requests whose
+/// handlers are cancelled (e.g. when the client disconnects).
+struct InFlightGuard;
+
+impl InFlightGuard {
+    /// Start counting a request.
+    fn new() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Factory for the in-flight request counting middleware.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct InFlightCounter;
+
+/// Middleware that counts in-flight requests.
+pub struct InFlightCounterMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+}
+
+impl<S> Transform<S> for InFlightCounter
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = InFlightCounterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(InFlightCounterMiddleware { service })
+    }
+}
+
+impl<S> Service for InFlightCounterMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // Count the request until the response is ready.
+        let guard = InFlightGuard::new();
+        let service_response_future = self.service.call(req);
+
+        Box::pin(async move {
+            let service_response = service_response_future.await;
+            drop(guard);
+            return service_response;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TelescopeError;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use std::sync::Mutex;
+
+    lazy_static! {
+        /// The counter is global, so tests that check it take turns.
+        static ref COUNTER_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    /// Respond with the number of requests in flight while handling this one.
+    async fn report() -> HttpResponse {
+        HttpResponse::Ok().body(in_flight().to_string())
+    }
+
+    /// Fail after checking that this request is counted.
+    async fn fail() -> Result<HttpResponse, TelescopeError> {
+        assert_eq!(in_flight(), 1);
+        Err(TelescopeError::ise("Handler failed"))
+    }
+
+    /// A request is counted while it is handled, and not after it succeeds.
+    #[actix_rt::test]
+    async fn counts_successful_requests() {
+        let _lock = COUNTER_LOCK.lock().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(InFlightCounter)
+                .default_service(web::to(report)),
+        )
+        .await;
+
+        assert_eq!(in_flight(), 0);
+        let body = test::read_response(&mut app, TestRequest::get().to_request()).await;
+        assert_eq!(body.as_ref(), b"1");
+        assert_eq!(in_flight(), 0);
+    }
+
+    /// A request is counted while it is handled, and not after it fails.
+    #[actix_rt::test]
+    async fn counts_failed_requests() {
+        let _lock = COUNTER_LOCK.lock().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(InFlightCounter)
+                .default_service(web::to(fail)),
+        )
+        .await;
+
+        assert_eq!(in_flight(), 0);
+        let response = test::call_service(&mut app, TestRequest::get().to_request()).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(in_flight(), 0);
+    }
+
+    /// A request that is cancelled before it is handled stops being counted.
+    #[actix_rt::test]
+    async fn counts_cancelled_requests() {
+        let _lock = COUNTER_LOCK.lock().unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(InFlightCounter)
+                .default_service(web::to(report)),
+        )
+        .await;
+
+        let pending = app.call(TestRequest::get().to_request());
+        assert_eq!(in_flight(), 1);
+        drop(pending);
+        assert_eq!(in_flight(), 0);
+    }
+}
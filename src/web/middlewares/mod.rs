@@ -1,4 +1,10 @@
 //! Telescope's middlewares.
 
 pub mod authorization;
+pub mod cors;
 pub mod error_rendering;
+pub mod https_redirect;
+pub mod in_flight;
+pub mod request_id;
+pub mod response_size;
+pub mod sampled_logging;
@@ -0,0 +1,198 @@
+//! Request correlation IDs.
+//!
+//! Every request is given an ID, which is sent back in the `X-Request-Id` response header,
+//! shown on error pages, and included in error and sampled request logs. This lets a user's
+//! report of an error be matched to the server logs. If the proxy in front of Telescope
+//! (Caddy) already set an ID on the request, that one is reused so that both logs agree.
+//!
+//! This middleware should be registered last (outermost), so that every other middleware can
+//! read the ID.
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error as ActixError;
+use actix_web::http::{HeaderName, HeaderValue};
+use actix_web::{HttpMessage, HttpRequest};
+use futures::future::{ok, Ready};
+use futures::task::{Context, Poll};
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+/// The header request IDs are read from and sent in.
+pub const REQUEST_ID_HEADER: &'static str = "x-request-id";
+
+/// The longest incoming request ID that will be reused.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The ID of a request, stored in the request extensions.
+#[derive(Clone, Debug)]
+struct RequestId(String);
+
+/// Get the ID of a request, if the [`RequestIds`] middleware gave it one.
+pub fn request_id(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+/// Get the ID of a request that has not reached a handler yet (for use in other middlewares).
+pub fn service_request_id(req: &ServiceRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+/// Get the request ID set by the proxy, if there is one that is safe to reuse. IDs are logged
+/// and shown on pages, so only short IDs made of letters, digits, dashes, underscores, and
+/// dots are accepted.
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    let id: &str = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?.trim();
+
+    let valid: bool = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    return valid.then(|| id.to_string());
+}
+
+/// Factory for the request ID middleware.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RequestIds;
+
+/// Middleware that gives every request an ID.
+pub struct RequestIdsMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+}
+
+impl<S> Transform<S> for RequestIds
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = RequestIdsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdsMiddleware { service })
+    }
+}
+
+impl<S> Service for RequestIdsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // Reuse the proxy's ID or make a new one.
+        let id: String = incoming_request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        // Call the wrapped service.
+        let service_response_future = self.service.call(req);
+
+        Box::pin(async move {
+            let mut service_response: ServiceResponse = service_response_future.await?;
+
+            // Send the ID back. It was either generated or checked above, so it is a valid
+            // header value.
+            if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+                service_response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+
+            return Ok(service_response);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    /// Respond with the request's ID.
+    async fn echo_id(req: HttpRequest) -> HttpResponse {
+        HttpResponse::Ok().body(request_id(&req).unwrap())
+    }
+
+    /// Send a request through the middleware to a handler that responds with the request's
+    /// ID. Return the ID in the response header and the one the handler saw.
+    async fn ids(req: TestRequest) -> (String, String) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RequestIds)
+                .default_service(web::to(echo_id)),
+        )
+        .await;
+
+        let response = test::call_service(&mut app, req.to_request()).await;
+        let header: String = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .expect("No request ID header")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = test::read_body(response).await;
+        return (header, String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    /// An ID set by the proxy is kept.
+    #[actix_rt::test]
+    async fn incoming_id_is_propagated() {
+        let (header, seen) =
+            ids(TestRequest::get().header(REQUEST_ID_HEADER, "caddy-1234.5_a")).await;
+        assert_eq!(header, "caddy-1234.5_a");
+        assert_eq!(seen, "caddy-1234.5_a");
+    }
+
+    /// Requests without an ID get a new one.
+    #[actix_rt::test]
+    async fn missing_id_is_generated() {
+        let (header, seen) = ids(TestRequest::get()).await;
+        assert!(header.parse::<Uuid>().is_ok());
+        assert_eq!(header, seen);
+
+        // Each request gets its own.
+        let (other, _) = ids(TestRequest::get()).await;
+        assert_ne!(header, other);
+    }
+
+    /// IDs with characters that are not safe to log or show are replaced.
+    #[actix_rt::test]
+    async fn invalid_id_is_replaced() {
+        for invalid in ["<script>alert(1)</script>", "id with spaces", "   "] {
+            let (header, seen) = ids(TestRequest::get().header(REQUEST_ID_HEADER, invalid)).await;
+            assert_ne!(header, invalid);
+            assert!(header.parse::<Uuid>().is_ok());
+            assert_eq!(header, seen);
+        }
+    }
+
+    /// IDs longer than the limit are replaced.
+    #[actix_rt::test]
+    async fn oversized_id_is_replaced() {
+        let longest: String = "a".repeat(MAX_REQUEST_ID_LEN);
+        let (header, _) = ids(TestRequest::get().header(REQUEST_ID_HEADER, longest.as_str())).await;
+        assert_eq!(header, longest);
+
+        let oversized: String = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        let (header, _) =
+            ids(TestRequest::get().header(REQUEST_ID_HEADER, oversized.as_str())).await;
+        assert!(header.parse::<Uuid>().is_ok());
+    }
+}
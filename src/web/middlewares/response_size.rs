@@ -0,0 +1,262 @@
+//! Accounting of response body sizes.
+//!
+//! Two sizes are recorded for a response: the size of the body Telescope produced, and the
+//! size actually sent over the wire. These differ when responses are compressed, in which
+//! case only the wire size reflects bandwidth use. The [`BodySizeCounter`] middleware
+//! measures the body before compression, so it is wrapped inside (registered before) the
+//! compression middleware. Middlewares outside compression measure the wire size themselves
+//! with [`count_body`] and read the body size with [`body_size_counter`]. The compression
+//! middleware changes the response body type, so its responses are converted back with
+//! [`box_body`] for the middlewares outside it.
+
+use actix_web::dev::{
+    Body, BodySize, MessageBody, ResponseBody, Service, ServiceRequest, ServiceResponse, Transform,
+};
+use actix_web::error::Error as ActixError;
+use actix_web::web::Bytes;
+use futures::future::{ok, Ready};
+use futures::task::{Context, Poll};
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// A shared count of the bytes sent in a response body so far.
+#[derive(Clone, Debug, Default)]
+pub struct ByteCounter {
+    /// The number of bytes counted.
+    bytes: Rc<Cell<u64>>,
+}
+
+impl ByteCounter {
+    /// Get the number of bytes counted so far.
+    pub fn get(&self) -> u64 {
+        self.bytes.get()
+    }
+
+    /// Add to the count.
+    fn add(&self, bytes: usize) {
+        self.bytes.set(self.bytes.get() + bytes as u64);
+    }
+}
+
+/// The counter of the response body before compression, stored in the request extensions by
+/// [`BodySizeCounter`].
+#[derive(Clone, Debug)]
+struct UncompressedBodySize(ByteCounter);
+
+/// Get the counter of a response's body size before compression, if the [`BodySizeCounter`]
+/// middleware counted it. The count is final once the response body has been sent.
+pub fn body_size_counter(response: &ServiceResponse) -> Option<ByteCounter> {
+    response
+        .request()
+        .extensions()
+        .get::<UncompressedBodySize>()
+        .map(|size| size.0.clone())
+}
+
+/// Response body wrapper that counts the bytes passing through it, and optionally calls a
+/// function once the body is finished (or dropped before finishing).
+struct CountingBody {
+    /// The wrapped body.
+    inner: ResponseBody<Body>,
+    /// The count of bytes sent so far.
+    counter: ByteCounter,
+    /// Function to call with the final count.
+    on_complete: Option<Box<dyn FnOnce(u64)>>,
+}
+
+impl CountingBody {
+    /// Call the completion function if it has not been called yet.
+    fn complete(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(self.counter.get());
+        }
+    }
+}
+
+impl MessageBody for CountingBody {
+    fn size(&self) -> BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, ActixError>>> {
+        let this = self.get_mut();
+        let polled = Pin::new(&mut this.inner).poll_next(cx);
+
+        match &polled {
+            // Count each chunk.
+            Poll::Ready(Some(Ok(chunk))) => this.counter.add(chunk.len()),
+            // The body is finished (or failed).
+            Poll::Ready(None) | Poll::Ready(Some(Err(_))) => this.complete(),
+            Poll::Pending => {}
+        }
+
+        return polled;
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        // Report the partial count if the client went away before the body finished.
+        self.complete();
+    }
+}
+
+/// Wrap a response's body so that the bytes sent are counted. If `on_complete` is given, it is
+/// called with the final count once the body has been sent.
+pub fn count_body(
+    response: ServiceResponse,
+    counter: ByteCounter,
+    on_complete: Option<Box<dyn FnOnce(u64)>>,
+) -> ServiceResponse {
+    response.map_body(move |_, body| {
+        ResponseBody::Other(Body::from_message(CountingBody {
+            inner: body,
+            counter,
+            on_complete,
+        }))
+    })
+}
+
+/// Convert a response with any body type (such as a compressed body) to one with a plain
+/// [`Body`], which the other Telescope middlewares expect.
+pub fn box_body<B: MessageBody + Unpin + 'static>(response: ServiceResponse<B>) -> ServiceResponse {
+    response.map_body(|_, body| ResponseBody::Other(Body::from_message(body)))
+}
+
+/// Factory for the middleware that counts response body sizes before compression.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BodySizeCounter;
+
+/// Middleware that counts response body sizes before compression.
+pub struct BodySizeCounterMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+}
+
+impl<S> Transform<S> for BodySizeCounter
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = BodySizeCounterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(BodySizeCounterMiddleware { service })
+    }
+}
+
+impl<S> Service for BodySizeCounterMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        let service_response_future = self.service.call(req);
+
+        Box::pin(async move {
+            let service_response: ServiceResponse = service_response_future.await?;
+
+            // Share the counter with the middlewares outside this one.
+            let counter = ByteCounter::default();
+            service_response
+                .request()
+                .extensions_mut()
+                .insert(UncompressedBodySize(counter.clone()));
+
+            return Ok(count_body(service_response, counter, None));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use futures::future::ready;
+    use futures::stream;
+
+    /// Respond to a request through the counter. Return the counted size and the body.
+    async fn counted(handler: fn() -> HttpResponse) -> (u64, Bytes) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(BodySizeCounter)
+                .default_service(web::to(move || ready(handler()))),
+        )
+        .await;
+
+        let response = test::call_service(&mut app, TestRequest::get().to_request()).await;
+        let counter: ByteCounter = body_size_counter(&response).expect("Body was not counted");
+        let body: Bytes = test::read_body(response).await;
+        return (counter.get(), body);
+    }
+
+    /// The size of a body with a known length is counted.
+    #[actix_rt::test]
+    async fn sized_body() {
+        let (size, body) = counted(|| HttpResponse::Ok().body("<p>Sponsors</p>")).await;
+        assert_eq!(size, body.len() as u64);
+        assert_eq!(size, 15);
+    }
+
+    /// The size of a streamed body is the total of its chunks.
+    #[actix_rt::test]
+    async fn streaming_body() {
+        let (size, body) = counted(|| {
+            HttpResponse::Ok().streaming(stream::iter(vec![
+                Ok::<Bytes, ActixError>(Bytes::from_static(b"id,title\n")),
+                Ok(Bytes::from_static(b"1,Large Group\n")),
+                Ok(Bytes::from_static(b"2,Small Group\n")),
+            ]))
+        })
+        .await;
+        assert_eq!(size, body.len() as u64);
+        assert_eq!(size, 37);
+    }
+
+    /// Empty bodies count as zero bytes.
+    #[actix_rt::test]
+    async fn empty_body() {
+        let (size, body) = counted(|| HttpResponse::NoContent().finish()).await;
+        assert!(body.is_empty());
+        assert_eq!(size, 0);
+    }
+
+    /// The completion function is called once with the final count.
+    #[actix_rt::test]
+    async fn completion_is_reported() {
+        let reported: Rc<Cell<Option<u64>>> = Rc::new(Cell::new(None));
+        let report = reported.clone();
+
+        let req = TestRequest::default().to_http_request();
+        let response = ServiceResponse::new(req, HttpResponse::Ok().body("abcdef"));
+        let response = count_body(
+            response,
+            ByteCounter::default(),
+            Some(Box::new(move |bytes| report.set(Some(bytes)))),
+        );
+
+        assert_eq!(reported.get(), None);
+        test::read_body(response).await;
+        assert_eq!(reported.get(), Some(6));
+    }
+}
@@ -0,0 +1,228 @@
+//! Middleware to verbosely log a random sample of requests and their responses.
+//!
+//! This is intended for debugging issues in production without logging every
+//! request in full. Sensitive headers are redacted before logging.
+//!
+//! Sampled requests are logged once their response body has been sent, with both the
+//! size of the body before compression (`body_bytes`) and the size sent over the wire
+//! (`wire_bytes`). See [`super::response_size`].
+
+use crate::web::middlewares::request_id;
+use crate::web::middlewares::response_size::{self, ByteCounter};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::Error as ActixError;
+use actix_web::http::header::{HeaderMap, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use futures::future::{ok, Ready};
+use futures::task::{Context, Poll};
+use rand::Rng;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+/// The value logged in place of a sensitive header.
+const REDACTED: &'static str = "[REDACTED]";
+
+/// Factory for the sampled logging middleware.
+#[derive(Copy, Clone, Debug)]
+pub struct SampledLogging {
+    /// The fraction of requests to log, between 0 and 1.
+    rate: f64,
+}
+
+/// Middleware that logs a sample of requests and responses.
+pub struct SampledLoggingMiddleware<S> {
+    /// The next service in the chain.
+    service: S,
+    /// The fraction of requests to log, between 0 and 1.
+    rate: f64,
+}
+
+impl SampledLogging {
+    /// Create a sampled logging middleware that logs the given fraction of requests.
+    /// Rates outside of 0 to 1 are clamped.
+    pub fn new(rate: f64) -> Self {
+        SampledLogging {
+            rate: rate.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Decide whether a request should be logged using the given random number generator.
+pub fn should_sample<R: Rng>(rng: &mut R, rate: f64) -> bool {
+    // Avoid drawing a number at all for the common cases.
+    if rate <= 0.0 {
+        return false;
+    }
+    if rate >= 1.0 {
+        return true;
+    }
+
+    return rng.gen_bool(rate);
+}
+
+/// Format a header map for logging, redacting any sensitive headers.
+fn redacted_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            // Check for sensitive headers.
+            let sensitive: bool = name == COOKIE
+                || name == SET_COOKIE
+                || name == AUTHORIZATION
+                || name == PROXY_AUTHORIZATION;
+
+            if sensitive {
+                format!("{}: {}", name, REDACTED)
+            } else {
+                format!(
+                    "{}: {}",
+                    name,
+                    value.to_str().unwrap_or("(non-ascii value)")
+                )
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl<S> Transform<S> for SampledLogging
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Transform = SampledLoggingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(SampledLoggingMiddleware {
+            service,
+            rate: self.rate,
+        })
+    }
+}
+
+impl<S> Service for SampledLoggingMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = ActixError>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // If this request is not sampled, just call the wrapped service.
+        if !should_sample(&mut rand::thread_rng(), self.rate) {
+            return Box::pin(self.service.call(req));
+        }
+
+        // Otherwise record the request info before it's consumed by the service.
+        let id: String = request_id::service_request_id(&req).unwrap_or_else(|| "-".into());
+        let method: String = req.method().to_string();
+        let path: String = req.path().to_string();
+        let request_headers: String = redacted_headers(req.headers());
+        let start: Instant = Instant::now();
+
+        // Call the wrapped service.
+        let service_response_future = self.service.call(req);
+
+        Box::pin(async move {
+            // Wait for the response.
+            let service_response: ServiceResponse = service_response_future.await?;
+
+            // Record the response info now, since the body is sent after this returns.
+            let status = service_response.status();
+            let elapsed_ms: u128 = start.elapsed().as_millis();
+            let response_headers: String = redacted_headers(service_response.headers());
+            let body_size: Option<ByteCounter> =
+                response_size::body_size_counter(&service_response);
+
+            // Log the request and response once the body has been sent.
+            let log = move |wire_bytes: u64| {
+                // Without a body size counter, assume the body was not compressed.
+                let body_bytes: u64 = body_size.map(|counter| counter.get()).unwrap_or(wire_bytes);
+
+                info!(
+                    "Sampled request {}: {} {} ({}) -> {} in {}ms ({}) body_bytes={} wire_bytes={}",
+                    id,
+                    method,
+                    path,
+                    request_headers,
+                    status,
+                    elapsed_ms,
+                    response_headers,
+                    body_bytes,
+                    wire_bytes
+                );
+            };
+
+            return Ok(response_size::count_body(
+                service_response,
+                ByteCounter::default(),
+                Some(Box::new(log)),
+            ));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderValue, USER_AGENT};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// The number of requests sampled from in these tests.
+    const REQUESTS: usize = 10_000;
+
+    /// Count how many of [`REQUESTS`] requests are sampled with a seeded generator.
+    fn sampled(seed: u64, rate: f64) -> usize {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        (0..REQUESTS)
+            .filter(|_| should_sample(&mut rng, rate))
+            .count()
+    }
+
+    /// The fraction of sampled requests is close to the configured rate.
+    #[test]
+    fn respects_rate() {
+        let count: usize = sampled(435, 0.25);
+        assert!((2_300..=2_700).contains(&count), "sampled {}", count);
+    }
+
+    /// The same seed samples the same requests.
+    #[test]
+    fn deterministic_with_seed() {
+        assert_eq!(sampled(7, 0.1), sampled(7, 0.1));
+    }
+
+    /// A rate of 0 logs nothing and a rate of 1 logs everything.
+    #[test]
+    fn rate_bounds() {
+        assert_eq!(sampled(1, 0.0), 0);
+        assert_eq!(sampled(1, 1.0), REQUESTS);
+    }
+
+    /// Sensitive headers are redacted and others are logged.
+    #[test]
+    fn redacts_sensitive_headers() {
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_static("telescope=secret"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert(USER_AGENT, HeaderValue::from_static("curl/7.68.0"));
+
+        let logged: String = redacted_headers(&headers);
+        assert!(!logged.contains("secret"));
+        assert!(logged.contains("cookie: [REDACTED]"));
+        assert!(logged.contains("user-agent: curl/7.68.0"));
+    }
+}
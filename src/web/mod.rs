@@ -2,9 +2,15 @@
 
 use reqwest::header::HeaderValue;
 
+pub mod cookie_keys;
 pub mod csrf;
+pub mod csv_stream;
+pub mod integrations;
+pub mod json_stream;
 pub mod middlewares;
+pub mod redirect;
 pub mod services;
+pub mod sessions;
 
 lazy_static! {
     static ref TELESCOPE_USER_AGENT: String =
@@ -0,0 +1,205 @@
+//! Helpers for building redirect responses.
+//!
+//! Redirects after a form submission (POST) should use `303 See Other` so that
+//! the browser follows them with a GET request. Plain navigation redirects use
+//! `302 Found`.
+//!
+//! All redirects within Telescope should go through these helpers. Targets are
+//! checked by [`sanitize_redirect`] so that a user influenced target can never
+//! send someone to another site. Redirects to external sites (e.g. OAuth2
+//! authorization pages) must use [`redirect_external`] explicitly.
+
+use actix_web::http::header::LOCATION;
+use actix_web::HttpResponse;
+
+/// The path to redirect to in place of a rejected target.
+const FALLBACK_PATH: &'static str = "/";
+
+/// The path prefixes that Telescope may redirect to. A target is allowed if its
+/// path is one of these or is nested under one of them. Paths that change state
+/// on a GET (like `/logout` or `/unlink`) are deliberately left out.
+const ALLOWED_PREFIXES: [&'static str; 11] = [
+    "/account",
+    "/admin",
+    "/developers",
+    "/edit_profile",
+    "/login",
+    "/meeting",
+    "/meetings",
+    "/projects",
+    "/register",
+    "/sponsors",
+    "/user",
+];
+
+/// Check that a redirect target is a same-origin path under one of the allowed
+/// prefixes (or the homepage). Return the target if it is allowed.
+///
+/// Absolute URLs (`https://evil.com`), protocol-relative URLs (`//evil.com`),
+/// backslashes (which some browsers treat as slashes), whitespace, and control
+/// characters are all rejected.
+pub fn sanitize_redirect(target: &str) -> Option<&str> {
+    // Only same-origin paths are allowed.
+    if !target.starts_with('/') || target.starts_with("//") {
+        return None;
+    }
+
+    // Reject backslashes, whitespace, and control characters anywhere in the target.
+    if target
+        .chars()
+        .any(|c| c == '\\' || c.is_whitespace() || c.is_control())
+    {
+        return None;
+    }
+
+    // Split the path from the query string and fragment.
+    let path: &str = target.split(['?', '#']).next().unwrap_or("");
+
+    // The homepage is always allowed.
+    if path == "/" {
+        return Some(target);
+    }
+
+    // Otherwise the path must be an allowed prefix or nested under one.
+    let allowed: bool = ALLOWED_PREFIXES.iter().any(|prefix| {
+        path == *prefix
+            || path
+                .strip_prefix(prefix)
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+    });
+
+    if allowed {
+        return Some(target);
+    } else {
+        return None;
+    }
+}
+
+/// Sanitize a redirect target, falling back to the homepage if it is rejected.
+fn sanitized_or_fallback(target: &str) -> &str {
+    sanitize_redirect(target).unwrap_or_else(|| {
+        warn!("Rejected redirect to {:?}.", target);
+        FALLBACK_PATH
+    })
+}
+
+/// Redirect the user to a path after a POST request. This is an alias for
+/// [`redirect_see_other`], which is the correct status code after a form
+/// submission.
+pub fn redirect_to(path: impl AsRef<str>) -> HttpResponse {
+    redirect_see_other(path)
+}
+
+/// Create a `302 Found` redirect response to the given path.
+pub fn redirect_found(path: impl AsRef<str>) -> HttpResponse {
+    HttpResponse::Found()
+        .header(LOCATION, sanitized_or_fallback(path.as_ref()))
+        .finish()
+}
+
+/// Create a `303 See Other` redirect response to the given path.
+pub fn redirect_see_other(path: impl AsRef<str>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .header(LOCATION, sanitized_or_fallback(path.as_ref()))
+        .finish()
+}
+
+/// Create a `302 Found` redirect response to an external URL. This is not
+/// sanitized, so the URL must never come from user input.
+pub fn redirect_external(url: impl AsRef<str>) -> HttpResponse {
+    HttpResponse::Found()
+        .header(LOCATION, url.as_ref())
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    /// Paths under the allowed prefixes are allowed, including ones with query strings and
+    /// fragments.
+    #[test]
+    fn allows_same_origin_paths() {
+        for target in [
+            "/",
+            "/meeting/5",
+            "/developers",
+            "/edit_profile",
+            "/meetings?semester=202209#today",
+            "/user/7a2c2c1e-5c3a-4a4e-9f43-1d5c1b7d1e0a",
+        ] {
+            assert_eq!(sanitize_redirect(target), Some(target));
+        }
+    }
+
+    /// Targets that could send the browser to another site are rejected.
+    #[test]
+    fn rejects_open_redirects() {
+        for target in [
+            "//evil.com",
+            "https://evil.com",
+            "http://evil.com/meeting/5",
+            "javascript:alert(1)",
+            "evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "/ /evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "",
+        ] {
+            assert_eq!(sanitize_redirect(target), None, "{:?} was allowed", target);
+        }
+    }
+
+    /// Redirects after a POST use `303 See Other`.
+    #[test]
+    fn redirect_to_is_see_other() {
+        let response = redirect_to("/meeting/5");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers().get(LOCATION).unwrap(), "/meeting/5");
+    }
+
+    /// Each helper sets its own status code and the Location header.
+    #[test]
+    fn redirect_status_codes() {
+        let found = redirect_found("/meetings");
+        assert_eq!(found.status(), StatusCode::FOUND);
+        assert_eq!(found.headers().get(LOCATION).unwrap(), "/meetings");
+
+        let see_other = redirect_see_other("/meetings");
+        assert_eq!(see_other.status(), StatusCode::SEE_OTHER);
+        assert_eq!(see_other.headers().get(LOCATION).unwrap(), "/meetings");
+
+        let external = redirect_external("https://discord.com/oauth2/authorize");
+        assert_eq!(external.status(), StatusCode::FOUND);
+        assert_eq!(
+            external.headers().get(LOCATION).unwrap(),
+            "https://discord.com/oauth2/authorize"
+        );
+    }
+
+    /// Paths on this site outside of the allowed prefixes are rejected, including ones that
+    /// only share the start of a prefix.
+    #[test]
+    fn rejects_paths_outside_allowlist() {
+        for target in [
+            "/logout",
+            "/unlink/github",
+            "/login_as_admin",
+            "/meetingsearch",
+            "/api/meetings",
+        ] {
+            assert_eq!(sanitize_redirect(target), None, "{:?} was allowed", target);
+        }
+    }
+
+    /// Rejected targets fall back to the homepage.
+    #[test]
+    fn rejected_redirect_goes_home() {
+        let response = redirect_found("//evil.com");
+        assert_eq!(response.headers().get(LOCATION).unwrap(), FALLBACK_PATH);
+    }
+}
@@ -0,0 +1,253 @@
+//! Services for managing the site-wide announcement banner.
+//!
+//! The form has a CSRF token, so that coordinators cannot be tricked into posting an
+//! announcement from another site.
+
+use crate::announcement::Announcement;
+use crate::app_data::AppData;
+use crate::error::TelescopeError;
+use crate::templates::page::Page;
+use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::redirect::redirect_to;
+use actix_web::web::{self as aweb, Form, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use oauth2::CsrfToken;
+
+/// The path to the announcement form template from the templates directory.
+const TEMPLATE_PATH: &'static str = "admin/announcement";
+
+/// The format used to display announcement timestamps.
+const TIMESTAMP_FORMAT: &'static str = "%B %-d, %Y %-I:%M %P";
+
+/// The longest announcement allowed, in characters.
+const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// The name CSRF tokens of the announcement form are saved under.
+const CSRF_NAME: &'static str = "admin_announcement";
+
+/// Register announcement services. These are registered under `/admin/announcement`.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(
+        aweb::resource("")
+            .route(aweb::get().to(announcement_page))
+            .route(aweb::post().to(save_announcement)),
+    );
+}
+
+/// Form submitted to set the announcement.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnnouncementForm {
+    /// The CSRF token from the form page.
+    #[serde(skip_serializing)]
+    csrf_token: String,
+    /// The announcement text. An empty message removes the announcement.
+    message: String,
+    /// The date the announcement expires, if it does.
+    #[serde(default)]
+    expires_date: Option<String>,
+    /// The time of day the announcement expires. Defaults to midnight.
+    #[serde(default)]
+    expires_time: Option<String>,
+}
+
+/// Make the announcement form template with the current announcement and the CSRF token to
+/// submit it with.
+fn make_form(csrf_token: &str) -> Template {
+    let format_timestamp = |timestamp: DateTime<Utc>| {
+        timestamp
+            .with_timezone(&Local)
+            .format(TIMESTAMP_FORMAT)
+            .to_string()
+    };
+
+    let mut form = Template::new(TEMPLATE_PATH);
+    form["csrf_token"] = json!(csrf_token);
+    if let Some(current) = AppData::global().announcement() {
+        form["current"] = json!({
+            "message": &current.message,
+            "posted_at": format_timestamp(current.posted_at),
+            "expires_at": current.expires_at.map(format_timestamp),
+            "is_active": current.is_active(Utc::now()),
+        });
+        // Start the form with the current message.
+        form["selections"]["message"] = json!(&current.message);
+    }
+    return form;
+}
+
+/// Page to view and change the announcement.
+async fn announcement_page(req: HttpRequest) -> Result<HttpResponse, TelescopeError> {
+    // Access is pre-checked by the scope this is in. Render the form and save its CSRF token
+    // for this browser.
+    let token: CsrfToken = CsrfToken::new_random();
+    let page: Page = make_form(token.secret())
+        .in_page(&req, "Announcement")
+        .await?;
+    let mut response: HttpResponse = page.respond_to(&req).await?;
+    csrf::save(CSRF_NAME, &req, &mut response, token)?;
+    return Ok(response);
+}
+
+/// Parse the expiration date and time from the form into a timestamp. Return `Ok(None)` if
+/// there is no expiration date and an issue message if the inputs are malformed or the time
+/// is not after `now`.
+fn parse_expiry(
+    expires_date: Option<&str>,
+    expires_time: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, &'static str> {
+    // Empty inputs are submitted as empty strings.
+    let date: &str = match expires_date.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(date) => date,
+        None => return Ok(None),
+    };
+
+    let date: NaiveDate = date
+        .parse()
+        .map_err(|_| "Could not parse expiration date.")?;
+    let time: NaiveTime = match expires_time.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(time) => format!("{}:00", time)
+            .parse()
+            .map_err(|_| "Could not parse expiration time.")?,
+        None => NaiveTime::from_hms(0, 0, 0),
+    };
+
+    // Expiration times are in the server's timezone.
+    let expiry: DateTime<Utc> = Local
+        .from_local_datetime(&date.and_time(time))
+        .single()
+        .map(|expiry| expiry.with_timezone(&Utc))
+        .ok_or("Expiration time is ambiguous in the local timezone.")?;
+
+    if expiry <= now {
+        return Err("Expiration time is in the past.");
+    }
+    return Ok(Some(expiry));
+}
+
+/// Save (or remove) the announcement.
+async fn save_announcement(
+    req: HttpRequest,
+    Form(form_input): Form<AnnouncementForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    let AnnouncementForm {
+        csrf_token,
+        message,
+        expires_date,
+        expires_time,
+    } = form_input.clone();
+
+    // An empty message clears the announcement. Tokens can only be used once, so this is
+    // checked right before changing the announcement.
+    let message: String = message.trim().to_string();
+    if message.is_empty() {
+        csrf::verify(CSRF_NAME, &req, CsrfToken::new(csrf_token))?;
+        AppData::global().set_announcement(None)?;
+        return Ok(redirect_to("/admin/announcement"));
+    }
+
+    // Validate the form. If it is shown again, the CSRF token has not been used yet, so the
+    // form can be submitted again with it.
+    let mut form: Template = make_form(csrf_token.as_str());
+    form["selections"] = json!(&form_input);
+
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        form["issues"]["message"] = json!(format!(
+            "Announcements can be at most {} characters.",
+            MAX_MESSAGE_LENGTH
+        ));
+    }
+
+    let expires_at: Option<DateTime<Utc>> =
+        match parse_expiry(expires_date.as_deref(), expires_time.as_deref(), Utc::now()) {
+            Ok(expiry) => expiry,
+            Err(issue) => {
+                form["issues"]["expires"] = json!(issue);
+                None
+            }
+        };
+
+    // If there was an issue, return the form as invalid.
+    if form["issues"] != json!(null) {
+        let page = form.in_page(&req, "Announcement").await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
+
+    // Post the announcement.
+    csrf::verify(CSRF_NAME, &req, CsrfToken::new(csrf_token))?;
+    AppData::global().set_announcement(Some(Announcement::new(message, expires_at)))?;
+    return Ok(redirect_to("/admin/announcement"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the timestamp of a local date and time.
+    fn local(date: &str, time: &str) -> DateTime<Utc> {
+        let date: NaiveDate = date.parse().unwrap();
+        let time: NaiveTime = time.parse().unwrap();
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// A time before every expiration in these tests.
+    fn now() -> DateTime<Utc> {
+        local("2022-09-06", "12:00:00")
+    }
+
+    /// Dates with and without times parse in the server's timezone.
+    #[test]
+    fn parses_valid_expiry() {
+        assert_eq!(
+            parse_expiry(Some("2022-09-10"), Some("14:30"), now()),
+            Ok(Some(local("2022-09-10", "14:30:00")))
+        );
+        // The time defaults to midnight.
+        assert_eq!(
+            parse_expiry(Some("2022-09-10"), Some(""), now()),
+            Ok(Some(local("2022-09-10", "00:00:00")))
+        );
+    }
+
+    /// No date means no expiration.
+    #[test]
+    fn empty_expiry_is_none() {
+        assert_eq!(parse_expiry(None, None, now()), Ok(None));
+        assert_eq!(parse_expiry(Some("  "), Some("14:30"), now()), Ok(None));
+    }
+
+    /// Expiration times must be after the current time.
+    #[test]
+    fn rejects_past_expiry() {
+        assert_eq!(
+            parse_expiry(Some("2022-09-01"), None, now()),
+            Err("Expiration time is in the past.")
+        );
+        assert_eq!(
+            parse_expiry(Some("2022-09-06"), Some("12:00"), now()),
+            Err("Expiration time is in the past.")
+        );
+    }
+
+    /// Malformed dates and times are reported.
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(
+            parse_expiry(Some("next tuesday"), None, now()),
+            Err("Could not parse expiration date.")
+        );
+        assert_eq!(
+            parse_expiry(Some("2022-09-10"), Some("noon"), now()),
+            Err("Could not parse expiration time.")
+        );
+        assert_eq!(
+            parse_expiry(Some("2022-09-10"), Some("25:00"), now()),
+            Err("Could not parse expiration time.")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Services for coordinators to deactivate and reactivate users.
+//!
+//! Deactivating a user hides them from listings and their profile (except for coordinators
+//! and admins) without deleting their enrollment, attendance, or meeting records, unlike
+//! deleting them. The same rules as deletion apply: users cannot deactivate themselves, and
+//! only admins can deactivate admins. The forms are on the user's profile, which saves a CSRF
+//! token bound to the user for them.
+
+use crate::api::rcos::users::deactivate::SetUserDeactivated;
+use crate::audit;
+use crate::error::TelescopeError;
+use crate::web::csrf;
+use crate::web::redirect::redirect_to;
+use crate::web::services::admin::delete_user::target_checked;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::web::{self as aweb, Form, Path, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse};
+use oauth2::CsrfToken;
+use uuid::Uuid;
+
+/// The prefix of the name CSRF tokens of these forms are saved under.
+const CSRF_NAME: &'static str = "admin_deactivate_user";
+
+/// Register the deactivation services. These are registered under `/admin/users`.
+pub fn register(config: &mut ServiceConfig) {
+    config
+        .route("/{user_id}/deactivate", aweb::post().to(deactivate))
+        .route("/{user_id}/reactivate", aweb::post().to(reactivate));
+}
+
+/// Form submitted to deactivate or reactivate a user.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeactivateForm {
+    /// The CSRF token from the user's profile.
+    csrf_token: String,
+}
+
+/// Get the name the CSRF token to deactivate or reactivate a user is saved under. This includes
+/// the user's ID, so the token from one user's profile cannot change another user.
+pub fn csrf_name(user_id: Uuid) -> String {
+    format!("{}:{}", CSRF_NAME, user_id)
+}
+
+/// Check the viewer can deactivate or reactivate a user, and that the form was submitted from
+/// that user's profile. Return the viewer's ID.
+async fn checked(
+    req: &HttpRequest,
+    auth: &AuthenticationCookie,
+    user_id: Uuid,
+    form: DeactivateForm,
+) -> Result<Uuid, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    target_checked(viewer, user_id).await?;
+    csrf::verify(
+        csrf_name(user_id).as_str(),
+        req,
+        CsrfToken::new(form.csrf_token),
+    )?;
+    return Ok(viewer);
+}
+
+/// Error for a user that disappeared between being checked and being updated.
+fn user_not_found() -> TelescopeError {
+    TelescopeError::resource_not_found("User Not Found", "Could not find a user with this ID.")
+}
+
+/// Deactivate a user and go back to their profile.
+async fn deactivate(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<DeactivateForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = checked(&req, &auth, user_id, form).await?;
+
+    SetUserDeactivated::deactivate(user_id)
+        .await?
+        .ok_or_else(user_not_found)?;
+    audit::record(viewer, "deactivate_user", format!("target={}", user_id));
+
+    return Ok(redirect_to(format!("/user/{}", user_id)));
+}
+
+/// Reactivate a deactivated user and go back to their profile.
+async fn reactivate(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<DeactivateForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = checked(&req, &auth, user_id, form).await?;
+
+    SetUserDeactivated::reactivate(user_id)
+        .await?
+        .ok_or_else(user_not_found)?;
+    audit::record(viewer, "reactivate_user", format!("target={}", user_id));
+
+    return Ok(redirect_to(format!("/user/{}", user_id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use crate::web::services::admin::delete_user;
+
+    /// Stub the viewer's authorization: their role and whether they are a current coordinator.
+    fn stub_viewer(api: &MockRcosApi, role: &str, is_coordinator: bool) {
+        api.stub(
+            "AuthorizationFor",
+            json!({
+                "current_semesters": [{
+                    "enrollments": [{ "is_coordinator": is_coordinator }],
+                    "small_groups": []
+                }],
+                "users_by_pk": { "role": role }
+            }),
+        );
+    }
+
+    /// Stub the user being deactivated.
+    fn stub_target(api: &MockRcosApi, user_id: Uuid, role: &str) {
+        api.stub(
+            "UserRoles",
+            json!({ "users": [{
+                "id": user_id,
+                "first_name": "Jane",
+                "last_name": "Smith",
+                "role": role
+            }] }),
+        );
+    }
+
+    /// Tokens are bound to the user, and are not shared with the deletion form.
+    #[test]
+    fn csrf_name_is_bound_to_user() {
+        let a: Uuid = Uuid::new_v4();
+        let b: Uuid = Uuid::new_v4();
+        assert_ne!(csrf_name(a), csrf_name(b));
+        assert_ne!(csrf_name(a), delete_user::csrf_name(a));
+    }
+
+    /// Coordinators can deactivate students.
+    #[actix_rt::test]
+    async fn coordinator_can_deactivate_student() {
+        let api = MockRcosApi::start();
+        let target: Uuid = Uuid::new_v4();
+        stub_viewer(&api, "student", true);
+        stub_target(&api, target, "student");
+
+        assert!(target_checked(Uuid::new_v4(), target).await.is_ok());
+    }
+
+    /// Only admins can deactivate admins.
+    #[actix_rt::test]
+    async fn coordinator_cannot_deactivate_admin() {
+        let api = MockRcosApi::start();
+        let target: Uuid = Uuid::new_v4();
+        stub_viewer(&api, "student", true);
+        stub_target(&api, target, "sysadmin");
+
+        let result = target_checked(Uuid::new_v4(), target).await;
+        assert!(matches!(
+            result,
+            Err(TelescopeError::ForbiddenAction { .. })
+        ));
+
+        stub_viewer(&api, "faculty_advisor", false);
+        assert!(target_checked(Uuid::new_v4(), target).await.is_ok());
+    }
+
+    /// Users who are not coordinators or admins cannot deactivate anyone.
+    #[actix_rt::test]
+    async fn others_are_forbidden() {
+        let api = MockRcosApi::start();
+        let target: Uuid = Uuid::new_v4();
+        stub_viewer(&api, "student", false);
+        stub_target(&api, target, "student");
+
+        let result = target_checked(Uuid::new_v4(), target).await;
+        assert!(matches!(result, Err(TelescopeError::Forbidden { .. })));
+    }
+}
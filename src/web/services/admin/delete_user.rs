@@ -0,0 +1,236 @@
+//! Service for coordinators to delete a user's account.
+//!
+//! Deleting an account cannot be undone, so the confirmation page has a CSRF token bound to
+//! the user being deleted, and the form must be submitted with the user's username typed out
+//! exactly. Users cannot delete their own account here (they can from their profile), and
+//! only admins can delete admins. Users are deleted one at a time: each deletion needs that
+//! user's own confirmation.
+
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::users::accounts::lookup::AccountLookup;
+use crate::api::rcos::users::bulk_role::{user_roles::UserRolesUsers, UserRoles};
+use crate::api::rcos::users::delete::DeleteUser;
+use crate::api::rcos::users::UserAccountType;
+use crate::audit;
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use crate::web::services::user::delete::kick_from_discord;
+use actix_web::web::{self as aweb, Form, Path, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use oauth2::CsrfToken;
+use uuid::Uuid;
+
+/// The path to the confirmation page template from the templates directory.
+const TEMPLATE_PATH: &'static str = "admin/users/delete";
+
+/// The prefix of the name CSRF tokens of this form are saved under.
+const CSRF_NAME: &'static str = "admin_delete_user";
+
+/// Register the user deletion services. These are registered under `/admin/users`.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(
+        aweb::resource("/{user_id}/delete")
+            .route(aweb::get().to(confirm_page))
+            .route(aweb::post().to(submit_delete)),
+    );
+}
+
+/// Form submitted to delete a user.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeleteUserForm {
+    /// The CSRF token from the confirmation page.
+    csrf_token: String,
+    /// The user's username, typed out to confirm.
+    #[serde(default)]
+    confirmation: String,
+}
+
+/// Get the name the CSRF token to delete a user is saved under. This includes the user's ID,
+/// so the token from one user's confirmation page cannot delete another user.
+pub(super) fn csrf_name(user_id: Uuid) -> String {
+    format!("{}:{}", CSRF_NAME, user_id)
+}
+
+/// Get the full name of a user, shown on the confirmation page.
+fn full_name(user: &UserRolesUsers) -> String {
+    format!("{} {}", user.first_name, user.last_name)
+}
+
+/// Get the username of a user, which must be typed to confirm deleting them. This is their
+/// RCS ID, or their user ID if they have not linked an RPI account.
+async fn username(user_id: Uuid) -> Result<String, TelescopeError> {
+    let rcs_id: Option<String> = AccountLookup::send(user_id, UserAccountType::Rpi).await?;
+    return Ok(rcs_id.unwrap_or_else(|| user_id.to_string()));
+}
+
+/// Does a typed confirmation match a user's username exactly?
+fn confirmation_matches(confirmation: &str, username: &str) -> bool {
+    !username.is_empty() && confirmation == username
+}
+
+/// Check that the viewer can delete (or deactivate) a user, and get the user. Users cannot
+/// delete themselves here, and only admins can delete admins. Other coordinators are
+/// forbidden.
+pub(super) async fn target_checked(
+    viewer: Uuid,
+    user_id: Uuid,
+) -> Result<UserRolesUsers, TelescopeError> {
+    if viewer == user_id {
+        return Err(TelescopeError::BadRequest {
+            header: "Cannot Change Your Own Account".into(),
+            message: "You cannot delete or deactivate your own account from the admin panel. \
+                You can delete it from your profile instead."
+                .into(),
+            show_status_code: false,
+        });
+    }
+
+    // Get the viewer's authorization and the user at the same time.
+    let (authorization, users) = futures::join!(
+        AuthorizationFor::get(Some(viewer)),
+        UserRoles::get(vec![user_id])
+    );
+    let authorization: UserMeetingAuthorization = authorization?;
+
+    // This is also checked by the scope these services are in.
+    if !authorization.is_coordinator_or_admin() {
+        return Err(TelescopeError::forbidden("this coordinator page"));
+    }
+
+    let user: UserRolesUsers =
+        users?
+            .into_iter()
+            .next()
+            .ok_or(TelescopeError::resource_not_found(
+                "User Not Found",
+                "Could not find a user with this ID.",
+            ))?;
+
+    if user.role.is_admin() && !authorization.is_admin() {
+        return Err(TelescopeError::forbidden_action(
+            "Cannot Change Admin",
+            "Only admins can delete or deactivate the account of an admin.",
+        ));
+    }
+
+    return Ok(user);
+}
+
+/// Make the confirmation form for a user, with the CSRF token to submit it with.
+fn make_form(user: &UserRolesUsers, username: &str, csrf_token: &str) -> Template {
+    let mut form = Template::new(TEMPLATE_PATH);
+    form.fields = json!({
+        "user": user,
+        "full_name": full_name(user),
+        "username": username,
+        "csrf_token": csrf_token,
+    });
+    return form;
+}
+
+/// Page to confirm deleting a user.
+async fn confirm_page(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(user_id): Path<Uuid>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let user: UserRolesUsers = target_checked(viewer, user_id).await?;
+    let username: String = username(user_id).await?;
+
+    // Render the form and save its CSRF token for this browser and user.
+    let token: CsrfToken = CsrfToken::new_random();
+    let mut response: HttpResponse = make_form(&user, username.as_str(), token.secret())
+        .in_page(&req, "Delete User")
+        .await?
+        .respond_to(&req)
+        .await?;
+    csrf::save(csrf_name(user_id).as_str(), &req, &mut response, token)?;
+    return Ok(response);
+}
+
+/// Delete a user once the confirmation matches.
+async fn submit_delete(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(user_id): Path<Uuid>,
+    Form(form_input): Form<DeleteUserForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let user: UserRolesUsers = target_checked(viewer, user_id).await?;
+    let username: String = username(user_id).await?;
+
+    // Show the form again if the confirmation does not match. The CSRF token has not been
+    // used yet, so the form can be submitted again with it.
+    if !confirmation_matches(form_input.confirmation.as_str(), username.as_str()) {
+        let mut form: Template =
+            make_form(&user, username.as_str(), form_input.csrf_token.as_str());
+        form["selections"]["confirmation"] = json!(&form_input.confirmation);
+        form["issues"]["confirmation"] =
+            json!("This does not match the user's username. Type it exactly as shown.");
+        let page = form.in_page(&req, "Delete User").await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
+
+    // Tokens can only be used once, so this is checked right before deleting.
+    csrf::verify(
+        csrf_name(user_id).as_str(),
+        &req,
+        CsrfToken::new(form_input.csrf_token),
+    )?;
+
+    // Remove the user from the RCOS Discord, then delete them.
+    kick_from_discord(user_id).await?;
+    DeleteUser::execute(user_id).await?;
+    audit::record(
+        viewer,
+        "delete_user",
+        format!(
+            "target={} username={:?} name={:?} role={:?}",
+            user_id,
+            username,
+            full_name(&user),
+            user.role
+        ),
+    );
+
+    let mut template = Template::new("admin/users/deleted");
+    template.fields = json!({ "full_name": full_name(&user) });
+    let page = template.in_page(&req, "User Deleted").await?;
+    return page.respond_to(&req).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only the exact username confirms a deletion.
+    #[test]
+    fn confirmation_must_match_username_exactly() {
+        assert!(confirmation_matches("smithj2", "smithj2"));
+        assert!(!confirmation_matches("SMITHJ2", "smithj2"));
+        assert!(!confirmation_matches(" smithj2", "smithj2"));
+        assert!(!confirmation_matches("John Smith", "smithj2"));
+        assert!(!confirmation_matches("", "smithj2"));
+        assert!(!confirmation_matches("", ""));
+    }
+
+    /// Tokens for one user's deletion are saved apart from other users'.
+    #[test]
+    fn csrf_name_is_bound_to_user() {
+        let a: Uuid = Uuid::new_v4();
+        let b: Uuid = Uuid::new_v4();
+        assert_ne!(csrf_name(a), csrf_name(b));
+        assert_eq!(csrf_name(a), csrf_name(a));
+    }
+
+    /// Viewers cannot delete themselves. This is refused before anything is looked up.
+    #[actix_rt::test]
+    async fn self_delete_is_refused() {
+        let viewer: Uuid = Uuid::new_v4();
+        let result = target_checked(viewer, viewer).await;
+        assert!(matches!(result, Err(TelescopeError::BadRequest { .. })));
+    }
+}
@@ -0,0 +1,206 @@
+//! Services to resync the RCOS Discord with the RCOS database.
+//!
+//! These services respond with JSON. Starting a resync or syncing a project needs a CSRF token,
+//! which is issued by a `GET` of the same path and submitted as the `csrf_token` form field of
+//! the `POST`.
+
+use crate::audit;
+use crate::discord_bot::project_sync::{self, ProjectSyncReport};
+use crate::discord_bot::resync::{self, ResyncJob};
+use crate::error::TelescopeError;
+use crate::web::csrf;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::http::header::LOCATION;
+use actix_web::web::{self as aweb, Form, Path, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse};
+use oauth2::CsrfToken;
+use uuid::Uuid;
+
+/// The name CSRF tokens for starting a resync are saved under.
+const RESYNC_CSRF_NAME: &'static str = "admin_discord_resync";
+
+/// The name CSRF tokens for syncing a project are saved under, before the project ID.
+const PROJECT_SYNC_CSRF_NAME: &'static str = "admin_discord_project_sync";
+
+/// Register Discord services. These are registered under `/admin/discord`.
+pub fn register(config: &mut ServiceConfig) {
+    config
+        .service(
+            aweb::resource("/resync")
+                .route(aweb::get().to(resync_token))
+                .route(aweb::post().to(start_resync)),
+        )
+        .service(aweb::resource("/resync/{job_id}").route(aweb::get().to(resync_status)))
+        .service(
+            aweb::resource("/projects/{project_id}/sync")
+                .route(aweb::get().to(project_sync_token))
+                .route(aweb::post().to(sync_project)),
+        );
+}
+
+/// Form submitted to start a Discord service.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CsrfForm {
+    /// The CSRF token issued by a `GET` of the same path.
+    csrf_token: String,
+}
+
+/// Save a new CSRF token under `name` and respond with it as JSON.
+fn token_response(name: &str, req: &HttpRequest) -> Result<HttpResponse, TelescopeError> {
+    let token: CsrfToken = CsrfToken::new_random();
+    let mut response: HttpResponse = HttpResponse::Ok().json(json!({
+        "csrf_token": token.secret(),
+    }));
+    csrf::save(name, req, &mut response, token)?;
+    return Ok(response);
+}
+
+/// Get a CSRF token to start a resync with.
+async fn resync_token(req: HttpRequest) -> Result<HttpResponse, TelescopeError> {
+    return token_response(RESYNC_CSRF_NAME, &req);
+}
+
+/// Start a full Discord resync in the background. Responds immediately with the ID of the job,
+/// and the URL to poll for its progress in the `Location` header.
+async fn start_resync(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Form(form): Form<CsrfForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Access is pre-checked by the scope this is in.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    csrf::verify(RESYNC_CSRF_NAME, &req, CsrfToken::new(form.csrf_token))?;
+
+    // Start the job (or get the one already running).
+    let job_id: Uuid = resync::start(viewer);
+    audit::record(viewer, "discord_resync", format!("job={}", job_id));
+
+    let status_url: String = format!("/admin/discord/resync/{}", job_id);
+    return Ok(HttpResponse::Accepted()
+        .header(LOCATION, status_url.as_str())
+        .json(json!({
+            "job_id": job_id,
+            "status_url": status_url,
+        })));
+}
+
+/// Get the progress of a Discord resync job.
+async fn resync_status(Path(job_id): Path<Uuid>) -> Result<HttpResponse, TelescopeError> {
+    let job: ResyncJob = resync::get(job_id).ok_or(TelescopeError::resource_not_found(
+        "Resync Job Not Found",
+        "Could not find a Discord resync job with that ID. Finished jobs are forgotten after a \
+        day, and all jobs are forgotten when Telescope restarts.",
+    ))?;
+
+    return Ok(HttpResponse::Ok().json(job));
+}
+
+/// Get the name CSRF tokens for syncing a project are saved under. Tokens are bound to the
+/// project, so that a token cannot be used to sync another project.
+fn project_sync_csrf_name(project_id: i64) -> String {
+    format!("{}:{}", PROJECT_SYNC_CSRF_NAME, project_id)
+}
+
+/// Get a CSRF token to sync a project with.
+async fn project_sync_token(
+    req: HttpRequest,
+    Path(project_id): Path<i64>,
+) -> Result<HttpResponse, TelescopeError> {
+    return token_response(project_sync_csrf_name(project_id).as_str(), &req);
+}
+
+/// Create or update the Discord role and channels of one project. This waits for Discord, and
+/// responds with what was done.
+async fn sync_project(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(project_id): Path<i64>,
+    Form(form): Form<CsrfForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Access is pre-checked by the scope this is in.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    csrf::verify(
+        project_sync_csrf_name(project_id).as_str(),
+        &req,
+        CsrfToken::new(form.csrf_token),
+    )?;
+
+    let report: ProjectSyncReport = project_sync::sync_project(project_id).await?;
+    audit::record(
+        viewer,
+        "discord_project_sync",
+        format!("project={}", project_id),
+    );
+
+    return Ok(HttpResponse::Ok().json(report));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::{self, TestRequest};
+
+    /// Get the token and scope cookie from a token response.
+    async fn issued(name: &str) -> (String, Cookie<'static>) {
+        let req: HttpRequest = TestRequest::default().to_http_request();
+        let response: HttpResponse = token_response(name, &req).unwrap();
+        let cookie: Cookie<'static> = response
+            .cookies()
+            .next()
+            .expect("No scope cookie set")
+            .into_owned();
+
+        let body = test::read_body(ServiceResponse::new(req, response)).await;
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        return (json["csrf_token"].as_str().unwrap().to_string(), cookie);
+    }
+
+    /// Issued tokens can be used once to start a resync, from the same browser.
+    #[actix_rt::test]
+    async fn resync_token_is_verified_once() {
+        let (token, cookie) = issued(RESYNC_CSRF_NAME).await;
+        let req: HttpRequest = TestRequest::default().cookie(cookie).to_http_request();
+
+        assert!(csrf::verify(RESYNC_CSRF_NAME, &req, CsrfToken::new(token.clone())).is_ok());
+        assert!(matches!(
+            csrf::verify(RESYNC_CSRF_NAME, &req, CsrfToken::new(token)),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+    }
+
+    /// Starting a resync without a token is refused.
+    #[actix_rt::test]
+    async fn resync_without_token_is_refused() {
+        let (_, cookie) = issued(RESYNC_CSRF_NAME).await;
+        let req: HttpRequest = TestRequest::default().cookie(cookie).to_http_request();
+
+        assert!(matches!(
+            csrf::verify(RESYNC_CSRF_NAME, &req, CsrfToken::new(String::new())),
+            Err(TelescopeError::CsrfTokenMismatch)
+        ));
+    }
+
+    /// Project sync tokens only work for the project they were issued for.
+    #[actix_rt::test]
+    async fn project_sync_token_is_bound_to_project() {
+        let (token, cookie) = issued(project_sync_csrf_name(7).as_str()).await;
+        let req: HttpRequest = TestRequest::default().cookie(cookie).to_http_request();
+
+        assert!(matches!(
+            csrf::verify(
+                project_sync_csrf_name(8).as_str(),
+                &req,
+                CsrfToken::new(token.clone())
+            ),
+            Err(TelescopeError::CsrfTokenNotFound)
+        ));
+        assert!(csrf::verify(
+            project_sync_csrf_name(7).as_str(),
+            &req,
+            CsrfToken::new(token)
+        )
+        .is_ok());
+    }
+}
@@ -1,7 +1,14 @@
 //! Services for the admin panel.
 
+mod announcement;
+pub mod deactivate_user;
+mod delete_user;
+mod discord;
+mod roles;
 mod semesters;
+mod users;
 
+use crate::api::rcos::meetings::authorization_for::AuthorizationFor;
 use crate::api::rcos::users::role_lookup::RoleLookup;
 use crate::api::rcos::users::UserRole;
 use crate::error::TelescopeError;
@@ -21,12 +28,27 @@ fn admin_authorization(user_id: Uuid) -> LocalBoxFuture<'static, AuthorizationRe
         // Then check that their role is admin.
         let role: UserRole = RoleLookup::get(user_id)
             .await?
-            // The role should not be none, since the account needs to exist at this point.
-            .expect("Viewer's account does not exist.");
+            // The account may have been deleted since the identity was checked.
+            .ok_or(TelescopeError::AccountNotFound)?;
 
         // Forbid access unless the user is an admin.
         if !role.is_admin() {
-            Err(TelescopeError::Forbidden)
+            Err(TelescopeError::forbidden("the admin panel"))
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Check that a user is a current coordinator or an admin.
+fn coordinator_authorization(user_id: Uuid) -> LocalBoxFuture<'static, AuthorizationResult> {
+    Box::pin(async move {
+        // Get the user's authorization object.
+        let authorization = AuthorizationFor::get(Some(user_id)).await?;
+
+        // Forbid access unless the user is a coordinator or admin.
+        if !authorization.is_coordinator_or_admin() {
+            Err(TelescopeError::forbidden("this coordinator page"))
         } else {
             Ok(())
         }
@@ -46,6 +68,33 @@ pub fn register(config: &mut ServiceConfig) {
             .to(index),
     );
 
+    // User cleanup, bulk role, user deletion and deactivation, and announcement services are available to
+    // coordinators as well as admins.
+    // These scopes must be registered before the admin scope so that the admin scope
+    // doesn't match these paths first.
+    config.service(
+        aweb::scope("/admin/users")
+            .wrap(Authorization::new(coordinator_authorization))
+            .configure(users::register)
+            .configure(roles::register)
+            .configure(delete_user::register)
+            .configure(deactivate_user::register),
+    );
+
+    // The site-wide announcement can also be set by coordinators.
+    config.service(
+        aweb::scope("/admin/announcement")
+            .wrap(Authorization::new(coordinator_authorization))
+            .configure(announcement::register),
+    );
+
+    // Coordinators can also resync the RCOS Discord.
+    config.service(
+        aweb::scope("/admin/discord")
+            .wrap(Authorization::new(coordinator_authorization))
+            .configure(discord::register),
+    );
+
     // Route every sub-service through the admin scope.
     config.service(
         // Create the admin scope.
@@ -0,0 +1,418 @@
+//! Services for changing the roles of many users at once.
+//!
+//! The form has a CSRF token scoped to the browser it was shown in. Each page with the form
+//! (including the results of a change) gets a new token.
+
+use crate::api::rcos::users::bulk_role::{user_roles::UserRolesUsers, BulkSetRole, UserRoles};
+use crate::api::rcos::users::role_lookup::RoleLookup;
+use crate::api::rcos::users::UserRole;
+use crate::audit;
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::web::{self as aweb, Form, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use oauth2::CsrfToken;
+use uuid::Uuid;
+
+/// The path to the bulk role form template from the templates directory.
+const TEMPLATE_PATH: &'static str = "admin/users/roles";
+
+/// The most users whose role can be changed in one submission.
+const MAX_USERS: usize = 500;
+
+/// The name CSRF tokens of the bulk role form are saved under.
+const CSRF_NAME: &'static str = "admin_bulk_roles";
+
+/// The title of the bulk role page.
+const PAGE_TITLE: &'static str = "Change User Roles";
+
+/// Register bulk role services. These are registered under `/admin/users`.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(
+        aweb::resource("/roles")
+            .route(aweb::get().to(roles_page))
+            .route(aweb::post().to(submit_roles)),
+    );
+}
+
+/// Form submitted to change the roles of a list of users.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BulkRoleForm {
+    /// The CSRF token from the form page.
+    #[serde(skip_serializing)]
+    csrf_token: String,
+    /// The user IDs, separated by whitespace or commas.
+    user_ids: String,
+    /// The role to give every listed user.
+    role: UserRole,
+    /// Change the viewer's own role, if they are in the list.
+    #[serde(default)]
+    confirm_self_change: Option<bool>,
+}
+
+/// The outcome of changing one user's role.
+#[derive(Clone, Debug, Serialize)]
+struct RoleChangeResult {
+    /// The user's ID.
+    user_id: Uuid,
+    /// The user's name, if they were found.
+    name: Option<String>,
+    /// The user's role before the change, if they were found.
+    previous_role: Option<UserRole>,
+    /// Was the user's role changed (or already correct)?
+    success: bool,
+    /// Why the role was not changed, or a note about the change.
+    message: String,
+}
+
+/// Make the bulk role form template, with the CSRF token to submit it with.
+fn make_form(csrf_token: &str) -> Template {
+    let mut form = Template::new(TEMPLATE_PATH);
+    form["roles"] = json!(UserRole::ALL_ROLES);
+    form["csrf_token"] = json!(csrf_token);
+    return form;
+}
+
+/// Render a bulk role page with a new CSRF token, and save the token for the browser that
+/// sent `req`. `fill` adds anything else the page shows to the form.
+async fn respond_with_new_token(
+    req: &HttpRequest,
+    fill: impl FnOnce(&mut Template),
+) -> Result<HttpResponse, TelescopeError> {
+    let token: CsrfToken = CsrfToken::new_random();
+    let mut form: Template = make_form(token.secret());
+    fill(&mut form);
+    let mut response: HttpResponse = form.in_page(req, PAGE_TITLE).await?.respond_to(req).await?;
+    csrf::save(CSRF_NAME, req, &mut response, token)?;
+    return Ok(response);
+}
+
+/// Page to change the roles of many users.
+async fn roles_page(req: HttpRequest) -> Result<HttpResponse, TelescopeError> {
+    // Access is pre-checked by the scope this is in.
+    return respond_with_new_token(&req, |_| {}).await;
+}
+
+/// Parse a list of user IDs separated by whitespace or commas. Duplicates are removed. Return
+/// the IDs and any entries that are not valid IDs.
+fn parse_user_ids(input: &str) -> (Vec<Uuid>, Vec<String>) {
+    let mut ids: Vec<Uuid> = Vec::new();
+    let mut invalid: Vec<String> = Vec::new();
+
+    for entry in input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|entry| !entry.is_empty())
+    {
+        match entry.parse::<Uuid>() {
+            Ok(id) if !ids.contains(&id) => ids.push(id),
+            Ok(_) => {}
+            Err(_) => invalid.push(entry.to_string()),
+        }
+    }
+
+    return (ids, invalid);
+}
+
+/// Would changing the listed users to a role change the viewer's own role?
+fn changes_own_role(viewer: Uuid, users: &[UserRolesUsers], role: UserRole) -> bool {
+    users
+        .iter()
+        .any(|user| user.id == viewer && user.role != role)
+}
+
+/// Decide what to do with each listed user. Return the outcome so far for each user, and the
+/// users whose role should be updated.
+fn plan_changes(
+    ids: Vec<Uuid>,
+    users: &[UserRolesUsers],
+    role: UserRole,
+    viewer_is_admin: bool,
+) -> (Vec<RoleChangeResult>, Vec<Uuid>) {
+    let mut results: Vec<RoleChangeResult> = Vec::with_capacity(ids.len());
+    let mut to_update: Vec<Uuid> = Vec::new();
+    for id in ids {
+        let user: Option<&UserRolesUsers> = users.iter().find(|user| user.id == id);
+        let mut result = RoleChangeResult {
+            user_id: id,
+            name: user.map(|user| format!("{} {}", user.first_name, user.last_name)),
+            previous_role: user.map(|user| user.role),
+            success: false,
+            message: String::new(),
+        };
+
+        match user {
+            None => result.message = "User not found.".into(),
+            Some(user) if user.role == role => {
+                result.success = true;
+                result.message = "Already has this role.".into();
+            }
+            Some(user) if user.role.is_admin() && !viewer_is_admin => {
+                result.message = "Only admins can change the role of an admin.".into();
+            }
+            Some(_) => to_update.push(id),
+        }
+
+        results.push(result);
+    }
+
+    return (results, to_update);
+}
+
+/// Record the outcome of updating the users in `to_update`, given the users that were
+/// actually `updated`. Each change is written to the audit log.
+fn record_updates(
+    viewer: Uuid,
+    role: UserRole,
+    results: &mut [RoleChangeResult],
+    to_update: &[Uuid],
+    updated: &[Uuid],
+) {
+    for result in results
+        .iter_mut()
+        .filter(|result| to_update.contains(&result.user_id))
+    {
+        if updated.contains(&result.user_id) {
+            result.success = true;
+            result.message = "Role changed.".into();
+
+            // Users to update were always found, so their previous role is known.
+            if let Some(previous_role) = result.previous_role {
+                audit::record(
+                    viewer,
+                    "set_user_role",
+                    format!(
+                        "target={} from={:?} to={:?}",
+                        result.user_id, previous_role, role
+                    ),
+                );
+            }
+        } else {
+            result.message = "The role could not be updated.".into();
+        }
+    }
+}
+
+/// Change the roles of the listed users and show the outcome for each one.
+async fn submit_roles(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Form(form_input): Form<BulkRoleForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    let BulkRoleForm {
+        csrf_token,
+        user_ids,
+        role,
+        confirm_self_change,
+    } = form_input.clone();
+
+    // Get the viewer and their role.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let viewer_is_admin: bool = RoleLookup::get(viewer)
+        .await?
+        .map(UserRole::is_admin)
+        .unwrap_or(false);
+
+    // If the form is shown again, the CSRF token has not been used yet, so the form can be
+    // submitted again with it.
+    let mut form: Template = make_form(csrf_token.as_str());
+    form["selections"] = json!(&form_input);
+
+    // Validate the user list.
+    let (ids, invalid) = parse_user_ids(user_ids.as_str());
+    if !invalid.is_empty() {
+        form["issues"]["user_ids"] = json!(format!(
+            "These are not valid user IDs: {}",
+            invalid.join(", ")
+        ));
+    } else if ids.is_empty() {
+        form["issues"]["user_ids"] = json!("Enter at least one user ID.");
+    } else if ids.len() > MAX_USERS {
+        form["issues"]["user_ids"] = json!(format!(
+            "At most {} users can be changed at once.",
+            MAX_USERS
+        ));
+    }
+
+    // Only admins can hand out admin roles.
+    if role.is_admin() && !viewer_is_admin {
+        form["issues"]["role"] = json!(format!("Only admins can give users the {} role.", role));
+    }
+
+    if form["issues"] != json!(null) {
+        let page = form.in_page(&req, PAGE_TITLE).await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
+
+    // Get the listed users' current roles.
+    let users: Vec<UserRolesUsers> = UserRoles::get(ids.clone()).await?;
+
+    // Make sure viewers do not change their own role without meaning to.
+    if changes_own_role(viewer, &users, role) && !confirm_self_change.unwrap_or(false) {
+        form["issues"]["user_ids"] = json!(format!(
+            "Your own user ID is in this list. This will change your role to {}, and you may \
+            lose access to this page. Check the box below and submit again to continue.",
+            role
+        ));
+        form["warnings"]["self_change"] = json!(true);
+        let page = form.in_page(&req, PAGE_TITLE).await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
+
+    // Tokens can only be used once, so this is checked right before changing any roles.
+    csrf::verify(CSRF_NAME, &req, CsrfToken::new(csrf_token))?;
+
+    // Decide what to do with each user.
+    let (mut results, to_update) = plan_changes(ids, &users, role, viewer_is_admin);
+
+    // Update everyone at once.
+    let updated: Vec<Uuid> = if to_update.is_empty() {
+        Vec::new()
+    } else {
+        BulkSetRole::execute(to_update.clone(), role).await?
+    };
+
+    // Record the outcome of each update.
+    record_updates(viewer, role, &mut results, &to_update, &updated);
+
+    // Show the results under an empty form.
+    let failures: usize = results.iter().filter(|result| !result.success).count();
+    return respond_with_new_token(&req, |form| {
+        form["selections"]["role"] = json!(role);
+        form["results"] = json!(results);
+        form["failures"] = json!(failures);
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+
+    /// Make a user with the given role, as returned by the API.
+    fn user(id: Uuid, role: UserRole) -> UserRolesUsers {
+        UserRolesUsers {
+            id,
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            role,
+        }
+    }
+
+    /// User IDs can be separated by commas or whitespace, and duplicates are removed.
+    #[test]
+    fn parses_user_id_list() {
+        let a: Uuid = Uuid::new_v4();
+        let b: Uuid = Uuid::new_v4();
+        let (ids, invalid) = parse_user_ids(format!("{}, {}\n{} nope", a, b, a).as_str());
+        assert_eq!(ids, vec![a, b]);
+        assert_eq!(invalid, vec!["nope".to_string()]);
+    }
+
+    /// Every listed user is updated in one mutation, and each outcome is reported.
+    #[actix_rt::test]
+    async fn successful_batch() {
+        let api = MockRcosApi::start();
+        let viewer: Uuid = Uuid::new_v4();
+        let (first, second, already, missing) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        api.stub(
+            "BulkSetRole",
+            json!({ "update_users": { "returning": [{ "id": first }, { "id": second }] } }),
+        );
+
+        let users: Vec<UserRolesUsers> = vec![
+            user(first, UserRole::Student),
+            user(second, UserRole::Alum),
+            user(already, UserRole::Faculty),
+        ];
+        let ids: Vec<Uuid> = vec![first, second, already, missing];
+        assert!(!changes_own_role(viewer, &users, UserRole::Faculty));
+
+        let (mut results, to_update) = plan_changes(ids, &users, UserRole::Faculty, false);
+        assert_eq!(to_update, vec![first, second]);
+
+        let updated: Vec<Uuid> = BulkSetRole::execute(to_update.clone(), UserRole::Faculty)
+            .await
+            .unwrap();
+        api.assert_called("BulkSetRole", 1);
+        api.assert_variables(
+            "BulkSetRole",
+            json!({ "ids": [first, second], "role": "faculty" }),
+        );
+
+        record_updates(
+            viewer,
+            UserRole::Faculty,
+            &mut results,
+            &to_update,
+            &updated,
+        );
+        let outcomes: Vec<(bool, &str)> = results
+            .iter()
+            .map(|result| (result.success, result.message.as_str()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (true, "Role changed."),
+                (true, "Role changed."),
+                (true, "Already has this role."),
+                (false, "User not found."),
+            ]
+        );
+    }
+
+    /// Users the mutation did not update are reported as failures.
+    #[test]
+    fn reports_users_not_updated() {
+        let viewer: Uuid = Uuid::new_v4();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let users: Vec<UserRolesUsers> = vec![
+            user(first, UserRole::Student),
+            user(second, UserRole::Student),
+        ];
+
+        let (mut results, to_update) =
+            plan_changes(vec![first, second], &users, UserRole::Alum, false);
+        record_updates(viewer, UserRole::Alum, &mut results, &to_update, &[first]);
+        assert!(results[0].success);
+        assert!(!results[1].success);
+        assert_eq!(results[1].message, "The role could not be updated.");
+    }
+
+    /// Viewers in the list are warned before their own role is changed, unless it would not
+    /// change.
+    #[test]
+    fn self_demotion_guard() {
+        let viewer: Uuid = Uuid::new_v4();
+        let users: Vec<UserRolesUsers> = vec![
+            user(viewer, UserRole::Faculty),
+            user(Uuid::new_v4(), UserRole::Faculty),
+        ];
+
+        assert!(changes_own_role(viewer, &users, UserRole::Student));
+        assert!(!changes_own_role(viewer, &users, UserRole::Faculty));
+        assert!(!changes_own_role(Uuid::new_v4(), &users, UserRole::Student));
+    }
+
+    /// Coordinators cannot change the role of an admin.
+    #[test]
+    fn admins_are_protected_from_coordinators() {
+        let admin: Uuid = Uuid::new_v4();
+        let users: Vec<UserRolesUsers> = vec![user(admin, UserRole::Sysadmin)];
+
+        let (results, to_update) = plan_changes(vec![admin], &users, UserRole::Student, false);
+        assert!(to_update.is_empty());
+        assert!(!results[0].success);
+
+        let (_, to_update) = plan_changes(vec![admin], &users, UserRole::Student, true);
+        assert_eq!(to_update, vec![admin]);
+    }
+}
@@ -1,10 +1,11 @@
 //! Semester record creation.
 
+use crate::api::rcos::meetings::creation::context::CreationContext;
 use crate::api::rcos::semesters::mutations::create::CreateSemester;
 use crate::error::TelescopeError;
 use crate::templates::Template;
+use crate::web::redirect::redirect_to;
 use crate::web::services::admin::semesters::semester_id_valid;
-use actix_web::http::header::LOCATION;
 use actix_web::{web::Form, HttpRequest, HttpResponse, Responder};
 use chrono::NaiveDate;
 
@@ -101,9 +102,9 @@ pub async fn submit_new(
 
     // Everything is valid -- create the semester.
     CreateSemester::execute(id, title, start, end).await?;
+    // Show the new semester in meeting forms right away.
+    CreationContext::clear_cache();
 
     // Redirect back to semesters page.
-    Ok(HttpResponse::Found()
-        .header(LOCATION, "/admin/semesters")
-        .finish())
+    Ok(redirect_to("/admin/semesters"))
 }
@@ -1,11 +1,12 @@
 //! Semester Edit services.
 
+use crate::api::rcos::meetings::creation::context::CreationContext;
 use crate::api::rcos::semesters::get_by_id::{semester::SemesterSemestersByPk, Semester};
 use crate::api::rcos::semesters::mutations::edit::EditSemester;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::Template;
-use actix_web::http::header::LOCATION;
+use crate::web::redirect::redirect_to;
 use actix_web::web::Form;
 use actix_web::{web::Path, HttpRequest, HttpResponse};
 use chrono::NaiveDate;
@@ -92,6 +93,8 @@ pub async fn submit_edit(
 
     // Data is valid. Execute changes.
     let edited = EditSemester::execute(semester_id, title, start, end).await?;
+    // Show the edited dates in meeting forms right away.
+    CreationContext::clear_cache();
 
     // Check if there was a semester for this ID.
     if edited.is_none() {
@@ -102,7 +105,5 @@ pub async fn submit_edit(
     }
 
     // Edit success! Redirect user.
-    Ok(HttpResponse::Found()
-        .header(LOCATION, "/admin/semesters")
-        .finish())
+    Ok(redirect_to("/admin/semesters"))
 }
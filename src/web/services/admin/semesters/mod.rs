@@ -25,6 +25,7 @@ pub fn register(config: &mut ServiceConfig) {
         .service(edit::edit)
         .service(edit::submit_edit)
         .service(view_enrollments::export_to_csv)
+        .service(view_enrollments::export_to_json)
         .route("/semesters", aweb::get().to(index))
         .route("/semesters/{page}", aweb::get().to(index));
 }
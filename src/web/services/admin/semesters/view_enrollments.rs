@@ -16,9 +16,11 @@ use crate::api::rcos::semesters::get_by_id::Semester;
 use crate::api::rcos::users::enrollments::enrollments_lookup::EnrollmentsLookup;
 use crate::api::rcos::users::enrollments::user_enrollment_lookup::UserEnrollmentLookup;
 use crate::error::TelescopeError;
+use crate::templates::empty_state::EmptyState;
 use crate::templates::page::Page;
 use crate::templates::pagination::PaginationInfo;
 use crate::templates::Template;
+use crate::web::json_stream;
 use crate::web::services::admin::semesters::PER_PAGE;
 use crate::web::services::auth::identity::Identity;
 
@@ -80,13 +82,12 @@ fn get_page_numbers(api_response: &Value, current_page: u64) -> Option<Paginatio
 pub async fn export_to_csv(
     Path(semester_id): Path<String>,
 ) -> Result<HttpResponse, TelescopeError> {
-    let query_response = EnrollmentsLookup::get(semester_id.clone()).await?;
+    let enrollments = EnrollmentsLookup::get_all(semester_id.clone()).await?;
     let mut buffer = Vec::new();
     // scope to ensure writer is dropped after its done, so we can use the buffer
     {
         let mut wtr = WriterBuilder::new().from_writer(&mut buffer);
-        let api_data = query_response.enrollments;
-        for enrollment in api_data {
+        for enrollment in enrollments {
             wtr.serialize(enrollment).map_err(|e| {
                 TelescopeError::ise(format!(
                     "There was an issue writing the data to CSV: {:?}",
@@ -117,6 +118,32 @@ pub async fn export_to_csv(
     Ok(resp)
 }
 
+/// Download all enrollments for a semester as a JSON array. The array is streamed row by row,
+/// since a semester can have thousands of enrollments.
+#[get("/download/enrollments/{semester_id}/json")]
+pub async fn export_to_json(
+    Path(semester_id): Path<String>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Query everything before starting the response. Once the stream starts, the status code
+    // can no longer change.
+    let enrollments = EnrollmentsLookup::get_all(semester_id.clone()).await?;
+
+    let resp = HttpResponse::Ok()
+        .set_header(header::CONTENT_TYPE, "application/json")
+        .set_header(
+            header::CONTENT_DISPOSITION,
+            ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!(
+                    "enrollments-{}.json",
+                    semester_id
+                ))],
+            },
+        )
+        .streaming(json_stream::json_array(enrollments));
+    Ok(resp)
+}
+
 pub async fn enrollments_page_index(
     req: HttpRequest,
     identity: Identity,
@@ -169,6 +196,7 @@ pub async fn enrollments_page_index(
         "identity": viewer,
         "prefix": prefix,
         "preserved_query_string": req.query_string(),
+        "empty_state": EmptyState::new("Could not find any enrollments matching these parameters."),
     });
     return template.in_page(&req, "Enrollments").await;
 }
@@ -216,6 +244,7 @@ pub async fn enrollments_page(
         "identity": viewer,
         "prefix": prefix,
         "preserved_query_string": req.query_string(),
+        "empty_state": EmptyState::new("Could not find any enrollments matching these parameters."),
     });
     return template.in_page(&req, "Enrollments").await;
 }
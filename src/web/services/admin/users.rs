@@ -0,0 +1,76 @@
+//! Services for user account cleanup.
+
+use crate::api::rcos::users::unlinked::{self, MissingPlatform, PER_PAGE};
+use crate::error::TelescopeError;
+use crate::templates::empty_state::EmptyState;
+use crate::templates::page::Page;
+use crate::templates::pagination::PaginationInfo;
+use crate::templates::Template;
+use actix_web::web::{self as aweb, Path, Query, ServiceConfig};
+use actix_web::HttpRequest;
+use serde_json::Value;
+
+/// The path to the unlinked users page template from the templates directory.
+const TEMPLATE_PATH: &'static str = "admin/users/unlinked";
+
+/// The query parameters passed to the unlinked users page.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct UnlinkedUsersQuery {
+    /// Which platform(s) the listed users should be missing.
+    #[serde(default)]
+    pub missing: MissingPlatform,
+}
+
+/// Register user cleanup services. These are registered under `/admin/users`.
+pub fn register(config: &mut ServiceConfig) {
+    // Route with or without the page number.
+    config
+        .route("/unlinked", aweb::get().to(unlinked_users))
+        .route("/unlinked/{page}", aweb::get().to(unlinked_users));
+}
+
+/// Try to get the pagination bar to use based on the api data.
+/// Panics if `current_page` is 0.
+fn get_page_numbers(api_response: &Value, current_page: u64) -> Option<PaginationInfo> {
+    api_response
+        // Check for the JSON field user_count
+        .get("user_count")?
+        // With field aggregate
+        .get("aggregate")?
+        // With field count
+        .get("count")?
+        // As an unsigned integer
+        .as_u64()
+        // Convert to pagination info
+        .and_then(|count| PaginationInfo::new(count, PER_PAGE as u64, current_page))
+}
+
+/// Page listing users who are missing a linked GitHub or Discord account so that
+/// coordinators can follow up with them.
+async fn unlinked_users(
+    req: HttpRequest,
+    page: Option<Path<u32>>,
+    Query(query): Query<UnlinkedUsersQuery>,
+) -> Result<Page, TelescopeError> {
+    // Resolve the page number from the request. Page numbers in the UI index from 1.
+    let page_num: u32 = page
+        .map(|page_path| page_path.0)
+        .filter(|p| *p >= 1)
+        .map(|p| p - 1)
+        .unwrap_or(0);
+
+    // Query the API.
+    let api_data: Value = unlinked::get(query.missing, page_num).await?;
+
+    // Build the page template.
+    let mut template = Template::new(TEMPLATE_PATH);
+    template.fields = json!({
+        "pagination": get_page_numbers(&api_data, page_num as u64 + 1),
+        "data": api_data,
+        "query": query,
+        "preserved_query_string": req.query_string(),
+        "empty_state": EmptyState::new("No users are missing these accounts.")
+    });
+
+    return template.in_page(&req, "Unlinked Users").await;
+}
@@ -0,0 +1,20 @@
+//! JSON API services.
+//!
+//! Everything under `/api` returns JSON and may be called cross-origin by the origins allowed
+//! in the config.
+
+mod semester_stats;
+mod users;
+
+use crate::web::middlewares::cors::Cors;
+use actix_web::web::{self, ServiceConfig};
+
+/// Register the JSON API services.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(
+        web::scope("/api")
+            .wrap(Cors::from_config())
+            .service(semester_stats::semester_stats)
+            .service(users::username_available),
+    );
+}
@@ -0,0 +1,72 @@
+//! JSON meeting statistics for a semester.
+
+use crate::api::rcos::meetings::authorization_for::AuthorizationFor;
+use crate::api::rcos::semester_stats::SemesterMeetingStats;
+use crate::error::TelescopeError;
+use crate::web::services::auth::identity::OptionalAuth;
+use actix_web::web::Path;
+use actix_web::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+
+/// How many seconds statistics are cached for.
+const CACHE_SECONDS: i64 = 60;
+
+lazy_static! {
+    /// Recently computed statistics (including attendance) and when they were computed, keyed
+    /// by semester ID.
+    static ref STATS_CACHE: DashMap<String, (DateTime<Utc>, SemesterMeetingStats)> = DashMap::new();
+}
+
+/// Get the statistics of a semester, from the cache if they are recent enough.
+async fn cached_stats(semester_id: String) -> Result<Option<SemesterMeetingStats>, TelescopeError> {
+    // Check the cache.
+    let cutoff: DateTime<Utc> = Utc::now() - Duration::seconds(CACHE_SECONDS);
+    if let Some(entry) = STATS_CACHE.get(&semester_id) {
+        let (computed_at, stats) = entry.value();
+        if *computed_at > cutoff {
+            return Ok(Some(stats.clone()));
+        }
+    }
+
+    // Compute and cache them otherwise. Semesters that do not exist are not cached.
+    let stats: Option<SemesterMeetingStats> =
+        SemesterMeetingStats::get(semester_id.clone()).await?;
+    if let Some(stats) = stats.as_ref() {
+        STATS_CACHE.insert(semester_id, (Utc::now(), stats.clone()));
+    }
+
+    // Drop expired entries.
+    STATS_CACHE.retain(|_, (computed_at, _)| *computed_at > cutoff);
+    return Ok(stats);
+}
+
+/// Meeting statistics of a semester. Meeting counts are public, but attendance figures are
+/// only included for coordinators, faculty advisors, and sysadmins.
+#[get("/semester/{semester_id}/stats")]
+pub async fn semester_stats(
+    Path(semester_id): Path<String>,
+    auth: OptionalAuth,
+) -> Result<HttpResponse, TelescopeError> {
+    // Check if the viewer can see attendance.
+    let viewer = auth.get_user_id().await?;
+    let show_attendance: bool = AuthorizationFor::get(viewer)
+        .await?
+        .is_coordinator_or_admin();
+
+    let stats: SemesterMeetingStats =
+        cached_stats(semester_id)
+            .await?
+            .ok_or(TelescopeError::resource_not_found(
+                "Semester Not Found",
+                "Could not find a semester with this ID.",
+            ))?;
+
+    let stats: SemesterMeetingStats = if show_attendance {
+        stats
+    } else {
+        stats.without_attendance()
+    };
+
+    return Ok(HttpResponse::Ok().json(stats));
+}
@@ -0,0 +1,104 @@
+//! JSON username availability checks.
+//!
+//! RCOS users do not have a separate username. The handle they are known by is their RCS ID,
+//! which can only be linked to one account, so that is what is checked here.
+
+use crate::api::rcos::users::accounts::reverse_lookup::ReverseLookup;
+use crate::api::rcos::users::UserAccountType;
+use crate::error::TelescopeError;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::web::Query;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use regex::Regex;
+
+/// How many availability checks each IP address can make per window.
+const CHECKS_PER_WINDOW: u32 = 20;
+
+/// The length of a rate limiting window in seconds.
+const WINDOW_SECONDS: i64 = 60;
+
+lazy_static! {
+    /// The format of an RCS ID (after normalizing): letters, optionally followed by digits.
+    static ref USERNAME_REGEX: Regex =
+        Regex::new(r"^[a-z]{1,16}[0-9]{0,4}$").expect("Could not create username regex");
+
+    /// The start of each IP address's current rate limiting window and how many checks it has
+    /// made in it.
+    static ref CHECKS: DashMap<String, (DateTime<Utc>, u32)> = DashMap::new();
+}
+
+/// Query on the username availability endpoint.
+#[derive(Clone, Debug, Deserialize)]
+struct AvailabilityQuery {
+    /// The username to check.
+    username: String,
+}
+
+/// Normalize a username for lookup. RCS IDs are case-insensitive and stored in lowercase.
+/// Return `None` if the username is not a valid RCS ID.
+fn normalize_username(username: &str) -> Option<String> {
+    let normalized: String = username.trim().to_lowercase();
+    return USERNAME_REGEX
+        .is_match(normalized.as_str())
+        .then(|| normalized);
+}
+
+/// Count a check by an IP address. Return how many seconds until it can check again if it is
+/// over the limit.
+fn rate_limit(ip: &str) -> Option<i64> {
+    let now: DateTime<Utc> = Utc::now();
+    let window: Duration = Duration::seconds(WINDOW_SECONDS);
+
+    // Forget windows that are over.
+    CHECKS.retain(|_, (started, _)| *started + window > now);
+
+    let mut entry = CHECKS.entry(ip.to_string()).or_insert((now, 0));
+    let (started, count) = entry.value_mut();
+    if *count >= CHECKS_PER_WINDOW {
+        return Some((*started + window - now).num_seconds().max(1));
+    }
+
+    *count += 1;
+    return None;
+}
+
+/// Check whether an RCS ID is available (not linked to any RCOS account). Usernames are
+/// normalized and validated before anything is queried, and each IP address can only make a
+/// limited number of checks per minute so this cannot be used to enumerate users.
+#[get("/users/available")]
+pub async fn username_available(
+    req: HttpRequest,
+    Query(query): Query<AvailabilityQuery>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Rate limit by IP address.
+    let ip: String = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_string)
+        .ok_or(TelescopeError::IpExtractionError)?;
+    if let Some(retry_after) = rate_limit(ip.as_str()) {
+        return Ok(HttpResponse::TooManyRequests()
+            .header(RETRY_AFTER, retry_after.to_string())
+            .json(json!({
+                "error": "Too many username checks. Try again later.",
+            })));
+    }
+
+    let username: String =
+        normalize_username(query.username.as_str()).ok_or(TelescopeError::BadRequest {
+            header: "Invalid Username".into(),
+            message: "Usernames are RCS IDs: letters optionally followed by digits.".into(),
+            show_status_code: false,
+        })?;
+
+    let taken: bool = ReverseLookup::execute(UserAccountType::Rpi, username.clone())
+        .await?
+        .is_some();
+
+    return Ok(HttpResponse::Ok().json(json!({
+        "username": username,
+        "available": !taken,
+    })));
+}
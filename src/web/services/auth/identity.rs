@@ -7,11 +7,13 @@ use crate::web::services::auth::oauth2_providers::{
     discord::DiscordIdentity, github::GitHubIdentity,
 };
 use crate::web::services::auth::rpi_cas::RpiCasIdentity;
+use crate::web::sessions::{self, ClientInfo};
 use actix_identity::Identity as ActixIdentity;
 use actix_web::dev::{Payload, PayloadStream};
 use actix_web::{FromRequest, HttpRequest};
 use futures::future::{ready, LocalBoxFuture, Ready};
 use serde::Serialize;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
 /// The root identity that this user is authenticated with.
@@ -66,23 +68,37 @@ impl RootIdentity {
         }
     }
 
-    /// Get the user's RCOS user ID. If the user is not found, throw an error.
-    pub async fn get_user_id_or_error(&self) -> Result<Uuid, TelescopeError> {
-        self.get_user_id()
-            .await
-            .map(|opt| opt.ok_or(TelescopeError::ise("The authenticated user doesn't exist.")))?
-    }
-
     /// Put this root in a top level identity cookie.
     pub fn make_authenticated_cookie(self) -> AuthenticationCookie {
         AuthenticationCookie {
             root: self,
             github: None,
             discord: None,
+            session_id: None,
+            session_version: 0,
+            user_id_cache: UserIdCache::default(),
         }
     }
 }
 
+/// The RCOS user ID linked to an identity cookie, once it has been looked up. Copies of a
+/// cookie share this, so the lookup happens at most once per request (see
+/// [`Identity::identity`]). This is never saved in the cookie itself.
+#[derive(Clone, Debug, Default)]
+pub struct UserIdCache(Arc<Mutex<Option<Option<Uuid>>>>);
+
+impl UserIdCache {
+    /// Get the cached user ID lookup, if there has been one.
+    fn get(&self) -> Option<Option<Uuid>> {
+        *self.0.lock().expect("User ID cache lock poisoned")
+    }
+
+    /// Cache the result of a user ID lookup.
+    fn set(&self, user_id: Option<Uuid>) {
+        *self.0.lock().expect("User ID cache lock poisoned") = Some(user_id);
+    }
+}
+
 /// The top level object stored in the identity cookie.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthenticationCookie {
@@ -94,8 +110,23 @@ pub struct AuthenticationCookie {
 
     /// An optional Discord access and refresh token.
     pub discord: Option<DiscordIdentity>,
+
+    /// The ID of the login session this cookie belongs to. This is assigned when the cookie
+    /// is first saved, and is used to list and revoke sessions. Cookies issued before
+    /// sessions were tracked do not have one.
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+
+    /// The user's session version when they logged in. The cookie is rejected once the user
+    /// logs out everywhere, which increments their version. Cookies issued before versions
+    /// were tracked are at version 0.
+    #[serde(default)]
+    pub session_version: i64,
     // We don't store an optional RCS ID because it can be queried from the
     // database.
+    /// The RCOS user ID of the root identity, once it has been looked up during this request.
+    #[serde(skip)]
+    user_id_cache: UserIdCache,
 }
 
 impl AuthenticationCookie {
@@ -119,15 +150,45 @@ impl AuthenticationCookie {
     }
 
     /// Get the RCOS user ID of an authenticated user. This is the same as just getting the
-    /// RCOS user ID of the root identity.
+    /// RCOS user ID of the root identity, but is only looked up once per request.
     pub async fn get_user_id(&self) -> Result<Option<Uuid>, TelescopeError> {
-        self.root.get_user_id().await
+        let user_id: Option<Uuid> = match self.user_id_cache.get() {
+            Some(user_id) => user_id,
+            None => {
+                let user_id: Option<Uuid> = self.root.get_user_id().await?;
+                self.user_id_cache.set(user_id);
+                user_id
+            }
+        };
+
+        // Record who this session belongs to so it shows up in their session list.
+        if let (Some(session_id), Some(user_id)) = (self.session_id, user_id) {
+            sessions::set_user(session_id, user_id);
+        }
+
+        return Ok(user_id);
     }
 
-    /// Get the authenticated user's RCOS user ID via the root identity or throw an internal
-    /// server error.
+    /// Was this cookie issued at the user's current session version? Cookies without a user
+    /// yet are always current.
+    async fn is_current_session(&self) -> Result<bool, TelescopeError> {
+        let user_id: Uuid = match self.get_user_id().await? {
+            Some(user_id) => user_id,
+            None => return Ok(true),
+        };
+        let user_version: i64 = sessions::session_version(user_id).await?;
+        return Ok(sessions::is_current_version(
+            self.session_version,
+            user_version,
+        ));
+    }
+
+    /// Get the authenticated user's RCOS user ID via the root identity. If the identity is
+    /// valid but has no RCOS user (for example because the user was deleted), return
+    /// [`TelescopeError::AccountNotFound`].
     pub async fn get_user_id_or_error(&self) -> Result<Uuid, TelescopeError> {
-        self.root.get_user_id_or_error().await
+        let user_id: Option<Uuid> = self.get_user_id().await?;
+        return user_id.ok_or(TelescopeError::AccountNotFound);
     }
 
     /// Get discord credentials if authenticated.
@@ -216,6 +277,8 @@ impl AuthenticationCookie {
     ///
     /// If the root can successfully be replaced, return `true`.
     async fn remove_root(&mut self) -> Result<bool, TelescopeError> {
+        // The new root identity is looked up again if needed.
+        self.user_id_cache = UserIdCache::default();
         match self.root {
             // When the root identity is an RCS ID.
             RootIdentity::RpiCas(_) => {
@@ -258,14 +321,41 @@ impl AuthenticationCookie {
     }
 }
 
+/// Should an identity cookie be kept after failing to refresh it with this error? This is
+/// the case when the identity provider is temporarily unavailable or rate limiting us, or
+/// another request is still refreshing the same token, since the refresh may work on a later
+/// request. Any other error (such as the provider rejecting the refresh token) means the
+/// identity can no longer be refreshed.
+pub fn refresh_error_keeps_cookie(err: &TelescopeError) -> bool {
+    matches!(
+        err,
+        TelescopeError::UpstreamUnavailable { .. }
+            | TelescopeError::UpstreamTimeout { .. }
+            | TelescopeError::RateLimited { .. }
+            | TelescopeError::RefreshContention { .. }
+    )
+}
+
 /// The identity of a user accessing telescope.
 #[derive(Clone)]
 pub struct Identity {
     /// The actix identity of this request. This handles cookie and
     /// security stuff.
     inner: ActixIdentity,
+
+    /// Information about the client making this request, recorded with its session.
+    client: ClientInfo,
+
+    /// The request, whose extensions cache the identity cookie.
+    request: HttpRequest,
 }
 
+/// The identity cookie of a request (if there is one), cached in the request's extensions so
+/// that it is only deserialized and refreshed once per request. Copies of the cookie share
+/// their [`UserIdCache`], so the user ID is also only looked up once.
+#[derive(Clone)]
+struct CachedIdentity(Option<AuthenticationCookie>);
+
 impl FromRequest for Identity {
     type Error = TelescopeError;
     type Future = Ready<Result<Self, Self::Error>>;
@@ -286,7 +376,11 @@ impl FromRequest for Identity {
                     ))
                 })
                 // Wrap the extracted identity.
-                .map(|inner| Self { inner }),
+                .map(|inner| Self {
+                    inner,
+                    client: ClientInfo::from_request(req),
+                    request: req.clone(),
+                }),
         )
     }
 }
@@ -313,41 +407,148 @@ impl FromRequest for AuthenticationCookie {
     }
 }
 
+/// Extractor for services that work with or without authentication. Anonymous requests
+/// extract as `OptionalAuth(None)` rather than failing with
+/// [`TelescopeError::NotAuthenticated`] like [`AuthenticationCookie`] does.
+#[derive(Clone, Debug)]
+pub struct OptionalAuth(pub Option<AuthenticationCookie>);
+
+impl OptionalAuth {
+    /// Is the request authenticated?
+    pub fn is_authenticated(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Get the RCOS user ID of the authenticated user, if the request is authenticated and
+    /// there is an RCOS account linked to its identity.
+    pub async fn get_user_id(&self) -> Result<Option<Uuid>, TelescopeError> {
+        match &self.0 {
+            Some(auth) => auth.get_user_id().await,
+            None => Ok(None),
+        }
+    }
+}
+
+impl FromRequest for OptionalAuth {
+    type Error = TelescopeError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload<PayloadStream>) -> Self::Future {
+        // Clone a reference to the HTTP req, since its behind an Rc pointer.
+        let owned_request: HttpRequest = req.clone();
+        return Box::pin(async move {
+            // Extract the telescope-identity from the request, and get the cookie if there is
+            // one. Missing cookies are not an error here.
+            let identity: Identity = Identity::extract(&owned_request).await?;
+            return Ok(OptionalAuth(identity.identity().await));
+        });
+    }
+}
+
 impl Identity {
     /// Forget the user's identity if it exists.
     pub fn forget(&self) {
+        self.request.extensions_mut().insert(CachedIdentity(None));
         self.inner.forget()
     }
 
     /// Save an identity object to the client's cookies.
     pub fn save(&self, identity: &AuthenticationCookie) {
+        // Assign a session ID to new cookies.
+        let mut identity: AuthenticationCookie = identity.clone();
+        let session_id: Uuid = *identity
+            .session_id
+            .get_or_insert_with(sessions::new_session_id);
+
+        // Record activity on this session.
+        sessions::touch(session_id, &self.client);
+
         // Serialize the cookie to JSON first. This serialization should not fail.
         let cookie: String =
-            serde_json::to_string(identity).expect("Could not serialize identity cookie");
+            serde_json::to_string(&identity).expect("Could not serialize identity cookie");
+
+        // Later extractions in this request get the saved cookie.
+        self.request
+            .extensions_mut()
+            .insert(CachedIdentity(Some(identity)));
 
         // Remember cookie.
         self.inner.remember(cookie)
     }
 
-    /// Get the user's identity. Refresh it if necessary.
+    /// Get the user's identity. Refresh it if necessary. This is cached for the rest of the
+    /// request.
     pub async fn identity(&self) -> Option<AuthenticationCookie> {
+        // Check for an identity already resolved for this request.
+        if let Some(CachedIdentity(cached)) = self.request.extensions().get::<CachedIdentity>() {
+            return cached.clone();
+        }
+
+        // Otherwise resolve it. Saving or forgetting the cookie caches the result.
+        let identity: Option<AuthenticationCookie> = self.resolve_identity().await;
+        if identity.is_none() {
+            self.request.extensions_mut().insert(CachedIdentity(None));
+        }
+        return identity;
+    }
+
+    /// Deserialize and refresh the user's identity cookie.
+    async fn resolve_identity(&self) -> Option<AuthenticationCookie> {
         // Get the inner identity as a String.
         let id: String = self.inner.identity()?;
         // try to deserialize it
         match serde_json::from_str::<AuthenticationCookie>(id.as_str()) {
+            // If the session was revoked, forget the cookie.
+            Ok(id) if id.session_id.map(sessions::is_revoked).unwrap_or(false) => {
+                info!("Revoked session forgotten.");
+                self.forget();
+                return None;
+            }
+
             // On okay, refresh the identity cookie if needed
             Ok(id) => match id.refresh().await {
                 // If this succeeds
-                Ok(id) => {
+                Ok(mut id) => {
+                    // Forget cookies issued before the user last logged out everywhere. If the
+                    // version cannot be checked, keep the cookie for the next request, but
+                    // treat this request as unauthenticated.
+                    match id.is_current_session().await {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            info!("Session from before logging out everywhere forgotten.");
+                            self.forget();
+                            return None;
+                        }
+                        Err(e) => {
+                            warn!("Could not check identity session version. Error: {}", e);
+                            return None;
+                        }
+                    }
+
+                    // Give cookies from before sessions were tracked a session ID.
+                    id.session_id.get_or_insert_with(sessions::new_session_id);
                     // Save and return the authenticated identity
                     self.save(&id);
                     return Some(id);
                 }
 
-                // If it fails to refresh, we have no identity. Send a warning
-                // and return None.
+                // If the refresh may work later, keep the cookie so the refresh can be
+                // tried again on the next request, but treat this request as
+                // unauthenticated.
+                Err(e) if refresh_error_keeps_cookie(&e) => {
+                    warn!(
+                        "Could not refresh identity token (will retry). Error: {}",
+                        e
+                    );
+                    return None;
+                }
+
+                // Otherwise the identity can no longer be refreshed. Forget it,
+                // send a warning, and return None.
                 Err(e) => {
                     warn!("Could not refresh identity token. Error: {}", e);
+                    self.forget();
                     return None;
                 }
             },
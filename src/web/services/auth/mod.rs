@@ -1,22 +1,26 @@
 use crate::api::discord::global_discord_client;
 use crate::api::rcos::users::accounts::for_user::UserAccounts;
+use crate::api::rcos::users::accounts::reverse_lookup::ReverseLookup;
 use crate::api::rcos::users::accounts::unlink::UnlinkUserAccount;
 use crate::api::rcos::users::UserAccountType;
 use crate::env::global_config;
 use crate::error::TelescopeError;
-
+use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::redirect::redirect_to;
 use crate::web::services::auth::identity::{AuthenticationCookie, Identity};
 use crate::web::services::auth::oauth2_providers::discord::DiscordOAuth;
 use crate::web::services::auth::rpi_cas::RpiCas;
-use actix_web::http::header::{HOST, LOCATION};
-use actix_web::web::ServiceConfig;
+use actix_web::http::header::HOST;
+use actix_web::web::{Form, ServiceConfig};
 use actix_web::{web as aweb, Responder};
 use actix_web::{HttpRequest, HttpResponse};
 use futures::future::LocalBoxFuture;
-use oauth2::RedirectUrl;
+use oauth2::{CsrfToken, RedirectUrl};
 use oauth2_providers::github::GitHubOauth;
 use std::collections::HashMap;
 use std::future::Future;
+use uuid::Uuid;
 
 pub mod identity;
 pub mod oauth2_providers;
@@ -29,6 +33,19 @@ const AUTHENTICATOR_ACCOUNT_TYPES: [UserAccountType; 3] = [
     UserAccountType::Discord,
 ];
 
+/// The path to the unlink confirmation page template from the templates directory.
+const UNLINK_TEMPLATE: &'static str = "user/unlink";
+
+/// The prefix of the name unlink CSRF tokens are saved under.
+const UNLINK_CSRF_NAME: &'static str = "unlink";
+
+/// Form submitted to unlink an identity service.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UnlinkForm {
+    /// The CSRF token from the confirmation page.
+    csrf_token: String,
+}
+
 /// Register auth services.
 pub fn register(config: &mut ServiceConfig) {
     // GitHub OAuth2 provider services.
@@ -59,6 +76,81 @@ fn make_redirect_url(req: &HttpRequest, redir_path: String) -> RedirectUrl {
         .expect("Could not create redirect URL");
 }
 
+/// Make sure an account on an identity platform is not already linked to an RCOS user other
+/// than `user_id`. Each platform account can only be linked to one RCOS user, so linking it
+/// to a second user is rejected instead of leaving the account ambiguous.
+async fn check_link_conflict(
+    user_id: Uuid,
+    account_type: UserAccountType,
+    platform_id: &str,
+) -> Result<(), TelescopeError> {
+    let linked_to: Option<Uuid> =
+        ReverseLookup::execute(account_type, platform_id.to_string()).await?;
+    match linked_to {
+        Some(other) if other != user_id => {
+            warn!(
+                "User {} tried to link {} account {}, which is already linked to user {}.",
+                user_id, account_type, platform_id, other
+            );
+            return Err(TelescopeError::BadRequest {
+                header: format!("{} account already linked", account_type),
+                message: format!(
+                    "This {} account is already linked to a different RCOS account. Sign in \
+                    with it and unlink it there before linking it here. If you did not link it, \
+                    please contact a coordinator.",
+                    account_type
+                ),
+                show_status_code: false,
+            });
+        }
+        _ => return Ok(()),
+    }
+}
+
+/// Get the name the CSRF token to unlink a platform is saved under. This includes the
+/// platform, so a token from one confirmation page cannot unlink another platform.
+fn unlink_csrf_name(platform: UserAccountType) -> String {
+    format!("{}:{}", UNLINK_CSRF_NAME, platform)
+}
+
+/// Make sure a user can unlink their account on `platform`. `root` is the platform the user
+/// signed in with and `linked` is the authentication accounts linked to the user.
+///
+/// The account the user signed in with cannot be unlinked, since the login cookie must always
+/// have a root identity. Unlinking is also refused if it would leave the user without any way
+/// to sign in.
+fn check_can_unlink(
+    platform: UserAccountType,
+    root: UserAccountType,
+    linked: &HashMap<UserAccountType, String>,
+) -> Result<(), TelescopeError> {
+    if root == platform {
+        return Err(TelescopeError::BadRequest {
+            header: format!("Cannot unlink {} account", platform),
+            message: format!(
+                "You are signed in with this {} account. Please sign out and sign in \
+                with another linked account to unlink it.",
+                platform
+            ),
+            show_status_code: false,
+        });
+    }
+
+    // If there is not another account for the user to authenticate with, return an error.
+    let remaining: usize = linked.keys().filter(|ty| **ty != platform).count();
+    if remaining == 0 {
+        return Err(TelescopeError::BadRequest {
+            header: format!("Cannot unlink {} account", platform),
+            message: "You have no other authentication methods linked, so unlinking \
+            this platform would prevent you from logging in."
+                .into(),
+            show_status_code: false,
+        });
+    }
+
+    return Ok(());
+}
+
 /// Trait for identity providers (GitHub OAuth2, Discord OAuth2, RPI CAS, etc).
 pub trait IdentityProvider: 'static {
     /// The lowercase, one word name of the service. This is used in generating
@@ -158,9 +250,10 @@ pub trait IdentityProvider: 'static {
                 Self::link_path().as_str(),
                 aweb::get().to(Self::link_handler),
             )
-            .route(
-                Self::unlink_path().as_str(),
-                aweb::get().to(Self::unlink_handler),
+            .service(
+                aweb::resource(Self::unlink_path().as_str())
+                    .route(aweb::get().to(Self::unlink_page))
+                    .route(aweb::post().to(Self::unlink_handler)),
             )
             .route(
                 Self::login_redirect_path().as_str(),
@@ -196,36 +289,71 @@ pub trait IdentityProvider: 'static {
     /// existing account on this platform first.
     fn link_handler(req: HttpRequest, ident: Identity) -> Self::LinkFut;
 
-    /// Actix-web handler for the route that unlinks an identity service.
+    /// Actix-web handler for the page confirming that the user wants to unlink this
+    /// identity service. Unlinking changes the user's account, so it is only done by
+    /// submitting this page's form (with its CSRF token) to [`Self::unlink_handler`].
+    fn unlink_page(
+        req: HttpRequest,
+        cookie: AuthenticationCookie,
+    ) -> LocalBoxFuture<'static, Result<HttpResponse, TelescopeError>> {
+        return Box::pin(async move {
+            let user_id = cookie.get_user_id_or_error().await?;
+
+            // Render the confirmation and save its CSRF token for this browser.
+            let token: CsrfToken = CsrfToken::new_random();
+            let mut template: Template = Template::new(UNLINK_TEMPLATE);
+            template.fields = json!({
+                "user_id": user_id,
+                "platform": Self::USER_ACCOUNT_TY.to_string(),
+                "csrf_token": token.secret(),
+            });
+            let mut response: HttpResponse = template
+                .in_page(&req, format!("Unlink {}", Self::USER_ACCOUNT_TY))
+                .await?
+                .respond_to(&req)
+                .await?;
+            csrf::save(
+                unlink_csrf_name(Self::USER_ACCOUNT_TY).as_str(),
+                &req,
+                &mut response,
+                token,
+            )?;
+            return Ok(response);
+        });
+    }
+
+    /// Actix-web handler for the form that unlinks an identity service. Guarded by this
+    /// trait to POST requests.
     fn unlink_handler(
+        req: HttpRequest,
         id: Identity,
         mut cookie: AuthenticationCookie,
+        form: Form<UnlinkForm>,
     ) -> LocalBoxFuture<'static, Result<HttpResponse, TelescopeError>> {
         return Box::pin(async move {
+            // Check the token from the confirmation page first. It can only be used once.
+            csrf::verify(
+                unlink_csrf_name(Self::USER_ACCOUNT_TY).as_str(),
+                &req,
+                CsrfToken::new(form.into_inner().csrf_token),
+            )?;
+
             // Lookup the ID of the user trying to unlink an account.
             let user_id = cookie.get_user_id_or_error().await?;
-            // Get all of the accounts linked to this user. Make sure at least one
-            // can function for authentication.
+            // Get all of the accounts linked to this user, filtered down to the
+            // authentication providers.
             let all_accounts: HashMap<UserAccountType, String> = UserAccounts::send(user_id)
                 .await?
-                // Iterate
                 .into_iter()
-                // filter down to the authentication providers
                 .filter(|(u, _)| AUTHENTICATOR_ACCOUNT_TYPES.contains(u))
-                // Collect into map.
                 .collect();
 
-            // If there is not a secondary account for the user to authenticate with,
-            // return an error.
-            if all_accounts.len() <= 1 {
-                return Err(TelescopeError::BadRequest {
-                    header: format!("Cannot unlink {} account", Self::USER_ACCOUNT_TY),
-                    message: "You have no other authentication methods linked, so unlinking \
-                    this platform would prevent you from logging in."
-                        .into(),
-                    show_status_code: false,
-                });
-            }
+            // Make sure the user can still sign in after unlinking.
+            check_can_unlink(
+                Self::USER_ACCOUNT_TY,
+                cookie.root.get_user_account_type(),
+                &all_accounts,
+            )?;
 
             // If the user is unlinking their Discord or RPI CAS, we remove them from the
             // RCOS Discord Server.
@@ -251,28 +379,12 @@ pub trait IdentityProvider: 'static {
                 _ => {}
             }
 
-            // Important: The root must be replaced with another platform (if possible)
-            // before the unlink mutation is executed. Doing this in the other order will
-            // lead to an account not found error while trying to replace the root of the
-            // auth cookie because the account lookup will fail/error out.
-            // See https://github.com/rcos/Telescope/issues/185.
-
-            // Try to replace the unlinking account in the authentication cookie's root
-            // (if it's authenticated as root).
-            let removed_auth: bool = cookie.remove_platform(Self::USER_ACCOUNT_TY).await?;
-            // If this is a success, then save the modified authentication cookie and redirect
-            // the user to their profile at the end.
-            // If not, the user has been logged out. Redirect them to the homepage at the end.
-            if removed_auth {
-                id.save(&cookie);
-            } else {
-                id.forget();
-            }
-
             // There is a secondary authenticator linked, delete this user account record.
-            // Log a message about the unlinked platform.
+            // This is done before changing the cookie, so that if it fails the cookie still
+            // matches the accounts in the database.
             let platform_id = UnlinkUserAccount::send(user_id, Self::USER_ACCOUNT_TY).await?;
 
+            // Log a message about the unlinked platform.
             if let Some(platform_id) = platform_id {
                 info!(
                     "User {} unlinked {} account with id {}.",
@@ -282,14 +394,13 @@ pub trait IdentityProvider: 'static {
                 );
             }
 
-            // Get the path to redirect the user to.
-            let redirect: String = removed_auth
-                // If the auth was replaced successfully, the user's profile.
-                .then(|| format!("/user/{}", user_id))
-                // Otherwise the homepage.
-                .unwrap_or("/".into());
+            // Remove the secondary identity from the authentication cookie. This never touches
+            // the root, which was checked above.
+            cookie.remove_platform(Self::USER_ACCOUNT_TY).await?;
+            id.save(&cookie);
 
-            return Ok(HttpResponse::Found().header(LOCATION, redirect).finish());
+            // Return the user to their profile.
+            return Ok(redirect_to(format!("/user/{}", user_id)));
         });
     }
 
@@ -307,3 +418,98 @@ pub trait IdentityProvider: 'static {
         ident: Identity,
     ) -> Self::LinkAuthenticatedFut;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+
+    /// Make a map of linked accounts of the given types.
+    fn linked(types: &[UserAccountType]) -> HashMap<UserAccountType, String> {
+        types.iter().map(|ty| (*ty, format!("{}-id", ty))).collect()
+    }
+
+    /// The account the user signed in with cannot be unlinked, even if others are linked.
+    #[test]
+    fn refuses_to_unlink_root_identity() {
+        let accounts = linked(&[UserAccountType::GitHub, UserAccountType::Discord]);
+        let result = check_can_unlink(UserAccountType::GitHub, UserAccountType::GitHub, &accounts);
+        assert!(matches!(result, Err(TelescopeError::BadRequest { .. })));
+    }
+
+    /// A secondary account can be unlinked while the root stays linked.
+    #[test]
+    fn unlinks_secondary_identity() {
+        let accounts = linked(&[UserAccountType::GitHub, UserAccountType::Discord]);
+        let result = check_can_unlink(UserAccountType::Discord, UserAccountType::GitHub, &accounts);
+        assert!(result.is_ok());
+    }
+
+    /// Unlinking the only authentication account is refused.
+    #[test]
+    fn refuses_to_unlink_last_authenticator() {
+        let accounts = linked(&[UserAccountType::Discord]);
+        let result = check_can_unlink(UserAccountType::Discord, UserAccountType::GitHub, &accounts);
+        assert!(matches!(result, Err(TelescopeError::BadRequest { .. })));
+    }
+
+    /// Tokens to unlink one platform are saved apart from other platforms'.
+    #[test]
+    fn unlink_csrf_name_is_bound_to_platform() {
+        assert_ne!(
+            unlink_csrf_name(UserAccountType::GitHub),
+            unlink_csrf_name(UserAccountType::Discord)
+        );
+    }
+
+    /// An account already linked to the same user can be linked again.
+    #[actix_rt::test]
+    async fn link_to_same_user() {
+        let api = MockRcosApi::start();
+        let user_id = Uuid::new_v4();
+        api.stub(
+            "ReverseLookup",
+            json!({ "user_accounts": [{ "user_id": user_id }] }),
+        );
+
+        let result = check_link_conflict(user_id, UserAccountType::GitHub, "1234").await;
+        assert!(result.is_ok());
+        api.assert_variables(
+            "ReverseLookup",
+            json!({ "platform": "github", "id": "1234" }),
+        );
+    }
+
+    /// An account linked to a different user cannot be linked.
+    #[actix_rt::test]
+    async fn link_to_different_user() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "ReverseLookup",
+            json!({ "user_accounts": [{ "user_id": Uuid::new_v4() }] }),
+        );
+
+        let result = check_link_conflict(Uuid::new_v4(), UserAccountType::Discord, "5678").await;
+        assert!(matches!(result, Err(TelescopeError::BadRequest { .. })));
+    }
+
+    /// An account that is not linked to anyone can be linked.
+    #[actix_rt::test]
+    async fn link_unlinked_account() {
+        let api = MockRcosApi::start();
+        api.stub("ReverseLookup", json!({ "user_accounts": [] }));
+
+        let result = check_link_conflict(Uuid::new_v4(), UserAccountType::Rpi, "montl").await;
+        assert!(result.is_ok());
+    }
+
+    /// A failed lookup is an error, not a pass.
+    #[actix_rt::test]
+    async fn link_lookup_error() {
+        let api = MockRcosApi::start();
+        api.stub_error("ReverseLookup", "Something went wrong");
+
+        let result = check_link_conflict(Uuid::new_v4(), UserAccountType::GitHub, "1234").await;
+        assert!(result.is_err());
+    }
+}
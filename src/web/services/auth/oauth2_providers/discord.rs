@@ -8,15 +8,20 @@ use crate::web::services::auth::identity::{AuthenticationCookie, RootIdentity};
 use crate::web::services::auth::oauth2_providers::{Oauth2Identity, Oauth2IdentityProvider};
 use crate::web::services::auth::IdentityProvider;
 use actix_web::http::header::ACCEPT;
+use actix_web::rt::time::{delay_for, timeout};
 use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
 use futures::future::LocalBoxFuture;
-use oauth2::basic::{BasicClient, BasicTokenResponse};
-use oauth2::{AccessToken, RefreshToken, Scope, TokenResponse};
+use futures::lock::Mutex;
+use oauth2::basic::{BasicClient, BasicErrorResponse, BasicTokenResponse};
+use oauth2::{AccessToken, RefreshToken, RequestTokenError, Scope, TokenResponse};
 use oauth2::{AuthUrl, TokenUrl};
 use reqwest::header::AUTHORIZATION;
 use serenity::model::id::RoleId;
 use serenity::model::user::CurrentUser;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration as StdDuration;
 use uuid::Uuid;
 
 /// The Discord API endpoint to query for user data.
@@ -36,7 +41,25 @@ pub struct DiscordIdentity {
     refresh_token: RefreshToken,
 }
 
+/// How long a refreshed token is reused for other requests holding the same
+/// (now rotated) refresh token.
+const REFRESH_REUSE_SECONDS: i64 = 60;
+
+/// The longest a request will wait on another request refreshing the same token.
+const REFRESH_LOCK_TIMEOUT: StdDuration = StdDuration::from_secs(30);
+
+/// How long to wait before retrying a failed token refresh. This is multiplied by the
+/// attempt number.
+const REFRESH_RETRY_BACKOFF: StdDuration = StdDuration::from_millis(250);
+
+/// A lock around the most recent result of refreshing a given refresh token. This is an async
+/// lock, so requests waiting on another refresh do not block their worker thread.
+type RefreshSlot = Arc<Mutex<Option<(DiscordIdentity, DateTime<Utc>)>>>;
+
 lazy_static! {
+    /// Refresh slots keyed by the refresh token being exchanged.
+    static ref IN_FLIGHT_REFRESHES: DashMap<String, RefreshSlot> = DashMap::new();
+
     static ref DISCORD_CLIENT: Arc<BasicClient> = {
         // Get the global config.
         let config = global_config();
@@ -114,36 +137,141 @@ impl DiscordIdentity {
         }
     }
 
+    /// Does this access token need to be refreshed as of `now`? Tokens are refreshed once
+    /// they are within `skew` of expiring, so that they do not expire while a request is
+    /// using them.
+    pub fn needs_refresh(&self, now: DateTime<Utc>, skew: Duration) -> bool {
+        self.expiration - skew <= now
+    }
+
     /// Refresh this access token if necessary.
+    ///
+    /// Concurrent requests from the same user will often try to refresh the same expired
+    /// token at once. Discord rotates refresh tokens, so only the first exchange succeeds and
+    /// the others would clobber the user's cookie with a stale token. To prevent this,
+    /// refreshes are coalesced per refresh token: the first request exchanges the token and
+    /// any other request waiting on the same token reuses the result.
     pub async fn refresh(self) -> Result<Self, TelescopeError> {
-        // If this token has expired
-        if self.expiration < Utc::now() {
-            // Get a discord client and make a refresh token request.
-            let client: Arc<BasicClient> = <DiscordOAuth as Oauth2IdentityProvider>::get_client();
+        // If this token is not about to expire, we don't need to refresh -- return self.
+        let skew: Duration =
+            Duration::seconds(global_config().identity_refresh_skew_seconds as i64);
+        if !self.needs_refresh(Utc::now(), skew) {
+            return Ok(self);
+        }
+
+        return self
+            .refresh_with(|identity| async move { identity.request_refresh().await })
+            .await;
+    }
+
+    /// Refresh this access token using `exchange` to exchange the refresh token, coalescing
+    /// concurrent refreshes of the same token (see [`DiscordIdentity::refresh`]).
+    async fn refresh_with<F, Fut>(self, exchange: F) -> Result<Self, TelescopeError>
+    where
+        F: FnOnce(Self) -> Fut,
+        Fut: Future<Output = Result<Self, TelescopeError>>,
+    {
+        // Get (or create) the refresh slot for this refresh token.
+        let key: String = self.refresh_token.secret().clone();
+        let slot: RefreshSlot = IN_FLIGHT_REFRESHES.entry(key).or_default().clone();
+
+        // Wait for any other refresh of this token to finish. Nothing else is locked
+        // while waiting, and the wait is bounded, so this cannot deadlock.
+        let mut guard = timeout(REFRESH_LOCK_TIMEOUT, slot.lock())
+            .await
+            .map_err(|_| TelescopeError::refresh_contention("Discord", REFRESH_LOCK_TIMEOUT))?;
+
+        // If another request already refreshed this token recently, reuse the result.
+        if let Some((refreshed, refreshed_at)) = guard.as_ref() {
+            if Utc::now() - *refreshed_at < Duration::seconds(REFRESH_REUSE_SECONDS) {
+                return Ok(refreshed.clone());
+            }
+        }
+
+        // Otherwise exchange the refresh token and save the result for other requests.
+        let refreshed: DiscordIdentity = exchange(self).await?;
+        *guard = Some((refreshed.clone(), Utc::now()));
+        drop(guard);
+
+        // Remove slots that are no longer useful. Skip any that are currently locked.
+        IN_FLIGHT_REFRESHES.retain(|_, slot| {
+            slot.try_lock()
+                .and_then(|guard| guard.as_ref().map(|(_, at)| *at))
+                .map(|at| Utc::now() - at < Duration::seconds(REFRESH_REUSE_SECONDS))
+                .unwrap_or(true)
+        });
+
+        return Ok(refreshed);
+    }
+
+    /// Exchange this identity's refresh token for a new access token. Transient failures
+    /// (network errors and unexpected responses such as a 5xx) are retried a configured
+    /// number of times and then reported as [`TelescopeError::UpstreamUnavailable`]. Errors
+    /// returned by Discord (such as `invalid_grant`) are not retried.
+    async fn request_refresh(&self) -> Result<Self, TelescopeError> {
+        // Get a discord client.
+        let client: Arc<BasicClient> = <DiscordOAuth as Oauth2IdentityProvider>::get_client();
+
+        // Make one refresh token request.
+        let try_refresh = || {
             let mut refresh_token_request = client.exchange_refresh_token(&self.refresh_token);
             // Add scopes.
             for scope in DiscordOAuth::scopes() {
                 refresh_token_request = refresh_token_request.add_scope(scope);
             }
-            // Create refresh response
-            let response = refresh_token_request
+
+            refresh_token_request
                 // Add login redirect path.
                 .add_extra_param("redirect_uri", DiscordOAuth::login_redirect_path().as_str())
                 // Send the request.
                 .request(oauth2::reqwest::http_client)
-                // Handle and propagate the error.
-                .map_err(|err| {
-                    TelescopeError::ise(format!(
+        };
+
+        return Self::retry_refresh(try_refresh, global_config().identity_refresh_retries).await;
+    }
+
+    /// Make a refresh token request with `try_refresh`, retrying transient failures up to
+    /// `retries` times (see [`DiscordIdentity::request_refresh`]).
+    async fn retry_refresh<F, RE>(mut try_refresh: F, retries: u32) -> Result<Self, TelescopeError>
+    where
+        F: FnMut() -> Result<BasicTokenResponse, RequestTokenError<RE, BasicErrorResponse>>,
+        RE: std::error::Error + 'static,
+    {
+        let mut attempt: u32 = 0;
+        loop {
+            match try_refresh() {
+                // Make and return the new token on success.
+                Ok(response) => return Ok(Self::from_response(&response)),
+
+                // Discord rejected the refresh token (e.g. `invalid_grant`). Retrying will
+                // not help.
+                Err(err @ RequestTokenError::ServerResponse(_)) => {
+                    return Err(TelescopeError::ise(format!(
                         "Could not refresh Discord OAuth2 token. Error: {}",
                         err
-                    ))
-                })?;
+                    )));
+                }
 
-            // Make and return the new token.
-            return Ok(Self::from_response(&response));
-        } else {
-            // We don't need to refresh -- return self.
-            return Ok(self);
+                // Anything else may be transient. Retry with a short backoff until we run
+                // out of attempts.
+                Err(err) => {
+                    if attempt >= retries {
+                        return Err(TelescopeError::upstream_unavailable(
+                            "Discord",
+                            format!("Could not refresh Discord OAuth2 token. Error: {}", err),
+                        ));
+                    }
+
+                    attempt += 1;
+                    warn!(
+                        "Transient error refreshing Discord OAuth2 token (attempt {} of {}): {}",
+                        attempt,
+                        retries + 1,
+                        err
+                    );
+                    delay_for(REFRESH_RETRY_BACKOFF * attempt).await;
+                }
+            }
         }
     }
 
@@ -256,3 +384,159 @@ impl DiscordIdentity {
         return Ok(());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::services::auth::identity::refresh_error_keeps_cookie;
+    use oauth2::basic::{BasicErrorResponseType, BasicTokenType};
+    use oauth2::EmptyExtraTokenFields;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Make an expired identity with the given refresh token.
+    fn expired(refresh_token: &str) -> DiscordIdentity {
+        DiscordIdentity {
+            access_token: AccessToken::new("expired".into()),
+            expiration: Utc::now() - Duration::minutes(5),
+            refresh_token: RefreshToken::new(refresh_token.into()),
+        }
+    }
+
+    /// Make a token exchange that counts how many times it is called. Each exchange takes a
+    /// moment, so that concurrent refreshes overlap.
+    fn counted_exchange(
+        exchanges: Arc<AtomicUsize>,
+    ) -> impl FnOnce(DiscordIdentity) -> LocalBoxFuture<'static, Result<DiscordIdentity, TelescopeError>>
+    {
+        move |_| {
+            Box::pin(async move {
+                let count: usize = exchanges.fetch_add(1, Ordering::SeqCst) + 1;
+                delay_for(StdDuration::from_millis(50)).await;
+                Ok(DiscordIdentity {
+                    access_token: AccessToken::new(format!("refreshed {}", count)),
+                    expiration: Utc::now() + Duration::days(7),
+                    refresh_token: RefreshToken::new(format!("rotated {}", count)),
+                })
+            })
+        }
+    }
+
+    /// Two requests refreshing the same token at once make one exchange and get the same
+    /// refreshed token.
+    #[actix_rt::test]
+    async fn concurrent_refreshes_exchange_once() {
+        let refresh_token: String = Uuid::new_v4().to_string();
+        let exchanges: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+        let (first, second) = futures::join!(
+            expired(refresh_token.as_str()).refresh_with(counted_exchange(exchanges.clone())),
+            expired(refresh_token.as_str()).refresh_with(counted_exchange(exchanges.clone()))
+        );
+
+        assert_eq!(exchanges.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            first.unwrap().access_token.secret(),
+            second.unwrap().access_token.secret()
+        );
+    }
+
+    /// Refreshes of different tokens are not coalesced.
+    #[actix_rt::test]
+    async fn different_tokens_exchange_separately() {
+        let exchanges: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+        let (first, second) = futures::join!(
+            expired(Uuid::new_v4().to_string().as_str())
+                .refresh_with(counted_exchange(exchanges.clone())),
+            expired(Uuid::new_v4().to_string().as_str())
+                .refresh_with(counted_exchange(exchanges.clone()))
+        );
+
+        assert_eq!(exchanges.load(Ordering::SeqCst), 2);
+        assert!(first.is_ok() && second.is_ok());
+    }
+
+    /// The result of a refresh token request in these tests.
+    type TestRefreshResult =
+        Result<BasicTokenResponse, RequestTokenError<io::Error, BasicErrorResponse>>;
+
+    /// Make a successful token response from Discord.
+    fn token_response() -> BasicTokenResponse {
+        let mut response = BasicTokenResponse::new(
+            AccessToken::new("refreshed".into()),
+            BasicTokenType::Bearer,
+            EmptyExtraTokenFields {},
+        );
+        response.set_expires_in(Some(&StdDuration::from_secs(604800)));
+        response.set_refresh_token(Some(RefreshToken::new("rotated".into())));
+        return response;
+    }
+
+    /// Make a transient failure, like a network error or a 5xx response.
+    fn transient_failure() -> TestRefreshResult {
+        Err(RequestTokenError::Other("Server returned 503".into()))
+    }
+
+    /// A transient failure is retried, and the retry succeeds.
+    #[actix_rt::test]
+    async fn transient_failure_retries_and_succeeds() {
+        let mut attempts: u32 = 0;
+        let result = DiscordIdentity::retry_refresh(
+            || {
+                attempts += 1;
+                if attempts == 1 {
+                    transient_failure()
+                } else {
+                    Ok(token_response())
+                }
+            },
+            2,
+        )
+        .await;
+
+        assert_eq!(attempts, 2);
+        assert_eq!(result.unwrap().access_token.secret(), "refreshed");
+    }
+
+    /// Once the retries run out, the failure is reported as transient, so the cookie is
+    /// kept for the next request.
+    #[actix_rt::test]
+    async fn transient_failure_keeps_cookie() {
+        let mut attempts: u32 = 0;
+        let result = DiscordIdentity::retry_refresh(
+            || {
+                attempts += 1;
+                transient_failure()
+            },
+            1,
+        )
+        .await;
+
+        assert_eq!(attempts, 2);
+        let err: TelescopeError = result.unwrap_err();
+        assert!(matches!(err, TelescopeError::UpstreamUnavailable { .. }));
+        assert!(refresh_error_keeps_cookie(&err));
+    }
+
+    /// Discord rejecting the refresh token is not retried, and the session is dropped.
+    #[actix_rt::test]
+    async fn invalid_grant_drops_session() {
+        let mut attempts: u32 = 0;
+        let result = DiscordIdentity::retry_refresh(
+            || -> TestRefreshResult {
+                attempts += 1;
+                Err(RequestTokenError::ServerResponse(BasicErrorResponse::new(
+                    BasicErrorResponseType::InvalidGrant,
+                    None,
+                    None,
+                )))
+            },
+            2,
+        )
+        .await;
+
+        assert_eq!(attempts, 1);
+        assert!(!refresh_error_keeps_cookie(&result.unwrap_err()));
+    }
+}
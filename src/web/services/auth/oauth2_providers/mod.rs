@@ -1,4 +1,4 @@
-use super::{make_redirect_url, IdentityProvider};
+use super::{check_link_conflict, make_redirect_url, IdentityProvider};
 use crate::api::rcos::users::accounts::for_user::UserAccounts;
 use crate::api::rcos::users::accounts::link::LinkUserAccount;
 use crate::api::rcos::users::accounts::reverse_lookup::ReverseLookup;
@@ -6,15 +6,21 @@ use crate::api::rcos::users::accounts::unlink::UnlinkUserAccount;
 use crate::api::rcos::users::UserAccountType;
 use crate::error::TelescopeError;
 use crate::web::csrf;
+use crate::web::redirect::{redirect_external, redirect_found};
 use crate::web::services::auth::identity::{AuthenticationCookie, Identity, RootIdentity};
 use crate::web::services::auth::AUTHENTICATOR_ACCOUNT_TYPES;
-use actix_web::http::header::LOCATION;
+use crate::web::sessions;
 use actix_web::web::Query;
 use actix_web::FromRequest;
 use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
 use futures::future::LocalBoxFuture;
 use oauth2::basic::{BasicClient, BasicTokenResponse};
-use oauth2::{AuthorizationCode, AuthorizationRequest, CsrfToken, RedirectUrl, Scope};
+use oauth2::{
+    AuthorizationCode, AuthorizationRequest, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
+    RedirectUrl, Scope,
+};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -22,14 +28,63 @@ use std::sync::Arc;
 pub mod discord;
 pub mod github;
 
+/// How long a PKCE code verifier is kept waiting for its callback. This matches the lifetime
+/// of the CSRF token of the same request.
+const PKCE_VERIFIER_LIFETIME_MINUTES: i64 = 10;
+
+lazy_static! {
+    /// PKCE code verifiers of authorization requests in progress, keyed by identity provider
+    /// and the state (CSRF token) sent with the request. The state is checked against the
+    /// browser before its verifier is used, so the verifier does not need its own cookie.
+    static ref PKCE_VERIFIERS: DashMap<(&'static str, String), (PkceCodeVerifier, DateTime<Utc>)> =
+        DashMap::new();
+}
+
+/// Save the PKCE code verifier of an authorization request under the request's state.
+/// Expired verifiers are dropped at the same time.
+fn save_pkce_verifier(idp_name: &'static str, state: &CsrfToken, verifier: PkceCodeVerifier) {
+    let now: DateTime<Utc> = Utc::now();
+    PKCE_VERIFIERS.retain(|_, (_, expiration)| *expiration > now);
+    PKCE_VERIFIERS.insert(
+        (idp_name, state.secret().clone()),
+        (
+            verifier,
+            now + Duration::minutes(PKCE_VERIFIER_LIFETIME_MINUTES),
+        ),
+    );
+}
+
+/// Take the PKCE code verifier saved for an authorization request's state, if there is one
+/// that has not expired. Each verifier can only be taken once.
+fn take_pkce_verifier(idp_name: &'static str, state: &str) -> Option<PkceCodeVerifier> {
+    let (_, (verifier, expiration)) = PKCE_VERIFIERS.remove(&(idp_name, state.to_string()))?;
+    return (expiration > Utc::now()).then(|| verifier);
+}
+
 /// Data returned by GitHub OAuth2 Authorization request.
 #[derive(Deserialize)]
 struct AuthResponse {
     /// The auth code.
     code: AuthorizationCode,
     /// The CSRF token. This should match the one that I sent them and stored
-    /// in the CSRF table.
-    state: CsrfToken,
+    /// in the CSRF table. This is optional here so that a missing state can be
+    /// reported on its own.
+    state: Option<CsrfToken>,
+}
+
+/// Make the error returned when the state of an OAuth2 callback is missing or does not match
+/// the one saved for this browser. This stops an attacker from signing a victim into the
+/// attacker's account (login CSRF) with a callback URL the victim did not start.
+fn invalid_state_error(service_name: &str) -> TelescopeError {
+    TelescopeError::BadRequest {
+        header: "Could Not Verify Sign In".into(),
+        message: format!(
+            "This {} sign in was not started from this browser, or took too long to finish. \
+            Please try again.",
+            service_name
+        ),
+        show_status_code: true,
+    }
 }
 
 /// Trait for identity types provided by OAuth2 Identity Providers.
@@ -66,6 +121,11 @@ pub trait Oauth2IdentityProvider {
     /// Add the appropriate scopes for the OAuth authentication request.
     fn scopes() -> Vec<Scope>;
 
+    /// Does this identity provider support PKCE (RFC 7636)? If so, each authorization request
+    /// sends an S256 code challenge, and the token exchange sends the matching verifier.
+    /// Providers that do not support it should set this to false to leave PKCE out entirely.
+    const SUPPORTS_PKCE: bool = true;
+
     /// Get the redirect URL for the associated client and build an HTTP response to take the user
     /// there. Saves the CSRF token in the process.
     fn auth_response(
@@ -85,15 +145,27 @@ pub trait Oauth2IdentityProvider {
         for scope in Self::scopes() {
             auth_req = auth_req.add_scope(scope);
         }
+
+        // Add a PKCE challenge if the provider supports it.
+        let mut pkce_verifier: Option<PkceCodeVerifier> = None;
+        if Self::SUPPORTS_PKCE {
+            let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+            auth_req = auth_req.set_pkce_challenge(challenge);
+            pkce_verifier = Some(verifier);
+        }
+
         let (url, csrf_token) = auth_req.url();
 
-        // Save CSRF token.
-        csrf::save(Self::SERVICE_NAME, http_req, csrf_token)?;
+        // Keep the PKCE verifier for the token exchange.
+        if let Some(verifier) = pkce_verifier {
+            save_pkce_verifier(Self::SERVICE_NAME, &csrf_token, verifier);
+        }
 
-        // Return the URL in an HTTP redirect response.
-        return Ok(HttpResponse::Found()
-            .header(LOCATION, url.as_str())
-            .finish());
+        // Make the HTTP redirect response to the URL, and save the CSRF token for this
+        // browser.
+        let mut response: HttpResponse = redirect_external(url.as_str());
+        csrf::save(Self::SERVICE_NAME, http_req, &mut response, csrf_token)?;
+        return Ok(response);
     }
 
     /// Extract the response parameters from the callback request invoked
@@ -123,19 +195,51 @@ pub trait Oauth2IdentityProvider {
 
         // Destructure the parameters.
         let AuthResponse { code, state } = params.0;
-        // Verify the CSRF token. Propagate any errors including a mismatch
-        // (we expect to verify without issue most of the time).
-        csrf::verify(Self::SERVICE_NAME, req, state)?;
+
+        // Reject callbacks without a state outright.
+        let state: CsrfToken = state.ok_or_else(|| {
+            warn!("{} OAuth2 callback without a state.", Self::SERVICE_NAME);
+            invalid_state_error(Self::SERVICE_NAME)
+        })?;
+
+        // Take the PKCE verifier saved for this state (whether or not the state checks out).
+        let pkce_verifier: Option<PkceCodeVerifier> =
+            take_pkce_verifier(Self::SERVICE_NAME, state.secret().as_str());
+
+        // Verify the state against the one saved for this browser. A missing or mismatched
+        // token is a bad request, rather than the usual not found for a missing token.
+        csrf::verify(Self::SERVICE_NAME, req, state).map_err(|err| match err {
+            TelescopeError::CsrfTokenNotFound | TelescopeError::CsrfTokenMismatch => {
+                warn!("{} OAuth2 callback rejected: {}", Self::SERVICE_NAME, err);
+                invalid_state_error(Self::SERVICE_NAME)
+            }
+            other => other,
+        })?;
+
+        // The verifier is saved with the state, so it can only be missing if it expired.
+        if Self::SUPPORTS_PKCE && pkce_verifier.is_none() {
+            warn!(
+                "{} OAuth2 callback without a PKCE verifier.",
+                Self::SERVICE_NAME
+            );
+            return Err(invalid_state_error(Self::SERVICE_NAME));
+        }
 
         // Get the OAuth2 client to exchange the auth code for an access token.
         let oauth_client: Arc<BasicClient> = Self::get_client();
 
+        // Build the exchange request, with the PKCE verifier if there is one.
+        let mut exchange = oauth_client
+            .exchange_code(code)
+            .add_extra_param("redirect_uri", redirect_uri.as_str());
+        if let Some(verifier) = pkce_verifier {
+            exchange = exchange.set_pkce_verifier(verifier);
+        }
+
         // Send the exchange request and wait for a response. This happens
         // synchronously so take care where you call this function from.
         // Return the response to the calling function.
-        return oauth_client
-            .exchange_code(code)
-            .add_extra_param("redirect_uri", redirect_uri.as_str())
+        return exchange
             // Send request and wait for response synchronously.
             .request(oauth2::reqwest::http_client)
             // Any errors that occur should be reported as internal server errors.
@@ -233,11 +337,12 @@ where
                 ))?;
 
             // Otherwise, store the identity in the user's cookies and redirect to their profile.
+            let mut cookie: AuthenticationCookie = root.make_authenticated_cookie();
+            // It stays valid until the user logs out everywhere.
+            cookie.session_version = sessions::session_version(user_id).await?;
             let identity: Identity = Identity::extract(&req).await?;
-            identity.save(&root.make_authenticated_cookie());
-            Ok(HttpResponse::Found()
-                .header(LOCATION, format!("/user/{}", user_id))
-                .finish())
+            identity.save(&cookie);
+            Ok(redirect_found(format!("/user/{}", user_id)))
         });
     }
 
@@ -258,9 +363,7 @@ where
             identity.save(&root.make_authenticated_cookie());
 
             // Success! Redirect the user to finish the registration process.
-            Ok(HttpResponse::Found()
-                .header(LOCATION, "/register/finish")
-                .finish())
+            Ok(redirect_found("/register/finish"))
         });
     }
 
@@ -297,6 +400,9 @@ where
                 user_id
             );
 
+            // Make sure this account is not linked to someone else.
+            check_link_conflict(user_id, Self::USER_ACCOUNT_TY, platform_id.as_str()).await?;
+
             // Check if there is already an account of this type linked.
             // Lookup all linked accounts.
             let linked_accounts = UserAccounts::send(user_id)
@@ -314,9 +420,7 @@ where
                     ident.save(&cookie);
 
                     // Return user to their profile.
-                    return Ok(HttpResponse::Found()
-                        .header(LOCATION, format!("/user/{}", user_id))
-                        .finish());
+                    return Ok(redirect_found(format!("/user/{}", user_id)));
                 }
 
                 // Otherwise try to replace the linked account.
@@ -345,9 +449,7 @@ where
             ident.save(&cookie);
 
             // Redirect the user to their profile page
-            Ok(HttpResponse::Found()
-                .header(LOCATION, format!("/user/{}", user_id))
-                .finish())
+            Ok(redirect_found(format!("/user/{}", user_id)))
         });
     }
 }
@@ -8,9 +8,12 @@ use crate::api::rcos::users::accounts::reverse_lookup::ReverseLookup;
 use crate::api::rcos::users::UserAccountType;
 use crate::error::TelescopeError;
 
+use crate::web::redirect::{redirect_external, redirect_found};
 use crate::web::services::auth::identity::{AuthenticationCookie, RootIdentity};
-use crate::web::services::auth::{identity::Identity, make_redirect_url, IdentityProvider};
-use actix_web::http::header::LOCATION;
+use crate::web::services::auth::{
+    check_link_conflict, identity::Identity, make_redirect_url, IdentityProvider,
+};
+use crate::web::sessions;
 use actix_web::{web::Query, FromRequest};
 use actix_web::{HttpRequest, HttpResponse};
 use futures::future::LocalBoxFuture;
@@ -174,25 +177,17 @@ impl IdentityProvider for RpiCas {
     type LinkAuthenticatedFut = LocalBoxFuture<'static, Result<HttpResponse, TelescopeError>>;
 
     fn login_handler(req: HttpRequest) -> Self::LoginFut {
-        ready(
-            HttpResponse::Found()
-                .header(
-                    LOCATION,
-                    make_authentication_url(&req, Self::login_redirect_path()),
-                )
-                .finish(),
-        )
+        ready(redirect_external(make_authentication_url(
+            &req,
+            Self::login_redirect_path(),
+        )))
     }
 
     fn registration_handler(req: HttpRequest) -> Self::RegistrationFut {
-        ready(
-            HttpResponse::Found()
-                .header(
-                    LOCATION,
-                    make_authentication_url(&req, Self::registration_redirect_path()),
-                )
-                .finish(),
-        )
+        ready(redirect_external(make_authentication_url(
+            &req,
+            Self::registration_redirect_path(),
+        )))
     }
 
     fn link_handler(req: HttpRequest, ident: Identity) -> Self::LinkFut {
@@ -212,7 +207,7 @@ impl IdentityProvider for RpiCas {
                 // If authenticated make the URL and direct the user there.
                 let auth_url = make_authentication_url(&req, Self::link_redirect_path());
 
-                Ok(HttpResponse::Found().header(LOCATION, auth_url).finish())
+                Ok(redirect_external(auth_url))
             } else {
                 // If not authenticated, return an error
                 Err(TelescopeError::NotAuthenticated)
@@ -240,12 +235,14 @@ impl IdentityProvider for RpiCas {
                 ))?;
 
             // Set the user's identity cookie
+            let mut cookie: AuthenticationCookie =
+                RootIdentity::RpiCas(token).make_authenticated_cookie();
+            // It stays valid until the user logs out everywhere.
+            cookie.session_version = sessions::session_version(user_id).await?;
             let identity: Identity = Identity::extract(&req).await?;
-            identity.save(&RootIdentity::RpiCas(token).make_authenticated_cookie());
+            identity.save(&cookie);
             // Redirect the user to their profile.
-            Ok(HttpResponse::Found()
-                .header(LOCATION, format!("/user/{}", user_id))
-                .finish())
+            Ok(redirect_found(format!("/user/{}", user_id)))
         });
     }
 
@@ -260,9 +257,7 @@ impl IdentityProvider for RpiCas {
             let identity: Identity = Identity::extract(&req).await?;
             identity.save(&cookie.make_authenticated_cookie());
             // Redirect the user to complete registration
-            Ok(HttpResponse::Found()
-                .header(LOCATION, "/register/finish")
-                .finish())
+            Ok(redirect_found("/register/finish"))
         });
     }
 
@@ -297,6 +292,8 @@ impl IdentityProvider for RpiCas {
 
             // Add to database if needed.
             if add_new_to_db {
+                // Make sure this RCS ID is not linked to someone else.
+                check_link_conflict(user_id, Self::USER_ACCOUNT_TY, new_rcs_id.as_str()).await?;
                 // Link the account.
                 LinkUserAccount::send(user_id, Self::USER_ACCOUNT_TY, new_rcs_id.clone()).await?;
             }
@@ -317,9 +314,7 @@ impl IdentityProvider for RpiCas {
             }
 
             // We are all set at this point, redirect to the user's account.
-            return Ok(HttpResponse::Found()
-                .header(LOCATION, format!("/user/{}", user_id))
-                .finish());
+            return Ok(redirect_found(format!("/user/{}", user_id)));
         });
     }
 }
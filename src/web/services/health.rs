@@ -0,0 +1,56 @@
+//! Liveness and readiness probes.
+//!
+//! `/healthz` responds as long as the server is running. `/readyz` also checks that the RCOS
+//! API can be reached, so that a deployment can hold traffic back until Hasura is up. Neither
+//! requires authentication, and neither is redirected to HTTPS.
+
+use crate::api::rcos::retry::RetryPolicy;
+use crate::api::rcos::send_json_query_with_retry;
+use actix_web::rt::time::timeout;
+use actix_web::HttpResponse;
+use std::time::Duration;
+
+/// How long the readiness check waits for the RCOS API. Probes have short timeouts of their
+/// own, so this should be well under a second or two.
+const READINESS_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// The query used to check the RCOS API. Every GraphQL schema can answer it.
+const READINESS_QUERY: &'static str = "query Readiness { __typename }";
+
+/// Liveness probe. Always OK if the server can respond at all.
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+/// Readiness probe. OK if the RCOS API answers a trivial query in time, and 503 Service
+/// Unavailable otherwise. The request is not retried, so a slow API fails the probe quickly.
+#[get("/readyz")]
+pub async fn readyz() -> HttpResponse {
+    let policy = RetryPolicy {
+        max_attempts: 1,
+        base_delay: Duration::from_millis(0),
+        timeout: READINESS_TIMEOUT,
+    };
+    let check = send_json_query_with_retry("Readiness", READINESS_QUERY, json!({}), policy);
+
+    let problem: Option<String> = match timeout(READINESS_TIMEOUT, check).await {
+        Ok(Ok(_)) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some(format!(
+            "The RCOS API did not respond within {}ms.",
+            READINESS_TIMEOUT.as_millis()
+        )),
+    };
+
+    return match problem {
+        None => HttpResponse::Ok().json(json!({ "status": "ready" })),
+        Some(problem) => {
+            warn!("Readiness check failed: {}", problem);
+            HttpResponse::ServiceUnavailable().json(json!({
+                "status": "unavailable",
+                "upstream": "RCOS API",
+            }))
+        }
+    };
+}
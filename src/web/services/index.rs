@@ -4,6 +4,7 @@ use crate::api::rcos::landing_page_stats::LandingPageStatistics;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::Template;
+use crate::web::services::auth::identity::OptionalAuth;
 use actix_web::HttpRequest;
 
 /// Path to the Handlebars file from the templates directory.
@@ -11,11 +12,13 @@ const TEMPLATE_PATH: &'static str = "index";
 
 /// Service that serves the telescope homepage.
 #[get("/")]
-pub async fn index(req: HttpRequest) -> Result<Page, TelescopeError> {
+pub async fn index(req: HttpRequest, auth: OptionalAuth) -> Result<Page, TelescopeError> {
     // Get the statistics.
     let stats = LandingPageStatistics::get().await?;
     // Make and return a template with the statistics.
     let mut template = Template::new(TEMPLATE_PATH);
     template["stats"] = json!(stats);
+    // Signed in visitors get a different call to action.
+    template["authenticated"] = json!(auth.is_authenticated());
     return template.in_page(&req, "RCOS").await;
 }
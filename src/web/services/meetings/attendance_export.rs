@@ -0,0 +1,150 @@
+//! CSV export of every meeting attendance in a semester.
+//!
+//! This is for coordinators and faculty advisors grading attendance, so each row also says
+//! whether the user was enrolled that semester and for how many credits. Attendances of users
+//! who were not enrolled are still included.
+
+use crate::api::rcos::cursor_pages;
+use crate::api::rcos::meetings::attendance_export::{
+    semester_attendance::SemesterAttendanceMeetingAttendances, SemesterAttendance,
+};
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::meetings::meeting_title;
+use crate::api::rcos::semesters::get_by_id::Semester;
+use crate::api::rcos::users::enrollments::enrollments_after::{
+    enrollments_after::EnrollmentsAfterEnrollments, SemesterEnrollments,
+};
+use crate::error::TelescopeError;
+use crate::web::csv_stream;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::http::header::{
+    self as header, ContentDisposition, DispositionParam, DispositionType,
+};
+use actix_web::web::{Path, ServiceConfig};
+use actix_web::HttpResponse;
+use futures::TryStreamExt;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Register the attendance export service.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(export_attendance);
+}
+
+/// One row of the attendance export.
+#[derive(Clone, Debug, Serialize)]
+struct AttendanceRow {
+    /// The meeting ID.
+    meeting_id: i64,
+    /// The meeting title (generated if the meeting does not have one).
+    meeting_title: String,
+    /// The meeting type.
+    meeting_type: String,
+    /// When the meeting started, in RFC 3339 format.
+    meeting_start: String,
+    /// The user's ID.
+    user_id: Uuid,
+    /// The user's RCS ID, if they linked their RPI account.
+    rcs_id: String,
+    /// The user's first name.
+    first_name: String,
+    /// The user's last name.
+    last_name: String,
+    /// When the attendance was recorded, in RFC 3339 format.
+    attended_at: String,
+    /// Was the attendance added by hand rather than by the user?
+    manually_added: bool,
+    /// Was the user enrolled in the semester?
+    enrolled: bool,
+    /// How many credits the user was enrolled for. Empty if they were not enrolled.
+    credits: Option<i64>,
+}
+
+impl AttendanceRow {
+    /// Make a row from an attendance record and the user's enrollment, if there is one.
+    fn new(
+        attendance: SemesterAttendanceMeetingAttendances,
+        enrollment: Option<&EnrollmentsAfterEnrollments>,
+    ) -> Self {
+        let meeting = attendance.meeting;
+        let user = attendance.user;
+        AttendanceRow {
+            meeting_id: meeting.meeting_id,
+            meeting_title: meeting_title(
+                meeting.title.as_deref(),
+                meeting.type_,
+                meeting.start_date_time,
+            ),
+            meeting_type: meeting.type_.to_string(),
+            meeting_start: meeting.start_date_time.to_rfc3339(),
+            user_id: attendance.user_id,
+            rcs_id: user
+                .rcs_id
+                .into_iter()
+                .next()
+                .map(|account| account.account_id)
+                .unwrap_or_default(),
+            first_name: user.first_name,
+            last_name: user.last_name,
+            attended_at: attendance.created_at.to_rfc3339(),
+            manually_added: attendance.is_manually_added.unwrap_or(false),
+            enrolled: enrollment.is_some(),
+            credits: enrollment.map(|enrollment| enrollment.credits),
+        }
+    }
+}
+
+/// Download every meeting attendance in a semester as a CSV file. Only coordinators, faculty
+/// advisors, and sysadmins can export attendance.
+#[get("/meetings/attendance/{semester_id}.csv")]
+async fn export_attendance(
+    Path(semester_id): Path<String>,
+    auth: AuthenticationCookie,
+) -> Result<HttpResponse, TelescopeError> {
+    // Check authorization.
+    let user_id: Uuid = auth.get_user_id_or_error().await?;
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(Some(user_id)).await?;
+    if !authorization.is_coordinator_or_admin() {
+        return Err(TelescopeError::forbidden("attendance exports"));
+    }
+
+    // Make sure the semester exists, so that a typo is not an empty file.
+    Semester::get_by_id(semester_id.clone())
+        .await?
+        .ok_or(TelescopeError::resource_not_found(
+            "Semester Not Found",
+            "Could not find a semester with this ID.",
+        ))?;
+
+    // Query everything before starting the response. Once the stream starts, the status code
+    // can no longer change.
+    let attendances = SemesterAttendance::get(semester_id.clone()).await?;
+    let enrollments: HashMap<Uuid, EnrollmentsAfterEnrollments> =
+        cursor_pages::pages(SemesterEnrollments {
+            semester_id: semester_id.clone(),
+        })
+        .try_concat()
+        .await?
+        .into_iter()
+        .map(|enrollment| (enrollment.user_id, enrollment))
+        .collect();
+
+    let rows = attendances.into_iter().map(move |attendance| {
+        let enrollment = enrollments.get(&attendance.user_id);
+        AttendanceRow::new(attendance, enrollment)
+    });
+
+    return Ok(HttpResponse::Ok()
+        .set_header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .set_header(
+            header::CONTENT_DISPOSITION,
+            ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!(
+                    "attendance-{}.csv",
+                    semester_id
+                ))],
+            },
+        )
+        .streaming(csv_stream::csv_rows(rows)));
+}
@@ -0,0 +1,128 @@
+//! Autosaves of in-progress meeting edits.
+//!
+//! The meeting edit page periodically sends its fields here, so that a long description is not
+//! lost if the page is reloaded or the browser closes. Autosaves are kept in memory per meeting
+//! and user, are not validated (that happens when the form is submitted), and expire after
+//! [`AUTOSAVE_TTL_HOURS`]. They are forgotten when Telescope restarts.
+
+use crate::error::TelescopeError;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use crate::web::services::meetings::edit::meeting_data_checked;
+use actix_web::web::{Json, Path, ServiceConfig};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// How long autosaves are kept after they are last saved.
+const AUTOSAVE_TTL_HOURS: i64 = 24;
+
+lazy_static! {
+    /// The latest autosave of each meeting by each user.
+    static ref AUTOSAVES: DashMap<(i64, Uuid), Autosave> = DashMap::new();
+}
+
+/// The fields of the meeting edit form that are autosaved. These are exactly what the user
+/// typed, so every field is optional and nothing is parsed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AutosaveFields {
+    /// The meeting title.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The meeting description.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The meeting location.
+    #[serde(default)]
+    pub location: Option<String>,
+    /// The URL of a remote meeting.
+    #[serde(default)]
+    pub meeting_url: Option<String>,
+    /// The URL of the meeting recording.
+    #[serde(default)]
+    pub recording_url: Option<String>,
+    /// The URL of externally hosted slides.
+    #[serde(default)]
+    pub external_slides_url: Option<String>,
+    /// The name of a host who is not an RCOS user.
+    #[serde(default)]
+    pub external_host_name: Option<String>,
+    /// The start date, as sent by the date input.
+    #[serde(default)]
+    pub start_date: Option<String>,
+    /// The start time, as sent by the time input.
+    #[serde(default)]
+    pub start_time: Option<String>,
+    /// The end date, as sent by the date input.
+    #[serde(default)]
+    pub end_date: Option<String>,
+    /// The end time, as sent by the time input.
+    #[serde(default)]
+    pub end_time: Option<String>,
+}
+
+/// An autosave of a meeting edit.
+#[derive(Clone, Debug, Serialize)]
+pub struct Autosave {
+    /// When this was saved.
+    pub saved_at: DateTime<Utc>,
+    /// The saved fields.
+    pub fields: AutosaveFields,
+}
+
+impl Autosave {
+    /// Has this autosave expired as of `now`?
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.saved_at + Duration::hours(AUTOSAVE_TTL_HOURS) < now
+    }
+}
+
+/// Register the autosave service.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(autosave);
+}
+
+/// Save an autosave, replacing the user's previous one for this meeting. Expired autosaves
+/// are dropped at the same time.
+pub fn save(meeting_id: i64, user_id: Uuid, fields: AutosaveFields) -> DateTime<Utc> {
+    let now: DateTime<Utc> = Utc::now();
+    AUTOSAVES.retain(|_, saved| !saved.is_expired(now));
+    AUTOSAVES.insert(
+        (meeting_id, user_id),
+        Autosave {
+            saved_at: now,
+            fields,
+        },
+    );
+    return now;
+}
+
+/// Get a user's latest autosave of a meeting, if there is one that has not expired.
+pub fn latest(meeting_id: i64, user_id: Uuid) -> Option<Autosave> {
+    let saved: Autosave = AUTOSAVES.get(&(meeting_id, user_id))?.clone();
+    if saved.is_expired(Utc::now()) {
+        AUTOSAVES.remove(&(meeting_id, user_id));
+        return None;
+    }
+    return Some(saved);
+}
+
+/// Remove a user's autosave of a meeting. This is done once their edits are submitted.
+pub fn clear(meeting_id: i64, user_id: Uuid) {
+    AUTOSAVES.remove(&(meeting_id, user_id));
+}
+
+/// Endpoint the meeting edit page sends its fields to. Only edit access is checked.
+#[post("/meeting/{meeting_id}/autosave")]
+async fn autosave(
+    Path(meeting_id): Path<i64>,
+    auth: AuthenticationCookie,
+    Json(fields): Json<AutosaveFields>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Error if the meeting does not exist or the user cannot edit it.
+    meeting_data_checked(&auth, meeting_id).await?;
+
+    let user_id: Uuid = auth.get_user_id_or_error().await?;
+    let saved_at: DateTime<Utc> = save(meeting_id, user_id, fields);
+    return Ok(HttpResponse::Ok().json(json!({ "saved_at": saved_at })));
+}
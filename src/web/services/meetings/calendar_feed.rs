@@ -0,0 +1,225 @@
+//! iCalendar feed of meetings.
+//!
+//! Each user has a feed of the meetings they can see, which calendar apps (e.g. Google
+//! Calendar) can subscribe to. Calendar apps cannot send Telescope's login cookie, so the feed
+//! is also served at a URL with an opaque token identifying the user. The token is signed with
+//! the JWT secret, so it does not need to be stored, but it also cannot be revoked without
+//! changing that secret.
+
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::meetings::get::{meetings::MeetingsMeetings, MeetingSort, Meetings};
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use crate::web::integrations::signatures::verify_hmac_sha256;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::web::{Path, ServiceConfig};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use ring::hmac;
+use uuid::Uuid;
+
+/// How far in the past the feed includes meetings from.
+const FEED_PAST_DAYS: i64 = 30;
+
+/// How far in the future the feed includes meetings to.
+const FEED_FUTURE_DAYS: i64 = 180;
+
+/// The timestamp format of iCalendar date-times (in UTC).
+const ICS_TIME_FORMAT: &'static str = "%Y%m%dT%H%M%SZ";
+
+/// The longest a line in an iCalendar file can be, in bytes (not counting the line break).
+const ICS_MAX_LINE_LENGTH: usize = 75;
+
+/// Prefix of the message signed to make a feed token, so that the signature cannot be confused
+/// with any other use of the JWT secret.
+const TOKEN_PURPOSE: &'static str = "telescope-calendar-feed:";
+
+/// Register the calendar feed services.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(own_feed).service(token_feed);
+}
+
+/// Get the signature of a user's feed token.
+fn token_signature(user_id: Uuid) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, global_config().jwt_secret.as_bytes());
+    let message: String = format!("{}{}", TOKEN_PURPOSE, user_id);
+    return hmac::sign(&key, message.as_bytes()).as_ref().to_vec();
+}
+
+/// Get the token in a user's calendar feed URL.
+pub fn feed_token(user_id: Uuid) -> String {
+    let signature: String = token_signature(user_id)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    return format!("{}.{}", user_id.to_simple(), signature);
+}
+
+/// Get the user a feed token belongs to. Return `None` if the token is malformed or its
+/// signature is wrong.
+fn verify_feed_token(token: &str) -> Option<Uuid> {
+    let mut parts = token.splitn(2, '.');
+    let user_id: Uuid = parts.next()?.parse().ok()?;
+    let signature_hex: &str = parts.next()?;
+
+    let message: String = format!("{}{}", TOKEN_PURPOSE, user_id);
+    let valid: bool = verify_hmac_sha256(
+        global_config().jwt_secret.as_bytes(),
+        message.as_bytes(),
+        signature_hex,
+    );
+    return valid.then(|| user_id);
+}
+
+/// Get the URL a user can subscribe to their calendar feed at.
+pub fn feed_url(user_id: Uuid) -> String {
+    format!(
+        "{}/meetings/calendar/{}.ics",
+        global_config().telescope_url.trim_end_matches('/'),
+        feed_token(user_id)
+    )
+}
+
+/// Escape a value of an iCalendar text property.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+/// Add a content line to an iCalendar file, folding it so that no line is longer than
+/// [`ICS_MAX_LINE_LENGTH`] bytes. Lines are never split inside a UTF-8 character.
+fn push_line(ics: &mut String, name: &str, value: &str) {
+    let line: String = format!("{}:{}", name, value);
+    let mut line_length: usize = 0;
+    for c in line.chars() {
+        // Continuation lines start with a space, which counts towards their length.
+        if line_length + c.len_utf8() > ICS_MAX_LINE_LENGTH {
+            ics.push_str("\r\n ");
+            line_length = 1;
+        }
+        ics.push(c);
+        line_length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Add a date-time property to an iCalendar file, in UTC.
+fn push_time(ics: &mut String, name: &str, time: DateTime<Utc>) {
+    push_line(ics, name, time.format(ICS_TIME_FORMAT).to_string().as_str());
+}
+
+/// Build the VEVENT of a meeting.
+fn push_event(ics: &mut String, meeting: &MeetingsMeetings, now: DateTime<Utc>) {
+    let config = global_config();
+    let telescope_url: &str = config.telescope_url.trim_end_matches('/');
+    let page_url: String = format!("{}/meeting/{}", telescope_url, meeting.meeting_id);
+
+    push_line(ics, "BEGIN", "VEVENT");
+    push_line(
+        ics,
+        "UID",
+        format!(
+            "meeting-{}@{}",
+            meeting.meeting_id,
+            domain_of(telescope_url)
+        )
+        .as_str(),
+    );
+    push_time(ics, "DTSTAMP", now);
+    push_time(ics, "DTSTART", meeting.start_date_time);
+    push_time(ics, "DTEND", meeting.end_date_time);
+    push_line(
+        ics,
+        "SUMMARY",
+        escape_text(meeting.title().as_str()).as_str(),
+    );
+
+    // Link the meeting page from the description, since the URL property is the meeting URL.
+    let description: String = format!("{}\n\n{}", page_url, meeting.description)
+        .trim()
+        .to_string();
+    push_line(
+        ics,
+        "DESCRIPTION",
+        escape_text(description.as_str()).as_str(),
+    );
+
+    if let Some(location) = meeting.location.as_deref().map(str::trim) {
+        if !location.is_empty() {
+            push_line(ics, "LOCATION", escape_text(location).as_str());
+        }
+    }
+    if let Some(meeting_url) = meeting.meeting_url.as_deref().map(str::trim) {
+        if !meeting_url.is_empty() {
+            push_line(ics, "URL", meeting_url);
+        }
+    }
+    if meeting.is_canceled {
+        push_line(ics, "STATUS", "CANCELLED");
+    }
+    push_line(ics, "END", "VEVENT");
+}
+
+/// Get the host of a URL, for event UIDs.
+fn domain_of(url: &str) -> &str {
+    let without_scheme: &str = url.splitn(2, "://").last().unwrap_or(url);
+    return without_scheme.split('/').next().unwrap_or(without_scheme);
+}
+
+/// Build an iCalendar file of meetings.
+fn build_calendar(meetings: &[MeetingsMeetings], now: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN", "VCALENDAR");
+    push_line(&mut ics, "VERSION", "2.0");
+    push_line(&mut ics, "PRODID", "-//RCOS//Telescope//EN");
+    push_line(&mut ics, "CALSCALE", "GREGORIAN");
+    push_line(&mut ics, "X-WR-CALNAME", "RCOS Meetings");
+    for meeting in meetings {
+        push_event(&mut ics, meeting, now);
+    }
+    push_line(&mut ics, "END", "VCALENDAR");
+    return ics;
+}
+
+/// Build the calendar feed response for a user. Drafts are left out, and canceled meetings are
+/// included (marked as canceled) so that calendar apps remove them.
+async fn feed_for(user_id: Uuid) -> Result<HttpResponse, TelescopeError> {
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(Some(user_id)).await?;
+    let now: DateTime<Utc> = Utc::now();
+    let meetings: Vec<MeetingsMeetings> = Meetings::get(
+        now - Duration::days(FEED_PAST_DAYS),
+        now + Duration::days(FEED_FUTURE_DAYS),
+        false,
+        true,
+        authorization.viewable_types(),
+        MeetingSort::DateAsc,
+    )
+    .await?;
+
+    return Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(build_calendar(meetings.as_slice(), now)));
+}
+
+/// The calendar feed of the signed in user.
+#[get("/meetings/calendar.ics")]
+async fn own_feed(auth: AuthenticationCookie) -> Result<HttpResponse, TelescopeError> {
+    let user_id: Uuid = auth.get_user_id_or_error().await?;
+    return feed_for(user_id).await;
+}
+
+/// The calendar feed of the user a token belongs to. This does not need a login cookie, so
+/// calendar apps can subscribe to it.
+#[get("/meetings/calendar/{token}.ics")]
+async fn token_feed(Path(token): Path<String>) -> Result<HttpResponse, TelescopeError> {
+    let user_id: Uuid =
+        verify_feed_token(token.as_str()).ok_or(TelescopeError::resource_not_found(
+            "Calendar Not Found",
+            "This calendar link is not valid. Please copy the link from the meetings page again.",
+        ))?;
+    return feed_for(user_id).await;
+}
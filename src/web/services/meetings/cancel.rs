@@ -0,0 +1,59 @@
+//! Services for canceling meetings. Unlike deleted meetings, canceled meetings are still
+//! listed (and can be uncanceled), but are marked as canceled.
+
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::meetings::cancel::SetMeetingCanceled;
+use crate::error::TelescopeError;
+use crate::web::redirect::redirect_to;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::web::{Path, ServiceConfig};
+use actix_web::HttpResponse;
+
+/// Register meeting cancellation services.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(cancel_meeting).service(uncancel_meeting);
+}
+
+/// Set a meeting's canceled flag if the viewer can edit it, and redirect back to the meeting.
+async fn set_canceled(
+    auth: AuthenticationCookie,
+    meeting_id: i64,
+    is_canceled: bool,
+) -> Result<HttpResponse, TelescopeError> {
+    // Require that there is a user authenticated.
+    let user_id = auth.get_user_id_or_error().await?;
+    // Require that they can edit this meeting.
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(Some(user_id)).await?;
+    if !authorization.can_edit_by_id(meeting_id).await? {
+        return Err(TelescopeError::forbidden("this meeting"));
+    }
+
+    // Authorized. Update the meeting.
+    SetMeetingCanceled::execute(meeting_id, is_canceled)
+        .await?
+        .ok_or(TelescopeError::resource_not_found(
+            "Meeting Not Found",
+            "Could not find a meeting for this ID.",
+        ))?;
+
+    // Redirect the user back to the meeting.
+    return Ok(redirect_to(format!("/meeting/{}", meeting_id)));
+}
+
+/// Meeting cancellation endpoint. Uses post so that it cannot be triggered by a link.
+#[post("/meeting/{meeting_id}/cancel")]
+async fn cancel_meeting(
+    auth: AuthenticationCookie,
+    Path(meeting_id): Path<i64>,
+) -> Result<HttpResponse, TelescopeError> {
+    set_canceled(auth, meeting_id, true).await
+}
+
+/// Undo a meeting cancellation.
+#[post("/meeting/{meeting_id}/uncancel")]
+async fn uncancel_meeting(
+    auth: AuthenticationCookie,
+    Path(meeting_id): Path<i64>,
+) -> Result<HttpResponse, TelescopeError> {
+    set_canceled(auth, meeting_id, false).await
+}
@@ -3,23 +3,28 @@
 //! The meeting creation flow is to first direct the user to pick a host,
 //! or specify no host. This gets its own page, since it involves searching through
 //! all users. Once the meeting creator has made a decision, they are directed to a form
-//! to finish meeting creation.
+//! to finish meeting creation. The finished form can also repeat the meeting weekly or
+//! every other week (see [`recurrence`](super::recurrence)).
 
 use crate::api::rcos::meetings::authorization_for::UserMeetingAuthorization;
 use crate::api::rcos::meetings::creation::context::CreationContext;
 use crate::api::rcos::meetings::creation::create::CreateMeeting;
 use crate::api::rcos::meetings::creation::host_selection::HostSelection;
-use crate::api::rcos::meetings::{MeetingType, ALL_MEETING_TYPES};
+use crate::api::rcos::meetings::{MeetingFields, MeetingType, ALL_MEETING_TYPES};
+use crate::env::global_config;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::Template;
-use crate::web::services::meetings::make_meeting_auth_middleware;
-use actix_web::http::header::LOCATION;
+use crate::web::redirect::redirect_to;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use crate::web::services::meetings::recurrence::{self, Recurrence};
+use crate::web::services::meetings::validation::FormTimezone;
+use crate::web::services::meetings::{make_meeting_auth_middleware, validation};
 use actix_web::web as aweb;
 use actix_web::web::{Form, Query, ServiceConfig};
 use actix_web::HttpRequest;
 use actix_web::HttpResponse;
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde_json::Value;
 use uuid::Uuid;
 
@@ -78,18 +83,26 @@ struct FinishQuery {
     host: Uuid,
 }
 
-/// Create an empty instance of the form to finish meeting creation.
-async fn finish_form(host: Option<Uuid>) -> Result<Template, TelescopeError> {
+/// Create an empty instance of the form to finish meeting creation. Dates and times are entered
+/// in `timezone`.
+async fn finish_form(
+    host: Option<Uuid>,
+    timezone: FormTimezone,
+) -> Result<Template, TelescopeError> {
     // Query RCOS API for meeting creation context.
     let context = CreationContext::execute(host, Vec::new()).await?;
+    // Make sure the selected host exists, rather than creating a meeting without one.
+    context.check_host(host)?;
 
     // Create form.
     let mut form = Template::new(FINISH_CREATION_TEMPLATE);
 
     // Add context to form.
     form.fields = json!({
+        "host": context.host(),
         "context": context,
-        "meeting_types": &ALL_MEETING_TYPES
+        "meeting_types": &ALL_MEETING_TYPES,
+        "timezone": timezone.description()
     });
 
     // Return form with context.
@@ -101,11 +114,15 @@ async fn finish_form(host: Option<Uuid>) -> Result<Template, TelescopeError> {
 async fn finish(
     req: HttpRequest,
     query: Option<Query<FinishQuery>>,
+    auth: AuthenticationCookie,
 ) -> Result<Page, TelescopeError> {
     // Extract query parameter.
     let host = query.map(|q| q.host);
+    // Get the timezone the user enters dates and times in.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let timezone: FormTimezone = FormTimezone::for_user(viewer).await?;
     // Return form in page.
-    finish_form(host)
+    finish_form(host, timezone)
         .await?
         .in_page(&req, "Create Meeting")
         .await
@@ -117,8 +134,9 @@ pub struct FinishForm {
     /// Selected semester ID.
     pub semester: String,
 
-    /// What type of meeting is being created.
-    pub kind: MeetingType,
+    /// What type of meeting is being created. This is checked against [`ALL_MEETING_TYPES`]
+    /// by [`validation::check_meeting_type`].
+    pub kind: String,
 
     /// The optional meeting title. Default empty.
     #[serde(default)]
@@ -155,6 +173,49 @@ pub struct FinishForm {
 
     #[serde(default)]
     pub is_draft: Option<bool>,
+
+    /// The name of the host if they are not an RCOS user (e.g. a guest speaker).
+    /// This is ignored for meetings with an RCOS host.
+    #[serde(default)]
+    pub external_host_name: Option<String>,
+
+    /// Save a recording URL even though the meeting has not ended yet.
+    #[serde(default)]
+    pub allow_future_recording: Option<bool>,
+
+    /// Save the meeting even though another meeting this semester has the same title.
+    #[serde(default)]
+    pub allow_duplicate_title: Option<bool>,
+
+    /// Save the meeting even though it overlaps another meeting with the same host or
+    /// location.
+    #[serde(default)]
+    pub allow_conflicts: Option<bool>,
+
+    /// Save the meeting even though its title or description has terms flagged by the
+    /// keyword filter.
+    #[serde(default)]
+    pub allow_flagged_keywords: Option<bool>,
+
+    /// How often the meeting repeats (`weekly` or `biweekly`). Empty if it does not.
+    #[serde(default)]
+    pub repeat: Option<String>,
+
+    /// The day of the week a repeating meeting is on (e.g. `tue`). Empty for the day of the
+    /// start date.
+    #[serde(default)]
+    pub repeat_weekday: Option<String>,
+
+    /// The last date a repeating meeting can start on. This is a string since it is empty
+    /// for meetings that do not repeat.
+    #[serde(default)]
+    pub repeat_until: Option<String>,
+
+    /// The time on the user's device when the form was submitted, in milliseconds since the
+    /// Unix epoch. This is only used to warn about clock skew. It is a string since it is
+    /// empty if the browser does not run JavaScript.
+    #[serde(default)]
+    pub client_timestamp: Option<String>,
 }
 
 /// Endpoint that users submit meeting creation forms to.
@@ -162,13 +223,17 @@ pub struct FinishForm {
 async fn submit_meeting(
     req: HttpRequest,
     query: Option<Query<FinishQuery>>,
+    auth: AuthenticationCookie,
     Form(form): Form<FinishForm>,
 ) -> Result<HttpResponse, TelescopeError> {
     // Resolve host user ID.
     let host = query.map(|q| q.host.clone());
 
     // Create a form instance to send back to the user if the one they submitted was invalid.
-    let mut return_form: Template = finish_form(host.clone()).await?;
+    // Submitted dates and times are in the user's timezone.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let timezone: FormTimezone = FormTimezone::for_user(viewer).await?;
+    let mut return_form: Template = finish_form(host, timezone).await?;
     // Add previously selected fields to the form.
     return_form["selections"] = json!(&form);
 
@@ -189,6 +254,15 @@ async fn submit_meeting(
         recording_url,
         external_slides_url,
         is_draft,
+        external_host_name,
+        allow_future_recording,
+        allow_duplicate_title,
+        allow_conflicts,
+        allow_flagged_keywords,
+        repeat,
+        repeat_weekday,
+        repeat_until,
+        client_timestamp,
     } = form;
 
     // We assume that semester_id is valid, since it includes only options from the creation
@@ -200,15 +274,56 @@ async fn submit_meeting(
     // TL;DR: Semester ID validation is handled client side and enforced enough API side that we
     // don't touch it here.
     //
-    // Same thing with meeting type variant and host user ID.
+    // Same thing with the host user ID. The meeting type is checked, since a malformed one
+    // would otherwise only fail once it reached the API.
+    let kind: Option<MeetingType> = validation::check_meeting_type(&mut return_form, &kind);
 
     // The title should be null (Option::None) if it is all whitespace or empty.
     // If it is, we don't bother user for this -- they can change the title later and
     // they know if they put in all whitespace. This also decreases form resubmission
     // and template complexity.
-    let title: Option<String> = (!title.trim().is_empty()).then(|| title);
+    let title: Option<String> = validation::blank_to_none(Some(title));
     return_form["selections"]["title"] = json!(&title);
 
+    // Same with the location and URLs.
+    let location: Option<String> = validation::blank_to_none(location);
+    let config = global_config();
+    let meeting_url: Option<String> =
+        validation::check_url(&mut return_form, "meeting_url", meeting_url, &[]);
+    let recording_url: Option<String> = validation::check_url(
+        &mut return_form,
+        "recording_url",
+        recording_url,
+        config.recording_url_domains.as_slice(),
+    );
+    let external_slides_url: Option<String> = validation::check_url(
+        &mut return_form,
+        "external_slides_url",
+        external_slides_url,
+        &[],
+    );
+    return_form["selections"]["location"] = json!(&location);
+    return_form["selections"]["meeting_url"] = json!(&meeting_url);
+    return_form["selections"]["recording_url"] = json!(&recording_url);
+    return_form["selections"]["external_slides_url"] = json!(&external_slides_url);
+
+    // An external host name is only kept if there is no RCOS host.
+    let external_host_name: Option<String> =
+        validation::resolve_external_host_name(host, external_host_name);
+    return_form["selections"]["external_host_name"] = json!(&external_host_name);
+
+    // Warn (without blocking) if the user's device clock looks wrong.
+    validation::check_clock_skew(&mut return_form, client_timestamp.as_deref());
+
+    // Check the lengths of the free text fields before anything is sent to the API.
+    validation::check_text_lengths(
+        &mut return_form,
+        title.as_deref(),
+        description.as_str(),
+        location.as_deref(),
+        external_host_name.as_deref(),
+    );
+
     // Check that the start date and end dates are during the semester selected.
     let selected_semester: &Value = return_form["context"]["available_semesters"]
         // This should be a JSON array
@@ -247,32 +362,25 @@ async fn submit_meeting(
         return_form["issues"]["end_date"] = json!("End date is before start date.");
     }
 
+    // Check how the meeting repeats, if it does.
+    let repeats: Option<Recurrence> = Recurrence::from_form(
+        &mut return_form,
+        repeat.as_deref(),
+        repeat_weekday.as_deref(),
+        repeat_until.as_deref(),
+        start_date,
+        semester_end,
+    );
+
     // Check for errors and return form if necessary.
     if return_form["issues"] != json!(null) {
         let page = return_form.in_page(&req, "Create Meeting").await?;
         return Err(TelescopeError::InvalidForm(page));
     }
 
-    // Dates are validated, let's check the times. Start by converting the times from strings.
-    let start_time: NaiveTime = format!("{}:00", start_time)
-        .parse::<NaiveTime>()
-        .map_err(|e| TelescopeError::BadRequest {
-            header: "Malformed Meeting Creation Form".into(),
-            message: format!("Could not parse start time. Internal error: {}", e),
-            show_status_code: false,
-        })?;
-
-    let end_time: NaiveTime = format!("{}:00", end_time)
-        .parse::<NaiveTime>()
-        .map_err(|e| TelescopeError::BadRequest {
-            header: "Malformed Meeting Creation Form".into(),
-            message: format!("Could not parse end time. Internal error: {}", e),
-            show_status_code: false,
-        })?;
-
-    // Now combine them with the dates.
-    let start: NaiveDateTime = start_date.and_time(start_time);
-    let end: NaiveDateTime = end_date.and_time(end_time);
+    // Dates are validated, let's check the times and combine them with the dates.
+    let start: NaiveDateTime = validation::from_form_datetime(start_date, &start_time, "start")?;
+    let end: NaiveDateTime = validation::from_form_datetime(end_date, &end_time, "end")?;
 
     // Check the ordering.
     if start > end {
@@ -281,53 +389,139 @@ async fn submit_meeting(
         return Err(TelescopeError::InvalidForm(page));
     }
 
-    // Ascribe local timezone.
-    let start: DateTime<Local> = Local
-        .from_local_datetime(&start)
-        // Expect that there is only one valid local time for this.
-        .single()
-        .ok_or(TelescopeError::BadRequest {
-            header: "Malformed Meeting Creation Form".into(),
-            message: "Could not ascribe local timezone to start timestamp.".into(),
-            show_status_code: false,
-        })?;
+    // Get the start and end of every occurrence. Occurrences keep the same local times, even
+    // across daylight saving time changes.
+    let occurrences: Vec<(NaiveDateTime, NaiveDateTime)> = match repeats {
+        None => vec![(start, end)],
+        Some(recurrence) => recurrence
+            .occurrences(start_date, end_date, semester_end)
+            .into_iter()
+            .map(|date| {
+                let offset = date - start_date;
+                (start + offset, end + offset)
+            })
+            .collect(),
+    };
+
+    if occurrences.is_empty() {
+        return_form["issues"]["repeat_until"] =
+            json!("No meetings fall on the chosen day before the repeat end date.");
+        let page = return_form.in_page(&req, "Create Meeting").await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
 
-    let end: DateTime<Local> = Local
-        .from_local_datetime(&end)
-        // Expect that there is only one valid local time for this.
-        .single()
-        .ok_or(TelescopeError::BadRequest {
-            header: "Malformed Meeting Creation Form".into(),
-            message: "Could not ascribe local timezone to end timestamp.".into(),
-            show_status_code: false,
-        })?;
+    // Interpret the times in the user's timezone.
+    let occurrences: Vec<(DateTime<Utc>, DateTime<Utc>)> = occurrences
+        .into_iter()
+        .map(|(start, end)| {
+            Ok((
+                timezone.to_utc(&start, "start")?,
+                timezone.to_utc(&end, "end")?,
+            ))
+        })
+        .collect::<Result<_, TelescopeError>>()?;
+    let (start, end) = occurrences[0];
+
+    // Check that no occurrence is scheduled absurdly far in the future.
+    let (last_start, last_end) = occurrences[occurrences.len() - 1];
+    validation::check_scheduling_horizon(&mut return_form, last_start, last_end);
+
+    // Recordings are per meeting, so they cannot be copied to every occurrence.
+    if repeats.is_some() && recording_url.is_some() {
+        return_form["issues"]["recording_url"] = json!(
+            "Repeating meetings cannot have a recording. Add it to each meeting after it happens."
+        );
+    }
+
+    // Check that there is only a recording for meetings that have ended.
+    validation::check_recording_url(
+        &mut return_form,
+        recording_url.as_deref(),
+        end,
+        allow_future_recording.unwrap_or(false),
+    );
+
+    // Check that the title does not duplicate another meeting in the semester.
+    validation::check_duplicate_title(
+        &mut return_form,
+        semester.as_str(),
+        title.as_deref(),
+        None,
+        is_draft.unwrap_or(false),
+        allow_duplicate_title.unwrap_or(false),
+    )
+    .await?;
+
+    // Check that no occurrence overlaps another meeting with the same host or location.
+    // Only the first conflicting occurrence is reported.
+    validation::check_occurrence_conflicts(
+        &mut return_form,
+        occurrences.as_slice(),
+        location.as_deref(),
+        is_remote.unwrap_or(false),
+        host,
+        None,
+        allow_conflicts.unwrap_or(false),
+    )
+    .await?;
+
+    // Check the public text against the keyword filter.
+    validation::check_keywords(
+        &mut return_form,
+        title.as_deref(),
+        description.as_str(),
+        is_draft.unwrap_or(false),
+        validation::skips_keyword_filter(viewer).await?,
+        allow_flagged_keywords.unwrap_or(false),
+    );
+    if return_form["issues"] != json!(null) {
+        let page = return_form.in_page(&req, "Create Meeting").await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
 
     // The rest of the fields are managed pretty tersely in the API call and do not need validation
     // or feedback.
-    let created_meeting_id: i64 = CreateMeeting::execute(
+    let fields = MeetingFields {
         host,
+        external_host_name,
         title,
-        start.with_timezone(&Utc),
-        end.with_timezone(&Utc),
-        description.trim().to_string(),
-        is_draft.unwrap_or(false),
-        is_remote.unwrap_or(false),
-        location.and_then(|string| (!string.trim().is_empty()).then(|| string.trim().to_string())),
+        start,
+        end,
+        description: description.trim().to_string(),
+        is_draft: is_draft.unwrap_or(false),
+        is_remote: is_remote.unwrap_or(false),
+        location,
         meeting_url,
         recording_url,
         external_slides_url,
-        semester,
-        kind,
-    )
-    .await?
-    .ok_or(TelescopeError::ise(
-        "Meeting creation call did not return ID.",
-    ))?;
-
-    // Redirect the user to the page for the meeting they created.
-    return Ok(HttpResponse::Found()
-        .header(LOCATION, format!("/meeting/{}", created_meeting_id))
-        .finish());
+        semester_id: semester,
+        kind: kind.expect("Meeting type was checked above"),
+    };
+
+    // Create each occurrence. If one fails, remove the ones already created, so that the form
+    // can be submitted again.
+    let mut created: Vec<i64> = Vec::with_capacity(occurrences.len());
+    for (start, end) in occurrences {
+        let result: Result<i64, TelescopeError> = CreateMeeting::execute(MeetingFields {
+            start,
+            end,
+            ..fields.clone()
+        })
+        .await
+        .and_then(|id| {
+            id.ok_or(TelescopeError::ise(
+                "Meeting creation call did not return ID.",
+            ))
+        });
+
+        match result {
+            Ok(meeting_id) => created.push(meeting_id),
+            Err(err) => return Err(recurrence::roll_back(created, err).await),
+        }
+    }
+
+    // Redirect the user to the page for the (first) meeting they created.
+    return Ok(redirect_to(format!("/meeting/{}", created[0])));
 }
 
 /// Get the start and end dates of a selected semester object from the meeting creation context.
@@ -3,8 +3,8 @@
 use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
 use crate::api::rcos::meetings::delete::DeleteMeeting;
 use crate::error::TelescopeError;
+use crate::web::redirect::redirect_to;
 use crate::web::services::auth::identity::AuthenticationCookie;
-use actix_web::http::header::LOCATION;
 use actix_web::web::{Path, ServiceConfig};
 use actix_web::HttpResponse;
 
@@ -24,7 +24,7 @@ async fn delete_meeting(
     // Require that they can delete meetings.
     let auth: UserMeetingAuthorization = AuthorizationFor::get(Some(user_id)).await?;
     if !auth.can_delete_meetings() {
-        return Err(TelescopeError::Forbidden);
+        return Err(TelescopeError::forbidden("this meeting"));
     }
 
     // Authorized. Delete the meeting and associated attendances.
@@ -37,5 +37,5 @@ async fn delete_meeting(
     }
 
     // Meeting deleted successfully. Redirect user back to meetings page.
-    Ok(HttpResponse::Found().header(LOCATION, "/meetings").finish())
+    Ok(redirect_to("/meetings"))
 }
@@ -1,27 +1,32 @@
 //! Services to support meeting edits.
 
-use crate::api::rcos::meetings::creation::create::normalize_url;
-use crate::api::rcos::meetings::edit::EditHostSelection;
-use crate::api::rcos::meetings::ALL_MEETING_TYPES;
+use crate::api::rcos::meetings::edit::{EditHostSelection, HostSelectionGroups};
 use crate::api::rcos::meetings::{
     authorization_for::{AuthorizationFor, UserMeetingAuthorization},
     creation::context::CreationContext,
     edit,
     get_by_id::{meeting::MeetingMeeting, Meeting},
+    history::{self, FieldChange},
 };
+use crate::api::rcos::meetings::{MeetingFields, MeetingType, ALL_MEETING_TYPES};
+use crate::audit;
+use crate::env::global_config;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::Template;
+use crate::web::redirect::redirect_to;
 use crate::web::services::auth::identity::AuthenticationCookie;
+use crate::web::services::meetings::autosave::{self, Autosave};
 use crate::web::services::meetings::create::{get_semester_bounds, FinishForm};
-use actix_web::http::header::LOCATION;
+use crate::web::services::meetings::validation::{self, FormTimezone};
 use actix_web::web::Form;
 use actix_web::{
     web::{Path, Query, ServiceConfig},
     HttpRequest, HttpResponse,
 };
-use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use serde_json::Value;
+use std::str::FromStr;
 use uuid::Uuid;
 
 /// The Handlebars file for the meeting edit form.
@@ -38,11 +43,72 @@ pub fn register(config: &mut ServiceConfig) {
         .service(host_selection);
 }
 
+/// The host selected through the `set_host` query parameter.
+///
+/// This is written in URLs as `none` for no host or the user's UUID otherwise. The nil UUID
+/// is also accepted as no host, since that is what older links used.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+enum HostChoice {
+    /// The meeting should have no host.
+    None,
+    /// The meeting should be hosted by the user with this ID.
+    User(Uuid),
+}
+
+impl FromStr for HostChoice {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Check for the explicit no host value first.
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(HostChoice::None);
+        }
+
+        // Otherwise this should be a UUID.
+        let id: Uuid = s.trim().parse()?;
+
+        // The nil UUID is the legacy sentinel for no host.
+        if id.is_nil() {
+            return Ok(HostChoice::None);
+        } else {
+            return Ok(HostChoice::User(id));
+        }
+    }
+}
+
+impl TryFrom<String> for HostChoice {
+    type Error = uuid::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<HostChoice> for String {
+    fn from(choice: HostChoice) -> Self {
+        match choice {
+            HostChoice::None => "none".into(),
+            HostChoice::User(id) => id.to_string(),
+        }
+    }
+}
+
+impl HostChoice {
+    /// Get the user ID of the chosen host if there is one.
+    fn user_id(&self) -> Option<Uuid> {
+        match self {
+            HostChoice::None => None,
+            HostChoice::User(id) => Some(*id),
+        }
+    }
+}
+
 /// Structure for query which can optionally be passed to the edit page to set a new host.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct HostQuery {
-    /// The new host for the meeting. Nil UUID for no host.
-    set_host: Uuid,
+    /// The new host for the meeting.
+    set_host: HostChoice,
 }
 
 /// Get meeting data or return a resource not found error.
@@ -67,24 +133,67 @@ async fn authorization_for_viewer(
     return AuthorizationFor::get(Some(viewer)).await;
 }
 
-/// Get meeting data and error if the authenticated user cannot edit the meeting.
-async fn meeting_data_checked(
+/// Get meeting data and error if the authenticated user cannot edit the meeting. Return the
+/// meeting data along with the user's authorization object.
+pub(super) async fn meeting_data_checked(
     auth: &AuthenticationCookie,
     meeting_id: i64,
-) -> Result<MeetingMeeting, TelescopeError> {
-    // Get meeting data. Extract host's user ID.
-    let meeting_data = get_meeting_data(meeting_id).await?;
+) -> Result<(MeetingMeeting, UserMeetingAuthorization), TelescopeError> {
+    // Get the meeting data and the user's authorization object at the same time. Errors are
+    // checked in the same order as if they were fetched one after the other, so a missing
+    // meeting is still reported before an authorization failure.
+    let (meeting_data, authorization) =
+        futures::join!(get_meeting_data(meeting_id), authorization_for_viewer(auth));
+    let meeting_data: MeetingMeeting = meeting_data?;
+    let authorization: UserMeetingAuthorization = authorization?;
+    // Extract host's user ID.
     let meeting_host: Option<_> = meeting_data.host.as_ref().map(|host| host.id);
 
-    // Get user's authorization object.
-    let authorization = authorization_for_viewer(auth).await?;
-
     // Check edit access.
     if !authorization.can_edit(meeting_host) {
-        return Err(TelescopeError::Forbidden);
-    } else {
-        return Ok(meeting_data);
+        return Err(TelescopeError::forbidden("this meeting"));
     }
+
+    // Check that the meeting has not been over for too long. Coordinators can always edit.
+    let grace_period_hours: Option<u32> = global_config().meeting_edit_grace_period_hours;
+    if let Some(hours) = grace_period_hours {
+        if !authorization.is_coordinator_or_admin()
+            && is_past_edit_grace_period(meeting_data.end_date_time, hours, Utc::now())
+        {
+            return Err(TelescopeError::forbidden_action(
+                "Meeting Edits Closed",
+                format!(
+                    "This meeting ended more than {} hours ago, so only coordinators can edit it.",
+                    hours
+                ),
+            ));
+        }
+    }
+
+    return Ok((meeting_data, authorization));
+}
+
+/// Has a meeting that ends at `end` been over for longer than the edit grace period as of `now`?
+fn is_past_edit_grace_period(
+    end: DateTime<Utc>,
+    grace_period_hours: u32,
+    now: DateTime<Utc>,
+) -> bool {
+    end + Duration::hours(grace_period_hours as i64) < now
+}
+
+/// Resolve the desired host user ID (see [`resolve_host_user_id`]) and error if the meeting
+/// would be left without an RCOS host by a user who cannot create hostless meetings.
+fn resolve_host_checked(
+    meeting_data: &MeetingMeeting,
+    authorization: &UserMeetingAuthorization,
+    set_host: Option<Query<HostQuery>>,
+) -> Result<Option<Uuid>, TelescopeError> {
+    let host: Option<Uuid> = resolve_host_user_id(meeting_data, set_host);
+    if host.is_none() && !authorization.can_create_hostless_meetings() {
+        return Err(TelescopeError::forbidden("meetings without a host"));
+    }
+    return Ok(host);
 }
 
 /// Resolve the desired host user ID from the set host query parameter or the existing meeting
@@ -94,14 +203,8 @@ fn resolve_host_user_id(
     set_host: Option<Query<HostQuery>>,
 ) -> Option<Uuid> {
     match set_host {
-        // If there is a specified nil UUID we want no host. Otherwise, we want the specified UUID.
-        Some(Query(HostQuery { set_host })) => {
-            if set_host.is_nil() {
-                None
-            } else {
-                Some(set_host)
-            }
-        }
+        // If there is a host choice in the query, use it (which may be no host).
+        Some(Query(HostQuery { set_host })) => set_host.user_id(),
 
         // If there is no host query then use the existing host parameter (which may be none).
         None => meeting_data.host.as_ref().map(|h| h.id),
@@ -128,13 +231,18 @@ async fn edit_page(
     set_host: Option<Query<HostQuery>>,
 ) -> Result<Page, TelescopeError> {
     // Get the meeting data. Error on meeting not found or permissions failure.
-    let meeting_data = meeting_data_checked(&auth, meeting_id).await?;
+    let (meeting_data, authorization) = meeting_data_checked(&auth, meeting_id).await?;
     // Resolve the desired host user ID.
-    let host: Option<Uuid> = resolve_host_user_id(&meeting_data, set_host);
-    // Get the creation context (based on the resolved host)
-    // so we know what semesters are available.
-    let context =
-        CreationContext::execute(host, vec![meeting_data.semester.semester_id.clone()]).await?;
+    let host: Option<Uuid> = resolve_host_checked(&meeting_data, &authorization, set_host)?;
+    // Get the creation context (based on the resolved host) so we know what semesters are
+    // available, and the viewer's timezone, at the same time.
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    let (context, timezone) = futures::join!(
+        CreationContext::execute(host, vec![meeting_data.semester.semester_id.clone()]),
+        FormTimezone::for_user(viewer)
+    );
+    let context = context?;
+    let timezone: FormTimezone = timezone?;
 
     // Create the meeting template.
     let mut form: Template = make_form();
@@ -142,19 +250,43 @@ async fn edit_page(
     form.fields = json!({
         "data": &meeting_data,
         "meeting_types": ALL_MEETING_TYPES,
+        "host": context.host(),
         "context": context
     });
 
-    // Add fields to the template converting the timestamps in the meeting data to the HTML versions.
-    let meeting_start: &DateTime<Utc> = &meeting_data.start_date_time;
-    let meeting_start_local: DateTime<Local> = meeting_start.with_timezone(&Local);
-    form.fields["data"]["start_date"] = json!(meeting_start_local.format("%Y-%m-%d").to_string());
-    form.fields["data"]["start_time"] = json!(meeting_start_local.format("%H:%M").to_string());
-
-    let meeting_end: &DateTime<Utc> = &meeting_data.end_date_time;
-    let meeting_end_local: DateTime<Local> = meeting_end.with_timezone(&Local);
-    form.fields["data"]["end_date"] = json!(meeting_end_local.format("%Y-%m-%d").to_string());
-    form.fields["data"]["end_time"] = json!(meeting_end_local.format("%H:%M").to_string());
+    // Add fields to the template converting the timestamps in the meeting data to the HTML
+    // versions, in the user's timezone.
+    form.fields["timezone"] = json!(timezone.description());
+
+    let (start_date, start_time) = timezone.to_form_datetime(&meeting_data.start_date_time);
+    form.fields["data"]["start_date"] = json!(start_date);
+    form.fields["data"]["start_time"] = json!(start_time);
+
+    let (end_date, end_time) = timezone.to_form_datetime(&meeting_data.end_date_time);
+    form.fields["data"]["end_date"] = json!(end_date);
+    form.fields["data"]["end_time"] = json!(end_time);
+
+    // Pre-fill the form from the user's autosave if it is newer than the last edit.
+    if let Some(Autosave { saved_at, fields }) = autosave::latest(meeting_id, viewer) {
+        if meeting_data
+            .updated_at
+            .map_or(true, |updated| saved_at > updated)
+        {
+            // Only overwrite the fields that were saved.
+            let saved: Value = json!(fields);
+            for (field, value) in saved.as_object().into_iter().flatten() {
+                if !value.is_null() {
+                    // Slides are stored under a different name than the form field.
+                    let key: &str = match field.as_str() {
+                        "external_slides_url" => "external_presentation_url",
+                        other => other,
+                    };
+                    form.fields["data"][key] = value.clone();
+                }
+            }
+            form.fields["autosave"]["saved_at"] = json!(saved_at);
+        }
+    }
 
     form.in_page(
         &req,
@@ -174,21 +306,31 @@ async fn submit_meeting_edits(
     Form(form_data): Form<FinishForm>,
 ) -> Result<HttpResponse, TelescopeError> {
     // Get meeting data. Error if there is no such meeting or the user cannot access it
-    let meeting_data = meeting_data_checked(&auth, meeting_id).await?;
+    let (meeting_data, authorization) = meeting_data_checked(&auth, meeting_id).await?;
     // Resolve the desired host user ID.
-    let host: Option<Uuid> = resolve_host_user_id(&meeting_data, set_host);
-    // Get the creation context (based on the resolved host)
-    // so we know what semesters are available.
-    let context =
-        CreationContext::execute(host, vec![meeting_data.semester.semester_id.clone()]).await?;
+    let host: Option<Uuid> = resolve_host_checked(&meeting_data, &authorization, set_host)?;
+    // Get the creation context (based on the resolved host) so we know what semesters are
+    // available, and the editor's timezone, since submitted dates and times are in it.
+    let editor: Uuid = auth.get_user_id_or_error().await?;
+    let (context, timezone) = futures::join!(
+        CreationContext::execute(host, vec![meeting_data.semester.semester_id.clone()]),
+        FormTimezone::for_user(editor)
+    );
+    let context = context?;
+
+    // Make sure the selected host exists, rather than silently dropping them.
+    context.check_host(host)?;
+    let timezone: FormTimezone = timezone?;
 
     // Create the meeting template.
     let mut form: Template = make_form();
     // Instantiate form with meeting types, context and data.
     form.fields = json!({
         "meeting_types": ALL_MEETING_TYPES,
+        "host": context.host(),
         "context": &context,
-        "data": &meeting_data
+        "data": &meeting_data,
+        "timezone": timezone.description()
     });
 
     // Destructure the submitted form.
@@ -201,6 +343,16 @@ async fn submit_meeting_edits(
         external_slides_url,
         is_remote,
         is_draft,
+        external_host_name,
+        allow_future_recording,
+        allow_duplicate_title,
+        allow_conflicts,
+        allow_flagged_keywords,
+        client_timestamp,
+        // Only new meetings can repeat.
+        repeat: _,
+        repeat_weekday: _,
+        repeat_until: _,
         semester,
         recording_url,
         meeting_url,
@@ -209,11 +361,19 @@ async fn submit_meeting_edits(
         title,
     } = form_data;
 
-    // Like the creation system, semester ID, meeting kind, and host ID are not validated.
+    // Like the creation system, semester ID and host ID are not validated, but the meeting
+    // type is.
+    let kind: Option<MeetingType> = validation::check_meeting_type(&mut form, &kind);
+
+    // Warn (without blocking) if the user's device clock looks wrong.
+    validation::check_clock_skew(&mut form, client_timestamp.as_deref());
 
     // Add submitted data to return form.
     form["data"]["semester"] = json!({ "semester_id": &semester });
-    form["data"]["type"] = json!(kind);
+    // Keep the meeting's current type selected if the submitted one was not valid.
+    if let Some(kind) = kind {
+        form["data"]["type"] = json!(kind);
+    }
     form["data"]["description"] = json!(&description);
 
     form["data"]["start_date"] = json!(&start_date);
@@ -222,19 +382,43 @@ async fn submit_meeting_edits(
     form["data"]["end_time"] = json!(&end_time);
 
     // Handle meeting title -- just whitespace and default to None if empty.
-    let title: Option<String> = (!title.trim().is_empty()).then(|| title.trim().to_string());
+    let title: Option<String> = validation::blank_to_none(Some(title));
     form["data"]["title"] = json!(&title);
 
     // Same with location.
-    let location: Option<String> =
-        location.and_then(|string| (!string.trim().is_empty()).then(|| string.trim().to_string()));
+    let location: Option<String> = validation::blank_to_none(location);
     form["data"]["location"] = json!(&location);
 
+    // An external host name is only kept if there is no RCOS host.
+    let external_host_name: Option<String> =
+        validation::resolve_external_host_name(host, external_host_name);
+    form["data"]["external_host_name"] = json!(&external_host_name);
+
     // Trim description.
     let description: String = description.trim().to_string();
     form["data"]["description"] = json!(&description);
 
-    // Don't bother trimming URLs, since the GraphQL mutation will normalize them.
+    // Check the lengths of the free text fields before anything is sent to the API.
+    validation::check_text_lengths(
+        &mut form,
+        title.as_deref(),
+        description.as_str(),
+        location.as_deref(),
+        external_host_name.as_deref(),
+    );
+
+    // And the URLs.
+    let config = global_config();
+    let meeting_url: Option<String> =
+        validation::check_url(&mut form, "meeting_url", meeting_url, &[]);
+    let recording_url: Option<String> = validation::check_url(
+        &mut form,
+        "recording_url",
+        recording_url,
+        config.recording_url_domains.as_slice(),
+    );
+    let external_slides_url: Option<String> =
+        validation::check_url(&mut form, "external_slides_url", external_slides_url, &[]);
     form["data"]["meeting_url"] = json!(&meeting_url);
     form["data"]["recording_url"] = json!(&recording_url);
     form["data"]["external_presentation_url"] = json!(&external_slides_url);
@@ -273,24 +457,9 @@ async fn submit_meeting_edits(
         form["issues"]["end_date"] = json!("End date is before semester starts.");
     }
 
-    // Parse times
-    let time_parse = |time: String| format!("{}:00", time).parse::<NaiveTime>();
-
-    let start_time: NaiveTime = time_parse(start_time).map_err(|e| TelescopeError::BadRequest {
-        header: "Malformed Start Time".into(),
-        message: format!("Could not parse start time. Internal error: {}", e),
-        show_status_code: false,
-    })?;
-
-    let end_time: NaiveTime = time_parse(end_time).map_err(|e| TelescopeError::BadRequest {
-        header: "Malformed End Time".into(),
-        message: format!("Could not parse end time. Internal error: {}", e),
-        show_status_code: false,
-    })?;
-
-    // Add times to dates.
-    let start: NaiveDateTime = start_date.and_time(start_time);
-    let end: NaiveDateTime = end_date.and_time(end_time);
+    // Parse times and add them to dates.
+    let start: NaiveDateTime = validation::from_form_datetime(start_date, &start_time, "start")?;
+    let end: NaiveDateTime = validation::from_form_datetime(end_date, &end_time, "end")?;
 
     // Make sure meeting starts before it ends.
     if start > end {
@@ -309,51 +478,104 @@ async fn submit_meeting_edits(
         return Err(TelescopeError::InvalidForm(page));
     }
 
-    // Add timestamps.
-    let timezone_adder = |timestamp: &NaiveDateTime| Local.from_local_datetime(timestamp).single();
-
-    let start: DateTime<Local> = timezone_adder(&start).ok_or(TelescopeError::BadRequest {
-        header: "Malformed Start Time".into(),
-        message: "Could not ascribe local timezone to start timestamp.".into(),
-        show_status_code: false,
-    })?;
-
-    let end: DateTime<Local> = timezone_adder(&end).ok_or(TelescopeError::BadRequest {
-        header: "Malformed End Time".into(),
-        message: "Could not ascribe local timezone to end timestamp.".into(),
-        show_status_code: false,
-    })?;
+    // Interpret the times in the user's timezone.
+    let start: DateTime<Utc> = timezone.to_utc(&start, "start")?;
+    let end: DateTime<Utc> = timezone.to_utc(&end, "end")?;
+
+    // Check that the meeting is not scheduled absurdly far in the future.
+    validation::check_scheduling_horizon(&mut form, start, end);
+
+    // Check that there is only a recording for meetings that have ended.
+    validation::check_recording_url(
+        &mut form,
+        recording_url.as_deref(),
+        end,
+        allow_future_recording.unwrap_or(false),
+    );
+
+    // Check that the title does not duplicate another meeting in the semester.
+    validation::check_duplicate_title(
+        &mut form,
+        semester.as_str(),
+        title.as_deref(),
+        Some(meeting_id),
+        is_draft,
+        allow_duplicate_title.unwrap_or(false),
+    )
+    .await?;
+
+    // Check that the meeting does not overlap another with the same host or location.
+    validation::check_conflicts(
+        &mut form,
+        start,
+        end,
+        location.as_deref(),
+        is_remote,
+        host,
+        Some(meeting_id),
+        allow_conflicts.unwrap_or(false),
+    )
+    .await?;
+
+    // Check the public text against the keyword filter. The editor is also recorded with the
+    // edit.
+    validation::check_keywords(
+        &mut form,
+        title.as_deref(),
+        description.as_str(),
+        is_draft,
+        validation::skips_keyword_filter(editor).await?,
+        allow_flagged_keywords.unwrap_or(false),
+    );
+    if form["issues"] != json!(null) {
+        let page = form
+            .in_page(
+                &req,
+                format!("Edit {}", resolve_meeting_title(&meeting_data)),
+            )
+            .await?;
+        return Err(TelescopeError::InvalidForm(page));
+    }
 
-    // Create variables for mutation.
-    let edit_mutation_variables = edit::edit_meeting::Variables {
-        meeting_id,
+    // Collect the validated fields for the mutation.
+    let fields = MeetingFields {
+        host,
+        external_host_name,
         title,
-        start: start.with_timezone(&Utc),
-        end: end.with_timezone(&Utc),
-        semester_id: semester,
-        kind,
+        start,
+        end,
         description,
-        is_remote,
         is_draft,
-        meeting_url: normalize_url(meeting_url),
+        is_remote,
         location,
-        external_slides_url: normalize_url(external_slides_url),
-        recording_url: normalize_url(recording_url),
-        // Extract the host from context object.
-        host: form["context"]["host"][0]["id"]
-            .as_str()
-            .and_then(|host_id| host_id.parse::<Uuid>().ok()),
+        meeting_url,
+        recording_url,
+        external_slides_url,
+        semester_id: semester,
+        kind: kind.expect("Meeting type was checked above"),
     };
 
+    // Find what the edit changes before the fields are sent off. These are recorded in the
+    // meeting's history by the same mutation.
+    let changes: Vec<FieldChange> = history::changes(&meeting_data, &fields);
+    let changed_fields: Vec<String> = changes.iter().map(|change| change.field.clone()).collect();
+
     // The returned meeting ID should match the existing one but we don't check.
-    let meeting_id: i64 = edit::EditMeeting::execute(edit_mutation_variables)
+    let meeting_id: i64 = edit::EditMeeting::execute(meeting_id, fields, editor, changes)
         .await?
         .unwrap_or(meeting_id);
 
+    audit::record(
+        editor,
+        "edit_meeting",
+        format!("meeting={} fields={}", meeting_id, changed_fields.join(",")),
+    );
+
+    // The edits are saved, so the autosave is no longer needed.
+    autosave::clear(meeting_id, editor);
+
     // Redirect the user back to the meeting they edited.
-    return Ok(HttpResponse::Found()
-        .header(LOCATION, format!("/meeting/{}", meeting_id))
-        .finish());
+    return Ok(redirect_to(format!("/meeting/{}", meeting_id)));
 }
 
 /// Host selection page.
@@ -370,14 +592,70 @@ async fn host_selection(
         .can_edit_by_id(meeting_id)
         .await?
     {
-        return Err(TelescopeError::Forbidden);
+        return Err(TelescopeError::forbidden("this meeting"));
     }
 
-    // Get host selection.
+    // Get host selection, and group the candidates.
     let data = EditHostSelection::get(meeting_id).await?;
+    let groups: Option<HostSelectionGroups> = HostSelectionGroups::from_response(&data);
 
-    // Create host selection page template.
+    // Create host selection page template. The raw data is kept for reference.
     let mut template: Template = Template::new(HOST_SELECTION_TEMPLATE);
     template["data"] = json!(data);
+    template["groups"] = json!(groups);
     return template.in_page(&req, "Select Host").await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the set host query of an edit page URL.
+    fn parse(query: &str) -> Option<HostChoice> {
+        Query::<HostQuery>::from_query(query)
+            .ok()
+            .map(|query| query.into_inner().set_host)
+    }
+
+    /// `none` (in any case) means no host.
+    #[test]
+    fn parses_none() {
+        assert_eq!(parse("set_host=none"), Some(HostChoice::None));
+        assert_eq!(parse("set_host=None"), Some(HostChoice::None));
+    }
+
+    /// A user ID means that user hosts.
+    #[test]
+    fn parses_user_id() {
+        let id: Uuid = Uuid::new_v4();
+        assert_eq!(
+            parse(format!("set_host={}", id).as_str()),
+            Some(HostChoice::User(id))
+        );
+    }
+
+    /// The nil UUID used by older links still means no host.
+    #[test]
+    fn parses_legacy_nil_uuid() {
+        assert_eq!(
+            parse("set_host=00000000-0000-0000-0000-000000000000"),
+            Some(HostChoice::None)
+        );
+    }
+
+    /// Anything else is rejected.
+    #[test]
+    fn rejects_invalid_host() {
+        assert_eq!(parse("set_host=someone"), None);
+        assert_eq!(parse("set_host="), None);
+    }
+
+    /// Host choices are written to links the same way they are parsed.
+    #[test]
+    fn round_trips() {
+        let id: Uuid = Uuid::new_v4();
+        for choice in [HostChoice::None, HostChoice::User(id)] {
+            assert_eq!(String::from(choice).parse::<HostChoice>().unwrap(), choice);
+        }
+    }
+}
@@ -1,12 +1,14 @@
 //! List of meetings page.
 
 use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
-use crate::api::rcos::meetings::get::Meetings;
+use crate::api::rcos::meetings::get::{MeetingSort, Meetings};
 use crate::api::rcos::meetings::MeetingType;
 use crate::error::TelescopeError;
+use crate::templates::empty_state::EmptyState;
 use crate::templates::page::Page;
 use crate::templates::Template;
 use crate::web::services::auth::identity::Identity;
+use crate::web::services::meetings::calendar_feed;
 use actix_web::web::{Query, ServiceConfig};
 use actix_web::HttpRequest;
 use chrono::{Date, DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
@@ -28,13 +30,33 @@ struct MeetingsQuery {
     pub end: NaiveDate,
 }
 
+/// The sort and filter query parameters on the meetings page. These are separate from the
+/// date range so that they can be given without the dates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct ListOptionsQuery {
+    /// The key of the sort to use (see [`MeetingSort::key`]).
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// Should canceled meetings be left out? They are shown by default.
+    #[serde(default)]
+    pub hide_canceled: Option<bool>,
+}
+
 /// Meetings page
 #[get("/meetings")]
 async fn meetings_list(
     req: HttpRequest,
     params: Option<Query<MeetingsQuery>>,
+    Query(list_options): Query<ListOptionsQuery>,
     identity: Identity,
 ) -> Result<Page, TelescopeError> {
+    // Check the sort before doing anything else. Unknown sorts are rejected.
+    let sort: MeetingSort = match list_options.sort.as_deref() {
+        // An empty sort (e.g. from an unselected form field) uses the default.
+        Some(key) if !key.trim().is_empty() => key.parse()?,
+        _ => MeetingSort::default(),
+    };
+
     // Resolve parameters to API query variables
     let start: DateTime<Utc> = params
         .as_ref()
@@ -77,7 +99,16 @@ async fn meetings_list(
     let visible_meeting_types: Vec<MeetingType> = authorization.viewable_types();
 
     // Query the RCOS API to get meeting data.
-    let events: Vec<_> = Meetings::get(start, end, include_drafts, visible_meeting_types).await?;
+    let hide_canceled: bool = list_options.hide_canceled.unwrap_or(false);
+    let events: Vec<_> = Meetings::get(
+        start,
+        end,
+        include_drafts,
+        !hide_canceled,
+        visible_meeting_types,
+        sort,
+    )
+    .await?;
 
     // Get the values to pre-fill in the filters.
     let query = params
@@ -89,11 +120,23 @@ async fn meetings_list(
             end: end.naive_local().date(),
         });
 
+    // Build the message to show if there are no meetings. Users who can create meetings
+    // get a link to do so.
+    let mut empty_state = EmptyState::new("Could not find any meetings matching these parameters.");
+    if authorization.can_create_meetings() {
+        empty_state = empty_state.with_action("Create a meeting", "/meeting/create/select_host");
+    }
+
     let mut template = Template::new(TEMPLATE_PATH);
     template.fields = json!({
         "meetings": events,
         "query": query,
+        "sort": sort.key(),
+        "hide_canceled": hide_canceled,
         "authorization": authorization,
+        "empty_state": empty_state,
+        // Signed in users can subscribe to their meetings from a calendar app.
+        "calendar_feed_url": viewer.map(calendar_feed::feed_url),
     });
 
     return template.in_page(&req, "RCOS Meetings").await;
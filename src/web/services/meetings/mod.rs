@@ -6,10 +6,17 @@ use crate::web::middlewares::authorization::Authorization;
 use actix_web::web::ServiceConfig;
 use uuid::Uuid;
 
+mod attendance_export;
+mod autosave;
+mod calendar_feed;
+mod cancel;
 mod create;
 mod delete;
 mod edit;
 mod list;
+mod recurrence;
+mod search;
+mod validation;
 mod view;
 
 /// Register calendar related services.
@@ -17,15 +24,28 @@ pub fn register(config: &mut ServiceConfig) {
     // Meetings list page
     list::register(config);
 
+    // Meeting search page.
+    search::register(config);
+
+    // Calendar feeds of meetings.
+    calendar_feed::register(config);
+
+    // Attendance exports.
+    attendance_export::register(config);
+
     // Meeting creation services
     create::register(config);
 
     // Meeting edit services.
     edit::register(config);
+    autosave::register(config);
 
     // Meeting destruction services.
     delete::register(config);
 
+    // Meeting cancellation services.
+    cancel::register(config);
+
     config
         // The meeting viewing endpoint must be registered after the meeting creation endpoint,
         // so that the ID path doesn't match the create path.
@@ -42,7 +62,9 @@ fn make_meeting_auth_middleware<F: 'static + Fn(&UserMeetingAuthorization) -> bo
             let auth: UserMeetingAuthorization = AuthorizationFor::get(Some(user_id)).await?;
 
             // Call the verification function on the access authorization object.
-            (f)(&auth).then(|| ()).ok_or(TelescopeError::Forbidden)
+            (f)(&auth)
+                .then(|| ())
+                .ok_or(TelescopeError::forbidden("this page"))
         })
     })
 }
@@ -0,0 +1,362 @@
+//! Repeating meetings.
+//!
+//! The meeting creation form can repeat a meeting every week or every other week on a day of
+//! the week, until a given date. Each occurrence is created as its own meeting with the same
+//! fields and times of day, so they can be edited or canceled separately afterwards. No
+//! occurrence is ever generated outside of the selected semester.
+
+use crate::api::rcos::meetings::delete::DeleteMeeting;
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+/// The most meetings a single submission can create. Semesters are short enough that this is
+/// only reached by mistake.
+const MAX_OCCURRENCES: usize = 30;
+
+/// How often a meeting repeats.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Frequency {
+    /// Every week.
+    Weekly,
+    /// Every other week.
+    Biweekly,
+}
+
+impl Frequency {
+    /// Parse the value of the repeat select box. The empty string means the meeting does not
+    /// repeat.
+    fn parse(value: &str) -> Result<Option<Self>, ()> {
+        match value.trim() {
+            "" | "none" => Ok(None),
+            "weekly" => Ok(Some(Frequency::Weekly)),
+            "biweekly" => Ok(Some(Frequency::Biweekly)),
+            _ => Err(()),
+        }
+    }
+
+    /// The number of days between occurrences.
+    fn interval_days(self) -> i64 {
+        match self {
+            Frequency::Weekly => 7,
+            Frequency::Biweekly => 14,
+        }
+    }
+}
+
+/// How a meeting repeats.
+#[derive(Copy, Clone, Debug)]
+pub struct Recurrence {
+    /// How often the meeting repeats.
+    pub frequency: Frequency,
+    /// The day of the week the meeting is on.
+    pub weekday: Weekday,
+    /// The last date an occurrence can start on.
+    pub until: NaiveDate,
+}
+
+impl Recurrence {
+    /// Read the recurrence fields of the meeting creation form. Return `None` if the meeting
+    /// does not repeat, or if there are problems, which are added to the form's issues.
+    ///
+    /// The day of the week defaults to the day of the start date.
+    pub fn from_form(
+        form: &mut Template,
+        repeat: Option<&str>,
+        weekday: Option<&str>,
+        until: Option<&str>,
+        start_date: NaiveDate,
+        semester_end: NaiveDate,
+    ) -> Option<Self> {
+        let frequency: Frequency = match Frequency::parse(repeat.unwrap_or("")) {
+            Ok(Some(frequency)) => frequency,
+            Ok(None) => return None,
+            Err(()) => {
+                form["issues"]["repeat"] = json!("Unknown repeat option.");
+                return None;
+            }
+        };
+
+        let weekday: Weekday = match weekday.map(str::trim).filter(|day| !day.is_empty()) {
+            None => start_date.weekday(),
+            Some(day) => match day.parse::<Weekday>() {
+                Ok(weekday) => weekday,
+                Err(_) => {
+                    form["issues"]["repeat_weekday"] = json!("Unknown day of the week.");
+                    return None;
+                }
+            },
+        };
+
+        let until: NaiveDate = match until.map(str::trim).filter(|date| !date.is_empty()) {
+            None => {
+                form["issues"]["repeat_until"] = json!("Choose when the meeting stops repeating.");
+                return None;
+            }
+            Some(date) => match date.parse::<NaiveDate>() {
+                Ok(until) => until,
+                Err(_) => {
+                    form["issues"]["repeat_until"] = json!("Could not read this date.");
+                    return None;
+                }
+            },
+        };
+
+        if until < start_date {
+            form["issues"]["repeat_until"] = json!("The repeat end date is before the start date.");
+            return None;
+        }
+        if until > semester_end {
+            form["issues"]["repeat_until"] =
+                json!("The repeat end date is after the semester ends.");
+            return None;
+        }
+
+        return Some(Recurrence {
+            frequency,
+            weekday,
+            until,
+        });
+    }
+
+    /// Get the start date of each occurrence of a meeting that starts on `start_date` and ends
+    /// on `end_date`. The first occurrence is on the first matching day of the week on or
+    /// after the start date. Occurrences are cut off at the repeat end date, and so that none
+    /// end after the semester does. At most [`MAX_OCCURRENCES`] dates are returned.
+    pub fn occurrences(
+        &self,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        semester_end: NaiveDate,
+    ) -> Vec<NaiveDate> {
+        // Meetings can span several days. Each occurrence keeps the same span.
+        let span: Duration = end_date - start_date;
+        let last_start: NaiveDate = self.until.min(semester_end - span);
+
+        // Move forward to the chosen day of the week.
+        let days_until_weekday: u32 = (7 + self.weekday.num_days_from_monday()
+            - start_date.weekday().num_days_from_monday())
+            % 7;
+        let mut date: NaiveDate = start_date + Duration::days(days_until_weekday as i64);
+
+        let mut dates: Vec<NaiveDate> = Vec::new();
+        while date <= last_start && dates.len() < MAX_OCCURRENCES {
+            dates.push(date);
+            date += Duration::days(self.frequency.interval_days());
+        }
+        return dates;
+    }
+}
+
+/// Delete meetings created by a repeating meeting submission that failed part way through.
+/// Return the error to show the user: the original error if every meeting was removed, or an
+/// error listing the meetings that are left over otherwise.
+pub async fn roll_back(created: Vec<i64>, err: TelescopeError) -> TelescopeError {
+    let mut left_over: Vec<String> = Vec::new();
+    for meeting_id in created {
+        if let Err(delete_err) = DeleteMeeting::execute(meeting_id).await {
+            error!(
+                "Could not roll back meeting {} after a failed repeating meeting submission: {}",
+                meeting_id, delete_err
+            );
+            left_over.push(format!("/meeting/{}", meeting_id));
+        }
+    }
+
+    if left_over.is_empty() {
+        return err;
+    }
+
+    return TelescopeError::ise(format!(
+        "Creating a repeating meeting failed part way through ({}), and these meetings could not \
+        be removed: {}. Please check them and delete any that should not exist.",
+        err,
+        left_over.join(", ")
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use serde_json::Value;
+
+    /// Make a date.
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    /// Make a recurrence.
+    fn recurrence(frequency: Frequency, weekday: Weekday, until: NaiveDate) -> Recurrence {
+        Recurrence {
+            frequency,
+            weekday,
+            until,
+        }
+    }
+
+    /// A weekly meeting has one occurrence per week up to and including the end date.
+    #[test]
+    fn weekly_occurrences() {
+        // 2022-09-06 is a Tuesday.
+        let weekly = recurrence(Frequency::Weekly, Weekday::Tue, date(2022, 10, 4));
+        let dates = weekly.occurrences(date(2022, 9, 6), date(2022, 9, 6), date(2022, 12, 20));
+        assert_eq!(
+            dates,
+            vec![
+                date(2022, 9, 6),
+                date(2022, 9, 13),
+                date(2022, 9, 20),
+                date(2022, 9, 27),
+                date(2022, 10, 4),
+            ]
+        );
+    }
+
+    /// A biweekly meeting skips every other week.
+    #[test]
+    fn biweekly_occurrences() {
+        let biweekly = recurrence(Frequency::Biweekly, Weekday::Tue, date(2022, 10, 4));
+        let dates = biweekly.occurrences(date(2022, 9, 6), date(2022, 9, 6), date(2022, 12, 20));
+        assert_eq!(
+            dates,
+            vec![date(2022, 9, 6), date(2022, 9, 20), date(2022, 10, 4)]
+        );
+    }
+
+    /// The first occurrence is on the first matching day on or after the start date.
+    #[test]
+    fn moves_to_weekday() {
+        // From a Tuesday to the next Friday.
+        let weekly = recurrence(Frequency::Weekly, Weekday::Fri, date(2022, 9, 20));
+        let dates = weekly.occurrences(date(2022, 9, 6), date(2022, 9, 6), date(2022, 12, 20));
+        assert_eq!(dates, vec![date(2022, 9, 9), date(2022, 9, 16)]);
+
+        // From a Friday to the next Monday, across the end of the week.
+        let weekly = recurrence(Frequency::Weekly, Weekday::Mon, date(2022, 9, 12));
+        let dates = weekly.occurrences(date(2022, 9, 9), date(2022, 9, 9), date(2022, 12, 20));
+        assert_eq!(dates, vec![date(2022, 9, 12)]);
+    }
+
+    /// No occurrence ends after the semester, even if the repeat end date is later.
+    #[test]
+    fn clamped_to_semester_end() {
+        let semester_end: NaiveDate = date(2022, 12, 20);
+        let weekly = recurrence(Frequency::Weekly, Weekday::Mon, date(2023, 1, 31));
+        let dates = weekly.occurrences(date(2022, 11, 28), date(2022, 11, 28), semester_end);
+        assert_eq!(
+            dates,
+            vec![
+                date(2022, 11, 28),
+                date(2022, 12, 5),
+                date(2022, 12, 12),
+                date(2022, 12, 19)
+            ]
+        );
+
+        // A meeting spanning two days cannot start on the last day of the semester.
+        let semester_end: NaiveDate = date(2022, 12, 19);
+        let one_day = weekly.occurrences(date(2022, 11, 28), date(2022, 11, 28), semester_end);
+        assert_eq!(one_day.last(), Some(&date(2022, 12, 19)));
+        let two_days = weekly.occurrences(date(2022, 11, 28), date(2022, 11, 29), semester_end);
+        assert_eq!(two_days.last(), Some(&date(2022, 12, 12)));
+    }
+
+    /// At most [`MAX_OCCURRENCES`] meetings are generated.
+    #[test]
+    fn capped_at_max_occurrences() {
+        let weekly = recurrence(Frequency::Weekly, Weekday::Mon, date(2023, 12, 31));
+        let dates = weekly.occurrences(date(2022, 1, 3), date(2022, 1, 3), date(2023, 12, 31));
+        assert_eq!(dates.len(), MAX_OCCURRENCES);
+    }
+
+    /// Read the recurrence fields of a form. Return the recurrence and the form's issues.
+    fn from_form(
+        repeat: &str,
+        weekday: &str,
+        until: &str,
+        semester_end: NaiveDate,
+    ) -> (Option<Recurrence>, Value) {
+        let mut form: Template = Template::new("meetings/creation/finish");
+        let recurrence = Recurrence::from_form(
+            &mut form,
+            Some(repeat),
+            Some(weekday),
+            Some(until),
+            date(2022, 9, 6),
+            semester_end,
+        );
+        return (recurrence, form["issues"].clone());
+    }
+
+    /// The recurrence fields are read from the form, and the day of the week defaults to the
+    /// start date's.
+    #[test]
+    fn reads_form() {
+        let (recurrence, issues) = from_form("biweekly", "", "2022-10-04", date(2022, 12, 20));
+        let recurrence: Recurrence = recurrence.unwrap();
+        assert_eq!(recurrence.frequency, Frequency::Biweekly);
+        assert_eq!(recurrence.weekday, Weekday::Tue);
+        assert_eq!(recurrence.until, date(2022, 10, 4));
+        assert_eq!(issues, Value::Null);
+
+        let (recurrence, _) = from_form("none", "", "", date(2022, 12, 20));
+        assert!(recurrence.is_none());
+    }
+
+    /// Repeat end dates outside of the meeting's dates and the semester are rejected.
+    #[test]
+    fn rejects_until_outside_semester() {
+        let (recurrence, issues) = from_form("weekly", "tue", "2023-01-10", date(2022, 12, 20));
+        assert!(recurrence.is_none());
+        assert_eq!(
+            issues["repeat_until"],
+            json!("The repeat end date is after the semester ends.")
+        );
+
+        let (recurrence, issues) = from_form("weekly", "tue", "2022-09-01", date(2022, 12, 20));
+        assert!(recurrence.is_none());
+        assert_eq!(
+            issues["repeat_until"],
+            json!("The repeat end date is before the start date.")
+        );
+    }
+
+    /// Unknown options are rejected.
+    #[test]
+    fn rejects_unknown_options() {
+        let (_, issues) = from_form("daily", "", "2022-10-04", date(2022, 12, 20));
+        assert_eq!(issues["repeat"], json!("Unknown repeat option."));
+
+        let (_, issues) = from_form("weekly", "someday", "2022-10-04", date(2022, 12, 20));
+        assert_eq!(issues["repeat_weekday"], json!("Unknown day of the week."));
+    }
+
+    /// Rolling back deletes every meeting created so far and keeps the original error.
+    #[actix_rt::test]
+    async fn roll_back_deletes_created_meetings() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "DeleteMeeting",
+            json!({
+                "delete_meeting_attendances": { "affected_rows": 0 },
+                "delete_meetings_by_pk": { "meeting_id": 1 }
+            }),
+        );
+
+        let err = roll_back(vec![1, 2], TelescopeError::ise("Third meeting failed")).await;
+        api.assert_called("DeleteMeeting", 2);
+        assert!(err.to_string().contains("Third meeting failed"));
+        assert!(!err.to_string().contains("could not be removed"));
+    }
+
+    /// Meetings that could not be deleted are listed in the error.
+    #[actix_rt::test]
+    async fn roll_back_reports_left_over_meetings() {
+        let api = MockRcosApi::start();
+        api.stub_error("DeleteMeeting", "permission denied");
+
+        let err = roll_back(vec![4], TelescopeError::ise("Second meeting failed")).await;
+        assert!(err.to_string().contains("/meeting/4"));
+    }
+}
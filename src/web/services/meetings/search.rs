@@ -0,0 +1,147 @@
+//! Meeting search page.
+
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::meetings::search::{SearchMeetings, PER_PAGE};
+use crate::api::rcos::meetings::{MeetingType, ALL_MEETING_TYPES};
+use crate::error::TelescopeError;
+use crate::templates::empty_state::EmptyState;
+use crate::templates::page::Page;
+use crate::templates::pagination::PaginationInfo;
+use crate::templates::Template;
+use crate::web::services::auth::identity::Identity;
+use actix_web::web as aweb;
+use actix_web::web::{Path, Query, ServiceConfig};
+use actix_web::HttpRequest;
+use chrono::{Date, DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde_json::Value;
+
+/// The path to the template's handlebars file.
+const TEMPLATE_PATH: &'static str = "meetings/search";
+
+/// Register the meeting search page.
+pub fn register(config: &mut ServiceConfig) {
+    config
+        .route("/meetings/search", aweb::get().to(search))
+        .route("/meetings/search/{page}", aweb::get().to(search));
+}
+
+/// Query parameters submitted via the form on the search page. Every field is optional, and
+/// empty fields (from an empty form input) are treated as missing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct SearchQuery {
+    /// Text to find in meeting titles and descriptions.
+    #[serde(default)]
+    pub q: Option<String>,
+    /// The first date to include meetings from.
+    #[serde(default)]
+    pub start: Option<String>,
+    /// The last date to include meetings from.
+    #[serde(default)]
+    pub end: Option<String>,
+    /// The meeting type to limit results to.
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+}
+
+/// Parse an optional date parameter. Empty values are `None`.
+fn parse_date(value: Option<&str>, name: &str) -> Result<Option<NaiveDate>, TelescopeError> {
+    let value: &str = match value.map(str::trim) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+
+    return value
+        .parse::<NaiveDate>()
+        .map(Some)
+        .map_err(|_| TelescopeError::BadRequest {
+            header: "Invalid Date".into(),
+            message: format!("Could not read the {} date {:?}.", name, value),
+            show_status_code: true,
+        });
+}
+
+/// Parse the optional meeting type parameter (e.g. `large_group`). Empty values are `None`.
+fn parse_kind(value: Option<&str>) -> Result<Option<MeetingType>, TelescopeError> {
+    let value: &str = match value.map(str::trim) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+
+    return serde_json::from_value::<MeetingType>(Value::String(value.to_string()))
+        .map(Some)
+        .map_err(|_| TelescopeError::BadRequest {
+            header: "Invalid Meeting Type".into(),
+            message: format!("There is no meeting type {:?}.", value),
+            show_status_code: true,
+        });
+}
+
+/// Get the timestamp at the start of a date in the local timezone.
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_date(&date)
+        // If it's ambiguous what date to use in the local timezone, pick the earlier one.
+        .earliest()
+        .map(|date: Date<Local>| date.and_hms(0, 0, 0).with_timezone(&Utc))
+}
+
+/// Get the timestamp at the end of a date in the local timezone.
+fn end_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_date(&date)
+        // If the date in the local timezone is ambiguous, use the later one.
+        .latest()
+        .map(|date: Date<Local>| date.and_hms(23, 59, 59).with_timezone(&Utc))
+}
+
+/// Meeting search page. Results are ordered by start time and paginated.
+async fn search(
+    req: HttpRequest,
+    page_num: Option<Path<u32>>,
+    Query(query): Query<SearchQuery>,
+    identity: Identity,
+) -> Result<Page, TelescopeError> {
+    // Resolve the page number. Default to Page 1.
+    let page_num: u32 = page_num.map(|path| path.0).unwrap_or(1).max(1);
+
+    // Check the filters before querying anything.
+    let start: Option<NaiveDate> = parse_date(query.start.as_deref(), "start")?;
+    let end: Option<NaiveDate> = parse_date(query.end.as_deref(), "end")?;
+    let kind: Option<MeetingType> = parse_kind(query.kind.as_deref())?;
+
+    // Check what the viewer can see. Types they cannot see are never searched, even if they
+    // are asked for.
+    let viewer: Option<_> = identity.get_user_id().await?;
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(viewer).await?;
+    let accept_types: Vec<MeetingType> = authorization
+        .viewable_types()
+        .into_iter()
+        .filter(|viewable| kind.map(|kind| kind == *viewable).unwrap_or(true))
+        .collect();
+
+    let results = SearchMeetings::get(
+        query.q.clone(),
+        start.and_then(start_of_day),
+        end.and_then(end_of_day),
+        authorization.can_view_drafts(),
+        accept_types,
+        page_num - 1,
+    )
+    .await?;
+
+    let total: u64 = results.total().ok_or(TelescopeError::ise(
+        "Meeting search count not returned by API",
+    ))? as u64;
+
+    let mut template = Template::new(TEMPLATE_PATH);
+    template.fields = json!({
+        "meetings": &results.meetings,
+        "query": &query,
+        "meeting_types": ALL_MEETING_TYPES,
+        "total": total,
+        "pagination": PaginationInfo::new(total, PER_PAGE as u64, page_num as u64),
+        "preserved_query_string": req.query_string(),
+        "empty_state": EmptyState::new("Could not find any meetings matching this search."),
+    });
+    return template.in_page(&req, "Search Meetings").await;
+}
@@ -0,0 +1,719 @@
+//! Validation shared between the meeting creation and edit forms.
+
+use crate::api::rcos::meetings::authorization_for::AuthorizationFor;
+use crate::api::rcos::meetings::conflicts::{
+    meeting_conflicts::MeetingConflictsMeetings, MeetingConflicts,
+};
+use crate::api::rcos::meetings::creation::create::{parse_url, url_on_domains};
+use crate::api::rcos::meetings::title_taken::MeetingTitleTaken;
+use crate::api::rcos::meetings::{MeetingType, ALL_MEETING_TYPES};
+use crate::api::rcos::users::timezone::UserTimezone;
+use crate::env::{global_config, KeywordFilterConfig};
+use crate::error::TelescopeError;
+use crate::templates::Template;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use futures::future::join_all;
+use uuid::Uuid;
+
+/// The format of the date inputs on meeting forms.
+const FORM_DATE_FORMAT: &'static str = "%Y-%m-%d";
+
+/// The format of the time inputs on meeting forms. Seconds are not included.
+const FORM_TIME_FORMAT: &'static str = "%H:%M";
+
+/// Trim an optional text field from a form. Empty and whitespace-only values become `None`,
+/// so that they are never stored as empty strings.
+pub fn blank_to_none(value: Option<String>) -> Option<String> {
+    value
+        .map(|string| string.trim().to_string())
+        .filter(|string| !string.is_empty())
+}
+
+/// Check that the submitted meeting type is one of [`ALL_MEETING_TYPES`], rather than leaving
+/// a malformed type for the API to reject. If it is not, an issue is set on the form under
+/// `kind` and `None` is returned.
+pub fn check_meeting_type(form: &mut Template, kind: &str) -> Option<MeetingType> {
+    let kind: &str = kind.trim();
+    let meeting_type: Option<MeetingType> = ALL_MEETING_TYPES
+        .iter()
+        .copied()
+        // Compare with the serialized name, which is what the form's options use.
+        .find(|meeting_type| json!(meeting_type) == json!(kind));
+
+    if meeting_type.is_none() {
+        form["issues"]["kind"] = json!("Unknown meeting type.");
+    }
+    return meeting_type;
+}
+
+/// Clean up an optional URL field with [`parse_url`]. Blank values become `None`. If the
+/// value is not a usable web address, or `domains` is not empty and the address is not on one
+/// of them, an issue is set on the form under the field's name and the value is returned as
+/// submitted so the user can fix it.
+pub fn check_url(
+    form: &mut Template,
+    field: &str,
+    value: Option<String>,
+    domains: &[String],
+) -> Option<String> {
+    let value: String = blank_to_none(value)?;
+    match parse_url(value.as_str()) {
+        Ok(url) if url_on_domains(url.as_str(), domains) => return Some(url),
+        Ok(_) => {
+            form["issues"][field] = json!(format!(
+                "This address must be on one of these sites: {}.",
+                domains.join(", ")
+            ));
+        }
+        Err(message) => form["issues"][field] = json!(message),
+    }
+    return Some(value);
+}
+
+/// Check that a text field is at most `max` unicode scalar values long (after trimming
+/// surrounding whitespace). If it is too long, set an issue on the form under the field's
+/// name.
+fn check_length(form: &mut Template, field: &str, label: &str, value: &str, max: usize) {
+    // Count characters rather than bytes so that multi-byte characters are not
+    // counted more than once.
+    let length: usize = value.trim().chars().count();
+    if length > max {
+        form["issues"][field] = json!(format!(
+            "{} is too long ({} characters). The maximum is {} characters.",
+            label, length, max
+        ));
+    }
+}
+
+/// Check the lengths of the free text fields on a meeting form against the configured
+/// limits. Any issues are added to the form's `issues` object.
+pub fn check_text_lengths(
+    form: &mut Template,
+    title: Option<&str>,
+    description: &str,
+    location: Option<&str>,
+    external_host_name: Option<&str>,
+) {
+    // Get the configured limits.
+    let config = global_config();
+    let limits = &config.meeting_limits;
+
+    if let Some(title) = title {
+        check_length(form, "title", "Title", title, limits.max_title_length);
+    }
+
+    check_length(
+        form,
+        "description",
+        "Description",
+        description,
+        limits.max_description_length,
+    );
+
+    if let Some(location) = location {
+        check_length(
+            form,
+            "location",
+            "Location",
+            location,
+            limits.max_location_length,
+        );
+    }
+
+    if let Some(external_host_name) = external_host_name {
+        check_length(
+            form,
+            "external_host_name",
+            "External host name",
+            external_host_name,
+            limits.max_external_host_name_length,
+        );
+    }
+}
+
+/// Resolve the external host name to save with a meeting. External host names are only
+/// kept for meetings without an RCOS host, and are trimmed (with empty names treated as none).
+pub fn resolve_external_host_name(
+    host: Option<Uuid>,
+    external_host_name: Option<String>,
+) -> Option<String> {
+    // Meetings with an RCOS host never have an external host.
+    if host.is_some() {
+        return None;
+    }
+
+    return blank_to_none(external_host_name);
+}
+
+/// Check that a recording URL is only set on meetings that have already ended, since a
+/// recording of a future meeting is usually a paste error. By default this is a warning that
+/// the user can override by checking the `allow_future_recording` box and submitting again.
+/// If `block_future_recording_urls` is set in the config, it cannot be overridden.
+///
+/// Any issue is added to the form's `issues` object. The form's `warnings` object is also set
+/// if the issue can be overridden.
+pub fn check_recording_url(
+    form: &mut Template,
+    recording_url: Option<&str>,
+    end: DateTime<Utc>,
+    allow_future_recording: bool,
+) {
+    // No recording URL is always fine. Invalid ones already have an issue.
+    let has_recording: bool = recording_url
+        .map(|url| !url.trim().is_empty())
+        .unwrap_or(false)
+        && form["issues"]["recording_url"].is_null();
+
+    // Neither is a meeting that has already ended.
+    if !has_recording || end <= Utc::now() {
+        return;
+    }
+
+    if global_config().block_future_recording_urls {
+        form["issues"]["recording_url"] =
+            json!("Recordings can only be added to meetings that have already ended.");
+    } else if !allow_future_recording {
+        form["issues"]["recording_url"] = json!(
+            "This meeting has not ended yet. Are you sure this is the right recording URL? \
+            Check the box below and submit again to save it anyway."
+        );
+        form["warnings"]["recording_url"] = json!(true);
+    }
+}
+
+/// Check that a meeting does not start or end further in the future than the configured
+/// scheduling horizon, which usually means the year was mistyped. The horizon is measured from
+/// now. Any issues are added to the form's `issues` object.
+pub fn check_scheduling_horizon(form: &mut Template, start: DateTime<Utc>, end: DateTime<Utc>) {
+    let days: u32 = global_config().meeting_limits.max_scheduling_horizon_days;
+    let horizon: DateTime<Utc> = Utc::now() + Duration::days(days as i64);
+    let message: String = format!(
+        "Meetings can be scheduled at most {} days ({}) in advance. Check the year.",
+        days,
+        horizon.format("%B %-d, %Y")
+    );
+
+    if start > horizon {
+        form["issues"]["start_date"] = json!(message);
+    }
+    if end > horizon {
+        form["issues"]["end_date"] = json!(message);
+    }
+}
+
+/// Check that no other published meeting in the semester has the same title (ignoring case
+/// and whitespace), since meetings with the same title look like duplicates. Drafts and
+/// untitled meetings are not checked. By default this is a warning that the user can override
+/// by checking the `allow_duplicate_title` box and submitting again. If
+/// `block_duplicate_meeting_titles` is set in the config, it cannot be overridden.
+///
+/// `meeting_id` is the meeting being edited, if any, so that it does not match itself. Any
+/// issue is added to the form's `issues` object, and the form's `warnings` object is also set
+/// if the issue can be overridden.
+pub async fn check_duplicate_title(
+    form: &mut Template,
+    semester_id: &str,
+    title: Option<&str>,
+    meeting_id: Option<i64>,
+    is_draft: bool,
+    allow_duplicate_title: bool,
+) -> Result<(), TelescopeError> {
+    let config = global_config();
+
+    // Skip the check if it is off, or if the user already confirmed the title.
+    let blocking: bool = config.block_duplicate_meeting_titles;
+    if !blocking && (!config.warn_duplicate_meeting_titles || allow_duplicate_title) {
+        return Ok(());
+    }
+
+    // Drafts and untitled meetings are never duplicates.
+    let title: &str = match title {
+        Some(title) if !is_draft => title,
+        _ => return Ok(()),
+    };
+
+    let existing: Option<i64> =
+        MeetingTitleTaken::get(semester_id.to_string(), title, meeting_id).await?;
+    if existing.is_none() {
+        return Ok(());
+    }
+
+    if blocking {
+        form["issues"]["title"] = json!(
+            "Another meeting this semester already has this title. Choose a different title."
+        );
+    } else {
+        form["issues"]["title"] = json!(
+            "Another meeting this semester already has this title. Rename this meeting, or \
+            check the box below and submit again to keep it."
+        );
+        form["warnings"]["title"] = json!(true);
+    }
+
+    return Ok(());
+}
+
+/// Check that no other meeting at the same time has the same host, or is in person at the
+/// same location. Remote meetings and meetings without a location only conflict on host.
+/// This is a warning that the user can override by checking the `allow_conflicts` box and
+/// submitting again.
+///
+/// `meeting_id` is the meeting being edited, if any, so that it does not conflict with
+/// itself. Any issue is added to the form's `issues` object under `conflicts`.
+pub async fn check_conflicts(
+    form: &mut Template,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    location: Option<&str>,
+    is_remote: bool,
+    host: Option<Uuid>,
+    meeting_id: Option<i64>,
+    allow_conflicts: bool,
+) -> Result<(), TelescopeError> {
+    return check_occurrence_conflicts(
+        form,
+        &[(start, end)],
+        location,
+        is_remote,
+        host,
+        meeting_id,
+        allow_conflicts,
+    )
+    .await;
+}
+
+/// Check each occurrence of a repeating meeting for conflicts, like [`check_conflicts`].
+/// The occurrences are checked concurrently. Only the conflicts of the earliest conflicting
+/// occurrence are reported, and if any check fails, the error of the earliest failing
+/// occurrence is returned.
+pub async fn check_occurrence_conflicts(
+    form: &mut Template,
+    occurrences: &[(DateTime<Utc>, DateTime<Utc>)],
+    location: Option<&str>,
+    is_remote: bool,
+    host: Option<Uuid>,
+    meeting_id: Option<i64>,
+    allow_conflicts: bool,
+) -> Result<(), TelescopeError> {
+    if allow_conflicts {
+        return Ok(());
+    }
+
+    // Query every occurrence at once. The results are in the same order as the occurrences.
+    let results: Vec<Result<Vec<MeetingConflictsMeetings>, TelescopeError>> =
+        join_all(occurrences.iter().map(|(start, end)| {
+            MeetingConflicts::get(*start, *end, location, is_remote, host, meeting_id)
+        }))
+        .await;
+
+    let mut conflicts: Vec<MeetingConflictsMeetings> = Vec::new();
+    for result in results {
+        conflicts = result?;
+        if !conflicts.is_empty() {
+            break;
+        }
+    }
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    // Describe each conflicting meeting and why it conflicts.
+    let descriptions: Vec<String> = conflicts
+        .iter()
+        .map(|meeting| {
+            let reason: &str = if host.is_some() && meeting.host_user_id == host {
+                "same host"
+            } else {
+                "same location"
+            };
+            format!(
+                "\"{}\" (/meeting/{}, {})",
+                meeting.title(),
+                meeting.meeting_id,
+                reason
+            )
+        })
+        .collect();
+
+    form["issues"]["conflicts"] = json!(format!(
+        "This meeting overlaps with {}. Change the time, location, or host, or check the box \
+        below and submit again to keep it.",
+        descriptions.join(", ")
+    ));
+    form["warnings"]["conflicts"] = json!(true);
+
+    return Ok(());
+}
+
+/// Split text into lowercase words, ignoring punctuation and spacing.
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Find the filtered terms that appear in some text. Terms match whole words, ignoring case
+/// and the spacing or punctuation between the words of a term.
+fn flagged_keywords<'a>(filter: &'a KeywordFilterConfig, text: &str) -> Vec<&'a str> {
+    let words: Vec<String> = normalized_words(text);
+
+    return filter
+        .words
+        .iter()
+        .filter(|term| {
+            let term_words: Vec<String> = normalized_words(term);
+            !term_words.is_empty()
+                && words
+                    .windows(term_words.len())
+                    .any(|window| window == term_words.as_slice())
+        })
+        .map(String::as_str)
+        .collect();
+}
+
+/// Does a user skip the keyword filter? This is only the case for coordinators (and faculty
+/// advisors and sysadmins) when the filter is configured to skip them. The user's
+/// authorization is only looked up if needed.
+pub async fn skips_keyword_filter(user_id: Uuid) -> Result<bool, TelescopeError> {
+    let skip_coordinators: bool = global_config()
+        .keyword_filter
+        .as_ref()
+        .map(|filter| filter.skip_coordinators)
+        .unwrap_or(false);
+
+    if !skip_coordinators {
+        return Ok(false);
+    }
+
+    return Ok(AuthorizationFor::get(Some(user_id))
+        .await?
+        .is_coordinator_or_admin());
+}
+
+/// Check the public text of a meeting (title and description) against the keyword filter, if
+/// it is enabled. Drafts are not public, so they are not checked. Flagged terms are an issue
+/// under `keywords`. Unless the filter blocks them,
+/// the user can override this by checking the `allow_flagged_keywords` box and submitting
+/// again, so the form's `warnings` object is also set.
+pub fn check_keywords(
+    form: &mut Template,
+    title: Option<&str>,
+    description: &str,
+    is_draft: bool,
+    skip_filter: bool,
+    allow_flagged_keywords: bool,
+) {
+    let config = global_config();
+    let filter: &KeywordFilterConfig = match config.keyword_filter.as_ref() {
+        Some(filter) => filter,
+        None => return,
+    };
+
+    if is_draft || skip_filter || (!filter.block && allow_flagged_keywords) {
+        return;
+    }
+
+    // Collect flagged terms from both fields without repeats.
+    let mut flagged: Vec<&str> = flagged_keywords(filter, title.unwrap_or(""));
+    for term in flagged_keywords(filter, description) {
+        if !flagged.contains(&term) {
+            flagged.push(term);
+        }
+    }
+
+    if flagged.is_empty() {
+        return;
+    }
+
+    let terms: String = flagged
+        .iter()
+        .map(|term| format!("\"{}\"", term))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if filter.block {
+        form["issues"]["keywords"] = json!(format!(
+            "The title or description contains terms that are not allowed in public meetings: \
+            {}. Remove them and submit again.",
+            terms
+        ));
+    } else {
+        form["issues"]["keywords"] = json!(format!(
+            "The title or description contains terms that may not be appropriate for public \
+            meetings: {}. Edit the meeting, or check the box below and submit again to keep it.",
+            terms
+        ));
+        form["warnings"]["keywords"] = json!(true);
+    }
+}
+
+/// Compare the time on the user's device when they submitted the form (milliseconds since the
+/// Unix epoch) to the server time. If they differ by more than the configured threshold, add a
+/// warning to the form's `warnings` object. This never blocks the form, since the server only
+/// uses the submitted date and time strings, but it helps explain times that look wrong.
+pub fn check_clock_skew(form: &mut Template, client_timestamp: Option<&str>) {
+    // Old forms and browsers without JavaScript do not send a timestamp.
+    let client_timestamp: i64 = match client_timestamp.and_then(|s| s.trim().parse().ok()) {
+        Some(timestamp) => timestamp,
+        None => return,
+    };
+
+    let skew_seconds: i64 = (Utc::now().timestamp_millis() - client_timestamp).abs() / 1000;
+    let threshold: u32 = global_config().clock_skew_warning_seconds;
+    if skew_seconds > threshold as i64 {
+        warn!(
+            "Meeting form submitted from a device clock {} seconds off from server time.",
+            skew_seconds
+        );
+        form["warnings"]["clock_skew"] = json!(format!(
+            "Your device clock may be off by about {} minutes. Double check that the meeting \
+            times are correct.",
+            (skew_seconds + 30) / 60
+        ));
+    }
+}
+
+/// The timezone that dates and times on meeting forms are entered in.
+#[derive(Copy, Clone, Debug)]
+pub enum FormTimezone {
+    /// The server's local timezone, for users without a timezone preference.
+    Server,
+    /// The user's preferred timezone.
+    User(Tz),
+}
+
+impl FormTimezone {
+    /// Get the timezone a user enters meeting dates and times in. This is their preferred
+    /// timezone if they have set one on their profile, and the server's otherwise.
+    pub async fn for_user(user_id: Uuid) -> Result<Self, TelescopeError> {
+        return Ok(UserTimezone::get(user_id)
+            .await?
+            .map(FormTimezone::User)
+            .unwrap_or(FormTimezone::Server));
+    }
+
+    /// Describe this timezone for the note on meeting forms.
+    pub fn description(&self) -> String {
+        match self {
+            FormTimezone::Server => format!("server time (UTC{})", Local::now().format("%:z")),
+            FormTimezone::User(tz) => format!("{} time", tz.name()),
+        }
+    }
+
+    /// Format a timestamp as the date and time strings of the meeting forms' date and time
+    /// inputs (`YYYY-MM-DD` and `HH:MM`) in this timezone.
+    pub fn to_form_datetime(self, timestamp: &DateTime<Utc>) -> (String, String) {
+        // Format a timestamp in either timezone the same way.
+        fn format<Z: TimeZone>(timestamp: DateTime<Z>) -> (String, String)
+        where
+            Z::Offset: std::fmt::Display,
+        {
+            (
+                timestamp.format(FORM_DATE_FORMAT).to_string(),
+                timestamp.format(FORM_TIME_FORMAT).to_string(),
+            )
+        }
+
+        match self {
+            FormTimezone::Server => format(timestamp.with_timezone(&Local)),
+            FormTimezone::User(tz) => format(timestamp.with_timezone(&tz)),
+        }
+    }
+
+    /// Interpret a date and time from a meeting form in this timezone and convert it to UTC.
+    /// `label` names the field in the error (e.g. "start"). Times that do not exist or are
+    /// ambiguous because of daylight saving time changes are rejected.
+    pub fn to_utc(
+        self,
+        timestamp: &NaiveDateTime,
+        label: &str,
+    ) -> Result<DateTime<Utc>, TelescopeError> {
+        let utc: Option<DateTime<Utc>> = match self {
+            FormTimezone::Server => Local
+                .from_local_datetime(timestamp)
+                .single()
+                .map(|local| local.with_timezone(&Utc)),
+            FormTimezone::User(tz) => tz
+                .from_local_datetime(timestamp)
+                .single()
+                .map(|local| local.with_timezone(&Utc)),
+        };
+
+        return utc.ok_or(TelescopeError::BadRequest {
+            header: "Malformed Meeting Form".into(),
+            message: format!(
+                "The {} time does not exist or is ambiguous in {}, usually because of a \
+                daylight saving time change.",
+                label,
+                self.description()
+            ),
+            show_status_code: false,
+        });
+    }
+}
+
+/// Combine the date and time submitted by a meeting form into a timestamp. `label` names the
+/// field in the error (e.g. "start"). Browsers send times as `HH:MM`, but seconds are accepted
+/// too.
+pub fn from_form_datetime(
+    date: NaiveDate,
+    time: &str,
+    label: &str,
+) -> Result<NaiveDateTime, TelescopeError> {
+    let time: &str = time.trim();
+    let parsed: NaiveTime = NaiveTime::parse_from_str(time, FORM_TIME_FORMAT)
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|e| TelescopeError::BadRequest {
+            header: "Malformed Meeting Form".into(),
+            message: format!("Could not parse {} time. Internal error: {}", label, e),
+            show_status_code: false,
+        })?;
+
+    return Ok(date.and_time(parsed));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+    use serde_json::Value;
+
+    /// Make an empty form to check fields against.
+    fn form() -> Template {
+        Template::new("meetings/edit/form")
+    }
+
+    /// Text at the maximum length is allowed, and one character more is not.
+    #[test]
+    fn length_limit_boundary() {
+        let mut at_limit: Template = form();
+        check_length(&mut at_limit, "title", "Title", "abcde", 5);
+        assert_eq!(at_limit["issues"]["title"], Value::Null);
+
+        let mut over_limit: Template = form();
+        check_length(&mut over_limit, "title", "Title", "abcdef", 5);
+        assert_eq!(
+            over_limit["issues"]["title"],
+            json!("Title is too long (6 characters). The maximum is 5 characters.")
+        );
+    }
+
+    /// Lengths are counted in characters, not bytes, after trimming whitespace.
+    #[test]
+    fn length_counts_trimmed_characters() {
+        let mut multi_byte: Template = form();
+        check_length(&mut multi_byte, "title", "Title", "été🎉", 4);
+        assert_eq!(multi_byte["issues"]["title"], Value::Null);
+
+        let mut padded: Template = form();
+        check_length(&mut padded, "location", "Location", "   DCC 308  \n", 7);
+        assert_eq!(padded["issues"]["location"], Value::Null);
+    }
+
+    /// Make the weekly occurrences of a one hour meeting.
+    fn weekly_occurrences(count: i64) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let start: DateTime<Utc> = Utc.ymd(2022, 9, 6).and_hms(16, 0, 0);
+        (0..count)
+            .map(|week| {
+                let start: DateTime<Utc> = start + Duration::weeks(week);
+                (start, start + Duration::hours(1))
+            })
+            .collect()
+    }
+
+    /// Every occurrence of a repeating meeting is checked, and a conflict is reported once.
+    #[actix_rt::test]
+    async fn checks_every_occurrence() {
+        let api = MockRcosApi::start();
+        let host: Uuid = Uuid::new_v4();
+        api.stub(
+            "MeetingConflicts",
+            json!({ "meetings": [{
+                "meeting_id": 12,
+                "title": "Mentor Meeting",
+                "type": "mentors",
+                "start_date_time": "2022-09-13T16:00:00Z",
+                "end_date_time": "2022-09-13T17:00:00Z",
+                "is_remote": true,
+                "location": null,
+                "host_user_id": host
+            }] }),
+        );
+
+        let mut form: Template = form();
+        check_occurrence_conflicts(
+            &mut form,
+            weekly_occurrences(3).as_slice(),
+            None,
+            true,
+            Some(host),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        api.assert_called("MeetingConflicts", 3);
+        let issue: &str = form["issues"]["conflicts"].as_str().unwrap();
+        assert!(issue.contains("\"Mentor Meeting\" (/meeting/12, same host)"));
+        assert_eq!(form["warnings"]["conflicts"], json!(true));
+    }
+
+    /// Occurrences that do not share a host or location do not conflict.
+    #[actix_rt::test]
+    async fn no_conflicts() {
+        let api = MockRcosApi::start();
+        api.stub(
+            "MeetingConflicts",
+            json!({ "meetings": [{
+                "meeting_id": 12,
+                "title": null,
+                "type": "small_group",
+                "start_date_time": "2022-09-13T16:00:00Z",
+                "end_date_time": "2022-09-13T17:00:00Z",
+                "is_remote": false,
+                "location": "DCC 308",
+                "host_user_id": null
+            }] }),
+        );
+
+        let mut form: Template = form();
+        check_occurrence_conflicts(
+            &mut form,
+            weekly_occurrences(2).as_slice(),
+            Some("Sage 3303"),
+            false,
+            Some(Uuid::new_v4()),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        api.assert_called("MeetingConflicts", 2);
+        assert_eq!(form["issues"], Value::Null);
+    }
+
+    /// Conflicts are not checked at all once the user allows them.
+    #[actix_rt::test]
+    async fn allowed_conflicts_are_not_checked() {
+        let api = MockRcosApi::start();
+
+        let mut form: Template = form();
+        check_occurrence_conflicts(
+            &mut form,
+            weekly_occurrences(4).as_slice(),
+            None,
+            true,
+            Some(Uuid::new_v4()),
+            None,
+            true,
+        )
+        .await
+        .unwrap();
+
+        api.assert_called("MeetingConflicts", 0);
+    }
+}
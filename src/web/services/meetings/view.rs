@@ -2,31 +2,77 @@
 
 use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
 use crate::api::rcos::meetings::get_by_id::{meeting::MeetingMeeting, Meeting};
+use crate::api::rcos::meetings::history::{MeetingEdit, MeetingEditHistory};
+use crate::env::global_config;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::tags::Tags;
 use crate::templates::Template;
-use crate::web::services::auth::identity::Identity;
-use actix_web::web::Path;
+use crate::web::services::auth::identity::{AuthenticationCookie, OptionalAuth};
+use crate::web::services::meetings::edit::meeting_data_checked;
+use actix_web::web::{Path, Query};
 use actix_web::HttpRequest;
 use chrono::{Local, TimeZone};
 
 /// The path from the templates directory to this template.
 const TEMPLATE_PATH: &'static str = "meetings/page";
 
+/// The value of the `preview` query parameter to view the page as a visitor who is not
+/// signed in.
+const PUBLIC_PREVIEW: &'static str = "public";
+
+/// The value of the `preview` query parameter to view a draft meeting as it will look once it
+/// is published.
+const DRAFT_PREVIEW: &'static str = "true";
+
+/// Query parameters accepted by the meeting page.
+#[derive(Clone, Debug, Deserialize)]
+struct MeetingPageQuery {
+    /// Set to `public` to preview the page as a visitor who is not signed in would see it.
+    /// This is ignored for users who cannot edit the meeting. Set to `true` to preview a
+    /// draft as it will look once published, which only users who can edit it may do.
+    #[serde(default)]
+    pub preview: Option<String>,
+}
+
+/// Build the error for a meeting the viewer cannot see. In a public preview this is labeled
+/// as what visitors would see, rather than an error for the viewer themselves.
+fn not_visible(preview: bool, header: &str, message: &str) -> TelescopeError {
+    if preview {
+        return TelescopeError::BadRequest {
+            header: format!("Public Preview: {}", header),
+            message: format!(
+                "Visitors who are not signed in would see this message instead of the \
+                meeting. {}",
+                message
+            ),
+            show_status_code: false,
+        };
+    }
+
+    return TelescopeError::BadRequest {
+        header: header.into(),
+        message: message.into(),
+        show_status_code: false,
+    };
+}
+
 /// Endpoint to preview a specific meeting.
 #[get("/meeting/{meeting_id}")]
 pub async fn meeting(
     req: HttpRequest,
     Path(meeting_id): Path<i64>,
-    identity: Identity,
+    Query(query): Query<MeetingPageQuery>,
+    auth: OptionalAuth,
 ) -> Result<Page, TelescopeError> {
-    // Get the viewer's user ID.
-    let viewer: Option<_> = identity.get_user_id().await?;
-    // Get the viewer's authorization info.
-    let authorization: UserMeetingAuthorization = AuthorizationFor::get(viewer).await?;
-    // Get the meeting data from the RCOS API.
-    let meeting: Option<MeetingMeeting> = Meeting::get(meeting_id).await?;
+    // Get the viewer's user ID. Anonymous viewers can see public meetings.
+    let viewer: Option<_> = auth.get_user_id().await?;
+    // Get the viewer's authorization info and the meeting data from the RCOS API at the same
+    // time. Errors are checked in the order the queries used to be made.
+    let (viewer_authorization, meeting) =
+        futures::join!(AuthorizationFor::get(viewer), Meeting::get(meeting_id));
+    let viewer_authorization: UserMeetingAuthorization = viewer_authorization?;
+    let meeting: Option<MeetingMeeting> = meeting?;
     // Check to make sure the meeting exists.
     if meeting.is_none() {
         return Err(TelescopeError::resource_not_found(
@@ -36,31 +82,52 @@ pub async fn meeting(
     }
 
     // Unwrap the meeting object.
-    let meeting: MeetingMeeting = meeting.unwrap();
+    let mut meeting: MeetingMeeting = meeting.unwrap();
+    let meeting_host: Option<_> = meeting.host.as_ref().map(|host| host.id);
+
+    // Users who can edit a draft can preview it as it will look once published. Anyone else
+    // asking for the preview is forbidden, even if they could see the draft itself.
+    let draft_preview: bool = query.preview.as_deref() == Some(DRAFT_PREVIEW) && meeting.is_draft;
+    if draft_preview {
+        let auth: &AuthenticationCookie =
+            auth.0.as_ref().ok_or(TelescopeError::NotAuthenticated)?;
+        let (meeting_data, _) = meeting_data_checked(auth, meeting_id).await?;
+        meeting = meeting_data;
+        // Render the draft the way it will be published.
+        meeting.is_draft = false;
+    }
+
+    // Users who can edit the meeting can preview it as a visitor who is not signed in. This
+    // uses an anonymous authorization object without touching the viewer's session.
+    let preview: bool = query.preview.as_deref() == Some(PUBLIC_PREVIEW)
+        && viewer_authorization.can_edit(meeting_host);
+    let authorization: UserMeetingAuthorization = if preview {
+        AuthorizationFor::get(None).await?
+    } else {
+        viewer_authorization
+    };
+
     // Make sure that the meeting is visible to the user.
     // First check for draft status.
-    let meeting_host: Option<_> = meeting.host.as_ref().map(|host| host.id);
-    let can_edit: bool = authorization.can_edit(meeting_host);
+    let can_edit: bool = !preview && !draft_preview && authorization.can_edit(meeting_host);
     if !can_edit && meeting.is_draft && !authorization.can_view_drafts() {
-        return Err(TelescopeError::BadRequest {
-            header: "Meeting Not Visible".into(),
-            message: "This meeting is currently marked as a draft and is only visible to \
+        return Err(not_visible(
+            preview,
+            "Meeting Not Visible",
+            "This meeting is currently marked as a draft and is only visible to \
             coordinators and faculty advisors. If you believe this is in error, please \
-            contact a coordinator."
-                .into(),
-            show_status_code: false,
-        });
+            contact a coordinator.",
+        ));
     }
 
     // Then check the meeting variant.
     if !authorization.can_view(meeting.type_) {
-        return Err(TelescopeError::BadRequest {
-            header: "Meeting Access Restricted".into(),
-            message: "Access to this meeting is restricted to mentors or coordinators. If you \
-            think this is in error, please contact a coordinator."
-                .into(),
-            show_status_code: false,
-        });
+        return Err(not_visible(
+            preview,
+            "Meeting Access Restricted",
+            "Access to this meeting is restricted to mentors or coordinators. If you \
+            think this is in error, please contact a coordinator.",
+        ));
     }
 
     // Create dynamic OGP tags and start with default so all other fields are correct
@@ -126,11 +193,33 @@ pub async fn meeting(
     // Add description to OGP tags.
     tags.description = description;
 
+    // Link to add the meeting to Google Calendar. Drafts are not offered since they may change.
+    // This opens a pre-filled event for the viewer to save themselves; Telescope does not
+    // create events through the Calendar API, since it has no calendar-scoped Google sign in.
+    let meeting_page_url: String =
+        format!("{}/meeting/{}", global_config().telescope_url, meeting_id);
+    let google_calendar_url = (!meeting.is_draft)
+        .then(|| meeting.google_calendar_url(meeting_page_url.as_str()))
+        .flatten();
+
+    // Coordinators can see who changed the meeting and how. This is left out of previews.
+    let edit_history: Option<Vec<MeetingEdit>> =
+        if !preview && !draft_preview && authorization.is_coordinator_or_admin() {
+            Some(MeetingEditHistory::get(meeting_id).await?)
+        } else {
+            None
+        };
+
     // Build meeting template.
     let mut template = Template::new(TEMPLATE_PATH);
     template.fields = json!({
         "meeting": &meeting,
-        "auth": authorization
+        "auth": authorization,
+        "can_edit": can_edit,
+        "preview": preview,
+        "draft_preview": draft_preview,
+        "google_calendar_url": google_calendar_url,
+        "edit_history": edit_history
     });
 
     // Build page around meeting template.
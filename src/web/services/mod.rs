@@ -1,14 +1,19 @@
 //! Web services.
 
-use actix_web::web::ServiceConfig;
+use crate::templates::static_pages::sponsors::SponsorsPage;
+use crate::templates::static_pages::StaticPage;
+use actix_web::web::{self as aweb, ServiceConfig};
 
 mod admin;
+mod api;
 pub mod auth;
+mod health;
 mod index;
 pub mod meetings;
 pub mod not_found;
 mod projects;
 pub mod user;
+mod webhooks;
 
 /// Register all of the routes to the actix app.
 pub fn register(config: &mut ServiceConfig) {
@@ -27,7 +32,18 @@ pub fn register(config: &mut ServiceConfig) {
     // Admin panel services.
     admin::register(config);
 
+    // JSON API services.
+    api::register(config);
+
+    // Webhooks from GitHub and Discord.
+    webhooks::register(config);
+
     config
+        // Liveness and readiness probes.
+        .service(health::healthz)
+        .service(health::readyz)
         // Homepage
-        .service(index::index);
+        .service(index::index)
+        // Sponsors page
+        .route("/sponsors", aweb::get().to(SponsorsPage::page));
 }
@@ -1,9 +1,202 @@
+//! Page not found service, with suggestions for mistyped paths.
+
 use crate::error::TelescopeError;
-use actix_web::HttpResponse;
+use actix_web::dev::ResourceMap;
+use actix_web::{HttpRequest, HttpResponse};
+
+/// The most routes suggested on a not found page.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The pages users are likely to type or bookmark. Segments in braces match any value, which
+/// is kept from the requested path in suggestions. Pages that only make sense as the target of
+/// a form or a redirect are left out. Only the routes the app actually registers are
+/// suggested (see [`registered_routes`]).
+const ROUTES: &'static [&'static str] = &[
+    "/",
+    "/account/sessions",
+    "/admin",
+    "/admin/announcement",
+    "/admin/semesters",
+    "/admin/users/roles",
+    "/admin/users/unlinked",
+    "/developers",
+    "/developers/{page}",
+    "/edit_profile",
+    "/join_discord",
+    "/login",
+    "/logout",
+    "/meeting/create/select_host",
+    "/meeting/{meeting_id}",
+    "/meeting/{meeting_id}/edit",
+    "/meetings",
+    "/meetings/search",
+    "/projects",
+    "/register",
+    "/sponsors",
+    "/user/{id}",
+    "/user/{id}/edit",
+];
+
+/// Get the edit (Levenshtein) distance between two strings, ignoring ASCII case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_ascii_lowercase().chars().collect();
+    let b: Vec<char> = b.to_ascii_lowercase().chars().collect();
+
+    // Only keep one row of the table at a time.
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current: Vec<usize> = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + (a_char != b_char) as usize;
+            let insertion: usize = current[j] + 1;
+            let deletion: usize = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+
+    return previous[b.len()];
+}
+
+/// Compare a requested path to a route. Return the path to suggest and how far it is from
+/// the request, or `None` if the route is not close enough to suggest. Each fixed segment of
+/// the route can be a few edits away from the requested one (about one per three characters).
+fn compare(requested: &[&str], route: &str) -> Option<(String, usize)> {
+    let route_segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    if route_segments.len() != requested.len() {
+        return None;
+    }
+
+    let mut distance: usize = 0;
+    let mut suggestion: Vec<&str> = Vec::with_capacity(requested.len());
+    for (requested_segment, route_segment) in requested.iter().zip(route_segments.iter()) {
+        // Parameters match anything and keep the requested value.
+        if route_segment.starts_with('{') {
+            suggestion.push(requested_segment);
+            continue;
+        }
+
+        let segment_distance: usize = edit_distance(requested_segment, route_segment);
+        if segment_distance > (route_segment.len() / 3).max(1) {
+            return None;
+        }
+        distance += segment_distance;
+        suggestion.push(route_segment);
+    }
+
+    return Some((format!("/{}", suggestion.join("/")), distance));
+}
+
+/// Get the entries of [`ROUTES`] that match a resource registered in the app. Parameter
+/// segments are matched as if they were values.
+fn registered_routes(resources: &ResourceMap) -> Vec<&'static str> {
+    ROUTES
+        .iter()
+        .copied()
+        .filter(|route| resources.has_resource(route))
+        .collect()
+}
+
+/// Get up to [`MAX_SUGGESTIONS`] of the given routes close to a requested path, closest
+/// first.
+fn suggest_routes(path: &str, routes: &[&str]) -> Vec<String> {
+    let requested: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut matches: Vec<(String, usize)> = routes
+        .iter()
+        .filter_map(|route| compare(requested.as_slice(), route))
+        // The path itself (with different case or slashes) is not a useful suggestion.
+        .filter(|(suggestion, _)| suggestion.as_str() != path)
+        .collect();
+
+    // Closest first, then alphabetically so the order is stable.
+    matches.sort_by(|(a, a_distance), (b, b_distance)| a_distance.cmp(b_distance).then(a.cmp(b)));
+    matches.dedup_by(|(a, _), (b, _)| a == b);
+
+    return matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(suggestion, _)| suggestion)
+        .collect();
+}
 
 // Use HttpResponse here because never type is not yet stable.
-/// Respond to all requests with page not found.
+/// Respond to all requests with page not found, suggesting close paths if there are any.
 /// Used as default service.
-pub async fn not_found() -> Result<HttpResponse, TelescopeError> {
-    Err(TelescopeError::PageNotFound)
+pub async fn not_found(req: HttpRequest) -> Result<HttpResponse, TelescopeError> {
+    let routes: Vec<&str> = registered_routes(req.resource_map());
+    let suggestions: Vec<String> = suggest_routes(req.path(), routes.as_slice());
+    if suggestions.is_empty() {
+        return Err(TelescopeError::PageNotFound);
+    }
+
+    return Err(TelescopeError::resource_not_found(
+        "Page Not Found",
+        format!(
+            "There is no page at {}. Did you mean {}?",
+            req.path(),
+            suggestions.join(" or ")
+        ),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    /// A typo in a fixed segment is corrected, and parameter values are kept.
+    #[test]
+    fn near_miss_is_suggested() {
+        assert_eq!(
+            suggest_routes("/meeting/12/edt", ROUTES),
+            vec!["/meeting/12/edit".to_string()]
+        );
+        assert_eq!(
+            suggest_routes("/projcts", ROUTES),
+            vec!["/projects".to_string()]
+        );
+    }
+
+    /// Paths nothing like a route get no suggestions.
+    #[test]
+    fn wildly_different_path_has_no_suggestions() {
+        assert!(suggest_routes("/completely/unrelated/path/here", ROUTES).is_empty());
+        assert!(suggest_routes("/xyzzy", ROUTES).is_empty());
+    }
+
+    /// Routes that are not registered are never suggested.
+    #[test]
+    fn only_given_routes_are_suggested() {
+        assert!(suggest_routes("/projcts", &["/sponsors"]).is_empty());
+    }
+
+    /// Respond with the entries of [`ROUTES`] that are not registered in the app.
+    async fn unregistered_routes(req: HttpRequest) -> HttpResponse {
+        let registered: Vec<&str> = registered_routes(req.resource_map());
+        let missing: Vec<&str> = ROUTES
+            .iter()
+            .copied()
+            .filter(|route| !registered.contains(route))
+            .collect();
+        HttpResponse::Ok().json(missing)
+    }
+
+    /// Every entry of [`ROUTES`] is routable in the app.
+    #[actix_rt::test]
+    async fn routes_are_registered() {
+        let mut app = test::init_service(
+            App::new()
+                .configure(crate::web::services::register)
+                // Report the unregistered routes from the default service, which has the
+                // app's resource map.
+                .default_service(web::to(unregistered_routes)),
+        )
+        .await;
+
+        let request = test::TestRequest::get().uri("/not-a-page").to_request();
+        let missing: Vec<String> = test::read_response_json(&mut app, request).await;
+        assert!(missing.is_empty(), "Unregistered routes: {:?}", missing);
+    }
 }
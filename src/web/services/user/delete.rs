@@ -7,6 +7,30 @@ use crate::templates::page::Page;
 use crate::templates::{jumbotron, Template};
 use crate::web::services::auth::identity::{AuthenticationCookie, Identity};
 use actix_web::{HttpRequest, Responder};
+use uuid::Uuid;
+
+/// Kick a user from the RCOS Discord before their account is deleted, if they have a Discord
+/// account linked.
+pub async fn kick_from_discord(user_id: Uuid) -> Result<(), TelescopeError> {
+    // Check if the user has a discord account linked.
+    let discord_id: Option<u64> = AccountLookup::send(user_id, UserAccountType::Discord)
+        .await?
+        .and_then(|string| string.as_str().parse::<u64>().ok());
+
+    // If there is one, kick it from the RCOS Discord.
+    if let Some(discord_id) = discord_id {
+        // Get the RCOS Discord Guild ID.
+        let rcos_guild = global_config().discord_config.rcos_guild_id();
+
+        // Kick the user from the RCOS guild.
+        global_discord_client()
+            .kick_member(rcos_guild, discord_id)
+            .await
+            .map_err(TelescopeError::serenity_error)?;
+    }
+
+    return Ok(());
+}
 
 /// Confirmation form to delete the profile
 #[get("/profile_delete")]
@@ -16,7 +40,7 @@ pub async fn confirm_delete(
 ) -> Result<Page, TelescopeError> {
     let user_id = auth.get_user_id_or_error().await?;
     // The viewer and target are both the same user ID.
-    let profile_data = Profile::for_user(user_id, Some(user_id)).await?;
+    let profile_data = Profile::get(user_id, Some(user_id), true).await?;
     // Make template.
     let mut template = Template::new("user/delete");
     template.fields = json!(profile_data);
@@ -33,24 +57,10 @@ pub async fn profile_delete(
     let user_id = identity
         .get_user_id()
         .await?
-        .ok_or(TelescopeError::NotAuthenticated)?;
-
-    // Check if the viewer has a discord account linked.
-    let discord_id: Option<u64> = AccountLookup::send(user_id, UserAccountType::Discord)
-        .await?
-        .and_then(|string| string.as_str().parse::<u64>().ok());
-
-    // If there is one, kick it from the RCOS Discord.
-    if let Some(discord_id) = discord_id {
-        // Get the RCOS Discord Guild ID.
-        let rcos_guild = global_config().discord_config.rcos_guild_id();
+        .ok_or(TelescopeError::AccountNotFound)?;
 
-        // Kick the user from the RCOS guild.
-        global_discord_client()
-            .kick_member(rcos_guild, discord_id)
-            .await
-            .map_err(TelescopeError::serenity_error)?;
-    }
+    // Kick the viewer from the RCOS Discord if they are in it.
+    kick_from_discord(user_id).await?;
 
     // Execute the user deletion.
     DeleteUser::execute(user_id).await?;
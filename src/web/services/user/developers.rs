@@ -5,8 +5,10 @@ use actix_web::HttpRequest;
 use serde_json::Value;
 use uuid::Uuid;
 
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
 use crate::api::rcos::users::developers_page::{AllDevelopers, CurrentDevelopers, PER_PAGE};
 use crate::error::TelescopeError;
+use crate::templates::empty_state::EmptyState;
 use crate::templates::page::Page;
 use crate::templates::pagination::PaginationInfo;
 use crate::templates::Template;
@@ -26,6 +28,11 @@ pub struct DevelopersPageQuery {
     /// Should the results include previous members?
     #[serde(default)]
     pub include_old: bool,
+
+    /// Should the results include deactivated users? This is ignored unless the viewer is a
+    /// coordinator or admin.
+    #[serde(default)]
+    pub include_deactivated: bool,
 }
 
 pub fn register_services(conf: &mut ServiceConfig) {
@@ -69,23 +76,42 @@ pub async fn developers_page(
         // Otherwise default to 0
         .unwrap_or(0);
 
+    // Get the viewers user ID
+    let viewer: Option<Uuid> = identity.get_user_id().await?;
+
+    // Only coordinators and admins can list deactivated users.
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(viewer).await?;
+    let can_see_deactivated: bool = authorization.is_coordinator_or_admin();
+    let include_deactivated: bool = query.include_deactivated && can_see_deactivated;
+
     // Get the API data by sending one of the developer page queries.
     let api_data: Value;
     // Determine which API query to send using the request query.
     if query.include_old {
         // Get all the developers (including ones not active this semester).
-        let query_response = AllDevelopers::get(page_num, query.search.clone()).await?;
+        let query_response =
+            AllDevelopers::get(page_num, query.search.clone(), include_deactivated).await?;
         // Convert the response into a JSON value.
         // Unwrap because this conversion should never fail.
         api_data = serde_json::to_value(query_response).unwrap();
     } else {
         // Get only the current developers.
-        let query_response = CurrentDevelopers::get(page_num, query.search.clone()).await?;
+        let query_response =
+            CurrentDevelopers::get(page_num, query.search.clone(), include_deactivated).await?;
         api_data = serde_json::to_value(query_response).unwrap();
     }
 
-    // Get the viewers user ID
-    let viewer: Option<Uuid> = identity.get_user_id().await?;
+    // Build the message to show if there are no users. If only current users were
+    // searched, suggest including previous members.
+    let empty_state: EmptyState = if query.include_old {
+        EmptyState::new("Could not find any users matching these parameters.")
+    } else {
+        EmptyState::new(
+            "Could not find any users matching these parameters. Perhaps the current semester \
+            is over or there have not been any recorded enrollments yet. You can check the box \
+            above to include all RCOS members from previous semesters.",
+        )
+    };
 
     // Build developers page template.
     let mut template = Template::new(TEMPLATE_PATH);
@@ -94,7 +120,9 @@ pub async fn developers_page(
         "data": api_data,
         "query": query,
         "identity": viewer,
-        "preserved_query_string": req.query_string()
+        "can_see_deactivated": can_see_deactivated,
+        "preserved_query_string": req.query_string(),
+        "empty_state": empty_state
     });
 
     return template.in_page(&req, "Developers").await;
@@ -0,0 +1,70 @@
+//! Account data export.
+//!
+//! Users can download everything Telescope stores about them as a single JSON file. The user
+//! is always resolved from their identity cookie, so there is no way to export anyone else's
+//! data.
+
+use crate::api::rcos::users::attendance::UserAttendance;
+use crate::api::rcos::users::profile::Profile;
+use crate::error::TelescopeError;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Download the viewer's account data as JSON.
+#[get("/account/export")]
+pub async fn export(auth: AuthenticationCookie) -> Result<HttpResponse, TelescopeError> {
+    // Get the viewer's user ID. The viewer is always the target of the export.
+    let user_id = auth.get_user_id_or_error().await?;
+
+    // Get the profile (with enrollments and hosted meetings) and attendance at the same time.
+    let (profile, attendance) = futures::try_join!(
+        Profile::get(user_id, Some(user_id), true),
+        UserAttendance::get(user_id)
+    )?;
+
+    // Make sure the user exists.
+    let target = profile.target.ok_or(TelescopeError::resource_not_found(
+        "User Not Found",
+        "Could not find your user record to export.",
+    ))?;
+
+    // Split the enrollments and hosted meetings out of the profile into their own sections.
+    let mut profile: Value = json!(target);
+    let enrollments: Value = profile["enrollments"].take();
+    let hosted_meetings: Value = profile["hosting"].take();
+    if let Some(object) = profile.as_object_mut() {
+        object.remove("enrollments");
+        object.remove("hosting");
+    }
+
+    // Build the export.
+    let exported_at: DateTime<Utc> = Utc::now();
+    let export = json!({
+        "exported_at": exported_at,
+        "profile": profile,
+        "enrollments": enrollments,
+        "hosted_meetings": hosted_meetings,
+        "attendance": attendance.meeting_attendances,
+    });
+
+    let body: Vec<u8> = serde_json::to_vec_pretty(&export)
+        .map_err(|e| TelescopeError::ise(format!("Could not serialize account export: {}", e)))?;
+
+    // Send it as a file download.
+    return Ok(HttpResponse::Ok()
+        .set_header(header::CONTENT_TYPE, "application/json")
+        .set_header(
+            header::CONTENT_DISPOSITION,
+            ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!(
+                    "telescope-export-{}.json",
+                    exported_at.format("%Y-%m-%d")
+                ))],
+            },
+        )
+        .body(body));
+}
@@ -5,9 +5,9 @@ use crate::api::rcos::users::discord_whois::DiscordWhoIs;
 use crate::error::TelescopeError;
 
 use crate::env::global_config;
+use crate::web::redirect::redirect_found;
 use crate::web::services::auth::identity::AuthenticationCookie;
 use actix_web::HttpResponse;
-use reqwest::header::LOCATION;
 use serenity::builder::EditMember;
 use serenity::model::prelude::RoleId;
 use serenity::utils;
@@ -95,12 +95,11 @@ pub async fn handle(auth: AuthenticationCookie) -> Result<HttpResponse, Telescop
         .ok_or(TelescopeError::ise("Could not get Verified role ID."))?;
 
     // Add user to Discord with verified role and nickname.
-    
+
     let nickname_copy = &nickname;
     discord
         .add_to_rcos_guild(Some(nickname_copy.to_string()), vec![verified_role])
         .await?;
-    
 
     // If user was already in the discord, they may not have the verified role, and the
     // previous call will do nothing. Make an additional call here to add the verified role
@@ -116,19 +115,20 @@ pub async fn handle(auth: AuthenticationCookie) -> Result<HttpResponse, Telescop
         .await
         .map_err(TelescopeError::serenity_error)?;
 
-
     // If user was already in the discord, they might not have a correctly formatted nickname
     // Therefore we need to add it manually
     let mut builder = EditMember::default();
     builder.nickname(nickname);
     let map = utils::hashmap_to_json_map(builder.0);
     global_discord_client()
-        .edit_member(global_config().discord_config.rcos_guild_id(), discord_user_id, &map)
+        .edit_member(
+            global_config().discord_config.rcos_guild_id(),
+            discord_user_id,
+            &map,
+        )
         .await
         .map_err(TelescopeError::serenity_error)?;
 
     // On success, redirect user back to their profile.
-    Ok(HttpResponse::Found()
-        .header(LOCATION, format!("/user/{}", user_id))
-        .finish())
+    Ok(redirect_found(format!("/user/{}", user_id)))
 }
@@ -3,9 +3,23 @@
 use crate::error::TelescopeError;
 use crate::templates::auth;
 use crate::templates::page::Page;
-use crate::web::services::auth::identity::Identity;
-use actix_web::http::header::LOCATION;
+use crate::web::csrf;
+use crate::web::redirect::{redirect_found, redirect_to};
+use crate::web::services::auth::identity::{AuthenticationCookie, Identity};
+use crate::web::sessions;
+use actix_web::web::Form;
 use actix_web::{HttpRequest, HttpResponse};
+use oauth2::CsrfToken;
+
+/// The name CSRF tokens of the form to log out everywhere are saved under.
+pub const LOGOUT_ALL_CSRF_NAME: &'static str = "logout_all";
+
+/// Form submitted to log out everywhere.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogoutAllForm {
+    /// The CSRF token from the session list page.
+    csrf_token: String,
+}
 
 #[get("/login")]
 /// Login page. Users go here and are presented options to login with a variety
@@ -21,5 +35,26 @@ pub async fn logout(identity: Identity) -> HttpResponse {
     // Forget the user's identity
     identity.forget();
     // Redirect the user to the homepage.
-    HttpResponse::Found().header(LOCATION, "/").finish()
+    redirect_found("/")
+}
+
+#[post("/logout-all")]
+/// Log the user out of every session, on every device, and redirect them to the homepage.
+/// This invalidates every identity cookie issued to them so far, including this one. The form
+/// is on the session list page, and must be submitted with that page's CSRF token.
+pub async fn logout_all(
+    req: HttpRequest,
+    identity: Identity,
+    auth: AuthenticationCookie,
+    Form(form): Form<LogoutAllForm>,
+) -> Result<HttpResponse, TelescopeError> {
+    csrf::verify(LOGOUT_ALL_CSRF_NAME, &req, CsrfToken::new(form.csrf_token))?;
+
+    let user_id = auth.get_user_id_or_error().await?;
+    sessions::log_out_everywhere(user_id).await?;
+    info!("User {} logged out everywhere.", user_id);
+
+    // Forget this browser's cookie now rather than on its next request.
+    identity.forget();
+    return Ok(redirect_to("/"));
 }
@@ -2,12 +2,14 @@
 
 use actix_web::web::ServiceConfig;
 
-mod delete;
+pub mod delete;
 pub mod developers;
+mod export;
 mod join_discord;
 mod login;
 pub mod profile;
 mod register;
+mod sessions;
 
 /// Register user related services.
 pub fn register(config: &mut ServiceConfig) {
@@ -22,6 +24,7 @@ pub fn register(config: &mut ServiceConfig) {
         // Login related services.
         .service(login::login_page)
         .service(login::logout)
+        .service(login::logout_all)
         // Registration related services
         .service(register::register_page)
         .service(register::finish_registration)
@@ -30,5 +33,10 @@ pub fn register(config: &mut ServiceConfig) {
         .service(join_discord::handle)
         // User Deletion
         .service(delete::confirm_delete)
-        .service(delete::profile_delete);
+        .service(delete::profile_delete)
+        // Account data export
+        .service(export::export)
+        // Active session list and revocation
+        .service(sessions::sessions_page)
+        .service(sessions::revoke_session);
 }
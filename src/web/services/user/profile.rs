@@ -1,21 +1,26 @@
 //! Profile services.
 
 use crate::api::discord::{self, global_discord_client};
-use crate::api::rcos::users::edit_profile::{EditProfileContext, SaveProfileEdits};
-use crate::api::rcos::users::profile::{
-    profile::{ProfileTarget, ResponseData},
-    Profile,
-};
+use crate::api::github::users::user_by_id::UserById;
+use crate::api::rcos::meetings::authorization_for::{AuthorizationFor, UserMeetingAuthorization};
+use crate::api::rcos::meetings::creation::create::parse_url;
+use crate::api::rcos::users::edit_profile::{EditProfileContext, ProfileFields, SaveProfileEdits};
+use crate::api::rcos::users::profile::{profile::ProfileTarget, Profile, ProfileData};
 use crate::api::rcos::users::UserRole;
 use crate::env::global_config;
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::tags::Tags;
 use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::redirect::redirect_to;
+use crate::web::services::admin::deactivate_user;
 use crate::web::services::auth::identity::{AuthenticationCookie, Identity};
 use actix_web::web::{Form, Path, ServiceConfig};
-use actix_web::{http::header::LOCATION, HttpRequest, HttpResponse};
+use actix_web::{HttpRequest, HttpResponse, Responder};
 use chrono::{Datelike, Local};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use oauth2::{AccessToken, CsrfToken};
 use serenity::model::guild::Member;
 use serenity::model::user::User;
 use std::collections::HashMap;
@@ -27,12 +32,23 @@ const TEMPLATE_NAME: &'static str = "user/profile";
 /// The path from the templates directory to the user settings form template.
 const SETTINGS_FORM: &'static str = "user/settings";
 
+/// The longest first, last, or preferred name, in characters.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The longest bio, in characters.
+const MAX_BIO_LENGTH: usize = 1000;
+
+/// The most external links a profile can have.
+const MAX_EXTERNAL_LINKS: usize = 5;
+
 /// Register services into actix app.
 pub fn register(config: &mut ServiceConfig) {
     config
         .service(profile)
         .service(settings)
-        .service(save_changes);
+        .service(edit_user)
+        .service(save_changes)
+        .service(save_user_changes);
 }
 
 /// User profile service. The target's user ID is in the path.
@@ -41,12 +57,53 @@ async fn profile(
     req: HttpRequest,
     identity: Identity,
     Path(id): Path<Uuid>,
-) -> Result<Page, TelescopeError> {
+) -> Result<HttpResponse, TelescopeError> {
     // Get the viewer's user ID.
     let viewer: Option<Uuid> = identity.get_user_id().await?;
 
+    // Coordinators and admins can see and manage deactivated users. They cannot manage
+    // themselves from here.
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(viewer).await?;
+    let can_manage: bool = authorization.is_coordinator_or_admin();
+
+    // The account management forms need a CSRF token bound to the user.
+    let manage_token: Option<CsrfToken> =
+        (can_manage && viewer != Some(id)).then(CsrfToken::new_random);
+
+    let page: Page = profile_page(
+        &req,
+        &identity,
+        id,
+        viewer,
+        can_manage,
+        manage_token.as_ref(),
+    )
+    .await?;
+    let mut response: HttpResponse = page.respond_to(&req).await?;
+    if let Some(token) = manage_token {
+        csrf::save(
+            deactivate_user::csrf_name(id).as_str(),
+            &req,
+            &mut response,
+            token,
+        )?;
+    }
+    return Ok(response);
+}
+
+/// Render the profile of user `id`. Deactivated users are only shown if the viewer
+/// `can_manage` users. If `manage_token` is given, the account management forms are shown
+/// with it.
+async fn profile_page(
+    req: &HttpRequest,
+    identity: &Identity,
+    id: Uuid,
+    viewer: Option<Uuid>,
+    can_manage: bool,
+    manage_token: Option<&CsrfToken>,
+) -> Result<Page, TelescopeError> {
     // Get the user's profile information (and viewer info) from the RCOS API.
-    let response: ResponseData = Profile::for_user(id, viewer).await?;
+    let response: ProfileData = Profile::get(id, viewer, can_manage).await?;
 
     // Throw an error if there is no user.
     if response.target.is_none() {
@@ -59,12 +116,42 @@ async fn profile(
     // Create the profile template to send back to the viewer.
     let mut template: Template = Template::new(TEMPLATE_NAME);
     template["data"] = json!(&response);
+    template["can_manage"] = json!(manage_token.is_some());
+    template["manage_csrf_token"] = json!(manage_token.map(|token| token.secret()));
 
     // Get the target user's info.
     let target_user: &ProfileTarget = response.target.as_ref().unwrap();
+    // Links are stored one per line.
+    template["links"] = json!(target_user
+        .external_links
+        .as_deref()
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .filter(|link| !link.is_empty())
+        .collect::<Vec<_>>());
     // And use it to make the page title
     let page_title: String = format!("{} {}", target_user.first_name, target_user.last_name);
 
+    // Get the target user's GitHub login and profile link, if they have linked GitHub. This
+    // is done before the Discord info, which can return early.
+    if let Some(github) = target_user.github.first() {
+        // The viewer's GitHub token is used if there is no GitHub App to send the query with.
+        let viewer_token: Option<AccessToken> = identity
+            .identity()
+            .await
+            .and_then(|cookie| cookie.get_github().map(|gh| gh.access_token.clone()));
+
+        match UserById::get(github.account_id.clone(), viewer_token.as_ref()).await {
+            Ok(Some(github_profile)) => template["github"]["target"] = json!(github_profile),
+            Ok(None) => template["github"]["target"] = json!({"errored": true}),
+            Err(e) => {
+                debug!("Could not look up GitHub user {}: {}", github.account_id, e);
+                template["github"]["target"] = json!({"errored": true});
+            }
+        }
+    }
+
     // Get the target user's discord info.
     let target_discord_id: Option<&str> = target_user
         .discord
@@ -88,7 +175,7 @@ async fn profile(
                 // Return early if there was an error.
                 // Otherwise we can go forward and check for the user's membership in the RCOS
                 // Discord server.
-                return template.in_page(&req, page_title).await;
+                return template.in_page(req, page_title).await;
             }
 
             // User returned successfully.
@@ -144,7 +231,7 @@ async fn profile(
     }
 
     // Render the profile template and send to user.
-    let mut page = template.in_page(&req, page_title.clone()).await?;
+    let mut page = template.in_page(req, page_title.clone()).await?;
 
     let mut tags = Tags::default();
     tags.title = page_title.clone();
@@ -189,39 +276,51 @@ fn make_settings_form() -> Template {
 
     // The max entry year should always be the current year.
     form.fields = json!({
-        "max_entry_year": Local::today().year()
+        "max_entry_year": Local::today().year(),
+        // Suggestions for the timezone preference.
+        "timezones": TZ_VARIANTS.iter().map(|tz| tz.name()).collect::<Vec<&str>>()
     });
 
     return form;
 }
 
-/// Get the viewer's user ID and make a profile edit form for them.
-async fn get_context_and_make_form(
-    auth: &AuthenticationCookie,
-) -> Result<Template, TelescopeError> {
-    // Get viewer's user ID. You have to be authenticated to edit your own profile.
-    let viewer: Uuid = auth.get_user_id_or_error().await?;
-    // Get the context for the edit form.
-    let context = EditProfileContext::get(viewer).await?;
-    // Ensure that the context exists.
-    if context.is_none() {
-        // Use an ISE since we should be able to get an edit context as long as there is an
-        // authenticated user.
-        return Err(TelescopeError::ise(format!(
-            "Could not get edit context for user ID {}.",
-            viewer
-        )));
+/// Check that the viewer can edit a user's profile. Users can edit their own profile, and
+/// coordinators and admins can edit anyone's.
+async fn check_can_edit(viewer: Uuid, target: Uuid) -> Result<(), TelescopeError> {
+    if viewer == target {
+        return Ok(());
     }
 
-    // Unwrap the context.
-    let context = context.unwrap();
+    let authorization: UserMeetingAuthorization = AuthorizationFor::get(Some(viewer)).await?;
+    if !authorization.is_coordinator_or_admin() {
+        return Err(TelescopeError::forbidden("this profile"));
+    }
+    return Ok(());
+}
+
+/// Check that the viewer can edit a user's profile and make the profile edit form for it.
+async fn get_context_and_make_form(viewer: Uuid, target: Uuid) -> Result<Template, TelescopeError> {
+    check_can_edit(viewer, target).await?;
+
+    // Get the context for the edit form.
+    let context =
+        EditProfileContext::get(target)
+            .await?
+            .ok_or(TelescopeError::resource_not_found(
+                "User Not Found",
+                "Could not find a user by this user ID.",
+            ))?;
 
     // Create the form to edit the profile.
     let mut form: Template = make_settings_form();
     // Add the context to the form.
     form["context"] = json!(&context);
+    // Links are stored one per line, which is also how they are edited.
+    form["context"]["external_links"] = json!(context.external_links.as_deref().unwrap_or(""));
     // Add user id to the form for the cancel button
-    form["user_id"] = json!(&viewer);
+    form["user_id"] = json!(&target);
+    // Account deletion and data exports are only offered on the user's own profile.
+    form["editing_self"] = json!(viewer == target);
 
     // Add the list of roles (and whether the current role can switch to them).
     let role_list = UserRole::ALL_ROLES
@@ -242,10 +341,25 @@ async fn get_context_and_make_form(
     return Ok(form);
 }
 
-/// User settings form.
+/// Form to edit the viewer's own profile.
 #[get("/edit_profile")]
 async fn settings(req: HttpRequest, auth: AuthenticationCookie) -> Result<Page, TelescopeError> {
-    get_context_and_make_form(&auth)
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    get_context_and_make_form(viewer, viewer)
+        .await?
+        .in_page(&req, "Edit Profile")
+        .await
+}
+
+/// Form to edit a user's profile by ID. Only the user themselves and coordinators can use it.
+#[get("/user/{id}/edit")]
+async fn edit_user(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(target): Path<Uuid>,
+) -> Result<Page, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    get_context_and_make_form(viewer, target)
         .await?
         .in_page(&req, "Edit Profile")
         .await
@@ -258,39 +372,114 @@ struct ProfileEdits {
     last_name: String,
     role: UserRole,
 
+    /// The first name to show instead of the legal one. Empty for none.
+    #[serde(default)]
+    preferred_name: String,
+
+    /// A short biography. Empty for none.
+    #[serde(default)]
+    bio: String,
+
+    /// Links to show on the profile, one per line.
+    #[serde(default)]
+    external_links: String,
+
     /// Entry year for RPI students.
     #[serde(default)]
     cohort: String,
+
+    /// The IANA name of the user's preferred timezone (e.g. `America/New_York`). Empty to use
+    /// the server's timezone.
+    #[serde(default)]
+    timezone: String,
+}
+
+/// Check the length of a text field (in unicode scalar values, after trimming) and set an
+/// issue on the form if it is too long.
+fn check_length(form: &mut Template, field: &str, value: &str, max: usize) {
+    if value.trim().chars().count() > max {
+        form["issues"][field] = json!(format!("Must be at most {} characters.", max));
+    }
 }
 
-/// Submission endpoint for the user settings form.
+/// Clean up the external links field with [`parse_url`]. Blank lines are ignored and
+/// duplicates are removed. Problems are added to the form's issues.
+fn check_external_links(form: &mut Template, input: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    let mut invalid: Vec<String> = Vec::new();
+
+    for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match parse_url(line) {
+            Ok(link) if !links.contains(&link) => links.push(link),
+            Ok(_) => {}
+            Err(_) => invalid.push(line.to_string()),
+        }
+    }
+
+    if !invalid.is_empty() {
+        form["issues"]["external_links"] = json!(format!(
+            "These are not valid web addresses: {}",
+            invalid.join(", ")
+        ));
+    } else if links.len() > MAX_EXTERNAL_LINKS {
+        form["issues"]["external_links"] =
+            json!(format!("Add at most {} links.", MAX_EXTERNAL_LINKS));
+    }
+
+    return links;
+}
+
+/// Submission endpoint for the viewer's own profile edit form.
 #[post("/edit_profile")]
 async fn save_changes(
     req: HttpRequest,
     auth: AuthenticationCookie,
-    Form(ProfileEdits {
+    Form(edits): Form<ProfileEdits>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    return save_edits(&req, viewer, viewer, edits).await;
+}
+
+/// Submission endpoint for the profile edit form of a user by ID.
+#[post("/user/{id}/edit")]
+async fn save_user_changes(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+    Path(target): Path<Uuid>,
+    Form(edits): Form<ProfileEdits>,
+) -> Result<HttpResponse, TelescopeError> {
+    let viewer: Uuid = auth.get_user_id_or_error().await?;
+    return save_edits(&req, viewer, target, edits).await;
+}
+
+/// Validate and save profile edits made by `viewer` to the profile of `target`.
+async fn save_edits(
+    req: &HttpRequest,
+    viewer: Uuid,
+    target: Uuid,
+    ProfileEdits {
         first_name,
         last_name,
         role,
+        preferred_name,
+        bio,
+        external_links,
         cohort,
-    }): Form<ProfileEdits>,
+        timezone,
+    }: ProfileEdits,
 ) -> Result<HttpResponse, TelescopeError> {
-    // Get authenticated user ID. This API call gets duplicated in the context creation unfortunately.
-    let user_id = auth.get_user_id_or_error().await?;
-
-    // Pass most of the handling here to the GET handler. This will get the context and make
-    // and fill the form.
-    let mut form: Template = get_context_and_make_form(&auth).await?;
+    // Pass most of the handling here to the GET handler. This checks that the viewer can edit
+    // the profile, and gets the context and makes and fills the form.
+    let mut form: Template = get_context_and_make_form(viewer, target).await?;
 
     // Convert the cohort to a number or default to no cohort input. This should be checked client side.
     let cohort: Option<i64> = cohort.parse::<i64>().ok();
 
     // Check if user is allowed to set their cohort and if it is within the valid range.
-    if cohort.is_some() {
-        if auth.get_rcs_id().await.unwrap().is_none() {
+    if let Some(cohort_int) = cohort {
+        if form["context"]["rcs_id"][0]["account_id"].is_null() {
             form["issues"]["cohort"] = json!("Please link RPI CAS before setting this.");
         }
-        let cohort_int = cohort.unwrap();
         let year: i64 = Local::today().year() as i64;
         if cohort_int < 1824 || cohort_int > year {
             form["issues"]["cohort"] = json!(format!("Year must be between 1824 and {}", year));
@@ -315,8 +504,19 @@ async fn save_changes(
     // Fill the form with the submitted info.
     form["context"]["first_name"] = json!(&first_name);
     form["context"]["last_name"] = json!(&last_name);
+    form["context"]["preferred_name"] = json!(&preferred_name);
+    form["context"]["bio"] = json!(&bio);
+    form["context"]["external_links"] = json!(&external_links);
     form["context"]["cohort"] = json!(&cohort);
     form["context"]["role"] = json!(role);
+    form["context"]["timezone"] = json!(&timezone);
+
+    // Check the timezone is one we know. Empty means no preference.
+    let timezone: String = timezone.trim().to_string();
+    if !timezone.is_empty() && timezone.parse::<Tz>().is_err() {
+        form["issues"]["timezone"] =
+            json!("Unknown timezone. Use a name like America/New_York, or leave this empty.");
+    }
 
     // Error if first or last name is empty.
     if first_name.trim().is_empty() {
@@ -327,20 +527,53 @@ async fn save_changes(
         form["issues"]["last_name"] = json!("Cannot be empty.");
     }
 
+    // Check the lengths of the free text fields.
+    check_length(
+        &mut form,
+        "first_name",
+        first_name.as_str(),
+        MAX_NAME_LENGTH,
+    );
+    check_length(&mut form, "last_name", last_name.as_str(), MAX_NAME_LENGTH);
+    check_length(
+        &mut form,
+        "preferred_name",
+        preferred_name.as_str(),
+        MAX_NAME_LENGTH,
+    );
+    check_length(&mut form, "bio", bio.as_str(), MAX_BIO_LENGTH);
+
+    // And the links.
+    let external_links: Vec<String> = check_external_links(&mut form, external_links.as_str());
+
     if form["issues"] != json!(null) {
-        let page = form.in_page(&req, "Edit Profile").await?;
+        let page = form.in_page(req, "Edit Profile").await?;
         return Err(TelescopeError::InvalidForm(page));
     }
 
     // Execute GraphQL mutation to save changes.
-    let user_id = SaveProfileEdits::execute(user_id, first_name, last_name, cohort, role)
+    let fields = ProfileFields {
+        first_name: first_name.trim().to_string(),
+        last_name: last_name.trim().to_string(),
+        preferred_name: blank_to_none(preferred_name),
+        bio: blank_to_none(bio),
+        external_links,
+        cohort,
+        role,
+        timezone,
+    };
+    let user_id = SaveProfileEdits::execute(target, fields)
         .await?
         .ok_or(TelescopeError::ise(
             "Could not save changes -- user not found.",
         ))?;
 
     // On success, redirect to user's profile.
-    return Ok(HttpResponse::Found()
-        .header(LOCATION, format!("/user/{}", user_id))
-        .finish());
+    return Ok(redirect_to(format!("/user/{}", user_id)));
+}
+
+/// Trim a text field. Empty and whitespace-only values become `None`.
+fn blank_to_none(value: String) -> Option<String> {
+    let value: &str = value.trim();
+    return (!value.is_empty()).then(|| value.to_string());
 }
@@ -3,9 +3,9 @@ use crate::api::rcos::users::{UserAccountType, UserRole};
 use crate::error::TelescopeError;
 use crate::templates::page::Page;
 use crate::templates::{auth, Template};
+use crate::web::redirect::{redirect_found, redirect_to};
 use crate::web::services::auth::identity::{AuthenticationCookie, RootIdentity};
 use crate::web::services::auth::rpi_cas::RpiCasIdentity;
-use actix_web::http::header::LOCATION;
 use actix_web::web::Form;
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use uuid::Uuid;
@@ -40,7 +40,7 @@ async fn empty_registration_form(id: &RootIdentity) -> Result<Template, Telescop
         RootIdentity::Discord(d) => {
             template.fields = d.get_authenticated_user().await.map(|discord_user| {
                 json!({
-                    "icon": UserAccountType::Discord,
+                    "icon": UserAccountType::Discord.icon(),
                     "info": {
                         "username": discord_user.tag(),
                         "avatar_url": discord_user.face(),
@@ -57,7 +57,7 @@ async fn empty_registration_form(id: &RootIdentity) -> Result<Template, Telescop
                 // Convert the info to a JSON object as necessary
                 .map(|gh_user| {
                     json!({
-                        "icon": UserAccountType::GitHub,
+                        "icon": UserAccountType::GitHub.icon(),
                         "info": {
                             "username": gh_user.login,
                             "avatar_url": gh_user.avatar_url,
@@ -136,9 +136,7 @@ pub async fn finish_registration(
 ) -> Result<HttpResponse, actix_web::Error> {
     // If this authenticated identity is already linked to an account
     if let Some(user_id) = identity_cookie.get_user_id().await? {
-        return Ok(HttpResponse::Found()
-            .header(LOCATION, format!("/user/{}", user_id))
-            .finish());
+        return Ok(redirect_found(format!("/user/{}", user_id)));
     } else {
         // Otherwise create a form for the authenticated the user's cookie.
         // And convert it to an HttpResponse
@@ -220,7 +218,5 @@ pub async fn submit_registration(
     ))?;
 
     // Redirect the user to the account we created for them
-    Ok(HttpResponse::Found()
-        .header(LOCATION, format!("/user/{}", created_user_id))
-        .finish())
+    Ok(redirect_to(format!("/user/{}", created_user_id)))
 }
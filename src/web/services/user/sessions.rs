@@ -0,0 +1,76 @@
+//! Active login session list and revocation.
+//!
+//! Users can see the sessions signed in to their account and log any of them out. See
+//! [`crate::web::sessions`] for how sessions are tracked.
+
+use crate::error::TelescopeError;
+use crate::templates::page::Page;
+use crate::templates::Template;
+use crate::web::csrf;
+use crate::web::redirect::redirect_to;
+use crate::web::services::auth::identity::AuthenticationCookie;
+use crate::web::sessions::{self, SessionInfo};
+use actix_web::web::Path;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use oauth2::CsrfToken;
+use uuid::Uuid;
+
+/// The path of the session list page.
+const SESSIONS_PATH: &'static str = "/account/sessions";
+
+/// List the viewer's active sessions. The page includes the CSRF token for the form to log
+/// out everywhere.
+#[get("/account/sessions")]
+pub async fn sessions_page(
+    req: HttpRequest,
+    auth: AuthenticationCookie,
+) -> Result<HttpResponse, TelescopeError> {
+    // Get the viewer's user ID. This also links the current session to them.
+    let user_id: Uuid = auth.get_user_id_or_error().await?;
+
+    // Make the template.
+    let token: CsrfToken = CsrfToken::new_random();
+    let mut template = Template::new("user/sessions");
+    template.fields = json!({
+        "sessions": sessions::for_user(user_id),
+        "current_session": auth.session_id,
+        "csrf_token": token.secret(),
+    });
+
+    // Render the page and save its CSRF token for this browser.
+    let mut response: HttpResponse = Page::new(&req, "Active Sessions", template)
+        .await?
+        .respond_to(&req)
+        .await?;
+    csrf::save(
+        super::login::LOGOUT_ALL_CSRF_NAME,
+        &req,
+        &mut response,
+        token,
+    )?;
+    return Ok(response);
+}
+
+/// Revoke one of the viewer's sessions.
+#[post("/account/sessions/{session_id}/revoke")]
+pub async fn revoke_session(
+    auth: AuthenticationCookie,
+    Path(session_id): Path<Uuid>,
+) -> Result<HttpResponse, TelescopeError> {
+    // Get the viewer's user ID.
+    let user_id: Uuid = auth.get_user_id_or_error().await?;
+
+    // Only the session's owner may revoke it. Sessions belonging to other users are
+    // reported the same way as missing ones.
+    let session: SessionInfo = sessions::get(session_id)
+        .filter(|session| session.user_id == Some(user_id))
+        .ok_or(TelescopeError::resource_not_found(
+            "Session Not Found",
+            "Could not find an active session with that ID on your account.",
+        ))?;
+
+    // Revoke it and go back to the session list. Revoking the current session logs the
+    // viewer out on their next request.
+    sessions::revoke(session.id)?;
+    return Ok(redirect_to(SESSIONS_PATH));
+}
@@ -0,0 +1,141 @@
+//! Webhook endpoints for outside services.
+//!
+//! Every request is checked with [`signatures`] against the raw body before anything in it is
+//! used, and requests that were already accepted are refused. An endpoint is disabled (and
+//! responds with not found) unless the secret or key it checks signatures with is configured.
+
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use crate::web::integrations::signatures::{self, ReplayGuard};
+use actix_web::web::{self as aweb, Bytes, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::Value;
+
+/// The header GitHub puts the unique ID of a webhook delivery in.
+const GITHUB_DELIVERY_HEADER: &'static str = "X-GitHub-Delivery";
+
+/// The header GitHub puts the event type of a webhook delivery in.
+const GITHUB_EVENT_HEADER: &'static str = "X-GitHub-Event";
+
+/// The type of the interactions Discord sends to check the endpoint.
+const DISCORD_PING: u64 = 1;
+
+lazy_static! {
+    /// GitHub deliveries accepted in the last day. GitHub does not sign a timestamp, so
+    /// deliveries are remembered for longer than Discord interactions.
+    static ref GITHUB_DELIVERIES: ReplayGuard = ReplayGuard::new(Duration::days(1));
+
+    /// Discord interactions accepted recently. Older ones are refused by their timestamp.
+    static ref DISCORD_INTERACTIONS: ReplayGuard = ReplayGuard::new(Duration::minutes(10));
+}
+
+/// Register webhook services.
+pub fn register(config: &mut ServiceConfig) {
+    config.service(
+        aweb::scope("/webhooks")
+            .route("/github", aweb::post().to(github_webhook))
+            .route("/discord", aweb::post().to(discord_interaction)),
+    );
+}
+
+/// Get a header as a string, or an empty string if it is missing or not valid text.
+fn header<'a>(req: &'a HttpRequest, name: &str) -> &'a str {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+}
+
+/// The error for a webhook endpoint that is not configured.
+fn not_configured() -> TelescopeError {
+    TelescopeError::resource_not_found(
+        "Webhook Not Configured",
+        "This webhook is not enabled on this Telescope instance.",
+    )
+}
+
+/// Receive a GitHub App webhook delivery. Deliveries are acknowledged and logged; Telescope
+/// does not act on any GitHub events yet.
+async fn github_webhook(req: HttpRequest, body: Bytes) -> Result<HttpResponse, TelescopeError> {
+    let secret: String = global_config()
+        .github_app
+        .as_ref()
+        .and_then(|app| app.webhook_secret.clone())
+        .ok_or_else(not_configured)?;
+
+    // Check the signature before anything else.
+    let signature: &str = header(&req, signatures::GITHUB_SIGNATURE_HEADER);
+    if !signatures::verify_github(secret.as_bytes(), body.as_ref(), signature) {
+        warn!("Refused a GitHub webhook delivery with an invalid signature.");
+        return Err(TelescopeError::NotAuthenticated);
+    }
+
+    // Refuse deliveries that were already accepted.
+    let delivery: &str = header(&req, GITHUB_DELIVERY_HEADER);
+    let replay_key: &str = if delivery.is_empty() {
+        signature
+    } else {
+        delivery
+    };
+    if !GITHUB_DELIVERIES.first_use(replay_key, Utc::now()) {
+        warn!("Refused a replayed GitHub webhook delivery {:?}.", delivery);
+        return Err(TelescopeError::NotAuthenticated);
+    }
+
+    info!(
+        "Received GitHub webhook delivery {:?} ({:?} event).",
+        delivery,
+        header(&req, GITHUB_EVENT_HEADER)
+    );
+    return Ok(HttpResponse::NoContent().finish());
+}
+
+/// Receive an interaction from Discord. Discord checks the endpoint with ping interactions,
+/// which are answered here. Commands are handled by the bot over the gateway, so any other
+/// interaction is refused.
+async fn discord_interaction(
+    req: HttpRequest,
+    body: Bytes,
+) -> Result<HttpResponse, TelescopeError> {
+    let public_key: String = global_config()
+        .discord_config
+        .public_key
+        .clone()
+        .ok_or_else(not_configured)?;
+
+    // Check the signature and its timestamp before anything else. Discord requires a 401
+    // response to requests with invalid signatures.
+    let signature: &str = header(&req, signatures::DISCORD_SIGNATURE_HEADER);
+    let timestamp: &str = header(&req, signatures::DISCORD_TIMESTAMP_HEADER);
+    let now = Utc::now();
+    if !signatures::verify_discord(public_key.as_str(), timestamp, body.as_ref(), signature)
+        || !signatures::is_fresh_timestamp(timestamp, now)
+    {
+        warn!("Refused a Discord interaction with an invalid or expired signature.");
+        return Err(TelescopeError::NotAuthenticated);
+    }
+
+    // Refuse interactions that were already accepted.
+    if !DISCORD_INTERACTIONS.first_use(signature, now) {
+        warn!("Refused a replayed Discord interaction.");
+        return Err(TelescopeError::NotAuthenticated);
+    }
+
+    let interaction: Value =
+        serde_json::from_slice(body.as_ref()).map_err(|_| TelescopeError::BadRequest {
+            header: "Malformed Interaction".into(),
+            message: "The interaction is not valid JSON.".into(),
+            show_status_code: true,
+        })?;
+
+    if interaction["type"].as_u64() == Some(DISCORD_PING) {
+        return Ok(HttpResponse::Ok().json(json!({ "type": DISCORD_PING })));
+    }
+
+    return Err(TelescopeError::BadRequest {
+        header: "Unsupported Interaction".into(),
+        message: "Telescope handles Discord commands through its bot, not this endpoint.".into(),
+        show_status_code: true,
+    });
+}
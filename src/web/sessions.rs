@@ -0,0 +1,345 @@
+//! Server side registry of login sessions.
+//!
+//! Identity cookies are otherwise stateless, so each one carries a random session ID that is
+//! tracked here. This lets users see where they are signed in and revoke sessions. Session
+//! metadata (last activity, IP, user agent) is kept in memory only and is rebuilt as sessions
+//! make requests after a restart. Revoked session IDs are saved to a file
+//! (`revoked_sessions_file` in the config) so that a revoked cookie stays revoked across
+//! restarts.
+//!
+//! Each user also has a session version, stored in the RCOS database. Identity cookies record
+//! the version they were issued at, and a user can log out everywhere by incrementing it. This
+//! works for every cookie, including ones this registry has never seen (for example after a
+//! restart).
+
+use crate::api::rcos::users::session_version::{BumpSessionVersion, SessionVersion};
+use crate::env::global_config;
+use crate::error::TelescopeError;
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use uuid::Uuid;
+
+/// Sessions with no activity for this many days are dropped from the registry. This matches
+/// the identity cookie lifetime.
+const INACTIVE_SESSION_DAYS: i64 = 1;
+
+/// How many days a revoked session ID is remembered. Identity cookies are re-issued on every
+/// request, so this is much longer than the cookie lifetime.
+const REVOKED_RETENTION_DAYS: i64 = 90;
+
+/// The longest user agent string that is stored.
+const MAX_USER_AGENT_LENGTH: usize = 300;
+
+/// How many seconds a user's session version is cached before it is read from the RCOS
+/// database again.
+const SESSION_VERSION_CACHE_SECONDS: i64 = 60;
+
+lazy_static! {
+    /// Metadata of the sessions that have been active recently, keyed by session ID.
+    static ref ACTIVE_SESSIONS: DashMap<Uuid, SessionInfo> = DashMap::new();
+
+    /// Revoked session IDs and when they were revoked.
+    static ref REVOKED_SESSIONS: DashMap<Uuid, DateTime<Utc>> = load_revoked();
+
+    /// Each user's session version and when it was last read from or written to the RCOS
+    /// database. Other Telescope instances (or a coordinator editing the database) can change
+    /// a version, so entries are only used for [`SESSION_VERSION_CACHE_SECONDS`].
+    static ref SESSION_VERSIONS: DashMap<Uuid, (i64, DateTime<Utc>)> = DashMap::new();
+}
+
+/// Information about the client making a request, recorded with its session.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    /// The client's IP address, if it could be determined.
+    pub ip: Option<String>,
+    /// The client's user agent string, if it sent one.
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    /// Get the client info of a request.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            ip: req
+                .connection_info()
+                .realip_remote_addr()
+                .map(str::to_string),
+            user_agent: req
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+        }
+    }
+}
+
+/// A login session.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    /// The random ID of the session, stored in its identity cookie.
+    pub id: Uuid,
+    /// The RCOS user signed in with this session, once it is known.
+    pub user_id: Option<Uuid>,
+    /// When this session was first seen (since the last restart).
+    pub first_seen: DateTime<Utc>,
+    /// When this session last made a request.
+    pub last_active: DateTime<Utc>,
+    /// The IP address of the last request.
+    pub ip: Option<String>,
+    /// The user agent of the last request.
+    pub user_agent: Option<String>,
+}
+
+/// Create a new random session ID.
+pub fn new_session_id() -> Uuid {
+    Uuid::new_v4()
+}
+
+/// Has a session been revoked?
+pub fn is_revoked(session_id: Uuid) -> bool {
+    REVOKED_SESSIONS.contains_key(&session_id)
+}
+
+/// Record activity on a session, adding it to the registry if it is not there yet.
+pub fn touch(session_id: Uuid, client: &ClientInfo) {
+    let now: DateTime<Utc> = Utc::now();
+    ACTIVE_SESSIONS
+        .entry(session_id)
+        .and_modify(|session| {
+            session.last_active = now;
+            session.ip = client.ip.clone();
+            session.user_agent = client.user_agent.clone();
+        })
+        .or_insert_with(|| SessionInfo {
+            id: session_id,
+            user_id: None,
+            first_seen: now,
+            last_active: now,
+            ip: client.ip.clone(),
+            user_agent: client.user_agent.clone(),
+        });
+}
+
+/// Record which user a session is signed in as.
+pub fn set_user(session_id: Uuid, user_id: Uuid) {
+    if let Some(mut session) = ACTIVE_SESSIONS.get_mut(&session_id) {
+        session.user_id = Some(user_id);
+    }
+}
+
+/// Get a session from the registry.
+pub fn get(session_id: Uuid) -> Option<SessionInfo> {
+    ACTIVE_SESSIONS
+        .get(&session_id)
+        .map(|session| session.value().clone())
+}
+
+/// Get the recently active sessions of a user, most recently active first. Sessions that
+/// have been inactive longer than the cookie lifetime are dropped first.
+pub fn for_user(user_id: Uuid) -> Vec<SessionInfo> {
+    // Drop stale sessions.
+    let cutoff: DateTime<Utc> = Utc::now() - Duration::days(INACTIVE_SESSION_DAYS);
+    ACTIVE_SESSIONS.retain(|_, session| session.last_active > cutoff);
+
+    let mut sessions: Vec<SessionInfo> = ACTIVE_SESSIONS
+        .iter()
+        .filter(|session| session.user_id == Some(user_id))
+        .map(|session| session.value().clone())
+        .collect();
+
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_active));
+    return sessions;
+}
+
+/// Revoke a session. Its identity cookie will be rejected from now on.
+pub fn revoke(session_id: Uuid) -> Result<(), TelescopeError> {
+    ACTIVE_SESSIONS.remove(&session_id);
+    REVOKED_SESSIONS.insert(session_id, Utc::now());
+
+    // Forget old revocations before saving.
+    let cutoff: DateTime<Utc> = Utc::now() - Duration::days(REVOKED_RETENTION_DAYS);
+    REVOKED_SESSIONS.retain(|_, revoked_at| *revoked_at > cutoff);
+    return save_revoked();
+}
+
+/// Get a user's current session version. Identity cookies issued at a lower version are
+/// rejected. Users that do not exist are at version 0.
+///
+/// If the RCOS database does not have session versions yet (the `users.session_version`
+/// migration has not been applied), the query fails with a GraphQL error. This is logged and
+/// every user is treated as being at version 0, so logins and existing cookies keep working
+/// until the migration is applied.
+pub async fn session_version(user_id: Uuid) -> Result<i64, TelescopeError> {
+    let now: DateTime<Utc> = Utc::now();
+    if let Some(entry) = SESSION_VERSIONS.get(&user_id) {
+        let (version, read_at) = *entry;
+        if now - read_at < Duration::seconds(SESSION_VERSION_CACHE_SECONDS) {
+            return Ok(version);
+        }
+    }
+
+    let version: i64 = match SessionVersion::get(user_id).await {
+        Ok(version) => version.unwrap_or(0),
+        Err(e @ TelescopeError::GraphQLError { .. })
+        | Err(e @ TelescopeError::SchemaMismatch { .. }) => {
+            warn!(
+                "Could not read session version (is the users.session_version migration \
+                applied?). Using version 0. Error: {}",
+                e
+            );
+            0
+        }
+        Err(e) => return Err(e),
+    };
+    SESSION_VERSIONS.insert(user_id, (version, now));
+    return Ok(version);
+}
+
+/// Is an identity cookie issued at `cookie_version` still valid for a user whose session
+/// version is now `user_version`? Cookies issued before the user last logged out everywhere
+/// are not.
+pub fn is_current_version(cookie_version: i64, user_version: i64) -> bool {
+    cookie_version >= user_version
+}
+
+/// Log a user out of every session by incrementing their session version. Their sessions are
+/// also dropped from the registry. Return the new version.
+pub async fn log_out_everywhere(user_id: Uuid) -> Result<i64, TelescopeError> {
+    let version: i64 = BumpSessionVersion::execute(user_id)
+        .await?
+        .ok_or(TelescopeError::AccountNotFound)?;
+    SESSION_VERSIONS.insert(user_id, (version, Utc::now()));
+    ACTIVE_SESSIONS.retain(|_, session| session.user_id != Some(user_id));
+    return Ok(version);
+}
+
+/// Load the revoked session IDs from the revoked sessions file. If the file cannot be read,
+/// log an error and start with no revoked sessions.
+fn load_revoked() -> DashMap<Uuid, DateTime<Utc>> {
+    let config = global_config();
+    let path: &str = config.revoked_sessions_file.as_str();
+    match std::fs::read(path) {
+        // Parse the saved list.
+        Ok(bytes) => serde_json::from_slice::<HashMap<Uuid, DateTime<Utc>>>(bytes.as_slice())
+            .map(|revoked| revoked.into_iter().collect())
+            .unwrap_or_else(|e| {
+                error!("Could not parse revoked sessions file {}: {}", path, e);
+                DashMap::new()
+            }),
+
+        // No sessions have been revoked.
+        Err(e) if e.kind() == ErrorKind::NotFound => DashMap::new(),
+
+        // Could not read the file.
+        Err(e) => {
+            error!("Could not read revoked sessions file {}: {}", path, e);
+            DashMap::new()
+        }
+    }
+}
+
+/// Save the revoked session IDs to the revoked sessions file.
+fn save_revoked() -> Result<(), TelescopeError> {
+    let config = global_config();
+    let path: &str = config.revoked_sessions_file.as_str();
+    let revoked: HashMap<Uuid, DateTime<Utc>> = REVOKED_SESSIONS
+        .iter()
+        .map(|entry| (*entry.key(), *entry.value()))
+        .collect();
+    let bytes: Vec<u8> = serde_json::to_vec(&revoked)
+        .map_err(|e| TelescopeError::ise(format!("Could not serialize revoked sessions: {}", e)))?;
+
+    return std::fs::write(path, bytes).map_err(|e| {
+        TelescopeError::ise(format!(
+            "Could not save revoked sessions file {}: {}",
+            path, e
+        ))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rcos::mock::MockRcosApi;
+
+    /// A cookie that was valid stops being valid once the user logs out everywhere.
+    #[actix_rt::test]
+    async fn bumped_version_invalidates_cookie() {
+        let api = MockRcosApi::start();
+        let user_id: Uuid = Uuid::new_v4();
+        api.stub(
+            "SessionVersion",
+            json!({ "users_by_pk": { "session_version": 2 } }),
+        );
+        api.stub(
+            "BumpSessionVersion",
+            json!({ "update_users_by_pk": { "session_version": 3 } }),
+        );
+
+        // A cookie issued at the current version is valid.
+        let cookie_version: i64 = session_version(user_id).await.unwrap();
+        assert!(is_current_version(cookie_version, cookie_version));
+
+        // After logging out everywhere it is not, without reading the version again.
+        assert_eq!(log_out_everywhere(user_id).await.unwrap(), 3);
+        let user_version: i64 = session_version(user_id).await.unwrap();
+        assert_eq!(user_version, 3);
+        assert!(!is_current_version(cookie_version, user_version));
+        api.assert_called("SessionVersion", 1);
+
+        // Cookies issued after are valid again.
+        assert!(is_current_version(user_version, user_version));
+    }
+
+    /// Logging out everywhere drops the user's sessions from the registry.
+    #[actix_rt::test]
+    async fn log_out_everywhere_drops_sessions() {
+        let api = MockRcosApi::start();
+        let user_id: Uuid = Uuid::new_v4();
+        api.stub(
+            "BumpSessionVersion",
+            json!({ "update_users_by_pk": { "session_version": 1 } }),
+        );
+
+        let session_id: Uuid = new_session_id();
+        touch(session_id, &ClientInfo::default());
+        set_user(session_id, user_id);
+        assert_eq!(for_user(user_id).len(), 1);
+
+        log_out_everywhere(user_id).await.unwrap();
+        assert!(for_user(user_id).is_empty());
+    }
+
+    /// Session versions are read from the database again once the cached one expires.
+    #[actix_rt::test]
+    async fn cached_version_expires() {
+        let api = MockRcosApi::start();
+        let user_id: Uuid = Uuid::new_v4();
+        api.stub(
+            "SessionVersion",
+            json!({ "users_by_pk": { "session_version": 5 } }),
+        );
+
+        let stale: DateTime<Utc> =
+            Utc::now() - Duration::seconds(SESSION_VERSION_CACHE_SECONDS + 1);
+        SESSION_VERSIONS.insert(user_id, (4, stale));
+        assert_eq!(session_version(user_id).await.unwrap(), 5);
+        api.assert_called("SessionVersion", 1);
+    }
+
+    /// If the database does not have session versions yet, every user is at version 0.
+    #[actix_rt::test]
+    async fn missing_schema_is_version_zero() {
+        let api = MockRcosApi::start();
+        api.stub_error(
+            "SessionVersion",
+            "field \"session_version\" not found in type: 'users'",
+        );
+
+        assert_eq!(session_version(Uuid::new_v4()).await.unwrap(), 0);
+    }
+}
@@ -18,5 +18,61 @@ $(document).ready(function () {
         });
     });
 
+    // Fill in the device time on forms that report it, so the server can detect clock skew.
+    $(".client-timestamp").each(function () {
+        let timestamp_input = $(this);
+        timestamp_input.parents("form:first").submit(function () {
+            timestamp_input.val(Date.now());
+        });
+    });
+
+    // Periodically save forms that support autosave, so in-progress edits survive a reload.
+    $("form[data-autosave-url]").each(function () {
+        let form = $(this);
+        let changed = false;
+        form.on("input change", function () {
+            changed = true;
+        });
+        // Stop saving once the form is submitted.
+        form.submit(function () {
+            changed = false;
+        });
+        setInterval(function () {
+            if (!changed) {
+                return;
+            }
+            changed = false;
+            let fields = {};
+            form.serializeArray().forEach(function (field) {
+                fields[field.name] = field.value;
+            });
+            fetch(form.attr("data-autosave-url"), {
+                method: "POST",
+                headers: { "Content-Type": "application/json" },
+                body: JSON.stringify(fields),
+            }).then(function (response) {
+                // Try again on the next interval.
+                if (!response.ok) {
+                    changed = true;
+                }
+            }).catch(function () {
+                changed = true;
+            });
+        }, 15000);
+    });
+
+    // Show the announcement banner unless this announcement was already dismissed.
+    let announcement = $("#announcement");
+    if (announcement.length) {
+        let posted_at = announcement.attr("data-posted-at");
+        if (localStorage.getItem("dismissed_announcement") !== posted_at) {
+            announcement.removeClass("d-none");
+        }
+        // Remember the dismissal so the banner stays hidden on other pages.
+        announcement.on("closed.bs.alert", function () {
+            localStorage.setItem("dismissed_announcement", posted_at);
+        });
+    }
+
 });
 